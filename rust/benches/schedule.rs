@@ -0,0 +1,55 @@
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+
+use chameleon_rust::benchmark::{anneal, neighbour, scores, synthetic_instance, INSTANCE_SIZES};
+
+/// Number of annealing iterations made before measuring neighbours and scores,
+/// so that they are measured on schedules with deliveries
+const WARM_UP_ITERATIONS: usize = 1000;
+
+fn bench_neighbours(c: &mut Criterion) {
+    let mut group = c.benchmark_group("neighbour");
+    for size in INSTANCE_SIZES.iter() {
+        let mut generator = synthetic_instance(size, 0).unwrap();
+        let empty_schedule = generator.empty_schedule();
+        let schedule = anneal(&mut generator, &empty_schedule, WARM_UP_ITERATIONS).unwrap();
+        group.bench_function(BenchmarkId::from_parameter(size.name), |b| {
+            b.iter(|| neighbour(&mut generator, &schedule))
+        });
+    }
+    group.finish();
+}
+
+fn bench_scores(c: &mut Criterion) {
+    let mut group = c.benchmark_group("scoring");
+    for size in INSTANCE_SIZES.iter() {
+        let mut generator = synthetic_instance(size, 0).unwrap();
+        let empty_schedule = generator.empty_schedule();
+        let schedule = anneal(&mut generator, &empty_schedule, WARM_UP_ITERATIONS).unwrap();
+        group.bench_function(BenchmarkId::from_parameter(size.name), |b| {
+            b.iter(|| scores(&generator, &schedule))
+        });
+    }
+    group.finish();
+}
+
+fn bench_annealing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("annealing");
+    // Each run takes a while, so take fewer samples than the default 100
+    group.sample_size(10);
+    for size in INSTANCE_SIZES.iter() {
+        let generator = synthetic_instance(size, 0).unwrap();
+        let empty_schedule = generator.empty_schedule();
+        group.bench_function(BenchmarkId::from_parameter(size.name), |b| {
+            // Each run starts from a fresh generator, so that runs are alike
+            b.iter_batched(
+                || synthetic_instance(size, 0).unwrap(),
+                |mut generator| anneal(&mut generator, &empty_schedule, 1000).unwrap(),
+                BatchSize::PerIteration,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_neighbours, bench_scores, bench_annealing);
+criterion_main!(benches);
@@ -1,15 +1,59 @@
 mod schedule;
 
-use schedule::schedule::{PyBooking, PyTruckData, Schedule, ScheduleGenerator};
+// For the benches in `benches/`
+pub use schedule::benchmark;
+
+use schedule::errors::{
+    ChameleonError, InfeasibleBookingError, InfeasibleScheduleError, InvalidIntervalError,
+    MissingDrivingTimeError, UnknownIdError, UnknownTerminalError,
+};
+use schedule::schedule::{
+    MoveDescription, OptimizationHandle, PyBooking, PyCalendar, PyCheckpoint, PyDriver,
+    PyIntervalChain, PyTruckData, Schedule, ScheduleGenerator, ScheduleStatistics,
+};
 
 use pyo3::prelude::*;
 
 /// The module for handling schedules
 #[pymodule]
 fn chameleon_rust(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    // Send log records to python's `logging`, under loggers named after the rust modules,
+    // e.g. "chameleon_rust.schedule.schedule"
+    pyo3_log::init();
+
     m.add_class::<PyTruckData>()?;
     m.add_class::<PyBooking>()?;
+    m.add_class::<PyCheckpoint>()?;
+    m.add_class::<PyCalendar>()?;
+    m.add_class::<PyDriver>()?;
+    m.add_class::<PyIntervalChain>()?;
     m.add_class::<Schedule>()?;
     m.add_class::<ScheduleGenerator>()?;
+    m.add_class::<ScheduleStatistics>()?;
+    m.add_class::<OptimizationHandle>()?;
+    m.add_class::<MoveDescription>()?;
+    m.add_function(wrap_pyfunction!(benchmark::benchmark, m)?)?;
+    m.add("ChameleonError", m.py().get_type::<ChameleonError>())?;
+    m.add(
+        "InvalidIntervalError",
+        m.py().get_type::<InvalidIntervalError>(),
+    )?;
+    m.add("UnknownIdError", m.py().get_type::<UnknownIdError>())?;
+    m.add(
+        "UnknownTerminalError",
+        m.py().get_type::<UnknownTerminalError>(),
+    )?;
+    m.add(
+        "MissingDrivingTimeError",
+        m.py().get_type::<MissingDrivingTimeError>(),
+    )?;
+    m.add(
+        "InfeasibleBookingError",
+        m.py().get_type::<InfeasibleBookingError>(),
+    )?;
+    m.add(
+        "InfeasibleScheduleError",
+        m.py().get_type::<InfeasibleScheduleError>(),
+    )?;
     Ok(())
 }
@@ -0,0 +1,73 @@
+// Simulated annealing over schedules, as in `src/metaheuristic/sa.py`.
+// Scores are compared as (deliveries, free trucks, driving time, penalty),
+// see `ScheduleGenerator.scores`
+
+/// Parameters of simulated annealing, see `ScheduleGenerator.optimize`
+#[derive(Clone, Debug)]
+pub struct AnnealingOptions {
+    pub num_iterations: usize,
+    pub initial_temperature: f64,
+    pub final_temperature: f64,
+    pub num_tries_per_action: usize,
+    /// Probability of going back to the best schedule found in each iteration
+    pub restart_probability: f64,
+    /// Number of iterations between reports of progress
+    pub progress_interval: usize,
+}
+
+/// How far simulated annealing has got
+#[derive(Clone, Debug)]
+pub struct AnnealingProgress {
+    pub iteration: usize,
+    pub num_iterations: usize,
+    pub temperature: f64,
+    pub best_scores: Vec<f64>,
+}
+
+/// Temperature after `progress` (between 0 and 1) of the iterations, which
+/// decreases exponentially from the initial to the final temperature
+pub fn temperature_at(options: &AnnealingOptions, progress: f64) -> f64 {
+    (progress * options.final_temperature.ln()
+        + (1.0 - progress) * options.initial_temperature.ln())
+    .exp()
+}
+
+/// Whether a schedule is better than another one, where
+/// `deltas` = its scores - the other one's scores
+pub fn is_better(deltas: &[f64]) -> bool {
+    let [deliveries_delta, free_trucks_delta, driving_time_delta, penalty_delta] = deltas else {
+        panic!("Expected 4 scores, got {}", deltas.len());
+    };
+    // Satisfying hard constraints takes priority over everything else
+    if *penalty_delta != 0.0 {
+        return *penalty_delta > 0.0;
+    }
+    if *deliveries_delta > 0.0 || (*deliveries_delta == 0.0 && *driving_time_delta > 0.0) {
+        return true;
+    }
+    3.0 * deliveries_delta + 0.5 * free_trucks_delta + driving_time_delta > 0.0
+}
+
+/// Probability of moving to a schedule which is not better, where
+/// `deltas` = its scores - the current schedule's scores
+pub fn acceptance_probability(deltas: &[f64], temperature: f64) -> f64 {
+    let [deliveries_delta, free_trucks_delta, driving_time_delta, penalty_delta] = deltas else {
+        panic!("Expected 4 scores, got {}", deltas.len());
+    };
+    // Never move towards violating hard constraints
+    if *penalty_delta < 0.0 {
+        return 0.0;
+    }
+
+    // We are mainly optimising for delivered cargo
+    let mut combined_delta = 3.0 * deliveries_delta;
+    // Don't encourage fewer deliveries with more free trucks
+    if *deliveries_delta >= 0.0 {
+        combined_delta += 0.05 * free_trucks_delta;
+    }
+    // Minimising driving time is secondary to maximising deliveries
+    if *deliveries_delta <= 0.0 {
+        combined_delta += driving_time_delta;
+    }
+    (combined_delta / temperature).exp().min(f64::MAX)
+}
@@ -0,0 +1,226 @@
+// Synthetic instances for measuring the hot paths, used by `benchmark`
+// and by the criterion benches in `benches/`
+
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+use pyo3::{
+    exceptions::PyTypeError,
+    pyfunction,
+    types::{PyDict, PyDictMethods},
+    Bound, PyResult, Python,
+};
+use rand::{seq::IndexedRandom, Rng, SeedableRng};
+use rand_xoshiro::Xoshiro256PlusPlus;
+use serde_json::json;
+
+use super::annealing::AnnealingOptions;
+use super::py_table::{PyKeyedRows, PyRows};
+use super::py_time::PyTime;
+use super::schedule::{PyBooking, PyTruckData, Schedule, ScheduleGenerator};
+
+/// Number of terminals, trucks and bookings of a synthetic instance
+#[derive(Clone, Debug)]
+pub struct InstanceSize {
+    pub name: &'static str,
+    pub num_terminals: usize,
+    pub num_trucks: usize,
+    pub num_bookings: usize,
+}
+
+pub const INSTANCE_SIZES: [InstanceSize; 3] = [
+    InstanceSize {
+        name: "small",
+        num_terminals: 5,
+        num_trucks: 5,
+        num_bookings: 20,
+    },
+    InstanceSize {
+        name: "medium",
+        num_terminals: 20,
+        num_trucks: 20,
+        num_bookings: 200,
+    },
+    InstanceSize {
+        name: "large",
+        num_terminals: 50,
+        num_trucks: 100,
+        num_bookings: 1000,
+    },
+];
+
+/// Start of the planning period of synthetic instances, a midnight in UTC
+const PLANNING_START: u64 = 1_735_689_600;
+const HOUR: u64 = 60 * 60;
+
+/// A random instance of `size` with a day-long planning period. Terminals are spread
+/// over about 100km, with driving times estimated from their locations, and each
+/// booking has a pickup window of a few hours and a dropoff window after it
+pub fn synthetic_instance(size: &InstanceSize, seed: u64) -> PyResult<ScheduleGenerator> {
+    let mut rng = Xoshiro256PlusPlus::seed_from_u64(seed);
+    let planning_period = (PyTime(PLANNING_START), PyTime(PLANNING_START + 24 * HOUR));
+
+    let terminal_ids: Vec<String> = (0..size.num_terminals)
+        .map(|terminal| format!("T{terminal}"))
+        .collect();
+    let terminal_data = terminal_ids
+        .iter()
+        .map(|terminal_id| (terminal_id.clone(), planning_period.into()))
+        .collect();
+    let terminal_locations = terminal_ids
+        .iter()
+        .map(|terminal_id| {
+            let location = (rng.random_range(52.0..53.0), rng.random_range(4.5..6.0));
+            (terminal_id.clone(), location)
+        })
+        .collect();
+
+    let truck_data = (0..size.num_trucks)
+        .map(|truck| {
+            let starting_terminal = terminal_ids.choose(&mut rng).unwrap().clone();
+            let truck_data = PyTruckData::new(
+                starting_terminal,
+                30_000,
+                2,
+                vec![],
+                None,
+                BTreeMap::new(),
+                0,
+                0,
+                None,
+                false,
+                None,
+                1.0,
+            )?;
+            Ok((format!("K{truck}"), truck_data))
+        })
+        .collect::<PyResult<_>>()?;
+
+    let booking_data = (0..size.num_bookings)
+        .map(|cargo| {
+            let from_terminal = terminal_ids.choose(&mut rng).unwrap();
+            let to_terminal = loop {
+                let to_terminal = terminal_ids.choose(&mut rng).unwrap();
+                if to_terminal != from_terminal {
+                    break to_terminal;
+                }
+            };
+            let pickup_open_time = PLANNING_START + rng.random_range(0..14 * HOUR);
+            let pickup_close_time = pickup_open_time + rng.random_range(2 * HOUR..6 * HOUR);
+            let booking = json!({
+                "cargo": format!("C{cargo}"),
+                "cargo_weight_kg": rng.random_range(5_000..25_000),
+                "from_terminal": from_terminal,
+                "to_terminal": to_terminal,
+                "pickup_open_time": pickup_open_time,
+                "pickup_close_time": pickup_close_time,
+                "dropoff_open_time": pickup_open_time,
+                "dropoff_close_time": pickup_close_time + 4 * HOUR,
+            });
+            serde_json::from_value(booking).map_err(|err| PyTypeError::new_err(err.to_string()))
+        })
+        .collect::<PyResult<Vec<PyBooking>>>()?;
+
+    let mut generator = ScheduleGenerator::new(
+        PyKeyedRows(terminal_data),
+        PyKeyedRows(truck_data),
+        PyRows(booking_data),
+        planning_period,
+        false,
+        BTreeMap::new(),
+        BTreeMap::new(),
+        terminal_locations,
+        BTreeMap::new(),
+        BTreeMap::new(),
+        Some(vec!["haversine".to_string()]),
+        None,
+    )?;
+    generator.seed(seed);
+    Ok(generator)
+}
+
+/// A random neighbour of `schedule`, as made by `get_schedule_neighbour`
+pub fn neighbour(generator: &mut ScheduleGenerator, schedule: &Schedule) -> Schedule {
+    generator
+        .make_schedule_neighbour(schedule, 10)
+        .expect("Synthetic instances have driving times between all terminals")
+}
+
+pub fn scores(generator: &ScheduleGenerator, schedule: &Schedule) -> Vec<f64> {
+    generator
+        .compute_scores(schedule)
+        .expect("Synthetic instances have driving times between all terminals")
+}
+
+/// The best schedule found by `num_iterations` iterations of simulated annealing
+/// with the default parameters of `optimize`
+pub fn anneal(
+    generator: &mut ScheduleGenerator,
+    schedule: &Schedule,
+    num_iterations: usize,
+) -> PyResult<Schedule> {
+    let options = AnnealingOptions {
+        num_iterations,
+        initial_temperature: 10.0,
+        final_temperature: 0.1,
+        num_tries_per_action: 10,
+        restart_probability: 0.001,
+        progress_interval: num_iterations.max(1),
+    };
+    let (best_schedule, _best_scores) = generator.anneal(schedule, &options, &mut |_| Ok(true))?;
+    Ok(best_schedule)
+}
+
+/// Times the hot paths on a synthetic instance of each size, from "small" with
+/// 20 bookings to "large" with 1000, returning a dict for each size with its "name",
+/// "num_terminals", "num_trucks", "num_bookings" and the seconds taken by
+/// - "annealing": `num_iterations` iterations of `optimize` from an empty schedule
+/// - "neighbour": making a neighbour of the annealed schedule, on average
+/// - "scoring": scoring the annealed schedule, on average
+///
+/// Other python threads can run meanwhile
+#[pyfunction]
+#[pyo3(signature = (num_iterations=1000, num_samples=1000, seed=0))]
+pub fn benchmark(
+    py: Python<'_>,
+    num_iterations: usize,
+    num_samples: usize,
+    seed: u64,
+) -> PyResult<Vec<Bound<'_, PyDict>>> {
+    let mut out = vec![];
+    for size in INSTANCE_SIZES.iter() {
+        let (annealing_time, neighbour_time, scoring_time) = py.allow_threads(|| {
+            let mut generator = synthetic_instance(size, seed)?;
+
+            let empty_schedule = generator.empty_schedule();
+            let started = Instant::now();
+            let schedule = anneal(&mut generator, &empty_schedule, num_iterations)?;
+            let annealing_time = started.elapsed();
+
+            let started = Instant::now();
+            for _ in 0..num_samples {
+                neighbour(&mut generator, &schedule);
+            }
+            let neighbour_time = started.elapsed();
+
+            let started = Instant::now();
+            for _ in 0..num_samples {
+                scores(&generator, &schedule);
+            }
+            let scoring_time = started.elapsed();
+            PyResult::Ok((annealing_time, neighbour_time, scoring_time))
+        })?;
+
+        let per_sample = num_samples.max(1) as f64;
+        let result = PyDict::new(py);
+        result.set_item("name", size.name)?;
+        result.set_item("num_terminals", size.num_terminals)?;
+        result.set_item("num_trucks", size.num_trucks)?;
+        result.set_item("num_bookings", size.num_bookings)?;
+        result.set_item("annealing", annealing_time.as_secs_f64())?;
+        result.set_item("neighbour", neighbour_time.as_secs_f64() / per_sample)?;
+        result.set_item("scoring", scoring_time.as_secs_f64() / per_sample)?;
+        out.push(result);
+    }
+    Ok(out)
+}
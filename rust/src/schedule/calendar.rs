@@ -0,0 +1,64 @@
+use std::collections::BTreeSet;
+
+use chrono::{DateTime, Days, NaiveDate, NaiveTime, Weekday};
+use chrono_tz::Tz;
+
+use super::common_types::SECONDS_PER_DAY;
+use super::intervals::{Interval, IntervalChain};
+use super::recurring_pattern::RecurringPattern;
+
+/// Days on which something (e.g. a terminal or a driver) is unavailable,
+/// such as weekends and public holidays. Days are local days in a timezone,
+/// see `to_pattern`, or UTC days for `restrict`
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Calendar {
+    /// Days of the week that are closed, with 0 being Monday
+    closed_weekdays: BTreeSet<u64>,
+    /// Closed dates
+    holidays: BTreeSet<NaiveDate>,
+}
+
+impl Calendar {
+    /// Create a calendar closed on `closed_weekdays` (with 0 being Monday)
+    /// and on `holidays`
+    pub fn new(closed_weekdays: &[u64], holidays: &[NaiveDate]) -> Self {
+        Self {
+            closed_weekdays: closed_weekdays.iter().copied().collect(),
+            holidays: holidays.iter().copied().collect(),
+        }
+    }
+
+    /// Whether the day (counted from the unix epoch) is not closed
+    pub fn is_open(&self, day: u64) -> bool {
+        // The unix epoch was on a Thursday
+        let weekday = (day + 3) % 7;
+        let date = DateTime::UNIX_EPOCH.date_naive() + Days::new(day);
+        !self.closed_weekdays.contains(&weekday) && !self.holidays.contains(&date)
+    }
+
+    /// Removes the closed days from `intervals`
+    pub fn restrict(&self, intervals: &IntervalChain) -> IntervalChain {
+        let mut out = vec![];
+        for interval in intervals {
+            let mut start_time = interval.get_start_time();
+            let first_day = interval.get_start_time() / SECONDS_PER_DAY;
+            let last_day = (interval.get_end_time() - 1) / SECONDS_PER_DAY;
+            for day in (first_day..=last_day).filter(|day| !self.is_open(*day)) {
+                // Keep the part before the closed day, if there is any
+                out.extend(Interval::new(start_time, day * SECONDS_PER_DAY, ()));
+                start_time = start_time.max((day + 1) * SECONDS_PER_DAY);
+            }
+            out.extend(Interval::new(start_time, interval.get_end_time(), ()));
+        }
+        IntervalChain::from_intervals(out)
+    }
+
+    /// The days on which this calendar is open, taking days to be local days in `timezone`
+    pub fn to_pattern(&self, timezone: Tz) -> RecurringPattern {
+        let open_weekdays = (0..7u8)
+            .filter(|weekday| !self.closed_weekdays.contains(&u64::from(*weekday)))
+            .map(|weekday| Weekday::try_from(weekday).unwrap());
+        RecurringPattern::weekly(timezone, open_weekdays, NaiveTime::MIN, NaiveTime::MIN)
+            .with_exceptions(self.holidays.iter().copied())
+    }
+}
@@ -0,0 +1,72 @@
+use std::collections::BTreeMap;
+
+use super::common_types::{CapacityDimension, IsID};
+use super::heap_size::HeapSize;
+
+/// An amount of each capacity dimension (weight, TEU, pallets, ...), either
+/// describing how much a truck can carry or how much of it a piece of cargo takes up.
+/// All capacities used together need to have the same number of dimensions
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Capacity {
+    amounts: Vec<usize>,
+}
+
+impl Capacity {
+    /// Create a capacity with `num_dimensions` dimensions, taking amounts from `amounts`
+    /// and `default` for dimensions not in `amounts`
+    pub fn from_map(
+        amounts: &BTreeMap<CapacityDimension, usize>,
+        num_dimensions: usize,
+        default: usize,
+    ) -> Self {
+        let mut out = vec![default; num_dimensions];
+        for (dimension, amount) in amounts.iter() {
+            out[dimension.get_id()] = *amount;
+        }
+        Self { amounts: out }
+    }
+
+    pub fn get(&self, dimension: CapacityDimension) -> usize {
+        self.amounts[dimension.get_id()]
+    }
+
+    pub fn set(&mut self, dimension: CapacityDimension, amount: usize) {
+        self.amounts[dimension.get_id()] = amount;
+    }
+
+    /// Take away `other` in each dimension, return None if any dimension would
+    /// become negative
+    pub fn checked_sub(&self, other: &Capacity) -> Option<Capacity> {
+        assert_eq!(self.amounts.len(), other.amounts.len());
+        let amounts = self
+            .amounts
+            .iter()
+            .zip(other.amounts.iter())
+            .map(|(amount, other_amount)| amount.checked_sub(*other_amount))
+            .collect::<Option<Vec<_>>>()?;
+        Some(Self { amounts })
+    }
+
+    /// Add `other` in each dimension
+    pub fn add(&mut self, other: &Capacity) {
+        assert_eq!(self.amounts.len(), other.amounts.len());
+        for (amount, other_amount) in self.amounts.iter_mut().zip(other.amounts.iter()) {
+            *amount = amount.saturating_add(*other_amount);
+        }
+    }
+
+    /// Whether this is at most `other` in each dimension
+    pub fn fits_within(&self, other: &Capacity) -> bool {
+        assert_eq!(self.amounts.len(), other.amounts.len());
+        self.amounts
+            .iter()
+            .zip(other.amounts.iter())
+            .all(|(amount, other_amount)| amount <= other_amount)
+    }
+}
+
+impl HeapSize for Capacity {
+    fn heap_size(&self) -> usize {
+        self.amounts.heap_size()
+    }
+}
@@ -0,0 +1,117 @@
+use smallvec::SmallVec;
+
+use super::common_types::Cargo;
+use super::heap_size::HeapSize;
+
+/// A set of cargo, e.g. the cargo picked up at a checkpoint. Kept as a sorted
+/// small vector, since checkpoints mostly handle one or two cargo, which then
+/// don't need a heap allocation when cloning schedules.
+/// Like a `BTreeSet`, it iterates in ascending order
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct CargoSet(SmallVec<[Cargo; 2]>);
+
+impl CargoSet {
+    pub fn new() -> Self {
+        Self(SmallVec::new())
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn contains(&self, cargo: &Cargo) -> bool {
+        self.0.binary_search(cargo).is_ok()
+    }
+
+    /// Adds `cargo`, returning whether it wasn't in the set yet
+    pub fn insert(&mut self, cargo: Cargo) -> bool {
+        match self.0.binary_search(&cargo) {
+            Ok(_) => false,
+            Err(index) => {
+                self.0.insert(index, cargo);
+                true
+            }
+        }
+    }
+
+    /// Removes `cargo`, returning whether it was in the set
+    pub fn remove(&mut self, cargo: &Cargo) -> bool {
+        match self.0.binary_search(cargo) {
+            Ok(index) => {
+                self.0.remove(index);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Cargo> {
+        self.0.iter()
+    }
+}
+
+// NOTE: only owns heap memory once it outgrows its inline space
+impl HeapSize for CargoSet {
+    fn heap_size(&self) -> usize {
+        if self.0.spilled() {
+            self.0.capacity() * std::mem::size_of::<Cargo>()
+        } else {
+            0
+        }
+    }
+}
+
+impl FromIterator<Cargo> for CargoSet {
+    fn from_iter<I: IntoIterator<Item = Cargo>>(iter: I) -> Self {
+        let mut cargo: SmallVec<[Cargo; 2]> = iter.into_iter().collect();
+        cargo.sort_unstable();
+        cargo.dedup();
+        Self(cargo)
+    }
+}
+
+impl<'a> IntoIterator for &'a CargoSet {
+    type Item = &'a Cargo;
+    type IntoIter = std::slice::Iter<'a, Cargo>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::schedule::common_types::IsID;
+
+    proptest! {
+        /// Inserting and removing cargo behaves like a `BTreeSet`
+        #[test]
+        fn behaves_like_btree_set(
+            operations in prop::collection::vec((0..10usize, any::<bool>()), 0..50)
+        ) {
+            let mut cargo_set = CargoSet::new();
+            let mut btree_set = BTreeSet::new();
+            for (id, insert) in operations {
+                let cargo = Cargo::from_id(id);
+                if insert {
+                    prop_assert_eq!(cargo_set.insert(cargo), btree_set.insert(cargo));
+                } else {
+                    prop_assert_eq!(cargo_set.remove(&cargo), btree_set.remove(&cargo));
+                }
+                prop_assert_eq!(cargo_set.len(), btree_set.len());
+                prop_assert_eq!(cargo_set.contains(&cargo), btree_set.contains(&cargo));
+                prop_assert!(cargo_set.iter().eq(btree_set.iter()));
+            }
+            prop_assert!(cargo_set == btree_set.into_iter().collect());
+        }
+    }
+}
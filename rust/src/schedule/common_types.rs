@@ -1,14 +1,39 @@
 // NOTE: this prevents recognising them as the same type, and e.g.
 // assigning a truck to a cargo by mistake
-#[derive(Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash, Debug)]
 pub struct Terminal(usize);
 
-#[derive(Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash, Debug)]
 pub struct Cargo(usize);
 
-#[derive(Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash, Debug)]
 pub struct Truck(usize);
 
+/// A qualification of a driver, such as an ADR licence
+#[derive(Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash, Debug)]
+pub struct Qualification(usize);
+
+/// A dimension in which the capacity of trucks is measured, e.g. weight or pallets
+#[derive(Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash, Debug)]
+pub struct CapacityDimension(usize);
+
+impl CapacityDimension {
+    /// Weight of cargo, in kg
+    pub const WEIGHT_KG: Self = Self(0);
+    /// Size of cargo, in TEU
+    pub const TEU: Self = Self(1);
+    /// Names of dimensions that always exist, in order of their ids
+    pub const BUILTIN_NAMES: [&'static str; 2] = ["weight_kg", "teu"];
+}
+
+/// A class of vehicles sharing driving times, e.g. articulated trucks
+#[derive(Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash, Debug)]
+pub struct VehicleClass(usize);
+
+/// A driver who can be assigned to drive a truck during their shift
+#[derive(Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash, Debug)]
+pub struct Driver(usize);
+
 pub trait IsID {
     fn get_id(&self) -> usize;
     fn from_id(id: usize) -> Self;
@@ -41,9 +66,80 @@ impl IsID for Truck {
     }
 }
 
+impl IsID for Qualification {
+    fn get_id(&self) -> usize {
+        self.0
+    }
+    fn from_id(id: usize) -> Self {
+        Self(id)
+    }
+}
+
+impl IsID for CapacityDimension {
+    fn get_id(&self) -> usize {
+        self.0
+    }
+    fn from_id(id: usize) -> Self {
+        Self(id)
+    }
+}
+
+impl IsID for VehicleClass {
+    fn get_id(&self) -> usize {
+        self.0
+    }
+    fn from_id(id: usize) -> Self {
+        Self(id)
+    }
+}
+
+impl IsID for Driver {
+    fn get_id(&self) -> usize {
+        self.0
+    }
+    fn from_id(id: usize) -> Self {
+        Self(id)
+    }
+}
+
 // TODO: maybe convert these to struct Time(u64), TimeDelta(i64)
 // and NonNegativeTimeDelta(i64)
 // to make it more fool-proof
 pub type Time = u64;
 // pub type TimeDelta = i64;
 pub type NonNegativeTimeDelta = u64;
+
+pub const SECONDS_PER_DAY: NonNegativeTimeDelta = 24 * 60 * 60;
+
+/// Geographic location of a terminal, in degrees
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Location {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl Location {
+    /// Returns None if the coordinates are not valid latitude and longitude
+    pub fn new(latitude: f64, longitude: f64) -> Option<Self> {
+        ((-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude)).then_some(
+            Self {
+                latitude,
+                longitude,
+            },
+        )
+    }
+
+    /// Great-circle distance to `other` in km, using the haversine formula
+    pub fn distance_km(&self, other: &Location) -> f64 {
+        const EARTH_RADIUS_KM: f64 = 6371.0;
+        let (latitude, other_latitude) = (self.latitude.to_radians(), other.latitude.to_radians());
+        let half_latitude_delta = (other_latitude - latitude) / 2.0;
+        let half_longitude_delta = (other.longitude - self.longitude).to_radians() / 2.0;
+        let a = half_latitude_delta.sin().powi(2)
+            + latitude.cos() * other_latitude.cos() * half_longitude_delta.sin().powi(2);
+        2.0 * EARTH_RADIUS_KM * a.sqrt().min(1.0).asin()
+    }
+}
+
+// NOTE: coordinates are never NaN, since `Location::new` rejects them
+impl Eq for Location {}
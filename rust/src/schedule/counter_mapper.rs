@@ -1,16 +1,23 @@
 use std::collections::BTreeMap;
 
 use super::common_types::IsID;
+use super::heap_size::HeapSize;
 
 /// A struct that bijectively maps from internal `usize` ids
 /// to external ids of type `T`
-#[derive(PartialEq, Eq)]
+#[derive(Clone, PartialEq, Eq)]
 pub struct CounterMapper<T: Clone + Ord + Eq> {
     counter: usize,
     map: BTreeMap<usize, T>,
     reverse_map: BTreeMap<T, usize>,
 }
 
+impl<T: Clone + Ord + Eq + HeapSize> HeapSize for CounterMapper<T> {
+    fn heap_size(&self) -> usize {
+        self.map.heap_size() + self.reverse_map.heap_size()
+    }
+}
+
 impl<T: Clone + Ord + Eq> CounterMapper<T> {
     pub fn new() -> Self {
         Self {
@@ -42,4 +49,16 @@ impl<T: Clone + Ord + Eq> CounterMapper<T> {
     pub fn reverse_map<U: IsID>(&self, item: &T) -> Option<U> {
         Some(U::from_id(*self.reverse_map.get(item)?))
     }
+
+    /// Iterates over all (internal id, external id) pairs, in order of internal id
+    pub fn iter<U: IsID>(&self) -> impl Iterator<Item = (U, &T)> {
+        self.map
+            .iter()
+            .map(|(index, item)| (U::from_id(*index), item))
+    }
+
+    /// Number of items mapped
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
 }
@@ -0,0 +1,185 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap};
+
+use super::common_types::{Location, NonNegativeTimeDelta, Terminal};
+use super::driving_times_cache::{get_legs_from, DrivingTimesMap};
+
+/// Average speed used to estimate missing driving times by default, in km/h
+pub const DEFAULT_ESTIMATE_SPEED_KMH: f64 = 50.0;
+
+/// The external ids of terminals, e.g. to pass them to python
+pub type TerminalIds = BTreeMap<Terminal, String>;
+
+/// A backend finding driving times which are missing from `DrivingTimesCache` on demand
+pub trait DrivingTimeProvider: Send + Sync {
+    /// Finds the driving time from `from` to `to`, given the driving times `known`
+    /// for all vehicles and the ids of all terminals. Can also return driving times
+    /// of other pairs found along the way, which are cached as well.
+    /// Returns an error message if it failed
+    fn find_driving_times(
+        &self,
+        known: &DrivingTimesMap,
+        terminal_ids: &TerminalIds,
+        from: Terminal,
+        to: Terminal,
+    ) -> Result<DrivingTimesMap, String>;
+
+    /// Whether the driving times found depend on `known`, and so need to be
+    /// found again when it changes
+    fn uses_known_driving_times(&self) -> bool {
+        false
+    }
+}
+
+/// The sources of driving times, which can be selected by name. Except for the static
+/// and time-dependent ones, which are kept in `DrivingTimesCache`, each has a
+/// `DrivingTimeProvider` finding driving times on demand
+#[derive(Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Debug)]
+pub enum DrivingTimeSource {
+    /// Driving times set for all vehicles, regardless of the time of day
+    Static,
+    /// Driving times depending on the time of day of departure
+    TimeDependent,
+    /// Calls a function, e.g. one querying a routing service such as OSRM
+    Callback,
+    /// Shortest routes through pairs of terminals with known driving times
+    ShortestPath,
+    /// Great-circle distance between the terminals' locations
+    Haversine,
+}
+
+impl DrivingTimeSource {
+    /// The order in which sources are tried by default
+    pub const DEFAULT_ORDER: [Self; 5] = [
+        Self::TimeDependent,
+        Self::Static,
+        Self::Callback,
+        Self::ShortestPath,
+        Self::Haversine,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Static => "static",
+            Self::TimeDependent => "time_dependent",
+            Self::Callback => "callback",
+            Self::ShortestPath => "shortest_path",
+            Self::Haversine => "haversine",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::DEFAULT_ORDER
+            .into_iter()
+            .find(|source| source.name() == name)
+    }
+}
+
+/// Function computing the driving time from the terminal with one id to another,
+/// returning an error message if it can't
+pub type DrivingTimeCallback =
+    Box<dyn Fn(&str, &str) -> Result<NonNegativeTimeDelta, String> + Send + Sync>;
+
+/// Finds driving times by calling a function, if one is set
+pub struct CallbackProvider {
+    callback: Option<DrivingTimeCallback>,
+}
+
+impl CallbackProvider {
+    pub fn new(callback: Option<DrivingTimeCallback>) -> Self {
+        Self { callback }
+    }
+}
+
+impl DrivingTimeProvider for CallbackProvider {
+    fn find_driving_times(
+        &self,
+        _known: &DrivingTimesMap,
+        terminal_ids: &TerminalIds,
+        from: Terminal,
+        to: Terminal,
+    ) -> Result<DrivingTimesMap, String> {
+        let Some(callback) = &self.callback else {
+            return Ok(DrivingTimesMap::new());
+        };
+        let (Some(from_id), Some(to_id)) = (terminal_ids.get(&from), terminal_ids.get(&to)) else {
+            return Err(format!("Unknown terminals {from:?}, {to:?}"));
+        };
+        Ok(DrivingTimesMap::from([(
+            (from, to),
+            callback(from_id, to_id)?,
+        )]))
+    }
+}
+
+/// Derives driving times from the shortest routes through pairs of terminals
+/// with known driving times, using Dijkstra's algorithm
+pub struct ShortestPathProvider;
+
+impl DrivingTimeProvider for ShortestPathProvider {
+    /// Finds the shortest driving times from `from` to all reachable terminals at once
+    fn find_driving_times(
+        &self,
+        known: &DrivingTimesMap,
+        _terminal_ids: &TerminalIds,
+        from: Terminal,
+        _to: Terminal,
+    ) -> Result<DrivingTimesMap, String> {
+        let mut out = DrivingTimesMap::new();
+        let mut queue = BinaryHeap::from([Reverse((0, from))]);
+        while let Some(Reverse((driving_time, terminal))) = queue.pop() {
+            if out.contains_key(&(from, terminal)) {
+                continue;
+            }
+            out.insert((from, terminal), driving_time);
+            for (next_terminal, leg_driving_time) in get_legs_from(known, terminal) {
+                if !out.contains_key(&(from, next_terminal)) {
+                    queue.push(Reverse((driving_time + leg_driving_time, next_terminal)));
+                }
+            }
+        }
+        out.remove(&(from, from));
+        Ok(out)
+    }
+
+    fn uses_known_driving_times(&self) -> bool {
+        true
+    }
+}
+
+/// Estimates driving times from the great-circle distance between terminals
+pub struct HaversineProvider {
+    locations: BTreeMap<Terminal, Location>,
+    /// Average speed along the great-circle, in km/h. Since roads are longer than
+    /// the great-circle distance, this is lower than the actual average speed of trucks
+    speed_kmh: f64,
+}
+
+impl HaversineProvider {
+    pub fn new(locations: BTreeMap<Terminal, Location>, speed_kmh: f64) -> Self {
+        assert!(speed_kmh.is_finite() && speed_kmh > 0.0);
+        Self {
+            locations,
+            speed_kmh,
+        }
+    }
+}
+
+impl DrivingTimeProvider for HaversineProvider {
+    fn find_driving_times(
+        &self,
+        _known: &DrivingTimesMap,
+        _terminal_ids: &TerminalIds,
+        from: Terminal,
+        to: Terminal,
+    ) -> Result<DrivingTimesMap, String> {
+        let (Some(from_location), Some(to_location)) =
+            (self.locations.get(&from), self.locations.get(&to))
+        else {
+            return Ok(DrivingTimesMap::new());
+        };
+        let distance_km = from_location.distance_km(to_location);
+        let driving_time = (distance_km / self.speed_kmh * 3600.0).round() as NonNegativeTimeDelta;
+        Ok(DrivingTimesMap::from([((from, to), driving_time)]))
+    }
+}
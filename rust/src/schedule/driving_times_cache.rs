@@ -1,43 +1,628 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, RwLock};
 
-use super::common_types::{NonNegativeTimeDelta, Terminal};
+use log::debug;
+
+use super::common_types::{
+    IsID, NonNegativeTimeDelta, Terminal, Time, VehicleClass, SECONDS_PER_DAY,
+};
+use super::driving_time_providers::{DrivingTimeProvider, DrivingTimeSource, TerminalIds};
+use super::heap_size::HeapSize;
+use super::intervals::Interval;
+
+pub type DrivingTimesMap = BTreeMap<(Terminal, Terminal), NonNegativeTimeDelta>;
+
+/// Driving times that depend on the time of day, as a list of
+/// (start of time bucket in seconds since midnight, driving time when departing in that bucket),
+/// sorted by start of time bucket. Each bucket lasts until the start of the next one,
+/// and the last one wraps around to the first one on the next day
+pub type DrivingTimeProfile = Vec<(NonNegativeTimeDelta, NonNegativeTimeDelta)>;
+
+/// Factors by which driving times are scaled when departing within an interval,
+/// for each pair of terminals, in the order they were added
+pub type TrafficFactors = BTreeMap<(Terminal, Terminal), Vec<(Interval, f64)>>;
+
+/// A driving time which is needed, but is not known and couldn't be found on demand
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct MissingDrivingTime {
+    pub from: Terminal,
+    pub to: Terminal,
+    /// Why it couldn't be found on demand
+    pub reason: String,
+}
+
+pub type DrivingTimeResult<T> = Result<T, MissingDrivingTime>;
+
+/// Driving times in `data` from `from`, as (to, driving time)
+pub fn get_legs_from(
+    data: &DrivingTimesMap,
+    from: Terminal,
+) -> impl Iterator<Item = (Terminal, NonNegativeTimeDelta)> + '_ {
+    // The pairs starting at `from` are next to each other in `data`
+    data.range((from, Terminal::from_id(0))..)
+        .take_while(move |((leg_from, _), _)| *leg_from == from)
+        .map(|((_, to), driving_time)| (*to, *driving_time))
+}
+
+/// Driving times missing from the cache, found on demand by one provider
+#[derive(Default, Clone)]
+struct ComputedDrivingTimes {
+    found: DrivingTimesMap,
+    /// Pairs of terminals the provider doesn't know the driving time of
+    not_found: BTreeSet<(Terminal, Terminal)>,
+}
 
-type DrivingTimesMap = BTreeMap<(Terminal, Terminal), NonNegativeTimeDelta>;
 /// A map from (from_terminal, to_terminal) to cached driving times
-#[derive(PartialEq, Eq, Debug)]
 pub struct DrivingTimesCache {
     // NOTE: assumes that driving from A to B might take a different time than
     // driving from B to A
     data: DrivingTimesMap,
+
+    /// Time-of-day dependent driving times
+    profiles: BTreeMap<(Terminal, Terminal), DrivingTimeProfile>,
+
+    /// Driving times specific to a class of vehicles, which take priority
+    /// over both `data` and `profiles` for these vehicles
+    vehicle_class_data: BTreeMap<VehicleClass, DrivingTimesMap>,
+
+    /// Factors by which driving times are scaled when departing within an interval,
+    /// e.g. due to traffic. Factors added later take priority over earlier ones
+    traffic_factors: TrafficFactors,
+
+    /// Standard deviations of driving times, for pairs of terminals whose driving
+    /// times vary, e.g. due to traffic. The driving times themselves are the means
+    spreads: DrivingTimesMap,
+
+    /// Asked in order for driving times, until one finds it. The static and
+    /// time-dependent sources have no provider, and look driving times up in `data`
+    /// and `profiles` instead. Shared with copies of the cache
+    providers: Vec<(DrivingTimeSource, Option<Arc<dyn DrivingTimeProvider>>)>,
+
+    /// Ids of the terminals, which providers look terminals up in when asked
+    terminal_ids: TerminalIds,
+
+    computed_data: RwLock<BTreeMap<DrivingTimeSource, ComputedDrivingTimes>>,
+}
+
+// NOTE: RwLock isn't Clone
+impl Clone for DrivingTimesCache {
+    fn clone(&self) -> Self {
+        Self {
+            data: self.data.clone(),
+            profiles: self.profiles.clone(),
+            vehicle_class_data: self.vehicle_class_data.clone(),
+            traffic_factors: self.traffic_factors.clone(),
+            spreads: self.spreads.clone(),
+            providers: self.providers.clone(),
+            terminal_ids: self.terminal_ids.clone(),
+            computed_data: RwLock::new(self.computed_data.read().unwrap().clone()),
+        }
+    }
+}
+
+// NOTE: doesn't count the providers, which are shared with copies of the cache
+impl HeapSize for DrivingTimesCache {
+    fn heap_size(&self) -> usize {
+        let computed_data = self.computed_data.read().unwrap();
+        self.data.heap_size()
+            + self.profiles.heap_size()
+            + self.vehicle_class_data.heap_size()
+            + self.traffic_factors.heap_size()
+            + self.spreads.heap_size()
+            + self.terminal_ids.heap_size()
+            + computed_data
+                .values()
+                .map(|computed| {
+                    std::mem::size_of::<(DrivingTimeSource, ComputedDrivingTimes)>()
+                        + computed.found.heap_size()
+                        + computed.not_found.heap_size()
+                })
+                .sum::<usize>()
+    }
+}
+
+/// Where a driving time was found
+enum FoundDrivingTime<'a> {
+    Fixed(NonNegativeTimeDelta),
+    Profile(&'a DrivingTimeProfile),
 }
 
 impl DrivingTimesCache {
-    pub fn new() -> Self {
+    /// `providers` lists the sources asked for driving times in order, with their
+    /// providers. The static and time-dependent sources don't need one
+    pub fn new(
+        providers: Vec<(DrivingTimeSource, Option<Box<dyn DrivingTimeProvider>>)>,
+        terminal_ids: TerminalIds,
+    ) -> Self {
         Self {
             data: DrivingTimesMap::new(),
+            profiles: BTreeMap::new(),
+            vehicle_class_data: BTreeMap::new(),
+            traffic_factors: BTreeMap::new(),
+            spreads: DrivingTimesMap::new(),
+            providers: providers
+                .into_iter()
+                .map(|(source, provider)| (source, provider.map(Arc::from)))
+                .collect(),
+            terminal_ids,
+            computed_data: RwLock::new(BTreeMap::new()),
         }
     }
-    pub fn from_map(map: DrivingTimesMap) -> Self {
-        Self { data: map }
+
+    /// Whether driving times are asked from `source`
+    pub fn has_source(&self, source: DrivingTimeSource) -> bool {
+        self.providers.iter().any(|(other, _)| *other == source)
+    }
+
+    /// Replace the provider of kind `source`, forgetting the driving times it found
+    /// so far. If there is no such provider, it is asked after all the others
+    pub fn set_provider(
+        &mut self,
+        source: DrivingTimeSource,
+        provider: Box<dyn DrivingTimeProvider>,
+    ) {
+        let provider = Some(Arc::from(provider));
+        match self
+            .providers
+            .iter_mut()
+            .find(|(other, _)| *other == source)
+        {
+            Some(entry) => entry.1 = provider,
+            None => self.providers.push((source, provider)),
+        }
+        self.computed_data.get_mut().unwrap().remove(&source);
+    }
+
+    /// Driving times which were found on demand by the provider of kind `source` so far
+    pub fn get_found_driving_times(&self, source: DrivingTimeSource) -> DrivingTimesMap {
+        self.computed_data
+            .read()
+            .unwrap()
+            .get(&source)
+            .map(|computed| computed.found.clone())
+            .unwrap_or_default()
+    }
+
+    /// Finds triples (a, b, c) of distinct terminals for which driving from a to c
+    /// directly takes more than `tolerance` longer than driving through b
+    pub fn find_triangle_violations(
+        &self,
+        tolerance: NonNegativeTimeDelta,
+    ) -> Vec<(Terminal, Terminal, Terminal)> {
+        let mut out = vec![];
+        for ((a, b), a_to_b) in self.data.iter() {
+            for (c, b_to_c) in get_legs_from(&self.data, *b) {
+                if c == *a || c == *b || *a == *b {
+                    continue;
+                }
+                if let Some(a_to_c) = self.data.get(&(*a, c)) {
+                    if *a_to_c > a_to_b + b_to_c + tolerance {
+                        out.push((*a, *b, c));
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    /// Finds pairs (a, b) of terminals for which driving from a to b takes more than
+    /// `tolerance` longer than driving from b to a
+    pub fn find_asymmetries(&self, tolerance: NonNegativeTimeDelta) -> Vec<(Terminal, Terminal)> {
+        self.data
+            .iter()
+            .filter(|((a, b), a_to_b)| {
+                self.data
+                    .get(&(*b, *a))
+                    .is_some_and(|b_to_a| **a_to_b > b_to_a + tolerance)
+            })
+            .map(|(pair, _)| *pair)
+            .collect()
+    }
+
+    /// Get a driving time from the provider of kind `source`, finding it on demand
+    /// and caching the result if needed. Returns None if the provider doesn't know it
+    fn get_provided_driving_time(
+        &self,
+        source: DrivingTimeSource,
+        provider: &dyn DrivingTimeProvider,
+        from: Terminal,
+        to: Terminal,
+    ) -> Result<Option<NonNegativeTimeDelta>, String> {
+        if let Some(computed) = self.computed_data.read().unwrap().get(&source) {
+            if let Some(out) = computed.found.get(&(from, to)) {
+                return Ok(Some(*out));
+            }
+            if computed.not_found.contains(&(from, to)) {
+                return Ok(None);
+            }
+        }
+
+        // NOTE: the lock is not held while calling the provider, as it might take a while
+        let found = provider.find_driving_times(&self.data, &self.terminal_ids, from, to)?;
+        let mut computed_data = self.computed_data.write().unwrap();
+        let computed = computed_data.entry(source).or_default();
+        let out = found.get(&(from, to)).copied();
+        computed.found.extend(found);
+        match out {
+            Some(_) => debug!(
+                "Driving time from {from:?} to {to:?} found by the {} provider",
+                source.name()
+            ),
+            None => {
+                computed.not_found.insert((from, to));
+            }
+        }
+        Ok(out)
     }
 
-    pub fn get_driving_time(&mut self, from: Terminal, to: Terminal) -> NonNegativeTimeDelta {
+    /// Find the driving time from `from` to `to` with the first source that knows it,
+    /// skipping the time-dependent one unless `use_profiles`
+    fn find_driving_time(
+        &self,
+        from: Terminal,
+        to: Terminal,
+        vehicle_class: Option<VehicleClass>,
+        use_profiles: bool,
+    ) -> DrivingTimeResult<FoundDrivingTime<'_>> {
         if from == to {
-            return 0;
+            return Ok(FoundDrivingTime::Fixed(0));
         }
 
-        // Get cached or recalculate cache
-        let out = self
-            .data
+        if let Some(out) = self.get_vehicle_class_driving_time(from, to, vehicle_class) {
+            return Ok(FoundDrivingTime::Fixed(out));
+        }
+
+        let mut errors = vec![];
+        for (source, provider) in self.providers.iter() {
+            let found = match (source, provider) {
+                (DrivingTimeSource::Static, _) => self
+                    .data
+                    .get(&(from, to))
+                    .copied()
+                    .map(FoundDrivingTime::Fixed),
+                (DrivingTimeSource::TimeDependent, _) => self
+                    .profiles
+                    .get(&(from, to))
+                    .filter(|_| use_profiles)
+                    .map(FoundDrivingTime::Profile),
+                (_, Some(provider)) => {
+                    match self.get_provided_driving_time(*source, provider.as_ref(), from, to) {
+                        Ok(out) => out.map(FoundDrivingTime::Fixed),
+                        Err(err) => {
+                            errors.push(format!(
+                                "the {} driving time provider failed: {err}",
+                                source.name()
+                            ));
+                            None
+                        }
+                    }
+                }
+                (_, None) => None,
+            };
+            if let Some(found) = found {
+                return Ok(found);
+            }
+        }
+
+        let reason = if errors.is_empty() {
+            let names: Vec<_> = self
+                .providers
+                .iter()
+                .map(|(source, _)| source.name())
+                .collect();
+            format!(
+                "none of the driving time providers [{}] could find it",
+                names.join(", ")
+            )
+        } else {
+            errors.join(", and ")
+        };
+        Err(MissingDrivingTime { from, to, reason })
+    }
+
+    /// Whether the driving time from `from` to `to` is known by one of the sources
+    /// without finding it on demand
+    pub fn is_known(&self, from: Terminal, to: Terminal) -> bool {
+        let computed_data = self.computed_data.read().unwrap();
+        from == to
+            || self.providers.iter().any(|(source, _)| match source {
+                DrivingTimeSource::Static => self.data.contains_key(&(from, to)),
+                DrivingTimeSource::TimeDependent => self.profiles.contains_key(&(from, to)),
+                _ => computed_data
+                    .get(source)
+                    .is_some_and(|computed| computed.found.contains_key(&(from, to))),
+            })
+    }
+
+    /// Replace the driving times that don't depend on time of day,
+    /// either for all vehicles or for vehicles of class `vehicle_class`
+    pub fn set_static_driving_times(
+        &mut self,
+        map: DrivingTimesMap,
+        vehicle_class: Option<VehicleClass>,
+    ) {
+        if let Some(vehicle_class) = vehicle_class {
+            self.vehicle_class_data.insert(vehicle_class, map);
+        } else {
+            self.data = map;
+            self.forget_derived_driving_times();
+        }
+    }
+
+    /// Replace the driving times for the pairs of terminals in `map`, keeping the others,
+    /// either for all vehicles or for vehicles of class `vehicle_class`
+    pub fn update_static_driving_times(
+        &mut self,
+        map: DrivingTimesMap,
+        vehicle_class: Option<VehicleClass>,
+    ) {
+        if let Some(vehicle_class) = vehicle_class {
+            self.vehicle_class_data
+                .entry(vehicle_class)
+                .or_default()
+                .extend(map);
+        } else {
+            self.data.extend(map);
+            self.forget_derived_driving_times();
+        }
+    }
+
+    /// Forget driving times found by providers using `data` after it changes,
+    /// e.g. since shorter routes might be possible now
+    fn forget_derived_driving_times(&mut self) {
+        let computed_data = self.computed_data.get_mut().unwrap();
+        for (source, provider) in self.providers.iter() {
+            if provider
+                .as_ref()
+                .is_some_and(|provider| provider.uses_known_driving_times())
+            {
+                computed_data.remove(source);
+            }
+        }
+    }
+
+    /// Driving times that don't depend on time of day, either for all vehicles
+    /// or for vehicles of class `vehicle_class`
+    pub fn get_static_driving_times(
+        &self,
+        vehicle_class: Option<VehicleClass>,
+    ) -> Option<&DrivingTimesMap> {
+        match vehicle_class {
+            Some(vehicle_class) => self.vehicle_class_data.get(&vehicle_class),
+            None => Some(&self.data),
+        }
+    }
+
+    /// Classes of vehicles with their own driving times
+    pub fn get_vehicle_classes(&self) -> impl Iterator<Item = VehicleClass> + '_ {
+        self.vehicle_class_data.keys().copied()
+    }
+
+    /// Time-of-day dependent driving times
+    pub fn get_profiles(&self) -> &BTreeMap<(Terminal, Terminal), DrivingTimeProfile> {
+        &self.profiles
+    }
+
+    /// Traffic factors added with `add_traffic_factor`
+    pub fn get_traffic_factors(&self) -> &TrafficFactors {
+        &self.traffic_factors
+    }
+
+    /// Standard deviations of driving times set with `update_spreads`
+    pub fn get_spreads(&self) -> &DrivingTimesMap {
+        &self.spreads
+    }
+
+    /// Get the driving time specific to `vehicle_class`, if known
+    fn get_vehicle_class_driving_time(
+        &self,
+        from: Terminal,
+        to: Terminal,
+        vehicle_class: Option<VehicleClass>,
+    ) -> Option<NonNegativeTimeDelta> {
+        self.vehicle_class_data
+            .get(&vehicle_class?)?
+            .get(&(from, to))
+            .copied()
+    }
+
+    /// Make driving time from `from` to `to` depend on the time of departure.
+    /// `profile` needs to be non-empty, and its bucket starts need to be within a day
+    pub fn set_profile(&mut self, from: Terminal, to: Terminal, mut profile: DrivingTimeProfile) {
+        assert!(!profile.is_empty());
+        assert!(profile
+            .iter()
+            .all(|(bucket_start, _)| *bucket_start < SECONDS_PER_DAY));
+        profile.sort();
+        self.profiles.insert((from, to), profile);
+    }
+
+    /// Get the driving time from `from` to `to` regardless of the time of departure,
+    /// which skips the time-dependent source
+    pub fn get_driving_time(
+        &self,
+        from: Terminal,
+        to: Terminal,
+        vehicle_class: Option<VehicleClass>,
+    ) -> DrivingTimeResult<NonNegativeTimeDelta> {
+        match self.find_driving_time(from, to, vehicle_class, false)? {
+            FoundDrivingTime::Fixed(out) => Ok(out),
+            FoundDrivingTime::Profile(_) => unreachable!(),
+        }
+    }
+
+    /// Scale the driving time from `from` to `to` by `factor` when departing within
+    /// `interval`, overriding factors added before. `factor` needs to be positive
+    pub fn add_traffic_factor(
+        &mut self,
+        from: Terminal,
+        to: Terminal,
+        factor: f64,
+        interval: Interval,
+    ) {
+        assert!(factor.is_finite() && factor > 0.0);
+        self.traffic_factors
             .entry((from, to))
-            .or_insert_with(|| {
-                // TODO: add a way to do this
-                unimplemented!(
-                    "Being able to get driving times on-demand hasn't been implemented yet. Requested driving time {:?}->{:?}", from, to
-                );
+            .or_default()
+            .push((interval, factor));
+    }
+
+    /// Replace the standard deviations of driving times for the pairs of terminals
+    /// in `spreads`, keeping the others
+    pub fn update_spreads(&mut self, spreads: DrivingTimesMap) {
+        self.spreads.extend(spreads);
+    }
+
+    /// Standard deviation of the driving time from `from` to `to`,
+    /// which is 0 unless set with `update_spreads`
+    pub fn get_spread(&self, from: Terminal, to: Terminal) -> NonNegativeTimeDelta {
+        self.spreads.get(&(from, to)).copied().unwrap_or(0)
+    }
+
+    /// Factor by which the driving time from `from` to `to` is scaled
+    /// when departing at `departure_time`
+    fn get_traffic_factor(&self, from: Terminal, to: Terminal, departure_time: Time) -> f64 {
+        self.traffic_factors
+            .get(&(from, to))
+            .and_then(|factors| {
+                factors.iter().rev().find(|(interval, _)| {
+                    interval.get_start_time() <= departure_time
+                        && departure_time < interval.get_end_time()
+                })
             })
-            .to_owned();
+            .map_or(1.0, |(_, factor)| *factor)
+    }
 
-        out
+    /// Get the driving time from `from` to `to` when departing at `departure_time`
+    pub fn get_driving_time_at(
+        &self,
+        from: Terminal,
+        to: Terminal,
+        vehicle_class: Option<VehicleClass>,
+        departure_time: Time,
+    ) -> DrivingTimeResult<NonNegativeTimeDelta> {
+        let driving_time =
+            self.get_untrafficked_driving_time_at(from, to, vehicle_class, departure_time)?;
+        let factor = self.get_traffic_factor(from, to, departure_time);
+        Ok((driving_time as f64 * factor).round() as NonNegativeTimeDelta)
+    }
+
+    /// Get the driving time from `from` to `to` when departing at `departure_time`,
+    /// ignoring traffic factors
+    fn get_untrafficked_driving_time_at(
+        &self,
+        from: Terminal,
+        to: Terminal,
+        vehicle_class: Option<VehicleClass>,
+        departure_time: Time,
+    ) -> DrivingTimeResult<NonNegativeTimeDelta> {
+        match self.find_driving_time(from, to, vehicle_class, true)? {
+            FoundDrivingTime::Profile(profile) => {
+                let time_of_day = departure_time % SECONDS_PER_DAY;
+                // Last bucket starting before `time_of_day`; if there is none,
+                // we are still in the last bucket of the previous day
+                Ok(profile
+                    .iter()
+                    .rev()
+                    .find(|(bucket_start, _)| *bucket_start <= time_of_day)
+                    .unwrap_or(profile.last().unwrap())
+                    .1)
+            }
+            FoundDrivingTime::Fixed(out) => Ok(out),
+        }
+    }
+
+    /// Finds a time `t` so that departing from `from` before `t` means arriving
+    /// at `to` before `arrival_time`. If driving times do not depend on time of day,
+    /// this is simply `arrival_time` minus the driving time
+    pub fn get_latest_departure_time(
+        &self,
+        from: Terminal,
+        to: Terminal,
+        vehicle_class: Option<VehicleClass>,
+        arrival_time: Time,
+    ) -> DrivingTimeResult<Time> {
+        let latest_departure_time =
+            self.get_untrafficked_latest_departure_time(from, to, vehicle_class, arrival_time)?;
+        let Some(factors) = self.traffic_factors.get(&(from, to)) else {
+            return Ok(latest_departure_time);
+        };
+
+        // Be pessimistic, and use the largest factor of the time windows
+        // in which we might depart
+        let driving_time = (arrival_time - latest_departure_time) as f64;
+        let max_factor = factors
+            .iter()
+            .map(|(_, factor)| *factor)
+            .fold(1.0, f64::max);
+        let earliest_departure_time =
+            arrival_time.saturating_sub((driving_time * max_factor).ceil() as Time);
+        let factor = factors
+            .iter()
+            .filter(|(interval, _)| {
+                interval.get_start_time() < arrival_time
+                    && earliest_departure_time < interval.get_end_time()
+            })
+            .map(|(_, factor)| *factor)
+            .fold(1.0, f64::max);
+        Ok(arrival_time.saturating_sub((driving_time * factor).ceil() as Time))
+    }
+
+    /// Like `get_latest_departure_time`, ignoring traffic factors
+    fn get_untrafficked_latest_departure_time(
+        &self,
+        from: Terminal,
+        to: Terminal,
+        vehicle_class: Option<VehicleClass>,
+        arrival_time: Time,
+    ) -> DrivingTimeResult<Time> {
+        let profile = match self.find_driving_time(from, to, vehicle_class, true)? {
+            FoundDrivingTime::Profile(profile) => profile,
+            FoundDrivingTime::Fixed(driving_time) => {
+                return Ok(arrival_time.saturating_sub(driving_time))
+            }
+        };
+
+        // Walk the time buckets backwards in time, starting from the one containing
+        // `arrival_time`. The first bucket that contains a departure time which
+        // gets us there in time contains the latest such departure time
+        let mut day_start = arrival_time - arrival_time % SECONDS_PER_DAY;
+        let mut index = profile
+            .iter()
+            .rposition(|(bucket_start, _)| day_start + bucket_start <= arrival_time);
+        let mut bucket_end = arrival_time;
+
+        // Avoid looping forever if driving takes longer than a few days
+        for _ in 0..(3 * profile.len()) {
+            let bucket_index = match index {
+                Some(bucket_index) => bucket_index,
+                None => {
+                    // Go to the last bucket of the previous day
+                    let Some(previous_day_start) = day_start.checked_sub(SECONDS_PER_DAY) else {
+                        break;
+                    };
+                    day_start = previous_day_start;
+                    profile.len() - 1
+                }
+            };
+            let (bucket_start, driving_time) = profile[bucket_index];
+            let bucket_start = day_start + bucket_start;
+
+            // Departing at the start of the bucket still takes its driving time
+            if let Some(latest_departure_time) = arrival_time
+                .checked_sub(driving_time)
+                .map(|departure_time| departure_time.min(bucket_end))
+                .filter(|departure_time| *departure_time >= bucket_start)
+            {
+                return Ok(latest_departure_time);
+            }
+
+            bucket_end = bucket_start;
+            index = bucket_index.checked_sub(1);
+        }
+
+        // Fall back to the most pessimistic driving time
+        let max_driving_time = profile.iter().map(|(_, time)| *time).max().unwrap();
+        Ok(arrival_time.saturating_sub(max_driving_time))
     }
 }
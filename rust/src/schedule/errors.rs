@@ -0,0 +1,46 @@
+use pyo3::{create_exception, exceptions::PyTypeError};
+
+// NOTE: the base class derives from TypeError, which used to be raised for
+// all errors, so that existing `except TypeError` handlers keep working
+create_exception!(
+    chameleon_rust,
+    ChameleonError,
+    PyTypeError,
+    "Base class of the errors raised by chameleon_rust"
+);
+create_exception!(
+    chameleon_rust,
+    InvalidIntervalError,
+    ChameleonError,
+    "An interval ends before it starts"
+);
+create_exception!(
+    chameleon_rust,
+    UnknownTerminalError,
+    ChameleonError,
+    "A terminal id is not one of the generator's terminals"
+);
+create_exception!(
+    chameleon_rust,
+    UnknownIdError,
+    ChameleonError,
+    "A truck, cargo or driver id is not known to the generator"
+);
+create_exception!(
+    chameleon_rust,
+    MissingDrivingTimeError,
+    ChameleonError,
+    "A driving time is needed, but is not known and can't be estimated"
+);
+create_exception!(
+    chameleon_rust,
+    InfeasibleScheduleError,
+    ChameleonError,
+    "A schedule given by the user violates its constraints"
+);
+create_exception!(
+    chameleon_rust,
+    InfeasibleBookingError,
+    ChameleonError,
+    "A booking can't be done within the planning period, see `ScheduleGenerator.rejected_bookings`"
+);
@@ -0,0 +1,93 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::mem::size_of;
+use std::sync::Arc;
+
+use chrono::NaiveTime;
+
+use super::common_types::{Cargo, Driver, Location, Qualification, Terminal, Truck, VehicleClass};
+use super::py_time::{PyDate, PyTime};
+
+/// Approximate number of bytes a value owns on the heap, for `memory_stats`.
+/// Collections count the space of their entries, but not their own bookkeeping
+pub trait HeapSize {
+    fn heap_size(&self) -> usize;
+}
+
+/// Implements `HeapSize` for types that don't own heap memory
+macro_rules! impl_no_heap_size {
+    ($($type:ty),*) => {
+        $(
+            impl HeapSize for $type {
+                fn heap_size(&self) -> usize {
+                    0
+                }
+            }
+        )*
+    };
+}
+
+impl_no_heap_size!(
+    (),
+    bool,
+    u32,
+    u64,
+    usize,
+    f64,
+    Terminal,
+    Cargo,
+    Truck,
+    Qualification,
+    VehicleClass,
+    Driver,
+    Location,
+    PyTime,
+    PyDate,
+    NaiveTime
+);
+
+impl<A: HeapSize, B: HeapSize> HeapSize for (A, B) {
+    fn heap_size(&self) -> usize {
+        self.0.heap_size() + self.1.heap_size()
+    }
+}
+
+impl<T: HeapSize> HeapSize for Option<T> {
+    fn heap_size(&self) -> usize {
+        self.as_ref().map_or(0, T::heap_size)
+    }
+}
+
+impl HeapSize for String {
+    fn heap_size(&self) -> usize {
+        self.capacity()
+    }
+}
+
+impl<T: HeapSize> HeapSize for Vec<T> {
+    fn heap_size(&self) -> usize {
+        self.capacity() * size_of::<T>() + self.iter().map(T::heap_size).sum::<usize>()
+    }
+}
+
+// NOTE: counted in full by each owner, even if shared
+impl<T: HeapSize> HeapSize for Arc<T> {
+    fn heap_size(&self) -> usize {
+        size_of::<T>() + (**self).heap_size()
+    }
+}
+
+impl<K: HeapSize, V: HeapSize> HeapSize for BTreeMap<K, V> {
+    fn heap_size(&self) -> usize {
+        self.iter()
+            .map(|(key, value)| size_of::<(K, V)>() + key.heap_size() + value.heap_size())
+            .sum()
+    }
+}
+
+impl<T: HeapSize> HeapSize for BTreeSet<T> {
+    fn heap_size(&self) -> usize {
+        self.iter()
+            .map(|item| size_of::<T>() + item.heap_size())
+            .sum()
+    }
+}
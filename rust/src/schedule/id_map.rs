@@ -0,0 +1,424 @@
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use super::common_types::IsID;
+use super::heap_size::HeapSize;
+
+/// A map from internal ids, which are handed out densely by `CounterMapper`,
+/// stored in a `Vec` indexed by id for O(1) access.
+/// Like a `BTreeMap`, it iterates in ascending order of id
+#[derive(Clone, Debug)]
+pub struct IdMap<K, V> {
+    entries: Vec<Option<(K, V)>>,
+    len: usize,
+}
+
+impl<K: IsID, V> IdMap<K, V> {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        let (_key, value) = self.entries.get(key.get_id())?.as_ref()?;
+        Some(value)
+    }
+
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        let (_key, value) = self.entries.get_mut(key.get_id())?.as_mut()?;
+        Some(value)
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Sets the value of `key`, returning its old value if it had one
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let id = key.get_id();
+        if id >= self.entries.len() {
+            self.entries.resize_with(id + 1, || None);
+        }
+        let old_value = self.entries[id]
+            .replace((key, value))
+            .map(|(_key, value)| value);
+        if old_value.is_none() {
+            self.len += 1;
+        }
+        old_value
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let (_key, value) = self.entries.get_mut(key.get_id())?.take()?;
+        self.len -= 1;
+        Some(value)
+    }
+
+    /// The value of `key`, inserting `default()` first if it has none
+    pub fn get_or_insert_with(&mut self, key: K, default: impl FnOnce() -> V) -> &mut V {
+        let id = key.get_id();
+        if !self.contains_key(&key) {
+            self.insert(key, default());
+        }
+        let (_key, value) = self.entries[id].as_mut().unwrap();
+        value
+    }
+
+    pub fn retain(&mut self, mut keep: impl FnMut(&K, &mut V) -> bool) {
+        for entry in self.entries.iter_mut() {
+            if let Some((key, value)) = entry {
+                if !keep(key, value) {
+                    *entry = None;
+                    self.len -= 1;
+                }
+            }
+        }
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (&K, &V)> + Clone {
+        self.entries
+            .iter()
+            .filter_map(|entry| entry.as_ref().map(|(key, value)| (key, value)))
+    }
+
+    pub fn keys(&self) -> impl DoubleEndedIterator<Item = &K> + Clone {
+        self.iter().map(|(key, _value)| key)
+    }
+
+    pub fn values(&self) -> impl DoubleEndedIterator<Item = &V> + Clone {
+        self.iter().map(|(_key, value)| value)
+    }
+}
+
+impl<K: HeapSize, V: HeapSize> HeapSize for IdMap<K, V> {
+    fn heap_size(&self) -> usize {
+        self.entries.heap_size()
+    }
+}
+
+impl<K: IsID, V> Default for IdMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: IsID, V> FromIterator<(K, V)> for IdMap<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut out = Self::new();
+        for (key, value) in iter {
+            out.insert(key, value);
+        }
+        out
+    }
+}
+
+// NOTE: compared and hashed by their entries, since removing an item
+// can leave unused slots at the end of `entries`
+impl<K: IsID + PartialEq, V: PartialEq> PartialEq for IdMap<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+impl<K: IsID + Eq, V: Eq> Eq for IdMap<K, V> {}
+
+impl<K: IsID + Hash, V: Hash> Hash for IdMap<K, V> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.len.hash(state);
+        for (key, value) in self.iter() {
+            key.hash(state);
+            value.hash(state);
+        }
+    }
+}
+
+/// An `IdMap` whose values are shared between its clones until they are changed,
+/// so that cloning it only copies pointers. Getting a shared value mutably copies it
+#[derive(Clone, Debug)]
+pub struct SharedIdMap<K, V>(IdMap<K, Arc<V>>);
+
+impl<K: IsID, V: Clone> SharedIdMap<K, V> {
+    pub fn new() -> Self {
+        Self(IdMap::new())
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.0.get(key).map(|value| &**value)
+    }
+
+    /// The value of `key` to change, copied first if it is shared with a clone
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        self.0.get_mut(key).map(Arc::make_mut)
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.0.contains_key(key)
+    }
+
+    /// Sets the value of `key`, returning its old value if it had one
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.0
+            .insert(key, Arc::new(value))
+            .map(Arc::unwrap_or_clone)
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        self.0.remove(key).map(Arc::unwrap_or_clone)
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (&K, &V)> + Clone {
+        self.0.iter().map(|(key, value)| (key, &**value))
+    }
+
+    pub fn keys(&self) -> impl DoubleEndedIterator<Item = &K> + Clone {
+        self.0.keys()
+    }
+
+    pub fn values(&self) -> impl DoubleEndedIterator<Item = &V> + Clone {
+        self.0.values().map(|value| &**value)
+    }
+}
+
+// NOTE: values shared with clones are counted by each of them
+impl<K: HeapSize, V: HeapSize> HeapSize for SharedIdMap<K, V> {
+    fn heap_size(&self) -> usize {
+        self.0.heap_size()
+    }
+}
+
+impl<K: IsID, V: Clone> Default for SharedIdMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: IsID, V: Clone> FromIterator<(K, V)> for SharedIdMap<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Self(
+            iter.into_iter()
+                .map(|(key, value)| (key, Arc::new(value)))
+                .collect(),
+        )
+    }
+}
+
+impl<K: IsID + PartialEq, V: PartialEq> PartialEq for SharedIdMap<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<K: IsID + Eq, V: Eq> Eq for SharedIdMap<K, V> {}
+
+impl<K: IsID + Hash, V: Hash> Hash for SharedIdMap<K, V> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+/// A set of internal ids, stored as a bitset indexed by id, so checking whether
+/// it contains an id is a bit lookup and missing ids can be found a word at a time.
+/// Like a `BTreeSet`, it iterates in ascending order of id
+#[derive(Clone, Debug)]
+pub struct IdSet<K> {
+    words: Vec<u64>,
+    len: usize,
+    _key: PhantomData<K>,
+}
+
+impl<K: IsID> IdSet<K> {
+    /// An empty set with room for ids below `capacity`. Larger ids grow it when inserted
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            words: vec![0; capacity.div_ceil(64)],
+            len: 0,
+            _key: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn contains(&self, key: &K) -> bool {
+        let id = key.get_id();
+        self.words
+            .get(id / 64)
+            .is_some_and(|word| word >> (id % 64) & 1 == 1)
+    }
+
+    /// Adds `key`, returning whether it was newly added
+    pub fn insert(&mut self, key: K) -> bool {
+        let id = key.get_id();
+        if id / 64 >= self.words.len() {
+            self.words.resize(id / 64 + 1, 0);
+        }
+        let bit = 1 << (id % 64);
+        let added = self.words[id / 64] & bit == 0;
+        self.words[id / 64] |= bit;
+        self.len += added as usize;
+        added
+    }
+
+    /// Removes `key`, returning whether it was in the set
+    pub fn remove(&mut self, key: &K) -> bool {
+        let id = key.get_id();
+        let Some(word) = self.words.get_mut(id / 64) else {
+            return false;
+        };
+        let bit = 1 << (id % 64);
+        let removed = *word & bit != 0;
+        *word &= !bit;
+        self.len -= removed as usize;
+        removed
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = K> + '_ {
+        set_bits(self.words.iter().copied()).map(K::from_id)
+    }
+
+    /// The ids below `end` that aren't in the set, in ascending order
+    pub fn iter_missing(&self, end: usize) -> impl Iterator<Item = K> + '_ {
+        let words = (0..end.div_ceil(64)).map(|index| !self.words.get(index).copied().unwrap_or(0));
+        set_bits(words)
+            .take_while(move |id| *id < end)
+            .map(K::from_id)
+    }
+}
+
+impl<K> HeapSize for IdSet<K> {
+    fn heap_size(&self) -> usize {
+        self.words.heap_size()
+    }
+}
+
+/// Indices of the set bits of `words`, in ascending order,
+/// skipping words without any
+fn set_bits(words: impl Iterator<Item = u64>) -> impl Iterator<Item = usize> {
+    words.enumerate().flat_map(|(index, mut word)| {
+        std::iter::from_fn(move || {
+            if word == 0 {
+                return None;
+            }
+            let bit = word.trailing_zeros() as usize;
+            // Clear the lowest set bit
+            word &= word - 1;
+            Some(index * 64 + bit)
+        })
+    })
+}
+
+// NOTE: compared and hashed by their ids, since sets with the same ids
+// can have different capacities
+impl<K: IsID> PartialEq for IdSet<K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len
+            && self
+                .iter()
+                .map(|key| key.get_id())
+                .eq(other.iter().map(|key| key.get_id()))
+    }
+}
+
+impl<K: IsID> Eq for IdSet<K> {}
+
+impl<K: IsID> Hash for IdSet<K> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.len.hash(state);
+        for key in self.iter() {
+            key.get_id().hash(state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::schedule::common_types::Cargo;
+
+    proptest! {
+        /// Inserting and removing items behaves like a `BTreeMap`
+        #[test]
+        fn behaves_like_btree_map(
+            operations in prop::collection::vec((0..20usize, any::<Option<u8>>()), 0..50)
+        ) {
+            let mut id_map = IdMap::new();
+            let mut btree_map = BTreeMap::new();
+            for (id, value) in operations {
+                let cargo = Cargo::from_id(id);
+                match value {
+                    Some(value) => prop_assert_eq!(
+                        id_map.insert(cargo, value),
+                        btree_map.insert(cargo, value)
+                    ),
+                    None => prop_assert_eq!(id_map.remove(&cargo), btree_map.remove(&cargo)),
+                }
+                prop_assert_eq!(id_map.len(), btree_map.len());
+                prop_assert!(id_map.iter().eq(btree_map.iter()));
+            }
+            prop_assert!(id_map == btree_map.into_iter().collect());
+        }
+
+        /// Inserting and removing ids behaves like a `BTreeSet`
+        #[test]
+        fn id_set_behaves_like_btree_set(
+            capacity in 0..100usize,
+            operations in prop::collection::vec((0..200usize, any::<bool>()), 0..100)
+        ) {
+            let mut id_set = IdSet::with_capacity(capacity);
+            let mut btree_set = BTreeSet::new();
+            for (id, insert) in operations {
+                let cargo = Cargo::from_id(id);
+                if insert {
+                    prop_assert_eq!(id_set.insert(cargo), btree_set.insert(cargo));
+                } else {
+                    prop_assert_eq!(id_set.remove(&cargo), btree_set.remove(&cargo));
+                }
+                prop_assert_eq!(id_set.len(), btree_set.len());
+                prop_assert!(id_set.iter().eq(btree_set.iter().copied()));
+                prop_assert_eq!(id_set.contains(&cargo), btree_set.contains(&cargo));
+                prop_assert!(id_set
+                    .iter_missing(150)
+                    .eq((0..150).map(Cargo::from_id).filter(|cargo| !btree_set.contains(cargo))));
+            }
+        }
+
+        /// Changing a clone of a `SharedIdMap` doesn't change the original
+        #[test]
+        fn shared_values_are_copied_on_write(
+            values in prop::collection::vec(any::<u8>(), 0..20),
+            changed in any::<prop::sample::Index>()
+        ) {
+            prop_assume!(!values.is_empty());
+            let original: SharedIdMap<Cargo, u8> = values
+                .iter()
+                .enumerate()
+                .map(|(id, value)| (Cargo::from_id(id), *value))
+                .collect();
+            let mut clone = original.clone();
+            let cargo = Cargo::from_id(changed.index(values.len()));
+            *clone.get_mut(&cargo).unwrap() = clone.get(&cargo).unwrap().wrapping_add(1);
+            prop_assert!(original.values().eq(values.iter()));
+            prop_assert!(clone.get(&cargo) != original.get(&cargo));
+        }
+    }
+}
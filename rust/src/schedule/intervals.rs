@@ -1,16 +1,23 @@
 use std::cmp::max;
 use std::cmp::min;
+use std::ops::Index;
 
 use rand::seq::IteratorRandom;
 use rand_xoshiro::Xoshiro256PlusPlus;
+use serde::{Deserialize, Serialize, Serializer};
 
 use super::common_types::NonNegativeTimeDelta;
 use super::common_types::Time;
+use super::heap_size::HeapSize;
 
 pub type Interval = IntervalWithData<()>;
 pub type IntervalChain = IntervalWithDataChain<()>;
 
-#[derive(PartialEq, Eq, Clone, Debug, PartialOrd, Ord)]
+#[derive(PartialEq, Eq, Clone, Debug, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(
+    try_from = "IntervalFields<T>",
+    bound(deserialize = "T: Deserialize<'de> + Clone")
+)]
 /// A non-empty interval of time
 pub struct IntervalWithData<T>
 where
@@ -21,6 +28,27 @@ where
     additional_data: T,
 }
 
+/// The fields of an interval before checking that it is non-empty, for deserializing
+#[derive(Deserialize)]
+struct IntervalFields<T> {
+    start_time: Time,
+    end_time: Time,
+    additional_data: T,
+}
+
+impl<T: Clone + Eq> TryFrom<IntervalFields<T>> for IntervalWithData<T> {
+    type Error = String;
+
+    fn try_from(fields: IntervalFields<T>) -> Result<Self, Self::Error> {
+        Self::new(fields.start_time, fields.end_time, fields.additional_data).ok_or_else(|| {
+            format!(
+                "Invalid interval starting at {}, ending at {}",
+                fields.start_time, fields.end_time
+            )
+        })
+    }
+}
+
 impl<T: Clone + Eq> IntervalWithData<T> {
     /// Try to create an interval, return None if its length is non-positive
     pub fn new(start_time: Time, end_time: Time, additional_data: T) -> Option<Self> {
@@ -44,7 +72,7 @@ impl<T: Clone + Eq> IntervalWithData<T> {
     }
 
     pub fn get_duration(&self) -> NonNegativeTimeDelta {
-        (self.end_time - self.start_time).try_into().unwrap()
+        self.end_time - self.start_time
     }
 
     pub fn get_additional_data(&self) -> &T {
@@ -74,7 +102,11 @@ impl<T: Clone + Eq> IntervalWithData<T> {
 }
 
 /// A list of non-overlapping intervals in an increasing order
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(
+    try_from = "Vec<IntervalWithData<T>>",
+    bound(deserialize = "T: Deserialize<'de> + Clone")
+)]
 pub struct IntervalWithDataChain<T>
 where
     T: Eq,
@@ -82,6 +114,41 @@ where
     intervals: Vec<IntervalWithData<T>>,
 }
 
+impl<T: Clone + Eq> TryFrom<Vec<IntervalWithData<T>>> for IntervalWithDataChain<T> {
+    type Error = String;
+
+    fn try_from(intervals: Vec<IntervalWithData<T>>) -> Result<Self, Self::Error> {
+        for (previous, next) in intervals.iter().zip(intervals.iter().skip(1)) {
+            if previous.end_time > next.start_time {
+                return Err(format!(
+                    "Interval starting at {} overlaps with or comes before the previous one",
+                    next.start_time
+                ));
+            }
+        }
+        Ok(Self { intervals })
+    }
+}
+
+// Serialized as just the list of intervals
+impl<T: Eq + Serialize> Serialize for IntervalWithDataChain<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.intervals.serialize(serializer)
+    }
+}
+
+impl<T: Eq + HeapSize> HeapSize for IntervalWithData<T> {
+    fn heap_size(&self) -> usize {
+        self.additional_data.heap_size()
+    }
+}
+
+impl<T: Eq + HeapSize> HeapSize for IntervalWithDataChain<T> {
+    fn heap_size(&self) -> usize {
+        self.intervals.heap_size()
+    }
+}
+
 impl<T: Clone + Eq> IntervalWithDataChain<T> {
     /// Create an empty interval chain
     pub fn new() -> Self {
@@ -101,16 +168,18 @@ impl<T: Clone + Eq> IntervalWithDataChain<T> {
     /// Create an IntervalChain that is the intersection of two IntervalChains,
     /// that is sub-intervals occurring in both. Keeps additional information of `self`
     pub fn intersect<U: Eq>(&self, other: &IntervalWithDataChain<U>) -> IntervalWithDataChain<T> {
-        // Lock-step with `other`, adding intervals if they intersect
+        // Walk both chains at the same time, adding intervals if they intersect
         let mut out = IntervalWithDataChain::new();
 
-        // Take iterators
-        let mut self_it = self.intervals.iter();
-        let mut other_it = other.intervals.iter();
+        let mut self_index = 0;
+        let mut other_index = 0;
 
         // While we have intervals left over in both
-        // https://stackoverflow.com/questions/71814858/using-while-let-with-two-variables-simultaneously#71814902
-        while let Some((self_interval, other_interval)) = self_it.next().zip(other_it.next()) {
+        while let Some((self_interval, other_interval)) = self
+            .intervals
+            .get(self_index)
+            .zip(other.intervals.get(other_index))
+        {
             // Add the intersection if they intersect
             if other_interval.end_time > self_interval.start_time
                 && self_interval.end_time > other_interval.start_time
@@ -121,17 +190,41 @@ impl<T: Clone + Eq> IntervalWithDataChain<T> {
                     additional_data: self_interval.additional_data.clone(),
                 });
             }
+
+            // The interval ending first can't intersect anything else
+            if self_interval.end_time <= other_interval.end_time {
+                self_index += 1;
+            } else {
+                other_index += 1;
+            }
+        }
+        out
+    }
+
+    /// Trims the intervals to lie within `bounds`, dropping the ones outside of it
+    pub fn clamp<U: Eq>(&self, bounds: &IntervalWithData<U>) -> IntervalWithDataChain<T> {
+        IntervalWithDataChain {
+            intervals: self
+                .intervals
+                .iter()
+                .filter_map(|interval| {
+                    IntervalWithData::new(
+                        max(interval.start_time, bounds.start_time),
+                        min(interval.end_time, bounds.end_time),
+                        interval.additional_data.clone(),
+                    )
+                })
+                .collect(),
         }
-        return out;
     }
 
     /// Checks whether all the intervals in this chain are contained in `other`
     pub fn contained_in<U: Eq>(&self, other: &IntervalWithData<U>) -> bool {
         if self.intervals.is_empty() {
-            return true;
+            true
         } else {
-            return other.start_time <= self.intervals.first().unwrap().start_time
-                && self.intervals.first().unwrap().end_time <= other.end_time;
+            other.start_time <= self.intervals.first().unwrap().start_time
+                && self.intervals.first().unwrap().end_time <= other.end_time
         }
     }
 
@@ -154,26 +247,24 @@ impl<T: Clone + Eq> IntervalWithDataChain<T> {
         let mut previous_end_time = start_time;
 
         for interval in self.intervals.iter() {
-            // Avoid creating zero-width gaps
-            if interval.start_time <= previous_end_time {
-                continue;
-            }
-            // Otherwise, add the gap [previous_end_time, interval.start_time]
-            out.push(IntervalWithData {
-                start_time: previous_end_time,
-                end_time: interval.start_time,
-                additional_data: (
-                    previous_additional_data,
-                    Some(interval.additional_data.clone()),
-                ),
-            });
-            previous_additional_data = Some(interval.additional_data.clone());
-            previous_end_time = interval.end_time;
-
             // Avoid going past end_time
-            if end_time <= interval.end_time {
+            if end_time <= previous_end_time {
                 break;
             }
+            // Avoid creating zero-width gaps
+            if interval.start_time > previous_end_time {
+                // Add the gap [previous_end_time, interval.start_time]
+                out.push(IntervalWithData {
+                    start_time: previous_end_time,
+                    end_time: min(interval.start_time, end_time),
+                    additional_data: (
+                        previous_additional_data,
+                        Some(interval.additional_data.clone()),
+                    ),
+                });
+            }
+            previous_additional_data = Some(interval.additional_data.clone());
+            previous_end_time = max(previous_end_time, interval.end_time);
         }
 
         // Could have the last interval remaining
@@ -185,20 +276,20 @@ impl<T: Clone + Eq> IntervalWithDataChain<T> {
             });
         }
 
-        return IntervalWithDataChain::from_intervals(out);
+        IntervalWithDataChain::from_intervals(out)
     }
 
     pub fn get_intervals(&self) -> &Vec<IntervalWithData<T>> {
-        return &self.intervals;
+        &self.intervals
     }
 
     pub fn get_intervals_mut(&mut self) -> &mut Vec<IntervalWithData<T>> {
-        return &mut self.intervals;
+        &mut self.intervals
     }
 
     /// Remove interval at index `index`, panic if index out of bounds
     pub fn remove(&mut self, index: usize) -> IntervalWithData<T> {
-        return self.intervals.remove(index);
+        self.intervals.remove(index)
     }
 
     /// Inserts a transition, returns true if and only if
@@ -216,17 +307,69 @@ impl<T: Clone + Eq> IntervalWithDataChain<T> {
             // occurs after the previous interval
             if index > 0 {
                 let prev = self.intervals.get(index - 1).unwrap();
-                if !(prev.end_time <= new.start_time) {
+                if prev.end_time > new.start_time {
                     return false;
                 }
             }
             self.intervals.insert(index, new);
-            return true;
+            true
         } else {
             // It should be the last interval
             self.intervals.push(new);
-            return true;
+            true
+        }
+    }
+
+    /// Moves all the intervals `delta` later in time (or earlier if negative).
+    /// Returns None if an interval would end up before time 0 or past the largest time
+    pub fn shift(&self, delta: i64) -> Option<Self> {
+        let shift_time = |time: Time| time.checked_add_signed(delta);
+        let intervals = self
+            .intervals
+            .iter()
+            .map(|interval| {
+                Some(IntervalWithData {
+                    start_time: shift_time(interval.start_time)?,
+                    end_time: shift_time(interval.end_time)?,
+                    additional_data: interval.additional_data.clone(),
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some(Self { intervals })
+    }
+
+    /// Finds the interval containing `time` using binary search, if there is one
+    pub fn find_interval_at(&self, time: Time) -> Option<&IntervalWithData<T>> {
+        let index = self
+            .intervals
+            .partition_point(|interval| interval.end_time <= time);
+        self.intervals
+            .get(index)
+            .filter(|interval| interval.start_time <= time)
+    }
+
+    /// Whether `time` is in one of the intervals
+    pub fn contains(&self, time: Time) -> bool {
+        self.find_interval_at(time).is_some()
+    }
+
+    /// Merges touching or overlapping intervals with equal additional data
+    /// into maximal intervals, sorting the intervals by start time first
+    pub fn normalize(&mut self) {
+        self.intervals.sort_by_key(|interval| interval.start_time);
+        let mut out: Vec<IntervalWithData<T>> = Vec::with_capacity(self.intervals.len());
+        for interval in self.intervals.drain(..) {
+            match out.last_mut() {
+                Some(last)
+                    if interval.start_time <= last.end_time
+                        && interval.additional_data == last.additional_data =>
+                {
+                    last.end_time = max(last.end_time, interval.end_time);
+                }
+                _ => out.push(interval),
+            }
         }
+        self.intervals = out;
     }
 
     pub fn total_length(&self) -> NonNegativeTimeDelta {
@@ -236,6 +379,32 @@ impl<T: Clone + Eq> IntervalWithDataChain<T> {
             .sum()
     }
 
+    /// Picks a time uniformly from the times in the intervals, so that longer
+    /// intervals are proportionally more likely. Returns None if the chain is empty
+    pub fn random_time(&self, rng: &mut Xoshiro256PlusPlus) -> Option<Time> {
+        // How far into the chain the time is, only counting the time contained
+        // in the intervals. For example, for intervals [1, 3), [10, 14), the value 3
+        // means we have "moved past" the 2 time steps in the first interval,
+        // and are on the 2nd time step of the second interval, i.e. time 11
+        let mut time_index = (0..self.total_length()).choose(rng)?;
+        for interval in self.intervals.iter() {
+            if time_index < interval.get_duration() {
+                return Some(interval.start_time + time_index);
+            }
+            time_index -= interval.get_duration();
+        }
+        unreachable!("The time index is less than the total length of the intervals")
+    }
+
+    /// Number of intervals in the chain
+    pub fn len(&self) -> usize {
+        self.intervals.len()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, IntervalWithData<T>> {
+        self.intervals.iter()
+    }
+
     /// Whether the total length of the intervals is 0
     pub fn is_empty(&self) -> bool {
         // since the individual intervals have a positive
@@ -245,8 +414,35 @@ impl<T: Clone + Eq> IntervalWithDataChain<T> {
     }
 }
 
+impl<'a, T: Eq> IntoIterator for &'a IntervalWithDataChain<T> {
+    type Item = &'a IntervalWithData<T>;
+    type IntoIter = std::slice::Iter<'a, IntervalWithData<T>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.intervals.iter()
+    }
+}
+
+impl<T: Eq> IntoIterator for IntervalWithDataChain<T> {
+    type Item = IntervalWithData<T>;
+    type IntoIter = std::vec::IntoIter<IntervalWithData<T>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.intervals.into_iter()
+    }
+}
+
+impl<T: Eq> Index<usize> for IntervalWithDataChain<T> {
+    type Output = IntervalWithData<T>;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.intervals[index]
+    }
+}
+
 pub trait IntervalWithDataChainIter {
-    /// Takes an iterator of IntervalWithData and returns their intersection
+    /// Takes an iterator of IntervalWithData and returns their intersection,
+    /// sweeping through all of them at the same time
     fn intersect_all<'a, T>(self) -> IntervalChain
     where
         Self: Iterator<Item = &'a IntervalWithDataChain<T>> + Sized,
@@ -262,14 +458,179 @@ where
         Self: Iterator<Item = &'a IntervalWithDataChain<T>> + Sized,
         T: Clone + Eq + 'a,
     {
-        let largest_interval = Interval {
-            start_time: Time::MIN,
-            end_time: Time::MAX,
-            additional_data: (),
-        };
-        let empty_intersection = IntervalWithDataChain::from_interval(largest_interval);
-        self.fold(empty_intersection, |intervals1, intervals2| {
-            intervals1.intersect(&intervals2)
-        })
+        let chains: Vec<&IntervalWithDataChain<T>> = self.collect();
+        if chains.is_empty() {
+            // The intersection of nothing is everything
+            return IntervalChain::from_interval(Interval {
+                start_time: Time::MIN,
+                end_time: Time::MAX,
+                additional_data: (),
+            });
+        }
+
+        // Sweep all the chains at the same time, keeping the index of the
+        // current interval in each. The current intervals overlap in
+        // [latest start, earliest end), if that is non-empty
+        let mut out = vec![];
+        let mut indices = vec![0; chains.len()];
+        loop {
+            let mut start_time = Time::MIN;
+            let mut end_time = Time::MAX;
+            for (chain, index) in chains.iter().zip(indices.iter()) {
+                let Some(interval) = chain.intervals.get(*index) else {
+                    return IntervalChain::from_intervals(out);
+                };
+                start_time = max(start_time, interval.start_time);
+                end_time = min(end_time, interval.end_time);
+            }
+            out.extend(Interval::new(start_time, end_time, ()));
+
+            // The intervals ending first can't intersect anything else
+            for (chain, index) in chains.iter().zip(indices.iter_mut()) {
+                if chain.intervals[*index].end_time == end_time {
+                    *index += 1;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    impl Arbitrary for Interval {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(_: ()) -> Self::Strategy {
+            (0..1000 as Time, 1..200 as NonNegativeTimeDelta)
+                .prop_map(|(start_time, length)| {
+                    Interval::new(start_time, start_time + length, ()).unwrap()
+                })
+                .boxed()
+        }
+    }
+
+    impl Arbitrary for IntervalChain {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        /// Builds a valid chain from the gaps before and lengths of its intervals
+        fn arbitrary_with(_: ()) -> Self::Strategy {
+            prop::collection::vec((0..100 as Time, 1..100 as NonNegativeTimeDelta), 0..10)
+                .prop_map(|gaps_and_lengths| {
+                    let mut end_time = 0;
+                    let intervals: Vec<_> = gaps_and_lengths
+                        .into_iter()
+                        .map(|(gap, length)| {
+                            let start_time = end_time + gap;
+                            end_time = start_time + length;
+                            Interval::new(start_time, end_time, ()).unwrap()
+                        })
+                        .collect();
+                    IntervalChain::try_from(intervals).unwrap()
+                })
+                .boxed()
+        }
+    }
+
+    /// Intervals in a chain are sorted and don't overlap
+    fn is_valid(chain: &IntervalChain) -> bool {
+        chain
+            .intervals
+            .windows(2)
+            .all(|pair| pair[0].end_time <= pair[1].start_time)
+    }
+
+    fn normalized(mut chain: IntervalChain) -> IntervalChain {
+        chain.normalize();
+        chain
+    }
+
+    fn chain(intervals: &[(Time, Time)]) -> IntervalChain {
+        IntervalChain::from_intervals(
+            intervals
+                .iter()
+                .map(|(start_time, end_time)| Interval::new(*start_time, *end_time, ()).unwrap())
+                .collect(),
+        )
+    }
+
+    /// An interval overlapping several intervals of the other chain intersects all
+    /// of them, e.g. a week of terminal hours with the open days of a calendar
+    #[test]
+    fn intervals_intersect_every_interval_they_overlap() {
+        let week = chain(&[(0, 70)]);
+        let days = chain(&[(0, 10), (20, 30), (40, 50), (60, 80)]);
+        let expected = chain(&[(0, 10), (20, 30), (40, 50), (60, 70)]);
+        assert_eq!(week.intersect(&days), expected);
+        assert_eq!(days.intersect(&week), expected);
+
+        // Intervals of the other chain ending first are skipped before this one
+        let shifts = chain(&[(5, 25), (45, 65)]);
+        assert_eq!(
+            shifts.intersect(&days),
+            chain(&[(5, 10), (20, 25), (45, 50), (60, 65)])
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn intersection_commutes(a: IntervalChain, b: IntervalChain) {
+            let ab = a.intersect(&b);
+            prop_assert!(is_valid(&ab));
+            prop_assert_eq!(normalized(ab), normalized(b.intersect(&a)));
+        }
+
+        #[test]
+        fn intersection_is_contained_in_both(a: IntervalChain, b: IntervalChain, time in 0..2000 as Time) {
+            prop_assert_eq!(
+                a.intersect(&b).contains(time),
+                a.contains(time) && b.contains(time)
+            );
+        }
+
+        #[test]
+        fn intersect_all_matches_pairwise(chains: Vec<IntervalChain>) {
+            prop_assume!(!chains.is_empty());
+            let pairwise = chains[1..]
+                .iter()
+                .fold(chains[0].clone(), |acc, chain| acc.intersect(chain));
+            prop_assert_eq!(
+                normalized(chains.iter().intersect_all()),
+                normalized(pairwise)
+            );
+        }
+
+        #[test]
+        fn gaps_and_chain_cover_bounds(chain: IntervalChain, bounds: Interval) {
+            let clamped = chain.clamp(&bounds);
+            prop_assert!(clamped.contained_in(&bounds));
+
+            let gaps = chain.gaps(&bounds);
+            let gaps = IntervalChain::from_intervals(
+                gaps.iter().map(|gap| gap.remove_additional_data()).collect(),
+            );
+            prop_assert!(is_valid(&gaps));
+            prop_assert!(gaps.intersect(&chain).is_empty());
+
+            let mut union = clamped;
+            union.intervals.extend(gaps.intervals);
+            prop_assert_eq!(normalized(union), IntervalChain::from_interval(bounds));
+        }
+
+        #[test]
+        fn normalization_is_idempotent(chain: IntervalChain, shuffled: Vec<Interval>) {
+            let once = normalized(chain);
+            prop_assert_eq!(normalized(once.clone()), once);
+
+            // Also for overlapping, unsorted intervals
+            let once = normalized(IntervalChain::from_intervals(shuffled));
+            prop_assert!(is_valid(&once));
+            prop_assert_eq!(normalized(once.clone()), once);
+        }
     }
 }
@@ -1,5 +1,21 @@
+mod annealing;
+pub mod benchmark;
+mod calendar;
+mod capacity;
+mod cargo_set;
 mod common_types;
 mod counter_mapper;
+mod driving_time_providers;
 mod driving_times_cache;
+pub mod errors;
+mod heap_size;
+mod id_map;
 pub mod intervals;
+mod py_matrix;
+mod py_table;
+mod py_time;
+mod recurring_pattern;
+mod routes;
+#[allow(clippy::module_inception)]
 pub mod schedule;
+mod timezones;
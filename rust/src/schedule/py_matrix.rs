@@ -0,0 +1,138 @@
+use pyo3::{
+    buffer::{Element, PyBuffer},
+    exceptions::PyTypeError,
+    Bound, FromPyObject, PyAny, PyResult,
+};
+
+/// A 2-D array of non-negative integers, e.g. a numpy array, read through the buffer
+/// protocol without converting it to python lists. Floats are rounded
+pub struct PyMatrix {
+    pub num_rows: usize,
+    pub num_columns: usize,
+    /// The values in row-major order
+    values: Vec<u64>,
+}
+
+impl PyMatrix {
+    pub fn get(&self, row: usize, column: usize) -> u64 {
+        self.values[row * self.num_columns + column]
+    }
+}
+
+impl<'py> FromPyObject<'py> for PyMatrix {
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        let to_integer =
+            |value: f64| (value.is_finite() && value >= 0.0).then(|| value.round() as u64);
+        read_buffer::<u64>(ob, Some)
+            .or_else(|| read_buffer::<i64>(ob, |value| u64::try_from(value).ok()))
+            .or_else(|| read_buffer::<u32>(ob, |value| Some(value.into())))
+            .or_else(|| read_buffer::<i32>(ob, |value| u64::try_from(value).ok()))
+            .or_else(|| read_buffer::<f64>(ob, to_integer))
+            .or_else(|| read_buffer::<f32>(ob, |value| to_integer(value.into())))
+            .unwrap_or_else(|| {
+                Err(PyTypeError::new_err(
+                    "Expected a 2-D array of integers or floats supporting the buffer protocol",
+                ))
+            })
+    }
+}
+
+/// Reads `ob` as a matrix of `T`, converting its values with `convert`.
+/// Returns None if `ob` doesn't give a buffer of `T`
+fn read_buffer<T: Element + Copy>(
+    ob: &Bound<'_, PyAny>,
+    convert: impl Fn(T) -> Option<u64>,
+) -> Option<PyResult<PyMatrix>> {
+    let buffer = PyBuffer::<T>::get(ob).ok()?;
+    let &[num_rows, num_columns] = buffer.shape() else {
+        return Some(Err(PyTypeError::new_err(format!(
+            "Expected a 2-D array, got one with {} dimensions",
+            buffer.dimensions()
+        ))));
+    };
+    Some(buffer.to_vec(ob.py()).and_then(|values| {
+        let values = values
+            .into_iter()
+            .enumerate()
+            .map(|(index, value)| {
+                convert(value).ok_or_else(|| {
+                    PyTypeError::new_err(format!(
+                        "Value at row {}, column {} is not a non-negative number",
+                        index / num_columns,
+                        index % num_columns
+                    ))
+                })
+            })
+            .collect::<PyResult<_>>()?;
+        Ok(PyMatrix {
+            num_rows,
+            num_columns,
+            values,
+        })
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CString;
+
+    use pyo3::{types::PyAnyMethods, Python};
+
+    use super::*;
+
+    /// A python buffer of `values` with struct format `format` and `shape`,
+    /// like a numpy array of the corresponding dtype
+    fn buffer<'py>(py: Python<'py>, format: &str, values: &str, shape: &str) -> Bound<'py, PyAny> {
+        let code = format!(
+            "memoryview(__import__('array').array('{format}', {values})).cast('B').cast('{format}', {shape})"
+        );
+        py.eval(&CString::new(code).unwrap(), None, None).unwrap()
+    }
+
+    #[test]
+    fn matrices_of_all_number_types_are_read() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            for format in ["Q", "q", "I", "i", "d", "f"] {
+                let matrix: PyMatrix = buffer(py, format, "[0, 1, 2, 3, 4, 5]", "[2, 3]")
+                    .extract()
+                    .unwrap();
+                assert_eq!((matrix.num_rows, matrix.num_columns), (2, 3));
+                assert_eq!(matrix.get(1, 0), 3);
+                assert_eq!(matrix.get(1, 2), 5);
+            }
+            // Floats are rounded
+            let matrix: PyMatrix = buffer(py, "d", "[0.4, 1.6, 2.5, 3.0]", "[2, 2]")
+                .extract()
+                .unwrap();
+            assert_eq!(matrix.values, vec![0, 2, 3, 3]);
+        });
+    }
+
+    #[test]
+    fn invalid_matrices_are_rejected() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            for (format, values) in [
+                ("q", "[0, 1, -2, 3]"),
+                ("i", "[0, 1, -2, 3]"),
+                ("d", "[0, 1, -2, 3]"),
+                ("d", "[0, 1, float('nan'), 3]"),
+                ("f", "[0, 1, float('inf'), 3]"),
+            ] {
+                let err = buffer(py, format, values, "[2, 2]")
+                    .extract::<PyMatrix>()
+                    .err()
+                    .unwrap();
+                assert!(err.to_string().contains("row 1, column 0"), "{err}");
+            }
+            // Only 2-D arrays of numbers are accepted
+            assert!(buffer(py, "q", "[0, 1, 2, 3]", "[4]")
+                .extract::<PyMatrix>()
+                .is_err());
+            assert!(buffer(py, "b", "[0, 1, 2, 3]", "[2, 2]")
+                .extract::<PyMatrix>()
+                .is_err());
+        });
+    }
+}
@@ -0,0 +1,670 @@
+use std::collections::{btree_map::Entry, BTreeMap, BTreeSet};
+
+use arrow_array::{
+    cast::AsArray,
+    ffi::{from_ffi, FFI_ArrowArray, FFI_ArrowSchema},
+    ffi_stream::ArrowArrayStreamReader,
+    types::{
+        Date32Type, Date64Type, DurationMicrosecondType, DurationMillisecondType,
+        DurationNanosecondType, DurationSecondType, Float32Type, Float64Type, Int16Type, Int32Type,
+        Int64Type, Int8Type, TimestampMicrosecondType, TimestampMillisecondType,
+        TimestampNanosecondType, TimestampSecondType, UInt16Type, UInt32Type, UInt64Type,
+        UInt8Type,
+    },
+    Array, ArrayRef, ArrowPrimitiveType, RecordBatchReader, StructArray,
+};
+use arrow_schema::{ArrowError, DataType, TimeUnit};
+use pyo3::{
+    exceptions::PyTypeError,
+    types::{
+        PyAnyMethods, PyBool, PyCapsule, PyCapsuleMethods, PyDict, PyDictMethods, PyList, PyString,
+        PyTuple,
+    },
+    Bound, FromPyObject, PyAny, PyErr, PyResult,
+};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{Map, Number, Value};
+
+use super::common_types::SECONDS_PER_DAY;
+use super::py_time::PyTime;
+
+/// Items given by python either as a list, or as the rows of a pandas `DataFrame`
+/// or a pyarrow `Table` or `RecordBatch` whose columns are named after their fields.
+/// Missing values (None, NaN or NaT) take the fields' defaults.
+/// Tables exporting their columns through the Arrow PyCapsule interface, e.g. pyarrow
+/// tables and record batches and pandas `DataFrame`s with pyarrow installed, are read
+/// without making a python object for each value. Other tables, and pandas `DataFrame`s
+/// whose columns pyarrow can't convert, e.g. of tuples, are read through `to_dict`
+pub struct PyRows<T>(pub Vec<T>);
+
+/// Like `PyRows`, for items given as a dict from their id, or as rows
+/// with the id in the "id" column
+pub struct PyKeyedRows<T>(pub BTreeMap<String, T>);
+
+/// A row of a table with an "id" column, see `PyKeyedRows`
+#[derive(Deserialize)]
+struct KeyedRow<T> {
+    id: String,
+    #[serde(flatten)]
+    value: T,
+}
+
+impl<'py, T: FromPyObject<'py> + DeserializeOwned> FromPyObject<'py> for PyRows<T> {
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        match get_table_columns(ob)? {
+            Some(columns) => Ok(Self(read_rows(columns)?)),
+            None => Ok(Self(ob.extract()?)),
+        }
+    }
+}
+
+impl<'py, T: FromPyObject<'py> + DeserializeOwned> FromPyObject<'py> for PyKeyedRows<T> {
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        let Some(columns) = get_table_columns(ob)? else {
+            return Ok(Self(ob.extract()?));
+        };
+        let mut out = BTreeMap::new();
+        let mut duplicate_ids = BTreeSet::new();
+        for row in read_rows::<KeyedRow<T>>(columns)? {
+            match out.entry(row.id) {
+                Entry::Occupied(entry) => {
+                    duplicate_ids.insert(entry.key().clone());
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(row.value);
+                }
+            }
+        }
+        if !duplicate_ids.is_empty() {
+            return Err(PyTypeError::new_err(format!(
+                "There is more than one row with id {duplicate_ids:?}"
+            )));
+        }
+        Ok(Self(out))
+    }
+}
+
+/// The columns of a table, as (column name, values)
+type Columns = Vec<(String, Vec<Value>)>;
+
+/// The columns of `ob`, or None if it isn't a table
+fn get_table_columns(ob: &Bound<'_, PyAny>) -> PyResult<Option<Columns>> {
+    if ob.is_instance_of::<PyList>() || ob.is_instance_of::<PyDict>() {
+        return Ok(None);
+    }
+    // NOTE: pandas raises if pyarrow isn't installed or can't convert a column,
+    // which the python objects still can be read from
+    if ob.hasattr("__arrow_c_stream__")? {
+        if let Ok(capsule) = ob.call_method0("__arrow_c_stream__") {
+            return read_arrow_stream(capsule.downcast()?).map(Some);
+        }
+    }
+    if ob.hasattr("__arrow_c_array__")? {
+        let capsules = ob.call_method0("__arrow_c_array__")?;
+        let (schema, array): (Bound<'_, PyCapsule>, Bound<'_, PyCapsule>) = capsules.extract()?;
+        return read_arrow_array(&schema, &array).map(Some);
+    }
+    // pyarrow tables and record batches
+    let columns = if ob.hasattr("to_pydict")? {
+        ob.call_method0("to_pydict")?
+    // pandas data frames
+    } else if ob.hasattr("to_dict")? && ob.hasattr("columns")? {
+        ob.call_method1("to_dict", ("list",))?
+    } else {
+        return Ok(None);
+    };
+    columns
+        .downcast::<PyDict>()?
+        .iter()
+        .map(|(name, values)| {
+            let values = values
+                .try_iter()?
+                .map(|value| to_json(&value?))
+                .collect::<PyResult<_>>()?;
+            Ok((name.extract()?, values))
+        })
+        .collect::<PyResult<_>>()
+        .map(Some)
+}
+
+/// Deserializes each row of `columns`, as if it was a dict from column name to value
+fn read_rows<T: DeserializeOwned>(columns: Columns) -> PyResult<Vec<T>> {
+    let mut rows: Option<Vec<Map<String, Value>>> = None;
+    for (name, values) in columns {
+        let rows = rows.get_or_insert_with(|| vec![Map::new(); values.len()]);
+        if rows.len() != values.len() {
+            return Err(PyTypeError::new_err(format!(
+                "Column {name:?} has {} values, but other columns have {}",
+                values.len(),
+                rows.len()
+            )));
+        }
+        for (row, value) in rows.iter_mut().zip(values) {
+            // Leave missing values out, so that they take their defaults
+            if !value.is_null() {
+                row.insert(name.clone(), value);
+            }
+        }
+    }
+
+    rows.unwrap_or_default()
+        .into_iter()
+        .enumerate()
+        .map(|(index, row)| {
+            serde_json::from_value(Value::Object(row))
+                .map_err(|err| PyTypeError::new_err(format!("Invalid row {index}: {err}")))
+        })
+        .collect()
+}
+
+fn arrow_error(err: ArrowError) -> PyErr {
+    PyTypeError::new_err(format!("Can't read the table: {err}"))
+}
+
+/// Reads the columns of a table exported as an `ArrowArrayStream` in `capsule`
+fn read_arrow_stream(capsule: &Bound<'_, PyCapsule>) -> PyResult<Columns> {
+    if capsule.name()? != Some(c"arrow_array_stream") {
+        return Err(PyTypeError::new_err(
+            "__arrow_c_stream__ didn't give an arrow_array_stream capsule",
+        ));
+    }
+    // SAFETY: capsules with this name hold an `ArrowArrayStream`. The reader moves it
+    // out, leaving a released one for the capsule to drop
+    let reader = unsafe { ArrowArrayStreamReader::from_raw(capsule.pointer().cast()) }
+        .map_err(arrow_error)?;
+    let mut columns: Columns = reader
+        .schema()
+        .fields()
+        .iter()
+        .map(|field| (field.name().clone(), vec![]))
+        .collect();
+    for batch in reader {
+        let batch = batch.map_err(arrow_error)?;
+        for ((name, values), column) in columns.iter_mut().zip(batch.columns()) {
+            values.extend(read_arrow_column(name, column.as_ref())?);
+        }
+    }
+    Ok(columns)
+}
+
+/// Reads the columns of a record batch exported as an `ArrowSchema` and an `ArrowArray`
+/// of structs in `schema` and `array`
+fn read_arrow_array(
+    schema: &Bound<'_, PyCapsule>,
+    array: &Bound<'_, PyCapsule>,
+) -> PyResult<Columns> {
+    if schema.name()? != Some(c"arrow_schema") || array.name()? != Some(c"arrow_array") {
+        return Err(PyTypeError::new_err(
+            "__arrow_c_array__ didn't give arrow_schema and arrow_array capsules",
+        ));
+    }
+    // SAFETY: capsules with these names hold an `ArrowSchema` and an `ArrowArray`.
+    // The array is moved out, leaving a released one for the capsule to drop
+    let data = unsafe {
+        let array = std::ptr::replace(array.pointer().cast(), FFI_ArrowArray::empty());
+        from_ffi(array, schema.reference::<FFI_ArrowSchema>())
+    }
+    .map_err(arrow_error)?;
+    let DataType::Struct(fields) = data.data_type().clone() else {
+        return Err(PyTypeError::new_err(
+            "__arrow_c_array__ didn't give an array of rows",
+        ));
+    };
+    let batch = StructArray::from(data);
+    fields
+        .iter()
+        .zip(batch.columns())
+        .map(|(field, column)| {
+            let values = read_arrow_column(field.name(), column.as_ref())?;
+            Ok((field.name().clone(), values))
+        })
+        .collect()
+}
+
+fn read_arrow_column(name: &str, column: &dyn Array) -> PyResult<Vec<Value>> {
+    arrow_to_json(column)
+        .map_err(|err| PyTypeError::new_err(format!("Can't read column {name:?}: {err}")))
+}
+
+/// Converts the values of an Arrow array to JSON, like `to_json`
+fn arrow_to_json(array: &dyn Array) -> Result<Vec<Value>, String> {
+    fn primitive<T: ArrowPrimitiveType>(
+        array: &dyn Array,
+        convert: impl Fn(T::Native) -> Result<Value, String>,
+    ) -> Result<Vec<Value>, String> {
+        array
+            .as_primitive::<T>()
+            .iter()
+            .map(|value| value.map_or(Ok(Value::Null), &convert))
+            .collect()
+    }
+    fn number(value: impl Into<Value>) -> Result<Value, String> {
+        Ok(value.into())
+    }
+    // Times are given in seconds since the unix epoch, rounded down
+    fn time(value: i64, per_second: i64) -> Result<Value, String> {
+        Ok(Value::from(value.div_euclid(per_second)))
+    }
+    // Durations are given in seconds, rounded to the nearest second as for `PyTimeDelta`
+    fn duration(value: i64, per_second: i64) -> Result<Value, String> {
+        Ok(Value::from((value + per_second / 2).div_euclid(per_second)))
+    }
+
+    if let Some(dictionary) = array.as_any_dictionary_opt() {
+        let values = arrow_to_json(dictionary.values().as_ref())?;
+        let keys = dictionary.keys();
+        return Ok(dictionary
+            .normalized_keys()
+            .into_iter()
+            .enumerate()
+            .map(|(index, key)| {
+                if keys.is_null(index) {
+                    Value::Null
+                } else {
+                    values[key].clone()
+                }
+            })
+            .collect());
+    }
+
+    match array.data_type() {
+        DataType::Null => Ok(vec![Value::Null; array.len()]),
+        DataType::Boolean => Ok(array
+            .as_boolean()
+            .iter()
+            .map(|value| value.map_or(Value::Null, Value::Bool))
+            .collect()),
+        DataType::Int8 => primitive::<Int8Type>(array, number),
+        DataType::Int16 => primitive::<Int16Type>(array, number),
+        DataType::Int32 => primitive::<Int32Type>(array, number),
+        DataType::Int64 => primitive::<Int64Type>(array, number),
+        DataType::UInt8 => primitive::<UInt8Type>(array, number),
+        DataType::UInt16 => primitive::<UInt16Type>(array, number),
+        DataType::UInt32 => primitive::<UInt32Type>(array, number),
+        DataType::UInt64 => primitive::<UInt64Type>(array, number),
+        DataType::Float32 => primitive::<Float32Type>(array, |value| float_to_json(value.into())),
+        DataType::Float64 => primitive::<Float64Type>(array, float_to_json),
+        DataType::Timestamp(TimeUnit::Second, _) => {
+            primitive::<TimestampSecondType>(array, |value| time(value, 1))
+        }
+        DataType::Timestamp(TimeUnit::Millisecond, _) => {
+            primitive::<TimestampMillisecondType>(array, |value| time(value, 1_000))
+        }
+        DataType::Timestamp(TimeUnit::Microsecond, _) => {
+            primitive::<TimestampMicrosecondType>(array, |value| time(value, 1_000_000))
+        }
+        DataType::Timestamp(TimeUnit::Nanosecond, _) => {
+            primitive::<TimestampNanosecondType>(array, |value| time(value, 1_000_000_000))
+        }
+        DataType::Date32 => primitive::<Date32Type>(array, |days| {
+            time(i64::from(days) * SECONDS_PER_DAY as i64, 1)
+        }),
+        DataType::Date64 => primitive::<Date64Type>(array, |value| time(value, 1_000)),
+        DataType::Duration(TimeUnit::Second) => {
+            primitive::<DurationSecondType>(array, |value| duration(value, 1))
+        }
+        DataType::Duration(TimeUnit::Millisecond) => {
+            primitive::<DurationMillisecondType>(array, |value| duration(value, 1_000))
+        }
+        DataType::Duration(TimeUnit::Microsecond) => {
+            primitive::<DurationMicrosecondType>(array, |value| duration(value, 1_000_000))
+        }
+        DataType::Duration(TimeUnit::Nanosecond) => {
+            primitive::<DurationNanosecondType>(array, |value| duration(value, 1_000_000_000))
+        }
+        DataType::Utf8 => Ok(strings(array.as_string::<i32>().iter())),
+        DataType::LargeUtf8 => Ok(strings(array.as_string::<i64>().iter())),
+        DataType::Utf8View => Ok(strings(array.as_string_view().iter())),
+        DataType::List(_) => lists(array.as_list::<i32>().iter()),
+        DataType::LargeList(_) => lists(array.as_list::<i64>().iter()),
+        DataType::FixedSizeList(_, _) => lists(array.as_fixed_size_list().iter()),
+        DataType::Struct(fields) => {
+            let array = array.as_struct();
+            let columns = array
+                .columns()
+                .iter()
+                .map(|column| arrow_to_json(column.as_ref()))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok((0..array.len())
+                .map(|index| {
+                    if array.is_null(index) {
+                        return Value::Null;
+                    }
+                    // Like missing values of rows, missing fields take their defaults
+                    fields
+                        .iter()
+                        .zip(&columns)
+                        .filter(|(_field, values)| !values[index].is_null())
+                        .map(|(field, values)| (field.name().clone(), values[index].clone()))
+                        .collect()
+                })
+                .collect())
+        }
+        DataType::Map(_, _) => array
+            .as_map()
+            .iter()
+            .map(|entries| {
+                let Some(entries) = entries else {
+                    return Ok(Value::Null);
+                };
+                let keys = arrow_to_json(entries.column(0).as_ref())?;
+                let values = arrow_to_json(entries.column(1).as_ref())?;
+                Ok(keys
+                    .into_iter()
+                    .map(|key| match key {
+                        Value::String(key) => key,
+                        key => key.to_string(),
+                    })
+                    .zip(values)
+                    .collect())
+            })
+            .collect(),
+        data_type => Err(format!("values of type {data_type} are not supported")),
+    }
+}
+
+fn strings<'a>(values: impl Iterator<Item = Option<&'a str>>) -> Vec<Value> {
+    values
+        .map(|value| value.map_or(Value::Null, |value| Value::String(value.to_string())))
+        .collect()
+}
+
+fn lists(values: impl Iterator<Item = Option<ArrayRef>>) -> Result<Vec<Value>, String> {
+    values
+        .map(|value| match value {
+            Some(value) => arrow_to_json(value.as_ref()).map(Value::Array),
+            None => Ok(Value::Null),
+        })
+        .collect()
+}
+
+/// Converts a number of a table to JSON. NaN is a missing value
+fn float_to_json(number: f64) -> Result<Value, String> {
+    if number.is_nan() {
+        return Ok(Value::Null);
+    }
+    // Integer columns with missing values are stored as floats
+    if number.fract() == 0.0 && number.abs() < i64::MAX as f64 {
+        return Ok(Value::from(number as i64));
+    }
+    Number::from_f64(number)
+        .map(Value::Number)
+        .ok_or_else(|| format!("Can't read the number {number} from a table"))
+}
+
+/// Converts a value of a table to JSON. Datetimes are converted to
+/// seconds since the unix epoch, as expected by `PyTime`
+fn to_json(ob: &Bound<'_, PyAny>) -> PyResult<Value> {
+    // NaN and NaT are the only values not equal to themselves
+    if ob.is_none() || ob.ne(ob)? {
+        return Ok(Value::Null);
+    }
+    // NOTE: bools are ints in python, so they are checked first
+    if ob.is_instance_of::<PyBool>() {
+        return Ok(Value::Bool(ob.extract()?));
+    }
+    if ob.is_instance_of::<PyString>() {
+        return Ok(Value::String(ob.extract()?));
+    }
+    if ob.is_instance_of::<PyList>() || ob.is_instance_of::<PyTuple>() {
+        return Ok(Value::Array(
+            ob.try_iter()?
+                .map(|item| to_json(&item?))
+                .collect::<PyResult<_>>()?,
+        ));
+    }
+    if let Ok(dict) = ob.downcast::<PyDict>() {
+        return dict
+            .iter()
+            .map(|(key, value)| Ok((key.extract::<String>()?, to_json(&value)?)))
+            .collect::<PyResult<_>>()
+            .map(Value::Object);
+    }
+    if let Ok(time) = ob.extract::<PyTime>() {
+        return Ok(Value::from(time.0));
+    }
+    // Also covers numpy integers
+    if let Ok(number) = ob.extract::<i64>() {
+        return Ok(Value::from(number));
+    }
+    if let Ok(number) = ob.extract::<f64>() {
+        return float_to_json(number).map_err(PyTypeError::new_err);
+    }
+    Err(PyTypeError::new_err(format!(
+        "Can't read the value {ob} from a table"
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::CString, sync::Arc};
+
+    use arrow_array::{
+        builder::{
+            BooleanBuilder, FixedSizeListBuilder, Int64Builder, ListBuilder, StringBuilder,
+            StringDictionaryBuilder, StructBuilder, TimestampMillisecondBuilder,
+            TimestampSecondBuilder,
+        },
+        ffi::to_ffi,
+        ffi_stream::FFI_ArrowArrayStream,
+        BinaryArray, BooleanArray, Float64Array, Int64Array, LargeStringArray, RecordBatch,
+        RecordBatchIterator, StringArray, TimestampNanosecondArray,
+    };
+    use arrow_schema::{Field, Fields};
+    use pyo3::{types::PyDictMethods, Python};
+    use serde_json::json;
+
+    use super::super::schedule::PyBooking;
+    use super::*;
+
+    const START: i64 = 1_735_689_600;
+
+    /// Bookings with columns of most of the types that can be read
+    fn bookings() -> RecordBatch {
+        let mut from_terminals = StringDictionaryBuilder::<Int32Type>::new();
+        from_terminals.append_value("A");
+        from_terminals.append_value("A");
+
+        let stop_fields = Fields::from(vec![
+            Field::new("terminal", DataType::Utf8, false),
+            Field::new(
+                "open_time",
+                DataType::Timestamp(TimeUnit::Second, None),
+                false,
+            ),
+            Field::new(
+                "close_time",
+                DataType::Timestamp(TimeUnit::Second, None),
+                false,
+            ),
+            Field::new("is_pickup", DataType::Boolean, false),
+        ]);
+        let mut stops = ListBuilder::new(StructBuilder::from_fields(stop_fields, 1));
+        let stop = stops.values();
+        stop.field_builder::<StringBuilder>(0)
+            .unwrap()
+            .append_value("C");
+        for (index, time) in [(1, START), (2, START + 3600)] {
+            stop.field_builder::<TimestampSecondBuilder>(index)
+                .unwrap()
+                .append_value(time);
+        }
+        stop.field_builder::<BooleanBuilder>(3)
+            .unwrap()
+            .append_value(true);
+        stop.append(true);
+        stops.append(true);
+        stops.append_null();
+
+        let mut windows = ListBuilder::new(FixedSizeListBuilder::new(
+            TimestampMillisecondBuilder::new(),
+            2,
+        ));
+        windows.append_null();
+        let window = windows.values();
+        window.values().append_value(START * 1000);
+        window.values().append_value((START + 3600) * 1000);
+        window.append(true);
+        windows.append(true);
+
+        let mut demands =
+            StructBuilder::from_fields(vec![Field::new("pallets", DataType::Int64, true)], 2);
+        demands
+            .field_builder::<Int64Builder>(0)
+            .unwrap()
+            .append_value(10);
+        demands.append(true);
+        demands
+            .field_builder::<Int64Builder>(0)
+            .unwrap()
+            .append_null();
+        demands.append(true);
+
+        let columns: Vec<(&str, ArrayRef)> = vec![
+            ("cargo", Arc::new(StringArray::from(vec!["C0", "C1"]))),
+            ("from_terminal", Arc::new(from_terminals.finish())),
+            (
+                "to_terminal",
+                Arc::new(LargeStringArray::from(vec!["B", "B"])),
+            ),
+            (
+                "pickup_open_time",
+                Arc::new(
+                    TimestampNanosecondArray::from(vec![Some(START * 1_000_000_000), None])
+                        .with_timezone("UTC"),
+                ),
+            ),
+            (
+                "mandatory",
+                Arc::new(BooleanArray::from(vec![Some(true), None])),
+            ),
+            (
+                "cargo_weight_kg",
+                Arc::new(Float64Array::from(vec![1000.0, f64::NAN])),
+            ),
+            ("priority", Arc::new(Int64Array::from(vec![1, 2]))),
+            ("stops", Arc::new(stops.finish())),
+            ("pickup_windows", Arc::new(windows.finish())),
+            ("demands", Arc::new(demands.finish())),
+        ];
+        RecordBatch::try_from_iter(columns).unwrap()
+    }
+
+    fn read_bookings(ob: &Bound<'_, PyAny>) -> Vec<Value> {
+        let columns = get_table_columns(ob).unwrap().unwrap();
+        read_rows::<PyBooking>(columns)
+            .unwrap()
+            .iter()
+            .map(|booking| serde_json::to_value(booking).unwrap())
+            .collect()
+    }
+
+    fn assert_bookings_are_read(bookings: &[Value]) {
+        assert_eq!(bookings.len(), 2);
+        let [first, second] = bookings else {
+            unreachable!()
+        };
+        assert_eq!(first["cargo"], "C0");
+        assert_eq!(first["from_terminal"], "A");
+        assert_eq!(first["to_terminal"], "B");
+        assert_eq!(first["pickup_open_time"], START);
+        assert_eq!(first["mandatory"], true);
+        assert_eq!(first["cargo_weight_kg"], 1000);
+        assert_eq!(first["stops"], json!([["C", START, START + 3600, true]]));
+        assert_eq!(first["pickup_windows"], Value::Null);
+        assert_eq!(first["demands"], json!({"pallets": 10}));
+
+        // Missing values take their defaults
+        assert_eq!(second["pickup_open_time"], Value::Null);
+        assert_eq!(second["mandatory"], false);
+        assert_eq!(second["cargo_weight_kg"], 0);
+        assert_eq!(second["stops"], json!([]));
+        assert_eq!(second["demands"], json!({}));
+        assert_eq!(second["priority"], 2);
+        assert_eq!(second["pickup_windows"][0]["start_time"], START);
+        assert_eq!(second["pickup_windows"][0]["end_time"], START + 3600);
+    }
+
+    /// A python object with a method `name` giving `value`
+    fn with_method<'py>(
+        py: Python<'py>,
+        name: &str,
+        value: Bound<'py, PyAny>,
+    ) -> Bound<'py, PyAny> {
+        let globals = PyDict::new(py);
+        globals.set_item("value", value).unwrap();
+        let code = format!("type('Table', (), {{'{name}': lambda self, *args: value}})()");
+        py.eval(&CString::new(code).unwrap(), Some(&globals), None)
+            .unwrap()
+    }
+
+    #[test]
+    fn arrow_streams_are_read() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let batch = bookings();
+            let schema = batch.schema();
+            let stream = FFI_ArrowArrayStream::new(Box::new(RecordBatchIterator::new(
+                [Ok(batch.slice(0, 1)), Ok(batch.slice(1, 1))],
+                schema,
+            )));
+            let name = CString::new("arrow_array_stream").unwrap();
+            let capsule = PyCapsule::new(py, stream, Some(name)).unwrap();
+            let table = with_method(py, "__arrow_c_stream__", capsule.into_any());
+            assert_bookings_are_read(&read_bookings(&table));
+        });
+    }
+
+    #[test]
+    fn arrow_arrays_are_read() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let (array, schema) = to_ffi(&StructArray::from(bookings()).into_data()).unwrap();
+            let schema = PyCapsule::new(py, schema, Some(CString::new("arrow_schema").unwrap()));
+            let array = PyCapsule::new(py, array, Some(CString::new("arrow_array").unwrap()));
+            let capsules = PyTuple::new(py, [schema.unwrap(), array.unwrap()]).unwrap();
+            let table = with_method(py, "__arrow_c_array__", capsules.into_any());
+            assert_bookings_are_read(&read_bookings(&table));
+        });
+    }
+
+    #[test]
+    fn tables_arrow_can_not_convert_are_read_through_python() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            // Like a pandas data frame with a column of tuples, which pyarrow can't convert
+            let code = format!(
+                "type('DataFrame', (), {{
+                    'columns': ['cargo', 'from_terminal', 'to_terminal', 'stops'],
+                    '__arrow_c_stream__': lambda self, *args: int('not a table'),
+                    'to_dict': lambda self, orient: {{
+                        'cargo': ['C0', 'C1'],
+                        'from_terminal': ['A', 'A'],
+                        'to_terminal': ['B', 'B'],
+                        'stops': [[('C', {START}, {}, True)], float('nan')],
+                    }},
+                }})()",
+                START + 3600
+            );
+            let table = py.eval(&CString::new(code).unwrap(), None, None).unwrap();
+            let bookings = read_bookings(&table);
+            assert_eq!(
+                bookings[0]["stops"],
+                json!([["C", START, START + 3600, true]])
+            );
+            assert_eq!(bookings[1]["stops"], json!([]));
+        });
+    }
+
+    #[test]
+    fn unsupported_arrow_columns_are_named() {
+        let batch = RecordBatch::try_from_iter([(
+            "cargo_weight_kg",
+            Arc::new(BinaryArray::from(vec![b"1".as_slice()])) as ArrayRef,
+        )])
+        .unwrap();
+        let err = read_arrow_column("cargo_weight_kg", batch.column(0).as_ref()).unwrap_err();
+        pyo3::prepare_freethreaded_python();
+        assert!(err.to_string().contains("\"cargo_weight_kg\""), "{err}");
+    }
+}
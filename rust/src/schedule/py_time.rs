@@ -0,0 +1,366 @@
+use chrono::{DateTime, Days, FixedOffset, NaiveDate, NaiveDateTime, TimeDelta, Utc};
+use pyo3::{
+    exceptions::PyTypeError,
+    types::{PyAnyMethods, PyInt},
+    Bound, FromPyObject, IntoPyObject, PyAny, PyErr, PyResult, Python,
+};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use super::common_types::{NonNegativeTimeDelta, Time, SECONDS_PER_DAY};
+
+/// How a generator gives times and durations back to python,
+/// see `ScheduleGenerator.set_time_formats`. By default both are given as seconds
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct TimeFormats {
+    /// Whether times are given back as datetimes in UTC, rather than
+    /// as seconds since the unix epoch
+    pub times_as_datetimes: bool,
+    /// Whether durations are given back as timedeltas, rather than as seconds
+    pub durations_as_timedeltas: bool,
+}
+
+impl TimeFormats {
+    pub fn time(self, time: Time) -> FormattedTime {
+        FormattedTime {
+            time,
+            formats: self,
+        }
+    }
+
+    pub fn duration(self, duration: NonNegativeTimeDelta) -> FormattedTimeDelta {
+        FormattedTimeDelta {
+            duration,
+            formats: self,
+        }
+    }
+}
+
+/// A time as exchanged with python. Python can give either a `datetime`
+/// or the number of seconds since the unix epoch, and gets back a `datetime` in UTC.
+/// Times coming from a generator are given back as `FormattedTime`s instead.
+/// Naive datetimes are taken to be in UTC
+#[derive(Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PyTime(pub Time);
+
+impl PyTime {
+    fn from_datetime(datetime: DateTime<Utc>) -> PyResult<Self> {
+        Time::try_from(datetime.timestamp())
+            .map(Self)
+            .map_err(|_| PyTypeError::new_err(format!("Time {datetime} is before 1970-01-01")))
+    }
+
+    fn to_datetime(self) -> PyResult<DateTime<Utc>> {
+        i64::try_from(self.0)
+            .ok()
+            .and_then(|seconds| DateTime::from_timestamp(seconds, 0))
+            .ok_or_else(|| {
+                PyTypeError::new_err(format!(
+                    "Time {} can't be represented as a datetime",
+                    self.0
+                ))
+            })
+    }
+}
+
+impl From<Time> for PyTime {
+    fn from(time: Time) -> Self {
+        Self(time)
+    }
+}
+
+impl From<PyTime> for Time {
+    fn from(time: PyTime) -> Self {
+        time.0
+    }
+}
+
+impl<'py> FromPyObject<'py> for PyTime {
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        if ob.is_instance_of::<PyInt>() {
+            return Ok(Self(ob.extract()?));
+        }
+        if let Ok(datetime) = ob.extract::<DateTime<FixedOffset>>() {
+            return Self::from_datetime(datetime.to_utc());
+        }
+        if let Ok(datetime) = ob.extract::<NaiveDateTime>() {
+            return Self::from_datetime(datetime.and_utc());
+        }
+        // Other integers, e.g. from numpy
+        if let Ok(seconds) = ob.extract::<Time>() {
+            return Ok(Self(seconds));
+        }
+        Err(PyTypeError::new_err(format!(
+            "Expected a datetime or seconds since the unix epoch, got {ob}"
+        )))
+    }
+}
+
+impl<'py> IntoPyObject<'py> for PyTime {
+    type Target = PyAny;
+    type Output = Bound<'py, PyAny>;
+    type Error = PyErr;
+
+    fn into_pyobject(self, py: Python<'py>) -> Result<Self::Output, Self::Error> {
+        Ok(self.to_datetime()?.into_pyobject(py)?.into_any())
+    }
+}
+
+impl<'py> IntoPyObject<'py> for &PyTime {
+    type Target = PyAny;
+    type Output = Bound<'py, PyAny>;
+    type Error = PyErr;
+
+    fn into_pyobject(self, py: Python<'py>) -> Result<Self::Output, Self::Error> {
+        (*self).into_pyobject(py)
+    }
+}
+
+/// A time given back to python in the `TimeFormats` of the generator it comes from
+#[derive(Clone, Copy, Debug)]
+pub struct FormattedTime {
+    pub time: Time,
+    formats: TimeFormats,
+}
+
+impl<'py> IntoPyObject<'py> for FormattedTime {
+    type Target = PyAny;
+    type Output = Bound<'py, PyAny>;
+    type Error = PyErr;
+
+    fn into_pyobject(self, py: Python<'py>) -> Result<Self::Output, Self::Error> {
+        if self.formats.times_as_datetimes {
+            Ok(PyTime(self.time)
+                .to_datetime()?
+                .into_pyobject(py)?
+                .into_any())
+        } else {
+            Ok(self.time.into_pyobject(py)?.into_any())
+        }
+    }
+}
+
+impl<'py> IntoPyObject<'py> for &FormattedTime {
+    type Target = PyAny;
+    type Output = Bound<'py, PyAny>;
+    type Error = PyErr;
+
+    fn into_pyobject(self, py: Python<'py>) -> Result<Self::Output, Self::Error> {
+        (*self).into_pyobject(py)
+    }
+}
+
+/// A date as exchanged with python, e.g. a public holiday. Python can give a `date`,
+/// a `datetime`, which is on its date in its own timezone, or a number of seconds
+/// since the unix epoch, which is on its UTC date. Gets back a `date`
+#[derive(Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash, Debug, Serialize)]
+#[serde(transparent)]
+pub struct PyDate(pub NaiveDate);
+
+impl PyDate {
+    fn from_seconds(seconds: Time) -> Self {
+        Self(DateTime::UNIX_EPOCH.date_naive() + Days::new(seconds / SECONDS_PER_DAY))
+    }
+}
+
+impl<'py> FromPyObject<'py> for PyDate {
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        if ob.is_instance_of::<PyInt>() {
+            return Ok(Self::from_seconds(ob.extract()?));
+        }
+        // NOTE: datetimes are dates too, whose date is the local one in their timezone
+        if let Ok(date) = ob.extract::<NaiveDate>() {
+            return Ok(Self(date));
+        }
+        // Other integers, e.g. from numpy
+        if let Ok(seconds) = ob.extract::<Time>() {
+            return Ok(Self::from_seconds(seconds));
+        }
+        Err(PyTypeError::new_err(format!(
+            "Expected a date, a datetime or seconds since the unix epoch, got {ob}"
+        )))
+    }
+}
+
+impl<'py> IntoPyObject<'py> for PyDate {
+    type Target = PyAny;
+    type Output = Bound<'py, PyAny>;
+    type Error = PyErr;
+
+    fn into_pyobject(self, py: Python<'py>) -> Result<Self::Output, Self::Error> {
+        Ok(self.0.into_pyobject(py)?.into_any())
+    }
+}
+
+impl<'py> IntoPyObject<'py> for &PyDate {
+    type Target = PyAny;
+    type Output = Bound<'py, PyAny>;
+    type Error = PyErr;
+
+    fn into_pyobject(self, py: Python<'py>) -> Result<Self::Output, Self::Error> {
+        (*self).into_pyobject(py)
+    }
+}
+
+// NOTE: saved generators used to have times within the day instead of dates
+impl<'de> Deserialize<'de> for PyDate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum DateOrSeconds {
+            Date(NaiveDate),
+            Seconds(Time),
+        }
+        Ok(match DateOrSeconds::deserialize(deserializer)? {
+            DateOrSeconds::Date(date) => Self(date),
+            DateOrSeconds::Seconds(seconds) => Self::from_seconds(seconds),
+        })
+    }
+}
+
+/// A duration as exchanged with python. Python can give either a `timedelta`, which
+/// is rounded to whole seconds, or a number of seconds, and gets back a number of
+/// seconds. Durations coming from a generator are given back as
+/// `FormattedTimeDelta`s instead
+#[derive(Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PyTimeDelta(pub NonNegativeTimeDelta);
+
+impl From<NonNegativeTimeDelta> for PyTimeDelta {
+    fn from(time_delta: NonNegativeTimeDelta) -> Self {
+        Self(time_delta)
+    }
+}
+
+impl From<PyTimeDelta> for NonNegativeTimeDelta {
+    fn from(time_delta: PyTimeDelta) -> Self {
+        time_delta.0
+    }
+}
+
+impl<'py> FromPyObject<'py> for PyTimeDelta {
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        let seconds = if ob.is_instance_of::<PyInt>() {
+            ob.extract::<i64>()?
+        } else if let Ok(time_delta) = ob.extract::<TimeDelta>() {
+            (time_delta.num_milliseconds() + 500).div_euclid(1000)
+        } else if let Ok(seconds) = ob.extract::<i64>() {
+            // Other integers, e.g. from numpy
+            seconds
+        } else {
+            return Err(PyTypeError::new_err(format!(
+                "Expected a timedelta or a number of seconds, got {ob}"
+            )));
+        };
+        NonNegativeTimeDelta::try_from(seconds)
+            .map(Self)
+            .map_err(|_| PyTypeError::new_err(format!("Duration {ob} is negative")))
+    }
+}
+
+impl<'py> IntoPyObject<'py> for PyTimeDelta {
+    type Target = PyAny;
+    type Output = Bound<'py, PyAny>;
+    type Error = PyErr;
+
+    fn into_pyobject(self, py: Python<'py>) -> Result<Self::Output, Self::Error> {
+        TimeFormats::default().duration(self.0).into_pyobject(py)
+    }
+}
+
+impl<'py> IntoPyObject<'py> for &PyTimeDelta {
+    type Target = PyAny;
+    type Output = Bound<'py, PyAny>;
+    type Error = PyErr;
+
+    fn into_pyobject(self, py: Python<'py>) -> Result<Self::Output, Self::Error> {
+        (*self).into_pyobject(py)
+    }
+}
+
+/// A duration given back to python in the `TimeFormats` of the generator it comes from
+#[derive(Clone, Copy, Debug)]
+pub struct FormattedTimeDelta {
+    pub duration: NonNegativeTimeDelta,
+    formats: TimeFormats,
+}
+
+impl<'py> IntoPyObject<'py> for FormattedTimeDelta {
+    type Target = PyAny;
+    type Output = Bound<'py, PyAny>;
+    type Error = PyErr;
+
+    fn into_pyobject(self, py: Python<'py>) -> Result<Self::Output, Self::Error> {
+        if self.formats.durations_as_timedeltas {
+            let seconds = i64::try_from(self.duration).map_err(|_| {
+                PyTypeError::new_err(format!(
+                    "Duration {} can't be represented as a timedelta",
+                    self.duration
+                ))
+            })?;
+            Ok(TimeDelta::seconds(seconds).into_pyobject(py)?.into_any())
+        } else {
+            Ok(self.duration.into_pyobject(py)?.into_any())
+        }
+    }
+}
+
+impl<'py> IntoPyObject<'py> for &FormattedTimeDelta {
+    type Target = PyAny;
+    type Output = Bound<'py, PyAny>;
+    type Error = PyErr;
+
+    fn into_pyobject(self, py: Python<'py>) -> Result<Self::Output, Self::Error> {
+        (*self).into_pyobject(py)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pyo3::types::PyAnyMethods;
+
+    use super::*;
+
+    #[test]
+    fn integer_like_objects_are_accepted() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            // Like a numpy integer, which isn't an int but can be used as one
+            let integer = py
+                .eval(
+                    c"type('Integer', (), {'__index__': lambda self: 60})()",
+                    None,
+                    None,
+                )
+                .unwrap();
+            assert_eq!(integer.extract::<PyTime>().unwrap(), PyTime(60));
+            assert_eq!(integer.extract::<PyTimeDelta>().unwrap(), PyTimeDelta(60));
+        });
+    }
+
+    #[test]
+    fn values_are_given_back_in_the_configured_formats() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let formats = TimeFormats {
+                times_as_datetimes: false,
+                durations_as_timedeltas: true,
+            };
+            let time = formats.time(60).into_pyobject(py).unwrap();
+            assert_eq!(time.extract::<Time>().unwrap(), 60);
+            let duration = formats.duration(60).into_pyobject(py).unwrap();
+            assert_eq!(
+                duration.extract::<TimeDelta>().unwrap(),
+                TimeDelta::seconds(60)
+            );
+
+            let time = TimeFormats::default().time(60).into_pyobject(py).unwrap();
+            assert_eq!(time.extract::<Time>().unwrap(), 60);
+            let time = PyTime(60).into_pyobject(py).unwrap();
+            assert_eq!(
+                time.extract::<DateTime<Utc>>().unwrap(),
+                DateTime::from_timestamp(60, 0).unwrap()
+            );
+        });
+    }
+}
@@ -0,0 +1,191 @@
+use std::collections::BTreeSet;
+
+use chrono::{Datelike, Days, NaiveDate, NaiveTime, Weekday};
+use chrono_tz::Tz;
+
+use super::intervals::{Interval, IntervalChain, IntervalWithDataChainIter};
+use super::timezones::{local_dates, local_to_time};
+
+/// Something that happens at the same local time of day on some days, e.g. the opening
+/// hours of a terminal, a driver's shifts or when a truck is available
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct RecurringPattern {
+    /// Timezone the times of day are in
+    timezone: Tz,
+    start_time: NaiveTime,
+    /// If not after `start_time`, this is on the next day
+    end_time: NaiveTime,
+    /// Days of the week on which this starts, with 0 being Monday,
+    /// or None for every day
+    weekdays: Option<BTreeSet<u32>>,
+    /// Local dates on which this doesn't start, e.g. public holidays
+    exceptions: BTreeSet<NaiveDate>,
+}
+
+impl RecurringPattern {
+    /// Happens every day from `start_time` to `end_time` local time in `timezone`
+    pub fn daily(timezone: Tz, start_time: NaiveTime, end_time: NaiveTime) -> Self {
+        Self {
+            timezone,
+            start_time,
+            end_time,
+            weekdays: None,
+            exceptions: BTreeSet::new(),
+        }
+    }
+
+    /// Happens from `start_time` to `end_time` local time in `timezone`
+    /// on the given days of the week
+    pub fn weekly(
+        timezone: Tz,
+        weekdays: impl IntoIterator<Item = Weekday>,
+        start_time: NaiveTime,
+        end_time: NaiveTime,
+    ) -> Self {
+        Self {
+            weekdays: Some(
+                weekdays
+                    .into_iter()
+                    .map(|weekday| weekday.num_days_from_monday())
+                    .collect(),
+            ),
+            ..Self::daily(timezone, start_time, end_time)
+        }
+    }
+
+    /// Doesn't happen on the local dates `exceptions`
+    pub fn with_exceptions(mut self, exceptions: impl IntoIterator<Item = NaiveDate>) -> Self {
+        self.exceptions.extend(exceptions);
+        self
+    }
+
+    /// Whether this starts on local date `date`
+    pub fn occurs_on(&self, date: NaiveDate) -> bool {
+        !self.exceptions.contains(&date)
+            && self
+                .weekdays
+                .as_ref()
+                .is_none_or(|weekdays| weekdays.contains(&date.weekday().num_days_from_monday()))
+    }
+
+    /// The times within `horizon` during which this is happening
+    pub fn expand(&self, horizon: &Interval) -> IntervalChain {
+        let mut dates = local_dates(self.timezone, horizon).peekable();
+        // Start a day early to include occurrences that started before the horizon
+        let day_before = *dates.peek().unwrap() - Days::new(1);
+
+        let mut out = vec![];
+        for date in std::iter::once(day_before)
+            .chain(dates)
+            .filter(|date| self.occurs_on(*date))
+        {
+            let end_date = if self.end_time > self.start_time {
+                date
+            } else {
+                date + Days::new(1)
+            };
+            let start_time = local_to_time(self.timezone, date.and_time(self.start_time));
+            let end_time = local_to_time(self.timezone, end_date.and_time(self.end_time));
+            out.extend(Interval::new(start_time, end_time, ()));
+        }
+        let mut out = IntervalChain::from_intervals(out);
+        // Occurrences can touch, e.g. for whole days
+        out.normalize();
+        [out, IntervalChain::from_interval(horizon.clone())]
+            .iter()
+            .intersect_all()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schedule::common_types::Time;
+
+    fn utc(date: (i32, u32, u32), hour: u32) -> Time {
+        NaiveDate::from_ymd_opt(date.0, date.1, date.2)
+            .unwrap()
+            .and_hms_opt(hour, 0, 0)
+            .unwrap()
+            .and_utc()
+            .timestamp() as Time
+    }
+
+    fn hour(hour: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, 0, 0).unwrap()
+    }
+
+    fn occurrences(pattern: &RecurringPattern, start: Time, end: Time) -> Vec<(Time, Time)> {
+        pattern
+            .expand(&Interval::new(start, end, ()).unwrap())
+            .get_intervals()
+            .iter()
+            .map(|interval| (interval.get_start_time(), interval.get_end_time()))
+            .collect()
+    }
+
+    #[test]
+    fn weekly_patterns_only_happen_on_their_weekdays() {
+        let pattern =
+            RecurringPattern::weekly(Tz::UTC, [Weekday::Mon, Weekday::Thu], hour(8), hour(17));
+        // 2025-01-06 is a Monday
+        assert_eq!(
+            occurrences(&pattern, utc((2025, 1, 6), 0), utc((2025, 1, 14), 0)),
+            vec![
+                (utc((2025, 1, 6), 8), utc((2025, 1, 6), 17)),
+                (utc((2025, 1, 9), 8), utc((2025, 1, 9), 17)),
+                (utc((2025, 1, 13), 8), utc((2025, 1, 13), 17)),
+            ]
+        );
+    }
+
+    #[test]
+    fn patterns_do_not_happen_on_exceptions() {
+        let pattern = RecurringPattern::daily(Tz::UTC, hour(8), hour(17))
+            .with_exceptions([NaiveDate::from_ymd_opt(2025, 1, 7).unwrap()]);
+        assert_eq!(
+            occurrences(&pattern, utc((2025, 1, 6), 0), utc((2025, 1, 9), 0)),
+            vec![
+                (utc((2025, 1, 6), 8), utc((2025, 1, 6), 17)),
+                (utc((2025, 1, 8), 8), utc((2025, 1, 8), 17)),
+            ]
+        );
+    }
+
+    #[test]
+    fn overnight_patterns_include_occurrences_started_before_the_horizon() {
+        let pattern = RecurringPattern::daily(Tz::UTC, hour(22), hour(6));
+        assert_eq!(
+            occurrences(&pattern, utc((2025, 1, 6), 2), utc((2025, 1, 8), 0)),
+            vec![
+                (utc((2025, 1, 6), 2), utc((2025, 1, 6), 6)),
+                (utc((2025, 1, 6), 22), utc((2025, 1, 7), 6)),
+                (utc((2025, 1, 7), 22), utc((2025, 1, 8), 0)),
+            ]
+        );
+        // Unless the day before the horizon is an exception
+        let pattern = pattern.with_exceptions([NaiveDate::from_ymd_opt(2025, 1, 5).unwrap()]);
+        assert_eq!(
+            occurrences(&pattern, utc((2025, 1, 6), 2), utc((2025, 1, 7), 0)),
+            vec![(utc((2025, 1, 6), 22), utc((2025, 1, 7), 0))]
+        );
+    }
+
+    #[test]
+    fn whole_day_patterns_merge_consecutive_days() {
+        let pattern = RecurringPattern::weekly(
+            Tz::Europe__Berlin,
+            [Weekday::Mon, Weekday::Tue, Weekday::Thu],
+            NaiveTime::MIN,
+            NaiveTime::MIN,
+        );
+        // Local midnight in Berlin is 23:00 UTC in winter
+        assert_eq!(
+            occurrences(&pattern, utc((2025, 1, 5), 0), utc((2025, 1, 11), 0)),
+            vec![
+                (utc((2025, 1, 5), 23), utc((2025, 1, 7), 23)),
+                (utc((2025, 1, 8), 23), utc((2025, 1, 9), 23)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,389 @@
+use std::{
+    hash::{Hash, Hasher},
+    ops::{Index, RangeBounds},
+    slice::SliceIndex,
+};
+
+use super::{common_types::IsID, heap_size::HeapSize, id_map::SharedIdMap};
+
+/// A stable reference to an item of a route of `Routes`, which stays valid while other
+/// items of the route are inserted or removed, until the item itself is removed.
+/// Handles are unique within a route: when the slot of a removed item is reused,
+/// its generation goes up, so that old handles don't refer to the new item
+#[derive(Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash, Debug)]
+pub struct Handle {
+    slot: u32,
+    generation: u32,
+}
+
+impl Handle {
+    /// The handle as a single number, e.g. to give it to python
+    pub fn to_bits(self) -> u64 {
+        (u64::from(self.generation) << 32) | u64::from(self.slot)
+    }
+
+    pub fn from_bits(bits: u64) -> Self {
+        Self {
+            slot: bits as u32,
+            generation: (bits >> 32) as u32,
+        }
+    }
+
+    fn slot(self) -> usize {
+        self.slot as usize
+    }
+}
+
+/// An ordered list of items for each key, e.g. the checkpoints of each truck.
+/// Each route keeps its items in its own arena, referenced by their `Handle`s, so
+/// inserting or removing an item doesn't move any items.
+/// NOTE: the order is still kept in a `Vec` of handles, so inserting or removing an
+/// item shifts the handles after it, which takes time linear in the length of the
+/// route. Handles are 8 bytes, so this is much cheaper than shifting the items, and
+/// routes only have tens of items. An ordered index without shifting (e.g. a tree
+/// keyed by position) would make the much more frequent indexing and iterating
+/// slower, so it's not worth it at these lengths
+/// Routes are shared between clones until they are changed, so cloning `Routes` and
+/// then changing one route only copies that route.
+/// Accessed like an `IdMap` from each key to its `Route`
+#[derive(Clone, Debug)]
+pub struct Routes<K, T> {
+    routes: SharedIdMap<K, RouteData<T>>,
+}
+
+/// The items of a route of `Routes` with their handles
+#[derive(Clone, Debug)]
+struct RouteData<T> {
+    items: Vec<Option<T>>,
+    /// The generation of the handle to each slot of `items`
+    generations: Vec<u32>,
+    /// Slots of removed items, to be reused
+    free_slots: Vec<usize>,
+    /// Handles of the items, in order
+    handles: Vec<Handle>,
+}
+
+/// The items of a route of `Routes`, in order. Behaves like a slice of them
+pub struct Route<'a, T> {
+    items: &'a [Option<T>],
+    handles: &'a [Handle],
+}
+
+// NOTE: derived Clone and Copy would require `T: Copy`
+impl<T> Clone for Route<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Route<'_, T> {}
+
+impl<'a, T> Route<'a, T> {
+    pub fn len(&self) -> usize {
+        self.handles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.handles.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&'a T> {
+        let handle = self.handles.get(index)?;
+        self.items[handle.slot()].as_ref()
+    }
+
+    /// The index of the item with `handle`, unless it was removed.
+    /// Takes time linear in the length of the route, since there's no map from handles
+    /// to indices: keeping one up to date would take linear time on every insertion
+    /// and removal, while this is only used to answer lookups from python
+    pub fn position(&self, handle: Handle) -> Option<usize> {
+        self.handles.iter().position(|other| *other == handle)
+    }
+
+    pub fn first(&self) -> Option<&'a T> {
+        self.get(0)
+    }
+
+    pub fn last(&self) -> Option<&'a T> {
+        self.get(self.len().checked_sub(1)?)
+    }
+
+    pub fn handles(&self) -> &'a [Handle] {
+        self.handles
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &'a T> + ExactSizeIterator + Clone {
+        let items = self.items;
+        self.handles
+            .iter()
+            .map(move |handle| items[handle.slot()].as_ref().unwrap())
+    }
+
+    /// Consecutive pairs of items, like `slice::windows(2)`
+    pub fn pairs(&self) -> impl Iterator<Item = (&'a T, &'a T)> + Clone {
+        self.iter().zip(self.iter().skip(1))
+    }
+
+    /// The items in `range`, like indexing a slice with it
+    pub fn slice<R>(&self, range: R) -> Route<'a, T>
+    where
+        R: RangeBounds<usize> + SliceIndex<[Handle], Output = [Handle]>,
+    {
+        Route {
+            items: self.items,
+            handles: &self.handles[range],
+        }
+    }
+
+    pub fn to_vec(self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        self.iter().cloned().collect()
+    }
+}
+
+impl<T> Index<usize> for Route<'_, T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        self.get(index).unwrap()
+    }
+}
+
+impl<T> RouteData<T> {
+    fn as_route(&self) -> Route<'_, T> {
+        Route {
+            items: &self.items,
+            handles: &self.handles,
+        }
+    }
+
+    fn allocate(&mut self, item: T) -> Handle {
+        let slot = match self.free_slots.pop() {
+            Some(slot) => {
+                self.items[slot] = Some(item);
+                slot
+            }
+            None => {
+                self.items.push(Some(item));
+                self.generations.push(0);
+                self.items.len() - 1
+            }
+        };
+        Handle {
+            slot: u32::try_from(slot).expect("Route has too many items"),
+            generation: self.generations[slot],
+        }
+    }
+
+    fn release(&mut self, handle: Handle) -> T {
+        let slot = handle.slot();
+        self.generations[slot] = self.generations[slot].wrapping_add(1);
+        self.free_slots.push(slot);
+        self.items[slot].take().unwrap()
+    }
+}
+
+impl<K: IsID, T: Clone> Routes<K, T> {
+    pub fn new() -> Self {
+        Self {
+            routes: SharedIdMap::new(),
+        }
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.routes.contains_key(key)
+    }
+
+    pub fn get(&self, key: &K) -> Option<Route<'_, T>> {
+        Some(self.routes.get(key)?.as_route())
+    }
+
+    pub fn keys(&self) -> impl DoubleEndedIterator<Item = &K> + Clone {
+        self.routes.keys()
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (&K, Route<'_, T>)> + Clone {
+        self.routes
+            .iter()
+            .map(|(key, route_data)| (key, route_data.as_route()))
+    }
+
+    pub fn values(&self) -> impl DoubleEndedIterator<Item = Route<'_, T>> + Clone {
+        self.iter().map(|(_key, route)| route)
+    }
+
+    /// Sets the route of `key` to `items`
+    pub fn insert(&mut self, key: K, items: Vec<T>) {
+        let route_data = RouteData {
+            handles: (0..items.len())
+                .map(|slot| Handle {
+                    slot: u32::try_from(slot).expect("Route has too many items"),
+                    generation: 0,
+                })
+                .collect(),
+            generations: vec![0; items.len()],
+            items: items.into_iter().map(Some).collect(),
+            free_slots: Vec::new(),
+        };
+        self.routes.insert(key, route_data);
+    }
+
+    /// Removes the route of `key` with all its items
+    pub fn remove(&mut self, key: &K) {
+        self.routes.remove(key);
+    }
+
+    /// Inserts `item` at `index` of the route of `key`, returning its handle
+    pub fn insert_item(&mut self, key: &K, index: usize, item: T) -> Handle {
+        let route_data = self.routes.get_mut(key).unwrap();
+        let handle = route_data.allocate(item);
+        route_data.handles.insert(index, handle);
+        handle
+    }
+
+    /// Removes the item at `index` of the route of `key`
+    pub fn remove_item(&mut self, key: &K, index: usize) -> T {
+        let route_data = self.routes.get_mut(key).unwrap();
+        let handle = route_data.handles.remove(index);
+        route_data.release(handle)
+    }
+
+    pub fn get_item_mut(&mut self, key: &K, index: usize) -> Option<&mut T> {
+        let route_data = self.routes.get_mut(key)?;
+        let handle = route_data.handles.get(index)?;
+        route_data.items[handle.slot()].as_mut()
+    }
+}
+
+impl HeapSize for Handle {
+    fn heap_size(&self) -> usize {
+        0
+    }
+}
+
+impl<T: HeapSize> HeapSize for RouteData<T> {
+    fn heap_size(&self) -> usize {
+        self.items.heap_size()
+            + self.generations.heap_size()
+            + self.free_slots.heap_size()
+            + self.handles.heap_size()
+    }
+}
+
+impl<K: HeapSize, T: HeapSize> HeapSize for Routes<K, T> {
+    fn heap_size(&self) -> usize {
+        self.routes.heap_size()
+    }
+}
+
+impl<K: IsID, T: Clone> Default for Routes<K, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: IsID, T: Clone> FromIterator<(K, Vec<T>)> for Routes<K, T> {
+    fn from_iter<I: IntoIterator<Item = (K, Vec<T>)>>(iter: I) -> Self {
+        let mut out = Self::new();
+        for (key, items) in iter {
+            out.insert(key, items);
+        }
+        out
+    }
+}
+
+// NOTE: compared and hashed by the items of their routes,
+// regardless of where in the arenas they are
+impl<K: IsID + PartialEq, T: Clone + PartialEq> PartialEq for Routes<K, T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.routes.len() == other.routes.len()
+            && self
+                .iter()
+                .zip(other.iter())
+                .all(|((key, route), (other_key, other_route))| {
+                    key == other_key && route.iter().eq(other_route.iter())
+                })
+    }
+}
+
+impl<K: IsID + Eq, T: Clone + Eq> Eq for Routes<K, T> {}
+
+impl<K: IsID + Hash, T: Clone + Hash> Hash for Routes<K, T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.routes.len().hash(state);
+        for (key, route) in self.iter() {
+            key.hash(state);
+            route.len().hash(state);
+            for item in route.iter() {
+                item.hash(state);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::schedule::common_types::Truck;
+
+    proptest! {
+        /// Inserting and removing items behaves like a `Vec` for each key,
+        /// and keeps the handles of other items, while those of removed items
+        /// no longer refer to any item
+        #[test]
+        fn behaves_like_vecs(
+            operations in prop::collection::vec(
+                (0..5usize, 0..10usize, any::<Option<u8>>()),
+                0..100
+            )
+        ) {
+            let mut routes = Routes::new();
+            let mut vecs = BTreeMap::new();
+            let mut removed_handles = vec![];
+            for (id, index, item) in operations {
+                let truck = Truck::from_id(id);
+                let vec: &mut Vec<u8> = vecs.entry(truck).or_insert_with(|| {
+                    routes.insert(truck, vec![]);
+                    vec![]
+                });
+                let old_handles = routes.get(&truck).unwrap().handles().to_vec();
+                match item {
+                    Some(item) => {
+                        let index = index.min(vec.len());
+                        vec.insert(index, item);
+                        let handle = routes.insert_item(&truck, index, item);
+                        let mut expected_handles = old_handles;
+                        expected_handles.insert(index, handle);
+                        prop_assert_eq!(routes.get(&truck).unwrap().handles(), &expected_handles);
+                    }
+                    None if index < vec.len() => {
+                        prop_assert_eq!(routes.remove_item(&truck, index), vec.remove(index));
+                        let mut expected_handles = old_handles;
+                        removed_handles.push((truck, expected_handles.remove(index)));
+                        prop_assert_eq!(routes.get(&truck).unwrap().handles(), &expected_handles);
+                    }
+                    None => {}
+                }
+                prop_assert!(routes
+                    .iter()
+                    .map(|(truck, route)| (*truck, route.to_vec()))
+                    .eq(vecs.clone()));
+                for (truck, route) in routes.iter() {
+                    for (index, handle) in route.handles().iter().enumerate() {
+                        prop_assert_eq!(route.position(*handle), Some(index));
+                    }
+                    for (_, handle) in removed_handles.iter().filter(|(other, _)| other == truck) {
+                        prop_assert_eq!(route.position(*handle), None);
+                    }
+                }
+            }
+            prop_assert!(routes == vecs.into_iter().collect());
+        }
+    }
+}
@@ -1,20 +1,155 @@
 use std::collections::BTreeMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 use std::{cmp::max, collections::BTreeSet};
 
-use pyo3::{exceptions::PyTypeError, pyclass, pymethods, FromPyObject, PyResult};
-use rand::{seq::IteratorRandom, Rng, SeedableRng};
+use chrono::{DateTime, NaiveTime};
+use chrono_tz::Tz;
+use log::{debug, info, trace, warn};
+use pyo3::{
+    exceptions::{PyIndexError, PyRuntimeError, PyTypeError},
+    pyclass, pymethods,
+    types::{PyAnyMethods, PyBool, PyByteArray, PyDict, PyDictMethods, PyIterator, PyList},
+    Bound, FromPyObject, IntoPyObject, Py, PyAny, PyErr, PyRefMut, PyResult, Python,
+};
+use rand::{
+    seq::{IndexedRandom, IteratorRandom},
+    Rng, SeedableRng,
+};
 use rand_xoshiro::Xoshiro256PlusPlus;
-
-use super::common_types::{Cargo, NonNegativeTimeDelta, Terminal, Time, Truck};
-use super::driving_times_cache::DrivingTimesCache;
-use super::{counter_mapper::CounterMapper, intervals::*};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize};
+use serde_json::{json, Value};
+
+use super::annealing::{
+    acceptance_probability, is_better, temperature_at, AnnealingOptions, AnnealingProgress,
+};
+use super::calendar::Calendar;
+use super::capacity::Capacity;
+use super::cargo_set::CargoSet;
+use super::common_types::{
+    CapacityDimension, Cargo, Driver, IsID, Location, NonNegativeTimeDelta, Qualification,
+    Terminal, Time, Truck, VehicleClass, SECONDS_PER_DAY,
+};
+use super::driving_time_providers::{
+    CallbackProvider, DrivingTimeProvider, DrivingTimeSource, HaversineProvider,
+    ShortestPathProvider, DEFAULT_ESTIMATE_SPEED_KMH,
+};
+use super::driving_times_cache::{DrivingTimeResult, DrivingTimesCache, MissingDrivingTime};
+use super::errors::{
+    InfeasibleBookingError, InfeasibleScheduleError, InvalidIntervalError, MissingDrivingTimeError,
+    UnknownIdError, UnknownTerminalError,
+};
+use super::py_matrix::PyMatrix;
+use super::py_table::{PyKeyedRows, PyRows};
+use super::py_time::{FormattedTime, FormattedTimeDelta, PyDate, PyTime, PyTimeDelta, TimeFormats};
+use super::recurring_pattern::RecurringPattern;
+use super::{
+    counter_mapper::CounterMapper,
+    heap_size::HeapSize,
+    id_map::{IdMap, IdSet, SharedIdMap},
+    intervals::*,
+    routes::{Handle, Route, Routes},
+};
 
 type PyTerminalID = String;
 type PyCargoID = String;
 type PyTruckID = String;
+type PyQualificationID = String;
+type PyVehicleClassID = String;
+type PyCapacityDimensionID = String;
+type PyDriverID = String;
+type PyRegionID = String;
+
+/// How much of a delivery is lost when it happens outside the requested
+/// time windows, as a fraction of an on-time delivery
+const RELAXED_TIME_WINDOWS_PENALTY: f64 = 0.9;
+
+// Defaults of the optional fields when deserializing, matching the Python constructors
+fn default_speed_factor() -> f64 {
+    1.0
+}
+
+fn default_num_containers() -> usize {
+    1
+}
+
+fn default_cargo_teu() -> usize {
+    1
+}
+
+fn default_closed_weekdays() -> Vec<u64> {
+    vec![5, 6]
+}
+
+/// When a terminal opens and closes, given by python as a tuple
+/// (opening_time, closing_time), or as the "opening_time" and "closing_time"
+/// columns of a table of terminals, see `PyKeyedRows`
+#[derive(FromPyObject, Deserialize)]
+#[serde(from = "TerminalTimesRow")]
+pub struct PyTerminalTimes(PyTime, PyTime);
+
+/// A row of a table of terminals, see `PyTerminalTimes`
+#[derive(Deserialize)]
+struct TerminalTimesRow {
+    opening_time: PyTime,
+    closing_time: PyTime,
+}
+
+impl From<TerminalTimesRow> for PyTerminalTimes {
+    fn from(row: TerminalTimesRow) -> Self {
+        Self(row.opening_time, row.closing_time)
+    }
+}
+
+impl From<(PyTime, PyTime)> for PyTerminalTimes {
+    fn from((opening_time, closing_time): (PyTime, PyTime)) -> Self {
+        Self(opening_time, closing_time)
+    }
+}
+
+/// An additional stop of a booking as (terminal, open_time, close_time, is_pickup),
+/// see `PyBooking::stops`
+type PyStop = (PyTerminalID, PyTime, PyTime, bool);
+
+/// A `PyStop`, read from a tuple or from a dict with these keys,
+/// as made for a column of lists of structs in a table of bookings, see `PyRows`
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StopRow {
+    Tuple(PyTerminalID, PyTime, PyTime, bool),
+    Fields {
+        terminal: PyTerminalID,
+        open_time: PyTime,
+        close_time: PyTime,
+        is_pickup: bool,
+    },
+}
+
+fn deserialize_stops<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<PyStop>, D::Error> {
+    let rows = Vec::<StopRow>::deserialize(deserializer)?;
+    Ok(rows
+        .into_iter()
+        .map(|row| match row {
+            StopRow::Tuple(terminal, open_time, close_time, is_pickup) => {
+                (terminal, open_time, close_time, is_pickup)
+            }
+            StopRow::Fields {
+                terminal,
+                open_time,
+                close_time,
+                is_pickup,
+            } => (terminal, open_time, close_time, is_pickup),
+        })
+        .collect())
+}
 
 #[pyclass]
-#[derive(FromPyObject, Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PyTruckData {
     #[pyo3(get, set)]
     starting_terminal: PyTerminalID,
@@ -22,118 +157,905 @@ pub struct PyTruckData {
     max_weight_kg: usize,
     #[pyo3(get, set)]
     max_teu: usize,
+    /// Qualifications of this truck and its driver, e.g. "ADR". With a driver roster,
+    /// the driver on duty also needs the qualifications the cargo requires
+    #[pyo3(get, set)]
+    #[serde(default)]
+    qualifications: Vec<PyQualificationID>,
+    /// Class of this vehicle, used to pick the driving times
+    /// set for this class with `ScheduleGenerator.set_driving_times`
+    #[pyo3(get, set)]
+    #[serde(default)]
+    vehicle_class: Option<PyVehicleClassID>,
+    /// Capacities in dimensions other than weight and TEU, e.g. "pallets".
+    /// The truck is not limited in the dimensions it doesn't list
+    #[pyo3(get, set)]
+    #[serde(default)]
+    capacities: BTreeMap<PyCapacityDimensionID, usize>,
+    /// Weight of the empty tractor, in kg
+    #[pyo3(get, set)]
+    #[serde(default)]
+    tractor_tare_weight_kg: usize,
+    /// Weight of the empty trailer/chassis, in kg
+    #[pyo3(get, set)]
+    #[serde(default)]
+    trailer_tare_weight_kg: usize,
+    /// Legal maximum for the weight of the tractor, trailer and cargo combined, in kg.
+    /// This is checked separately from `max_weight_kg`, which is how much the truck
+    /// can physically carry
+    #[pyo3(get, set)]
+    #[serde(default)]
+    max_gross_weight_kg: Option<usize>,
+    /// Whether the trailer is loaded from the rear only, so that
+    /// cargo needs to be dropped off in the reverse order of pickup
+    #[pyo3(get, set)]
+    #[serde(default)]
+    rear_loading: bool,
+    /// Time at which the driver's shift starts. Defaults to when
+    /// the starting terminal opens
+    #[pyo3(get, set)]
+    #[serde(default)]
+    shift_start_time: Option<PyTime>,
+    /// How fast this truck drives compared to the driving times given, e.g. 0.8
+    /// for a truck whose legs take 25% longer
+    #[pyo3(get, set)]
+    #[serde(default = "default_speed_factor")]
+    speed_factor: f64,
+}
+
+impl HeapSize for PyTruckData {
+    fn heap_size(&self) -> usize {
+        self.starting_terminal.heap_size()
+            + self.qualifications.heap_size()
+            + self.vehicle_class.heap_size()
+            + self.capacities.heap_size()
+    }
 }
 
 #[pymethods]
 impl PyTruckData {
     #[new]
-    pub fn new(starting_terminal: PyTerminalID, max_weight_kg: usize, max_teu: usize) -> Self {
-        Self {
+    #[pyo3(signature = (
+        starting_terminal,
+        max_weight_kg,
+        max_teu,
+        qualifications=vec![],
+        vehicle_class=None,
+        capacities=BTreeMap::new(),
+        tractor_tare_weight_kg=0,
+        trailer_tare_weight_kg=0,
+        max_gross_weight_kg=None,
+        rear_loading=false,
+        shift_start_time=None,
+        speed_factor=1.0,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        starting_terminal: PyTerminalID,
+        max_weight_kg: i64,
+        max_teu: i64,
+        qualifications: Vec<PyQualificationID>,
+        vehicle_class: Option<PyVehicleClassID>,
+        capacities: BTreeMap<PyCapacityDimensionID, i64>,
+        tractor_tare_weight_kg: i64,
+        trailer_tare_weight_kg: i64,
+        max_gross_weight_kg: Option<i64>,
+        rear_loading: bool,
+        shift_start_time: Option<PyTime>,
+        speed_factor: f64,
+    ) -> PyResult<Self> {
+        let record = format!("Truck starting at terminal {starting_terminal:?}");
+        let data = Self {
+            max_weight_kg: non_negative(&record, "max_weight_kg", max_weight_kg)?,
+            max_teu: non_negative(&record, "max_teu", max_teu)?,
+            qualifications,
+            vehicle_class,
+            capacities: capacities
+                .into_iter()
+                .map(|(name, capacity)| {
+                    let capacity = non_negative(&record, &format!("{name:?} capacity"), capacity)?;
+                    Ok((name, capacity))
+                })
+                .collect::<PyResult<_>>()?,
+            tractor_tare_weight_kg: non_negative(
+                &record,
+                "tractor_tare_weight_kg",
+                tractor_tare_weight_kg,
+            )?,
+            trailer_tare_weight_kg: non_negative(
+                &record,
+                "trailer_tare_weight_kg",
+                trailer_tare_weight_kg,
+            )?,
+            max_gross_weight_kg: max_gross_weight_kg
+                .map(|weight| non_negative(&record, "max_gross_weight_kg", weight))
+                .transpose()?,
             starting_terminal,
-            max_weight_kg,
-            max_teu,
+            rear_loading,
+            shift_start_time,
+            speed_factor,
+        };
+        data.validate(&record)?;
+        Ok(data)
+    }
+}
+
+impl PyTruckData {
+    /// Checks the fields which don't depend on the rest of the input,
+    /// naming `record` in the error
+    fn validate(&self, record: &str) -> PyResult<()> {
+        if !(self.speed_factor.is_finite() && self.speed_factor > 0.0) {
+            return Err(PyTypeError::new_err(format!(
+                "{record} has speed factor {}, which is not positive",
+                self.speed_factor
+            )));
+        }
+        let tare_weight_kg = self.tractor_tare_weight_kg + self.trailer_tare_weight_kg;
+        if let Some(max_gross_weight_kg) = self.max_gross_weight_kg {
+            if max_gross_weight_kg < tare_weight_kg {
+                return Err(PyTypeError::new_err(format!(
+                    "{record} has max_gross_weight_kg {max_gross_weight_kg}, \
+                     which is less than its tare weight {tare_weight_kg}"
+                )));
+            }
         }
+        Ok(())
     }
 }
 
-#[derive(PartialEq, Eq)]
+/// Converts `value` of the field `field` of `record` to an unsigned number,
+/// naming them in the error if it is negative
+fn non_negative(record: &str, field: &str, value: i64) -> PyResult<usize> {
+    usize::try_from(value)
+        .map_err(|_| PyTypeError::new_err(format!("{record} has negative {field} {value}")))
+}
+
+#[derive(Clone, PartialEq, Eq)]
 pub struct TruckData {
     starting_terminal: Terminal,
+    /// Time before which the truck can't do anything
     start_time: Time,
-    max_weight_kg: usize,
-    max_teu: usize,
+    /// How much the truck can carry in each capacity dimension
+    capacity: Capacity,
+    /// Combined weight of the empty tractor and trailer
+    tare_weight_kg: usize,
+    /// Legal maximum for the weight of the truck with its cargo
+    max_gross_weight_kg: Option<usize>,
+    /// Whether cargo needs to be dropped off in the reverse order of pickup
+    rear_loading: bool,
+    qualifications: BTreeSet<Qualification>,
+    vehicle_class: Option<VehicleClass>,
+    /// How long this truck takes to drive, in thousandths of the driving times given
+    driving_time_per_mille: NonNegativeTimeDelta,
+}
+
+impl HeapSize for TruckData {
+    fn heap_size(&self) -> usize {
+        self.capacity.heap_size() + self.qualifications.heap_size()
+    }
 }
 
 #[pyclass]
 #[derive(FromPyObject, Debug)]
+/// A driver from the roster, who can be assigned to drive trucks
+pub struct PyDriver {
+    #[pyo3(get, set)]
+    id: PyDriverID,
+    /// Terminal the driver starts from. If given, the driver
+    /// can only drive trucks starting at this terminal
+    #[pyo3(get, set)]
+    home_terminal: Option<PyTerminalID>,
+    #[pyo3(get, set)]
+    shift_start_time: PyTime,
+    #[pyo3(get, set)]
+    shift_end_time: PyTime,
+    /// Qualifications of the driver, e.g. "ADR"
+    #[pyo3(get, set)]
+    qualifications: Vec<PyQualificationID>,
+    /// If given, the driver doesn't work on days closed in this region's calendar
+    #[pyo3(get, set)]
+    region: Option<PyRegionID>,
+}
+
+#[pymethods]
+impl PyDriver {
+    #[new]
+    #[pyo3(signature = (
+        id,
+        shift_start_time,
+        shift_end_time,
+        qualifications=vec![],
+        home_terminal=None,
+        region=None,
+    ))]
+    pub fn new(
+        id: PyDriverID,
+        shift_start_time: PyTime,
+        shift_end_time: PyTime,
+        qualifications: Vec<PyQualificationID>,
+        home_terminal: Option<PyTerminalID>,
+        region: Option<PyRegionID>,
+    ) -> Self {
+        Self {
+            id,
+            home_terminal,
+            shift_start_time,
+            shift_end_time,
+            qualifications,
+            region,
+        }
+    }
+}
+
+/// A driver from the roster
+#[derive(Clone, PartialEq, Eq)]
+struct DriverData {
+    /// The only terminal whose trucks the driver can drive, if any
+    home_terminal: Option<Terminal>,
+    /// When the driver is available to drive, excluding the days
+    /// closed in the driver's calendar
+    shifts: IntervalChain,
+    qualifications: BTreeSet<Qualification>,
+}
+
+impl HeapSize for DriverData {
+    fn heap_size(&self) -> usize {
+        self.home_terminal.heap_size() + self.shifts.heap_size() + self.qualifications.heap_size()
+    }
+}
+
+#[pyclass]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 /// The representation of request for delivery that the rust code gets from python
 pub struct PyBooking {
     #[pyo3(get, set)]
     cargo: PyCargoID,
     #[pyo3(get, set)]
+    #[serde(default)]
     cargo_weight_kg: usize,
     #[pyo3(get, set)]
+    #[serde(default = "default_cargo_teu")]
     cargo_teu: usize,
     #[pyo3(get, set)]
     from_terminal: PyTerminalID,
     #[pyo3(get, set)]
     to_terminal: PyTerminalID,
+    /// Defaults to the start of the planning period
+    #[pyo3(get, set)]
+    #[serde(default)]
+    pickup_open_time: Option<PyTime>,
+    /// Defaults to the end of the planning period
+    #[pyo3(get, set)]
+    #[serde(default)]
+    pickup_close_time: Option<PyTime>,
+    /// Defaults to `pickup_open_time`, i.e. any time after pickup
+    #[pyo3(get, set)]
+    #[serde(default)]
+    dropoff_open_time: Option<PyTime>,
+    /// Defaults to the end of the planning period
+    #[pyo3(get, set)]
+    #[serde(default)]
+    dropoff_close_time: Option<PyTime>,
+    /// Whether a schedule that doesn't deliver this cargo is infeasible,
+    /// rather than just worse
+    #[pyo3(get, set)]
+    #[serde(default)]
+    mandatory: bool,
+    /// If set, only this truck is allowed to deliver this cargo
     #[pyo3(get, set)]
-    pickup_open_time: Time,
+    #[serde(default)]
+    locked_truck: Option<PyTruckID>,
+    /// Qualifications the truck and its driver need to have to deliver this cargo
     #[pyo3(get, set)]
-    pickup_close_time: Time,
+    #[serde(default)]
+    required_qualifications: Vec<PyQualificationID>,
+    /// Number of identical containers in this booking. These can be
+    /// delivered by different trucks; if there is more than one,
+    /// the i-th container gets the cargo id "{cargo}#{i}"
     #[pyo3(get, set)]
-    dropoff_open_time: Time,
+    #[serde(default = "default_num_containers")]
+    num_containers: usize,
+    /// If set, after dropping off the container, the empty container needs to be
+    /// returned to this (depot) terminal. This generates an additional delivery
+    /// with cargo id "{cargo}/empty", which can only be picked up after the container
+    /// has been dropped off
     #[pyo3(get, set)]
-    dropoff_close_time: Time,
+    #[serde(default)]
+    empty_return_terminal: Option<PyTerminalID>,
+    /// Time from which the empty container can be returned,
+    /// defaults to `dropoff_open_time`
+    #[pyo3(get, set)]
+    #[serde(default)]
+    empty_return_open_time: Option<PyTime>,
+    /// Time before which the empty container needs to be returned,
+    /// defaults to the end of the planning period
+    #[pyo3(get, set)]
+    #[serde(default)]
+    empty_return_close_time: Option<PyTime>,
+    /// How much of each capacity dimension other than weight and TEU
+    /// each container takes up, e.g. "pallets"
+    #[pyo3(get, set)]
+    #[serde(default)]
+    demands: BTreeMap<PyCapacityDimensionID, usize>,
+    /// Additional stops the same truck needs to make between picking up at
+    /// `from_terminal` and dropping off at `to_terminal`, in order, as
+    /// (terminal, open_time, close_time, is_pickup). Cargo picked up at a stop is
+    /// dropped off at `to_terminal`, and cargo dropped off at a stop is picked
+    /// up at `from_terminal`. Each stop moves one more container with cargo id
+    /// "{cargo}@{stop_index}", taking up the same capacity as the main one
+    #[pyo3(get, set)]
+    #[serde(default, deserialize_with = "deserialize_stops")]
+    stops: Vec<PyStop>,
+    /// How urgent delivering this cargo is. Higher priority cargo counts
+    /// for more in the scores and is preferred when inserting deliveries
+    #[pyo3(get, set)]
+    #[serde(default)]
+    priority: usize,
+    /// Who requested this delivery, e.g. a customer id
+    #[pyo3(get, set)]
+    #[serde(default)]
+    requested_by: Option<String>,
+    /// If given, cargo can only be picked up within these windows,
+    /// e.g. for a terminal with several slots a day
+    #[pyo3(get, set)]
+    #[serde(default)]
+    pickup_windows: Option<PyIntervalChain>,
+    /// If given, cargo can only be dropped off within these windows
+    #[pyo3(get, set)]
+    #[serde(default)]
+    dropoff_windows: Option<PyIntervalChain>,
+}
+
+impl HeapSize for PyBooking {
+    fn heap_size(&self) -> usize {
+        self.cargo.heap_size()
+            + self.from_terminal.heap_size()
+            + self.to_terminal.heap_size()
+            + self.locked_truck.heap_size()
+            + self.required_qualifications.heap_size()
+            + self.empty_return_terminal.heap_size()
+            + self.demands.heap_size()
+            + self.stops.capacity() * std::mem::size_of::<PyStop>()
+            + self
+                .stops
+                .iter()
+                .map(|(terminal_id, ..)| terminal_id.heap_size())
+                .sum::<usize>()
+            + self.requested_by.heap_size()
+            + self
+                .pickup_windows
+                .as_ref()
+                .map_or(0, |windows| windows.chain.heap_size())
+            + self
+                .dropoff_windows
+                .as_ref()
+                .map_or(0, |windows| windows.chain.heap_size())
+    }
 }
 
 #[pymethods]
 impl PyBooking {
     #[new]
+    #[pyo3(signature = (
+        cargo,
+        cargo_weight_kg=0,
+        cargo_teu=1,
+        *,
+        from_terminal,
+        to_terminal,
+        pickup_open_time=None,
+        pickup_close_time=None,
+        dropoff_open_time=None,
+        dropoff_close_time=None,
+        mandatory=false,
+        locked_truck=None,
+        required_qualifications=vec![],
+        num_containers=1,
+        empty_return_terminal=None,
+        empty_return_open_time=None,
+        empty_return_close_time=None,
+        demands=BTreeMap::new(),
+        stops=vec![],
+        priority=0,
+        requested_by=None,
+        pickup_windows=None,
+        dropoff_windows=None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         cargo: PyCargoID,
-        cargo_weight_kg: usize,
-        cargo_teu: usize,
+        cargo_weight_kg: i64,
+        cargo_teu: i64,
         from_terminal: PyTerminalID,
         to_terminal: PyTerminalID,
-        pickup_open_time: Time,
-        pickup_close_time: Time,
-        dropoff_open_time: Time,
-        dropoff_close_time: Time,
-    ) -> Self {
-        Self {
+        pickup_open_time: Option<PyTime>,
+        pickup_close_time: Option<PyTime>,
+        dropoff_open_time: Option<PyTime>,
+        dropoff_close_time: Option<PyTime>,
+        mandatory: bool,
+        locked_truck: Option<PyTruckID>,
+        required_qualifications: Vec<PyQualificationID>,
+        num_containers: usize,
+        empty_return_terminal: Option<PyTerminalID>,
+        empty_return_open_time: Option<PyTime>,
+        empty_return_close_time: Option<PyTime>,
+        demands: BTreeMap<PyCapacityDimensionID, i64>,
+        stops: Vec<PyStop>,
+        priority: usize,
+        requested_by: Option<String>,
+        pickup_windows: Option<PyIntervalChain>,
+        dropoff_windows: Option<PyIntervalChain>,
+    ) -> PyResult<Self> {
+        let record = format!("Booking for cargo {cargo:?}");
+        let booking = Self {
+            cargo_weight_kg: non_negative(&record, "cargo_weight_kg", cargo_weight_kg)?,
+            cargo_teu: non_negative(&record, "cargo_teu", cargo_teu)?,
+            demands: demands
+                .into_iter()
+                .map(|(name, demand)| {
+                    let demand = non_negative(&record, &format!("{name:?} demand"), demand)?;
+                    Ok((name, demand))
+                })
+                .collect::<PyResult<_>>()?,
             cargo,
-            cargo_weight_kg,
-            cargo_teu,
             from_terminal,
             to_terminal,
             pickup_open_time,
             pickup_close_time,
             dropoff_open_time,
             dropoff_close_time,
+            mandatory,
+            locked_truck,
+            required_qualifications,
+            num_containers,
+            empty_return_terminal,
+            empty_return_open_time,
+            empty_return_close_time,
+            stops,
+            priority,
+            requested_by,
+            pickup_windows,
+            dropoff_windows,
+        };
+        booking.validate()?;
+        Ok(booking)
+    }
+}
+
+impl PyBooking {
+    /// Ids of all the terminals this booking refers to
+    fn terminal_ids(&self) -> impl Iterator<Item = &PyTerminalID> {
+        [&self.from_terminal, &self.to_terminal]
+            .into_iter()
+            .chain(self.empty_return_terminal.iter())
+            .chain(self.stops.iter().map(|(terminal_id, ..)| terminal_id))
+    }
+
+    /// Cargo id of the container with index `container_index` of this booking
+    fn container_cargo_id(&self, container_index: usize) -> PyCargoID {
+        if self.num_containers == 1 {
+            self.cargo.clone()
+        } else {
+            format!("{}#{container_index}", self.cargo)
+        }
+    }
+
+    /// Ids of all the cargo this booking can generate, including the
+    /// empty returns and the additional stops of each container
+    fn generated_cargo_ids(&self) -> Vec<PyCargoID> {
+        let mut out = vec![];
+        for container_index in 0..self.num_containers {
+            let cargo_id = self.container_cargo_id(container_index);
+            if self.empty_return_terminal.is_some() {
+                out.push(format!("{cargo_id}/empty"));
+            }
+            out.extend((0..self.stops.len()).map(|stop_index| format!("{cargo_id}@{stop_index}")));
+            out.push(cargo_id);
+        }
+        out
+    }
+
+    /// Checks the fields which don't depend on the rest of the input,
+    /// naming the cargo in the error
+    fn validate(&self) -> PyResult<()> {
+        let cargo = &self.cargo;
+        if self.from_terminal == self.to_terminal {
+            return Err(PyTypeError::new_err(format!(
+                "Booking for cargo {cargo:?} is picked up and dropped off at the same terminal {:?}",
+                self.from_terminal
+            )));
+        }
+        if self.num_containers == 0 {
+            return Err(PyTypeError::new_err(format!(
+                "Booking for cargo {cargo:?} has no containers"
+            )));
+        }
+
+        let mut windows = vec![
+            ("pickup", self.pickup_open_time, self.pickup_close_time),
+            ("dropoff", self.dropoff_open_time, self.dropoff_close_time),
+            (
+                "empty return",
+                self.empty_return_open_time,
+                self.empty_return_close_time,
+            ),
+        ];
+        windows.extend(self.stops.iter().map(
+            |(_terminal_id, open_time, close_time, _is_pickup)| {
+                ("stop", Some(*open_time), Some(*close_time))
+            },
+        ));
+        for (name, open_time, close_time) in windows {
+            if let (Some(open_time), Some(close_time)) = (open_time, close_time) {
+                if open_time.0 >= close_time.0 {
+                    return Err(InvalidIntervalError::new_err(format!(
+                        "Booking for cargo {cargo:?} has {name} window opening at {} \
+                         and closing at {}, which is not after it opens",
+                        open_time.0, close_time.0
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[pyclass]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Days on which a region is closed, e.g. for weekends and public holidays
+pub struct PyCalendar {
+    /// Days of the week which are closed, with 0 being Monday
+    #[pyo3(get, set)]
+    #[serde(default = "default_closed_weekdays")]
+    closed_weekdays: Vec<u64>,
+    /// Dates which are closed, in the timezone of each terminal, or UTC for terminals
+    /// without one and for drivers. Datetimes are taken to be on their local date
+    #[pyo3(get, set)]
+    #[serde(default)]
+    holidays: Vec<PyDate>,
+}
+
+impl HeapSize for PyCalendar {
+    fn heap_size(&self) -> usize {
+        self.closed_weekdays.heap_size() + self.holidays.heap_size()
+    }
+}
+
+#[pymethods]
+impl PyCalendar {
+    #[new]
+    #[pyo3(signature = (closed_weekdays=vec![5, 6], holidays=vec![]))]
+    pub fn new(closed_weekdays: Vec<u64>, holidays: Vec<PyDate>) -> Self {
+        Self {
+            closed_weekdays,
+            holidays,
         }
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 struct BookingInformation {
     /// Terminal where cargo can be picked up from
     from: Terminal,
     /// Terminal where cargo needs to be dropped off to
     to: Terminal,
-    weight_kg: usize,
-    teu: usize,
+    /// How much of each capacity dimension this cargo takes up
+    demand: Capacity,
+    /// Whether the schedule is infeasible without this cargo being delivered
+    mandatory: bool,
+    /// The only truck allowed to deliver this cargo, if any
+    locked_truck: Option<Truck>,
+    /// Qualifications the truck's driver needs to have
+    required_qualifications: BTreeSet<Qualification>,
+    /// Cargo that needs to be dropped off before this cargo can be picked up,
+    /// e.g. the full container for an empty container return
+    predecessor: Option<Cargo>,
+    /// Whether the requested pickup or dropoff window could not be met and
+    /// had to be relaxed, so that delivering this cargo needs renegotiating
+    relaxed_time_windows: bool,
+    /// How urgent delivering this cargo is
+    priority: usize,
+    /// Who requested this delivery
+    requested_by: Option<String>,
+}
+
+impl HeapSize for BookingInformation {
+    fn heap_size(&self) -> usize {
+        self.demand.heap_size()
+            + self.required_qualifications.heap_size()
+            + self.requested_by.heap_size()
+    }
+}
+
+impl BookingInformation {
+    /// How much delivering this cargo counts for in the scores
+    fn delivery_weight(&self) -> f64 {
+        self.priority.saturating_add(1) as f64
+    }
+}
+
+#[pyclass]
+#[derive(Debug, Clone, Serialize)]
+#[serde(transparent)]
+/// A list of non-overlapping time windows in increasing order,
+/// which can be indexed and iterated over as (start_time, end_time) pairs
+pub struct PyIntervalChain {
+    chain: IntervalChain,
+}
+
+/// A window of a `PyIntervalChain`. Read from a (start_time, end_time) pair,
+/// e.g. from a table of bookings, see `PyRows`, or from a dict with these keys,
+/// as the windows are serialized
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum WindowRow {
+    Pair(PyTime, PyTime),
+    Fields {
+        start_time: PyTime,
+        end_time: PyTime,
+    },
+}
+
+// Deserialized like `from_pairs`
+impl<'de> Deserialize<'de> for PyIntervalChain {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let rows = Vec::<WindowRow>::deserialize(deserializer)?;
+        let mut chain = IntervalChain::from_intervals(
+            rows.into_iter()
+                .map(|row| {
+                    let (start_time, end_time) = match row {
+                        WindowRow::Pair(start_time, end_time) => (start_time, end_time),
+                        WindowRow::Fields {
+                            start_time,
+                            end_time,
+                        } => (start_time, end_time),
+                    };
+                    Interval::new(start_time.0, end_time.0, ()).ok_or_else(|| {
+                        D::Error::custom(format!(
+                            "Invalid interval starting at {}, ending at {}",
+                            start_time.0, end_time.0
+                        ))
+                    })
+                })
+                .collect::<Result<_, _>>()?,
+        );
+        chain.normalize();
+        Ok(Self { chain })
+    }
+}
+
+#[pymethods]
+impl PyIntervalChain {
+    /// Creates the windows from a list of (start_time, end_time) pairs,
+    /// merging the ones that overlap
+    #[staticmethod]
+    pub fn from_pairs(pairs: Vec<(PyTime, PyTime)>) -> PyResult<Self> {
+        let mut chain = IntervalChain::from_intervals(
+            pairs
+                .into_iter()
+                .map(|(start_time, end_time)| interval_or_error(start_time.0, end_time.0))
+                .collect::<PyResult<_>>()?,
+        );
+        chain.normalize();
+        Ok(Self { chain })
+    }
+
+    pub fn __len__(&self) -> usize {
+        self.chain.len()
+    }
+
+    pub fn __getitem__(&self, index: isize) -> PyResult<(PyTime, PyTime)> {
+        // Negative indices count from the end, like for lists
+        let index = if index < 0 {
+            index + self.chain.len() as isize
+        } else {
+            index
+        };
+        let interval = usize::try_from(index)
+            .ok()
+            .filter(|index| *index < self.chain.len())
+            .map(|index| &self.chain[index])
+            .ok_or_else(|| PyIndexError::new_err("Interval index out of range"))?;
+        Ok((
+            interval.get_start_time().into(),
+            interval.get_end_time().into(),
+        ))
+    }
+
+    pub fn __contains__(&self, time: PyTime) -> bool {
+        self.chain.contains(time.0)
+    }
+
+    /// Total length of the windows, in seconds
+    pub fn total_length(&self) -> NonNegativeTimeDelta {
+        self.chain.total_length()
+    }
 }
 
-type IntervalsByCargoMap = BTreeMap<Cargo, IntervalChain>;
+type IntervalsByCargoMap = IdMap<Cargo, IntervalChain>;
 
 /// An operation that the truck needs to carry out
 /// [       ]
 /// ^    ^  ^
 /// |    |  |
 /// time | time+duration
-///      | at this point, have available_teu TEU, available_weight_kg weight
+///      | at this point, have available_capacity left
 ///      |
 ///    do all pickups and dropoffs
-#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
 struct Checkpoint {
     time: Time,
     // Needs to be at this terminal
     terminal: Terminal,
-    pickup_cargo: BTreeSet<Cargo>,
-    dropoff_cargo: BTreeSet<Cargo>,
-    /// These values describe weight and size left
+    pickup_cargo: CargoSet,
+    dropoff_cargo: CargoSet,
+    /// Describes capacity (e.g. weight and size) left
     /// after doing the pickups and dropoffs
-    available_teu: usize,
-    available_weight_kg: usize,
+    available_capacity: Capacity,
     /// How long to stay in the checkpoint after `time`
     duration: NonNegativeTimeDelta,
 }
 
+impl HeapSize for Checkpoint {
+    fn heap_size(&self) -> usize {
+        self.pickup_cargo.heap_size()
+            + self.dropoff_cargo.heap_size()
+            + self.available_capacity.heap_size()
+    }
+}
+
+/// A driver's break, during which the truck neither drives
+/// nor does anything at a terminal
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+struct Break {
+    time: Time,
+    duration: NonNegativeTimeDelta,
+}
+
+impl HeapSize for Break {
+    fn heap_size(&self) -> usize {
+        0
+    }
+}
+
+/// Limits on how long drivers can drive, e.g. from EU driving-hour rules
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct DrivingHourRules {
+    /// The longest a driver can drive without taking a break
+    max_continuous_driving_time: NonNegativeTimeDelta,
+    /// The shortest break after which the driver can drive for
+    /// `max_continuous_driving_time` again
+    min_break_duration: NonNegativeTimeDelta,
+}
+
+/// External ids of the trucks, terminals and cargo of a generator, indexed by
+/// internal id, so that schedules can be displayed without their generator
+struct IdNames {
+    trucks: Vec<PyTruckID>,
+    terminals: Vec<PyTerminalID>,
+    cargo: Vec<PyCargoID>,
+    capacity_dimensions: Vec<PyCapacityDimensionID>,
+    /// How much each truck can carry, if it is registered
+    truck_capacities: Vec<Option<Capacity>>,
+    time_formats: TimeFormats,
+}
+
+impl IdNames {
+    fn truck(&self, truck: &Truck) -> &str {
+        &self.trucks[truck.get_id()]
+    }
+
+    fn terminal(&self, terminal: &Terminal) -> &str {
+        &self.terminals[terminal.get_id()]
+    }
+
+    fn cargo(&self, cargo: &Cargo) -> &str {
+        &self.cargo[cargo.get_id()]
+    }
+
+    /// Describes a checkpoint of `truck` for python
+    fn checkpoint(&self, truck: &Truck, checkpoint: &Checkpoint) -> PyCheckpoint {
+        let cargo_ids = |cargo: &CargoSet| {
+            cargo
+                .iter()
+                .map(|cargo| self.cargo(cargo).to_string())
+                .collect()
+        };
+        // Only include the dimensions the truck is limited in
+        let truck_capacity = self.truck_capacities[truck.get_id()].as_ref();
+        let available_capacity = self
+            .capacity_dimensions
+            .iter()
+            .enumerate()
+            .map(|(index, dimension_id)| (CapacityDimension::from_id(index), dimension_id))
+            .filter(|(dimension, _)| {
+                truck_capacity.is_some_and(|capacity| capacity.get(*dimension) != usize::MAX)
+            })
+            .map(|(dimension, dimension_id)| {
+                (
+                    dimension_id.clone(),
+                    checkpoint.available_capacity.get(dimension),
+                )
+            })
+            .collect();
+        PyCheckpoint {
+            time: self.time_formats.time(checkpoint.time),
+            terminal: self.terminal(&checkpoint.terminal).to_string(),
+            pickups: cargo_ids(&checkpoint.pickup_cargo),
+            dropoffs: cargo_ids(&checkpoint.dropoff_cargo),
+            available_capacity,
+        }
+    }
+}
+
+/// A stop of a truck's route, as given by iterating over a `Schedule`
 #[pyclass]
+pub struct PyCheckpoint {
+    #[pyo3(get)]
+    time: FormattedTime,
+    #[pyo3(get)]
+    terminal: PyTerminalID,
+    /// Cargo picked up at this checkpoint
+    #[pyo3(get)]
+    pickups: Vec<PyCargoID>,
+    /// Cargo dropped off at this checkpoint
+    #[pyo3(get)]
+    dropoffs: Vec<PyCargoID>,
+    /// Capacity left after the pickups and dropoffs, in each
+    /// dimension the truck is limited in
+    #[pyo3(get)]
+    available_capacity: BTreeMap<PyCapacityDimensionID, usize>,
+}
+
+#[pymethods]
+impl PyCheckpoint {
+    pub fn __repr__(&self) -> String {
+        format!(
+            "PyCheckpoint(time={}, terminal={:?}, pickups={:?}, dropoffs={:?}, available_capacity={:?})",
+            self.time.time, self.terminal, self.pickups, self.dropoffs, self.available_capacity
+        )
+    }
+}
+
+/// `IdNames` shared between schedules, which are ignored when comparing
+/// or hashing schedules
 #[derive(Clone)]
+struct SharedIdNames(Arc<IdNames>);
+
+impl PartialEq for SharedIdNames {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for SharedIdNames {}
+
+impl Hash for SharedIdNames {
+    fn hash<H: Hasher>(&self, _state: &mut H) {}
+}
+
+#[pyclass]
+#[derive(Clone, PartialEq, Eq, Hash)]
+/// Cloned for every candidate move, so the per-truck data is kept in `SharedIdMap`s
+/// and `Routes`, whose clones share each truck's data until it is changed
 pub struct Schedule {
+    /// Positions in `truck_checkpoints` of the checkpoints of each truck at each
+    /// terminal it visits, in ascending order. Kept up to date by `Move`s, and by
+    /// `index_checkpoints` when checkpoints change otherwise
+    truck_terminal_positions: SharedIdMap<Truck, BTreeMap<Terminal, Vec<usize>>>,
+
+    /// Total number of checkpoints of all trucks, kept up to date like
+    /// `truck_terminal_positions`
+    num_checkpoints: usize,
+
+    /// Positions in `truck_checkpoints` of the checkpoints of each truck without pickups
+    /// or dropoffs, which are the ones that can be removed, in ascending order.
+    /// Kept up to date like `truck_terminal_positions`
+    truck_empty_positions: SharedIdMap<Truck, Vec<usize>>,
+
+    /// Total number of checkpoints without pickups or dropoffs
+    num_empty_checkpoints: usize,
+
     /// The list of checkpoints for each truck.
     /// An invariant we are maintaining is that the times of checkpoints
     /// in each Vec are in a strictly ascending order and no two consecutive
@@ -147,52 +1069,387 @@ pub struct Schedule {
     ///            to drive from               to drive from
     ///           starting_terminal             C1.terminal
     ///           to C1.terminal             to C2.terminal
-    truck_checkpoints: BTreeMap<Truck, Vec<Checkpoint>>,
+    truck_checkpoints: Routes<Truck, Checkpoint>,
 
     /// Map from cargo that was scheduled to truck taking it
-    scheduled_cargo_truck: BTreeMap<Cargo, Truck>,
+    scheduled_cargo_truck: IdMap<Cargo, Truck>,
+
+    /// The cargo in `scheduled_cargo_truck`, as a bitset to cheaply
+    /// check whether cargo is scheduled and find unscheduled cargo
+    scheduled_cargo: IdSet<Cargo>,
 
     /// Total length of time this truck is driving under this schedule
-    truck_driving_times: BTreeMap<Truck, NonNegativeTimeDelta>,
+    truck_driving_times: IdMap<Truck, NonNegativeTimeDelta>,
+
+    /// The breaks the driver of each truck takes, in ascending order of time.
+    /// Breaks don't overlap with each other or with the time spent at checkpoints,
+    /// and the truck drives between checkpoints outside of breaks
+    truck_breaks: SharedIdMap<Truck, Vec<Break>>,
+
+    /// Map from drivers from the roster to the truck they are driving.
+    /// Drivers of the same truck have shifts that don't overlap
+    driver_trucks: BTreeMap<Driver, Truck>,
+
+    /// Ids of the generator that created the schedule, for displaying it
+    id_names: SharedIdNames,
 }
 
-impl Schedule {
-    fn get_checkpoint_mut(
-        &mut self,
-        truck: Truck,
-        checkpoint_index: usize,
-    ) -> Option<&mut Checkpoint> {
-        self.truck_checkpoints
-            .get_mut(&truck)?
-            .get_mut(checkpoint_index)
+/// A stop of a truck's route, as (time, terminal, cargo picked up, cargo dropped off,
+/// capacity left in each dimension the truck is limited in)
+type PyRouteStop = (
+    FormattedTime,
+    PyTerminalID,
+    Vec<PyCargoID>,
+    Vec<PyCargoID>,
+    BTreeMap<PyCapacityDimensionID, usize>,
+);
+
+/// A step of the capacity a truck has available, as (time, weight in kg, TEU).
+/// The capacity is available from this time until the time of the next step
+type PyCapacityStep = (FormattedTime, usize, usize);
+
+/// Columns of `Schedule.to_csv`, in their default order
+const CSV_COLUMNS: [&str; 10] = [
+    "truck",
+    "stop",
+    "time",
+    "terminal",
+    "latitude",
+    "longitude",
+    "pickups",
+    "dropoffs",
+    "available_weight_kg",
+    "available_teu",
+];
+
+/// Quotes a CSV field if it contains a separator, a quote or a line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
     }
+}
 
-    /// Given a checkpoint, finds the checkpoints directly before and after it
-    fn get_prev_and_next_checkpoints(
-        &self,
-        truck: Truck,
-        checkpoint: &Checkpoint,
-    ) -> (Option<&Checkpoint>, Option<&Checkpoint>) {
-        let checkpoints = self.truck_checkpoints.get(&truck).unwrap();
+/// A leg of a truck's route, as (truck, from terminal, to terminal, points along the
+/// road as (latitude, longitude)). The points are empty if the geometry is not known
+type PyRouteLeg = (PyTruckID, PyTerminalID, PyTerminalID, Vec<(f64, f64)>);
+
+/// How a piece of cargo is delivered, as (truck, pickup time, pickup terminal,
+/// dropoff time, dropoff terminal)
+type PyCargoItinerary = (
+    PyTruckID,
+    FormattedTime,
+    PyTerminalID,
+    FormattedTime,
+    PyTerminalID,
+);
+
+/// Driving times between terminals given to `ScheduleGenerator.set_driving_times`
+#[derive(FromPyObject)]
+pub enum PyDrivingTimeTable {
+    /// Map from each terminal id to the driving times to all the terminals
+    Rows(BTreeMap<PyTerminalID, Vec<PyTimeDelta>>),
+    /// Matrix whose rows and columns are the terminals the driving times are from and to
+    Matrix(PyMatrix),
+}
 
-        let time = checkpoint.time;
+/// Aggregate numbers describing a schedule, see `Schedule.statistics`
+#[pyclass]
+pub struct ScheduleStatistics {
+    /// Total time all trucks spend driving
+    #[pyo3(get)]
+    total_driving_time: FormattedTimeDelta,
+    #[pyo3(get)]
+    truck_driving_times: BTreeMap<PyTruckID, FormattedTimeDelta>,
+    /// Number of checkpoints of each truck
+    #[pyo3(get)]
+    truck_num_stops: BTreeMap<PyTruckID, usize>,
+    /// For each truck and each capacity dimension it is limited in, the percentage
+    /// of the capacity in use on average over time, from the start of the truck's shift
+    /// to the end of its last checkpoint
+    #[pyo3(get)]
+    truck_utilisation: BTreeMap<PyTruckID, BTreeMap<PyCapacityDimensionID, f64>>,
+    /// Time each truck spends neither driving, at checkpoints nor on breaks,
+    /// from the start of its shift to the end of its last checkpoint
+    #[pyo3(get)]
+    truck_idle_times: BTreeMap<PyTruckID, FormattedTimeDelta>,
+    /// Number of (pickups, dropoffs) at each terminal
+    #[pyo3(get)]
+    deliveries_by_terminal: BTreeMap<PyTerminalID, (usize, usize)>,
+}
 
-        // NOTE: this inequality is strict since we don't expect
-        // 2 checkpoints to have the same time
-        let prev = checkpoints
-            .iter()
-            .rev()
-            .find(|checkpoint| checkpoint.time < time);
-        let next = checkpoints.iter().find(|checkpoint| checkpoint.time > time);
+/// A bar of a Gantt chart, as (truck, kind, start time, end time, terminal).
+/// kind is "driving" (to terminal), "stop" (at terminal), "idle" or "break"
+type PyGanttBar = (
+    PyTruckID,
+    &'static str,
+    FormattedTime,
+    FormattedTime,
+    Option<PyTerminalID>,
+);
+
+/// A bar of a Gantt chart of a single truck, as (kind, start time, end time, terminal)
+type GanttBar = (&'static str, Time, Time, Option<Terminal>);
+
+/// A checkpoint as saved by `Schedule.to_json`
+#[derive(Serialize, Deserialize)]
+struct CheckpointJson {
+    terminal: PyTerminalID,
+    time: Time,
+    duration: NonNegativeTimeDelta,
+    pickup_cargo: Vec<PyCargoID>,
+    dropoff_cargo: Vec<PyCargoID>,
+}
 
-        if let Some(prev) = prev {
-            assert!(prev.time < time);
-        }
+/// A schedule as saved by `Schedule.to_json`
+#[derive(Serialize, Deserialize)]
+struct ScheduleJson {
+    truck_checkpoints: BTreeMap<PyTruckID, Vec<CheckpointJson>>,
+    /// (time, duration) of the breaks of each truck
+    #[serde(default)]
+    truck_breaks: BTreeMap<PyTruckID, Vec<(Time, NonNegativeTimeDelta)>>,
+    #[serde(default)]
+    driver_trucks: BTreeMap<PyDriverID, PyTruckID>,
+    /// Only saved for reference, since they are recomputed when loading
+    #[serde(default)]
+    truck_driving_times: BTreeMap<PyTruckID, NonNegativeTimeDelta>,
+}
 
-        if let Some(next) = next {
-            assert!(time < next.time);
+/// Whether cargo is dropped off in the reverse order of pickup (last in, first out).
+/// Cargo picked up or dropped off at the same checkpoint can be (un)loaded in any order
+fn is_last_in_first_out(checkpoints: Route<'_, Checkpoint>) -> bool {
+    // Cargo currently on the truck, grouped by the checkpoint it was loaded at,
+    // in the order it was loaded in
+    let mut loaded_groups: Vec<CargoSet> = vec![];
+    for checkpoint in checkpoints.iter() {
+        // All the cargo dropped off here needs to be in the last groups loaded,
+        // and every group but the earliest of these needs to be unloaded completely
+        let mut num_to_unload = checkpoint.dropoff_cargo.len();
+        while num_to_unload > 0 {
+            let Some(group) = loaded_groups.last_mut() else {
+                return false;
+            };
+            num_to_unload -= checkpoint
+                .dropoff_cargo
+                .iter()
+                .filter(|cargo| group.remove(cargo))
+                .count();
+            if group.is_empty() {
+                loaded_groups.pop();
+            } else if num_to_unload > 0 {
+                return false;
+            }
         }
-
+        if !checkpoint.pickup_cargo.is_empty() {
+            loaded_groups.push(checkpoint.pickup_cargo.clone());
+        }
+    }
+    true
+}
+
+/// Positions of `checkpoints` at each terminal, see `Schedule.truck_terminal_positions`
+fn terminal_positions(checkpoints: Route<'_, Checkpoint>) -> BTreeMap<Terminal, Vec<usize>> {
+    let mut out: BTreeMap<Terminal, Vec<usize>> = BTreeMap::new();
+    for (index, checkpoint) in checkpoints.iter().enumerate() {
+        out.entry(checkpoint.terminal).or_default().push(index);
+    }
+    out
+}
+
+/// Positions of the checkpoints without pickups or dropoffs,
+/// see `Schedule.truck_empty_positions`
+fn empty_positions(checkpoints: Route<'_, Checkpoint>) -> Vec<usize> {
+    (0..checkpoints.len())
+        .filter(|index| checkpoints[*index].is_empty())
+        .collect()
+}
+
+/// Adds `index` to the ascending `positions`
+fn insert_position(positions: &mut Vec<usize>, index: usize) {
+    let position_index = positions.partition_point(|position| *position < index);
+    positions.insert(position_index, index);
+}
+
+/// Updates the `positions` of checkpoints after one was inserted at `index`
+fn shift_positions_for_insert(positions: &mut [usize], index: usize) {
+    for position in positions.iter_mut() {
+        if *position >= index {
+            *position += 1;
+        }
+    }
+}
+
+/// Updates the `positions` of checkpoints after the one at `index` was removed
+fn shift_positions_for_removal(positions: &mut Vec<usize>, index: usize) {
+    positions.retain(|position| *position != index);
+    for position in positions.iter_mut() {
+        if *position > index {
+            *position -= 1;
+        }
+    }
+}
+
+impl Checkpoint {
+    /// Whether nothing is picked up or dropped off at the checkpoint
+    fn is_empty(&self) -> bool {
+        self.pickup_cargo.is_empty() && self.dropoff_cargo.is_empty()
+    }
+}
+
+/// The actions of the rows of `Schedule.to_columns`, in the order of their codes
+/// in `Schedule.to_numpy`
+const STOP_ACTIONS: [&str; 2] = ["pickup", "dropoff"];
+
+impl Schedule {
+    /// A row for each pickup and dropoff, as (truck, time, terminal, cargo,
+    /// index in `STOP_ACTIONS`), by truck and then in order of the truck's checkpoints
+    fn stop_rows(&self) -> impl Iterator<Item = (Truck, Time, Terminal, Cargo, usize)> + '_ {
+        self.truck_checkpoints
+            .iter()
+            .flat_map(|(truck, checkpoints)| {
+                checkpoints.iter().flat_map(move |checkpoint| {
+                    [&checkpoint.pickup_cargo, &checkpoint.dropoff_cargo]
+                        .into_iter()
+                        .enumerate()
+                        .flat_map(move |(action, cargo_collection)| {
+                            cargo_collection.iter().map(move |cargo| {
+                                (*truck, checkpoint.time, checkpoint.terminal, *cargo, action)
+                            })
+                        })
+                })
+            })
+    }
+
+    /// Records that `truck` takes `cargo`, returning the truck that took it before
+    fn assign_cargo(&mut self, cargo: Cargo, truck: Truck) -> Option<Truck> {
+        self.scheduled_cargo.insert(cargo);
+        self.scheduled_cargo_truck.insert(cargo, truck)
+    }
+
+    /// Records that `cargo` is no longer scheduled, returning the truck that took it
+    fn unassign_cargo(&mut self, cargo: &Cargo) -> Option<Truck> {
+        self.scheduled_cargo.remove(cargo);
+        self.scheduled_cargo_truck.remove(cargo)
+    }
+
+    /// Rebuilds the positions of checkpoints of `truck` at each terminal and of
+    /// the empty ones, and the numbers of checkpoints, after they were changed
+    /// other than by a `Move`
+    fn index_checkpoints(&mut self, truck: Truck) {
+        self.num_checkpoints = self
+            .truck_checkpoints
+            .values()
+            .map(|route| route.len())
+            .sum();
+        match self.truck_checkpoints.get(&truck) {
+            Some(checkpoints) => {
+                self.truck_terminal_positions
+                    .insert(truck, terminal_positions(checkpoints));
+                self.truck_empty_positions
+                    .insert(truck, empty_positions(checkpoints));
+            }
+            None => {
+                self.truck_terminal_positions.remove(&truck);
+                self.truck_empty_positions.remove(&truck);
+            }
+        }
+        self.num_empty_checkpoints = self.truck_empty_positions.values().map(Vec::len).sum();
+    }
+
+    /// Inserts `checkpoint` at `index` of the checkpoints of `truck`
+    fn insert_checkpoint(&mut self, truck: Truck, index: usize, checkpoint: Checkpoint) {
+        let terminal = checkpoint.terminal;
+        let is_empty = checkpoint.is_empty();
+        self.truck_checkpoints
+            .insert_item(&truck, index, checkpoint);
+        self.num_checkpoints += 1;
+
+        let positions = self.truck_terminal_positions.get_mut(&truck).unwrap();
+        for terminal_positions in positions.values_mut() {
+            shift_positions_for_insert(terminal_positions, index);
+        }
+        insert_position(positions.entry(terminal).or_default(), index);
+
+        let empty_positions = self.truck_empty_positions.get_mut(&truck).unwrap();
+        shift_positions_for_insert(empty_positions, index);
+        if is_empty {
+            insert_position(empty_positions, index);
+            self.num_empty_checkpoints += 1;
+        }
+    }
+
+    /// Removes the checkpoint at `index` of the checkpoints of `truck`
+    fn remove_checkpoint(&mut self, truck: Truck, index: usize) -> Checkpoint {
+        let checkpoint = self.truck_checkpoints.remove_item(&truck, index);
+        self.num_checkpoints -= 1;
+
+        let positions = self.truck_terminal_positions.get_mut(&truck).unwrap();
+        for terminal_positions in positions.values_mut() {
+            shift_positions_for_removal(terminal_positions, index);
+        }
+        positions.retain(|_terminal, terminal_positions| !terminal_positions.is_empty());
+
+        shift_positions_for_removal(self.truck_empty_positions.get_mut(&truck).unwrap(), index);
+        if checkpoint.is_empty() {
+            self.num_empty_checkpoints -= 1;
+        }
+        checkpoint
+    }
+
+    /// Overwrites the checkpoints of `truck` from `first_index` on with `checkpoints`,
+    /// which are at the same terminals
+    fn replace_checkpoints(
+        &mut self,
+        truck: Truck,
+        first_index: usize,
+        checkpoints: &[Checkpoint],
+    ) {
+        let empty_positions = self.truck_empty_positions.get_mut(&truck).unwrap();
+        for (index, new_checkpoint) in (first_index..).zip(checkpoints) {
+            let checkpoint = self.truck_checkpoints.get_item_mut(&truck, index).unwrap();
+            assert!(checkpoint.terminal == new_checkpoint.terminal);
+            match (checkpoint.is_empty(), new_checkpoint.is_empty()) {
+                (false, true) => {
+                    insert_position(empty_positions, index);
+                    self.num_empty_checkpoints += 1;
+                }
+                (true, false) => {
+                    empty_positions.retain(|position| *position != index);
+                    self.num_empty_checkpoints -= 1;
+                }
+                _ => {}
+            }
+            checkpoint.clone_from(new_checkpoint);
+        }
+    }
+
+    /// Given a checkpoint, finds the checkpoints directly before and after it
+    fn get_prev_and_next_checkpoints(
+        &self,
+        truck: Truck,
+        checkpoint: &Checkpoint,
+    ) -> (Option<&Checkpoint>, Option<&Checkpoint>) {
+        let checkpoints = self.truck_checkpoints.get(&truck).unwrap();
+
+        let time = checkpoint.time;
+
+        // NOTE: this inequality is strict since we don't expect
+        // 2 checkpoints to have the same time
+        let prev = checkpoints
+            .iter()
+            .rev()
+            .find(|checkpoint| checkpoint.time < time);
+        let next = checkpoints.iter().find(|checkpoint| checkpoint.time > time);
+
+        if let Some(prev) = prev {
+            assert!(prev.time < time);
+        }
+
+        if let Some(next) = next {
+            assert!(time < next.time);
+        }
+
         (prev, next)
     }
 
@@ -227,51 +1484,155 @@ impl Schedule {
 
         (prev, next)
     }
-}
 
-#[pymethods]
-impl Schedule {
-    /// Generates a textual representation of the schedule
-    pub fn repr(&self, schedule_generator: &ScheduleGenerator) -> String {
+    /// Textual representation of the schedule, see `repr`
+    fn describe(&self, id_names: &IdNames) -> String {
         let mut out = String::new();
         for (truck, checkpoints) in self.truck_checkpoints.iter() {
+            let breaks = self.truck_breaks.get(truck).unwrap();
             // Don't print empty trucks
-            if checkpoints.is_empty() {
+            if checkpoints.is_empty() && breaks.is_empty() {
                 continue;
             }
 
-            let truck_id = schedule_generator.truck_mapper.map(truck).unwrap();
+            let truck_id = id_names.truck(truck);
             out.push_str(&format!("Truck {truck_id:?}:\n"));
 
             for checkpoint in checkpoints.iter() {
                 out.push_str(&format!(
                     "Time: {}, Terminal {:?}: Pick up {:?}, drop off {:?}, new available weight: {}, new available TEU: {}\n",
                     checkpoint.time,
-                    schedule_generator
-                        .terminal_mapper
-                        .map(&checkpoint.terminal)
-                        .unwrap(),
+                    id_names.terminal(&checkpoint.terminal),
                     // Display as vector
                     checkpoint
                         .pickup_cargo
                         .iter()
-                        .map(|cargo| schedule_generator.cargo_mapper.map(cargo).unwrap())
+                        .map(|cargo| id_names.cargo(cargo))
                         .collect::<Vec<_>>(),
                     // Display as vector
                     checkpoint
                         .dropoff_cargo
                         .iter()
-                        .map(|cargo| schedule_generator.cargo_mapper.map(cargo).unwrap())
+                        .map(|cargo| id_names.cargo(cargo))
                         .collect::<Vec<_>>(),
 
-                    checkpoint.available_weight_kg,
-                    checkpoint.available_teu
+                    checkpoint.available_capacity.get(CapacityDimension::WEIGHT_KG),
+                    checkpoint.available_capacity.get(CapacityDimension::TEU)
+                ));
+            }
+            for break_ in breaks.iter() {
+                out.push_str(&format!(
+                    "Time: {}, Break for {}\n",
+                    break_.time, break_.duration
                 ));
             }
             out.push_str("\n\n");
         }
         out
     }
+}
+
+#[pymethods]
+impl Schedule {
+    /// Whether the schedules have the same checkpoints, breaks and driver assignments
+    pub fn __eq__(&self, other: &Schedule) -> bool {
+        self == other
+    }
+
+    /// Hash consistent with `__eq__`, e.g. for keeping sets of visited schedules
+    pub fn __hash__(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Returns an independent copy of the schedule, e.g. to keep
+    /// a snapshot of an intermediate solution
+    pub fn copy(&self) -> Schedule {
+        self.clone()
+    }
+
+    pub fn __copy__(&self) -> Schedule {
+        self.clone()
+    }
+
+    /// Same as `__copy__`. Copies share their data until either one is changed,
+    /// which copies the changed part first, so they never affect each other
+    pub fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Schedule {
+        self.clone()
+    }
+
+    /// Approximate heap usage of the schedule in bytes, as a dict with "checkpoints",
+    /// "checkpoint_positions" (the indices of checkpoints by terminal),
+    /// "breaks", "assignments" (of cargo and drivers to trucks) and "total".
+    /// Data shared with copies of the schedule is counted in full
+    pub fn memory_stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let stats = [
+            ("checkpoints", self.truck_checkpoints.heap_size()),
+            (
+                "checkpoint_positions",
+                self.truck_terminal_positions.heap_size() + self.truck_empty_positions.heap_size(),
+            ),
+            ("breaks", self.truck_breaks.heap_size()),
+            (
+                "assignments",
+                self.scheduled_cargo_truck.heap_size()
+                    + self.scheduled_cargo.heap_size()
+                    + self.driver_trucks.heap_size(),
+            ),
+        ];
+        let total = stats.iter().map(|(_name, size)| size).sum::<usize>()
+            + self.truck_driving_times.heap_size();
+
+        let out = PyDict::new(py);
+        for (name, size) in stats {
+            out.set_item(name, size)?;
+        }
+        out.set_item("total", total)?;
+        Ok(out)
+    }
+
+    /// Generates a textual representation of the schedule.
+    /// Same as `str(schedule)`, which doesn't need the generator
+    pub fn repr(&self, schedule_generator: &ScheduleGenerator) -> String {
+        self.describe(&schedule_generator.get_id_names())
+    }
+
+    pub fn __str__(&self) -> String {
+        self.describe(&self.id_names.0)
+    }
+
+    /// Iterates over the checkpoints of all trucks, as (truck_id, `PyCheckpoint`),
+    /// in order of time for each truck
+    pub fn __iter__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyIterator>> {
+        let id_names = &self.id_names.0;
+        let mut items = vec![];
+        for (truck, checkpoints) in self.truck_checkpoints.iter() {
+            for checkpoint in checkpoints.iter() {
+                items.push((
+                    id_names.truck(truck).to_string(),
+                    id_names.checkpoint(truck, checkpoint),
+                ));
+            }
+        }
+        PyList::new(py, items)?.try_iter()
+    }
+
+    /// Number of checkpoints of all trucks, as iterated over by `__iter__`
+    pub fn __len__(&self) -> usize {
+        self.num_checkpoints
+    }
+
+    pub fn __repr__(&self) -> String {
+        format!(
+            "Schedule({} trucks with checkpoints, {} cargo scheduled)",
+            self.truck_checkpoints
+                .values()
+                .filter(|checkpoints| !checkpoints.is_empty())
+                .count(),
+            self.scheduled_cargo.len()
+        )
+    }
 
     /// Represents the schedule as a list of tuples
     ///(truck, datetime, terminal, cargo, was_picked_up)
@@ -279,7 +1640,7 @@ impl Schedule {
     pub fn to_list_of_tuples(
         &self,
         schedule_generator: &ScheduleGenerator,
-    ) -> Vec<(PyTruckID, Time, PyTerminalID, PyCargoID, bool)> {
+    ) -> Vec<(PyTruckID, FormattedTime, PyTerminalID, PyCargoID, bool)> {
         let mut out = Vec::new();
         for (truck, checkpoints) in self.truck_checkpoints.iter() {
             let truck_id = schedule_generator.truck_mapper.map(truck).unwrap();
@@ -291,7 +1652,7 @@ impl Schedule {
                 for cargo in checkpoint.pickup_cargo.iter() {
                     out.push((
                         truck_id.clone(),
-                        checkpoint.time,
+                        schedule_generator.time_formats.time(checkpoint.time),
                         terminal_id.clone(),
                         schedule_generator.cargo_mapper.map(cargo).unwrap(),
                         true,
@@ -300,7 +1661,7 @@ impl Schedule {
                 for cargo in checkpoint.dropoff_cargo.iter() {
                     out.push((
                         truck_id.clone(),
-                        checkpoint.time,
+                        schedule_generator.time_formats.time(checkpoint.time),
                         terminal_id.clone(),
                         schedule_generator.cargo_mapper.map(cargo).unwrap(),
                         false,
@@ -310,938 +1671,9235 @@ impl Schedule {
         }
         out
     }
-}
-
-/// Class with logic and data needed to create schedules
-#[pyclass]
-#[derive(PartialEq, Eq)]
-pub struct ScheduleGenerator {
-    /// A map from (from_terminal, to_terminal) to cached driving times
-    driving_times_cache: DrivingTimesCache,
-
-    // A map from (start_terminal, end_terminal) to collection of cargo
-    // that can be delivered from start_terminal to end_terminal
-    cargo_by_terminals: BTreeMap<(Terminal, Terminal), BTreeSet<Cargo>>,
 
-    /// Times during which pickup can occur. Takes into account e.g. terminals
-    /// closing overnight
-    pickup_times: IntervalsByCargoMap,
-
-    /// Times during which dropoff can occur. Takes into account e.g. terminals
-    /// closing overnight
-    dropoff_times: IntervalsByCargoMap,
-
-    /// A map from cargo to information about delivering it
-    cargo_booking_info: BTreeMap<Cargo, BookingInformation>,
-
-    terminals: BTreeSet<Terminal>,
-
-    trucks: BTreeSet<Truck>,
-
-    /// Terminals when and where the trucks start at
-    truck_data: BTreeMap<Truck, TruckData>,
+    /// Saves the checkpoints, breaks, driver assignments and driving times of each truck
+    /// as JSON, to be loaded with `from_json`, e.g. as a warm start for the next planning run
+    pub fn to_json(&self, schedule_generator: &ScheduleGenerator) -> PyResult<String> {
+        let truck_id = |truck: &Truck| schedule_generator.truck_mapper.map(truck).unwrap();
+        let cargo_ids = |cargo: &CargoSet| {
+            cargo
+                .iter()
+                .map(|cargo| schedule_generator.cargo_mapper.map(cargo).unwrap())
+                .collect()
+        };
+        let data = ScheduleJson {
+            truck_checkpoints: self
+                .truck_checkpoints
+                .iter()
+                .map(|(truck, checkpoints)| {
+                    let checkpoints = checkpoints
+                        .iter()
+                        .map(|checkpoint| CheckpointJson {
+                            terminal: schedule_generator
+                                .terminal_mapper
+                                .map(&checkpoint.terminal)
+                                .unwrap(),
+                            time: checkpoint.time,
+                            duration: checkpoint.duration,
+                            pickup_cargo: cargo_ids(&checkpoint.pickup_cargo),
+                            dropoff_cargo: cargo_ids(&checkpoint.dropoff_cargo),
+                        })
+                        .collect();
+                    (truck_id(truck), checkpoints)
+                })
+                .collect(),
+            truck_breaks: self
+                .truck_breaks
+                .iter()
+                .map(|(truck, breaks)| {
+                    let breaks = breaks
+                        .iter()
+                        .map(|break_| (break_.time, break_.duration))
+                        .collect();
+                    (truck_id(truck), breaks)
+                })
+                .collect(),
+            driver_trucks: self.get_driver_assignments(schedule_generator),
+            truck_driving_times: self
+                .truck_driving_times
+                .iter()
+                .map(|(truck, driving_time)| (truck_id(truck), *driving_time))
+                .collect(),
+        };
+        serde_json::to_string(&data)
+            .map_err(|err| PyTypeError::new_err(format!("Can't save schedule as JSON: {err}")))
+    }
 
-    /// Time in which we are allowed to schedule trucks
-    planning_period: Interval,
+    /// Describes the schedule as a GeoJSON FeatureCollection, e.g. for kepler.gl or Leaflet,
+    /// with a Point for each terminal with a known location and a LineString for the route
+    /// of each truck with checkpoints. Legs follow the geometries set with
+    /// `ScheduleGenerator.set_route_geometry`, or go straight between terminals otherwise.
+    /// Legs from or to terminals without a location are left out.
+    /// Times are in seconds since the unix epoch
+    pub fn to_geojson(&self, schedule_generator: &ScheduleGenerator) -> PyResult<String> {
+        schedule_generator.check_trucks(self)?;
+        let terminal_id =
+            |terminal: &Terminal| schedule_generator.terminal_mapper.map(terminal).unwrap();
+        let cargo_ids = |cargo: &CargoSet| -> Vec<PyCargoID> {
+            cargo
+                .iter()
+                .map(|cargo| schedule_generator.cargo_mapper.map(cargo).unwrap())
+                .collect()
+        };
+        // GeoJSON puts longitude first
+        let coordinates = |location: &Location| json!([location.longitude, location.latitude]);
 
-    rng: Xoshiro256PlusPlus,
+        let mut features: Vec<Value> = schedule_generator
+            .terminal_locations
+            .iter()
+            .map(|(terminal, location)| {
+                json!({
+                    "type": "Feature",
+                    "geometry": {"type": "Point", "coordinates": coordinates(location)},
+                    "properties": {"kind": "terminal", "terminal": terminal_id(terminal)},
+                })
+            })
+            .collect();
 
-    terminal_mapper: CounterMapper<String>,
-    cargo_mapper: CounterMapper<String>,
-    truck_mapper: CounterMapper<String>,
-}
+        for (truck, checkpoints) in self.truck_checkpoints.iter() {
+            let (Some(first_checkpoint), Some(last_checkpoint)) =
+                (checkpoints.first(), checkpoints.last())
+            else {
+                continue;
+            };
 
-impl ScheduleGenerator {
-    /// Makes sure that checkpoints for a certain truck have a correct format
-    fn assert_truck_checkpoints_invariant(&self, schedule: &Schedule, truck: Truck) {
-        let checkpoints = schedule.truck_checkpoints.get(&truck).unwrap();
-        // Make sure that we don't have 2 checkpoints in the same terminal
-        // together
-        assert!(checkpoints
-            .windows(2)
-            .all(|checkpoints| checkpoints[0].terminal != checkpoints[1].terminal));
+            let mut points: Vec<Location> = vec![];
+            let mut terminal = schedule_generator
+                .truck_data
+                .get(truck)
+                .unwrap()
+                .starting_terminal;
+            for checkpoint in checkpoints.iter() {
+                for point in schedule_generator.get_leg_geometry(terminal, checkpoint.terminal) {
+                    // Legs start where the previous ones end
+                    if points.last() != Some(&point) {
+                        points.push(point);
+                    }
+                }
+                terminal = checkpoint.terminal;
+            }
+            // A LineString needs at least 2 points
+            if points.len() < 2 {
+                continue;
+            }
 
-        // Also check the starting terminal
-        if let Some(first_checkpoint) = checkpoints.first() {
-            assert!(
-                first_checkpoint.terminal != self.truck_data.get(&truck).unwrap().starting_terminal
-            );
+            let stops: Vec<Value> = checkpoints
+                .iter()
+                .map(|checkpoint| {
+                    json!({
+                        "terminal": terminal_id(&checkpoint.terminal),
+                        "time": checkpoint.time,
+                        "pickups": cargo_ids(&checkpoint.pickup_cargo),
+                        "dropoffs": cargo_ids(&checkpoint.dropoff_cargo),
+                    })
+                })
+                .collect();
+            let cargo: CargoSet = checkpoints
+                .iter()
+                .flat_map(|checkpoint| checkpoint.pickup_cargo.iter().copied())
+                .collect();
+            features.push(json!({
+                "type": "Feature",
+                "geometry": {
+                    "type": "LineString",
+                    "coordinates": points.iter().map(coordinates).collect::<Vec<_>>(),
+                },
+                "properties": {
+                    "kind": "route",
+                    "truck": schedule_generator.truck_mapper.map(truck).unwrap(),
+                    "start_time": first_checkpoint.time,
+                    "end_time": last_checkpoint.time,
+                    "driving_time": self.truck_driving_times.get(truck),
+                    "cargo": cargo_ids(&cargo),
+                    "stops": stops,
+                },
+            }));
         }
 
-        // Make sure that the times are still in strictly ascending order of time
-        // https://stackoverflow.com/questions/51272571/how-do-i-check-if-a-slice-is-sorted
-        assert!(checkpoints.windows(2).all(|checkpoints| {
-            let c1 = &checkpoints[0];
-            let c2 = &checkpoints[1];
-            c1.time + c1.duration < c2.time
-        }));
+        serde_json::to_string(&json!({"type": "FeatureCollection", "features": features}))
+            .map_err(|err| PyTypeError::new_err(format!("Can't save schedule as GeoJSON: {err}")))
     }
 
-    /// Get driving time between `from` and `to`.
-    /// If `from` is None, assume it is the starting terminal
-    /// If `to` is None, assume that there is no restriction
-    /// on what `to` is, and so we can stay at `from` for 0 driving time
-    fn get_driving_time(
-        &mut self,
-        from: Option<Terminal>,
-        to: Option<Terminal>,
-        truck: Truck,
-    ) -> NonNegativeTimeDelta {
-        let from = from.unwrap_or_else(|| self.truck_data.get(&truck).unwrap().starting_terminal);
-        if let Some(to) = to {
-            let out = self.driving_times_cache.get_driving_time(from, to);
-            out
-        } else {
-            0
+    /// Describes the schedule as CSV with a row for each checkpoint, e.g. for importing it
+    /// into a TMS. `columns` picks the columns and their order out of "truck", "stop"
+    /// (index of the checkpoint within the truck's route), "time" (seconds since the
+    /// unix epoch), "terminal", "latitude", "longitude" (empty if unknown), "pickups",
+    /// "dropoffs" (cargo ids separated by ";"), "available_weight_kg" and "available_teu"
+    /// (capacity left after the checkpoint), defaulting to all of them.
+    /// Writes the CSV to `path` if given, and returns it otherwise
+    #[pyo3(signature = (schedule_generator, columns=None, path=None))]
+    pub fn to_csv(
+        &self,
+        schedule_generator: &ScheduleGenerator,
+        columns: Option<Vec<String>>,
+        path: Option<PathBuf>,
+    ) -> PyResult<Option<String>> {
+        let columns = columns.unwrap_or_else(|| {
+            CSV_COLUMNS
+                .iter()
+                .map(|column| column.to_string())
+                .collect()
+        });
+        if let Some(column) = columns
+            .iter()
+            .find(|column| !CSV_COLUMNS.contains(&column.as_str()))
+        {
+            return Err(PyTypeError::new_err(format!(
+                "Unknown column {column:?}, expected one of {CSV_COLUMNS:?}"
+            )));
         }
-    }
-
-    /// Find the interval between `prev_checkpoint.time` and `next_checkpoint.time`
-    /// containing the times during which we can put a checkpoint in `new_terminal`
-    /// and have time to drive from `prev_checkpoint.terminal` to `new_terminal` and
-    /// from `new_terminal` to `next_checkpoint.terminal`
-    fn get_transit_time_constraints(
-        &mut self,
-        truck: Truck,
-        prev_checkpoint: Option<&Checkpoint>,
-        next_checkpoint: Option<&Checkpoint>,
-        new_terminal: Terminal,
-    ) -> Option<Interval> {
-        let (prev_terminal, prev_time, prev_duration) =
-            if let Some(prev_checkpoint) = prev_checkpoint {
-                (
-                    Some(prev_checkpoint.terminal),
-                    prev_checkpoint.time,
-                    prev_checkpoint.duration,
-                )
-            } else {
-                // Don't need to wait any time if at starting terminal, so 0 duration
-                // TODO: add proper bound on time
-                (None, self.planning_period.get_start_time(), 0)
-            };
-
-        let (next_terminal, next_time) = if let Some(next_checkpoint) = next_checkpoint {
-            (Some(next_checkpoint.terminal), next_checkpoint.time)
-        } else {
-            // TODO: add proper bound on time
-            (None, self.planning_period.get_end_time())
+        let cargo_ids = |cargo: &CargoSet| {
+            cargo
+                .iter()
+                .map(|cargo| schedule_generator.cargo_mapper.map(cargo).unwrap())
+                .collect::<Vec<_>>()
+                .join(";")
         };
 
-        let driving_time1 = self.get_driving_time(prev_terminal, Some(new_terminal), truck);
-        let driving_time2 = self.get_driving_time(Some(new_terminal), next_terminal, truck);
+        let mut out = columns.join(",") + "\n";
+        for (truck, checkpoints) in self.truck_checkpoints.iter() {
+            let truck_id = schedule_generator.truck_mapper.map(truck).unwrap();
+            for (stop, checkpoint) in checkpoints.iter().enumerate() {
+                let location = schedule_generator
+                    .terminal_locations
+                    .get(&checkpoint.terminal);
+                let row: Vec<String> = columns
+                    .iter()
+                    .map(|column| match column.as_str() {
+                        "truck" => truck_id.clone(),
+                        "stop" => stop.to_string(),
+                        "time" => checkpoint.time.to_string(),
+                        "terminal" => schedule_generator
+                            .terminal_mapper
+                            .map(&checkpoint.terminal)
+                            .unwrap(),
+                        "latitude" => location
+                            .map(|location| location.latitude.to_string())
+                            .unwrap_or_default(),
+                        "longitude" => location
+                            .map(|location| location.longitude.to_string())
+                            .unwrap_or_default(),
+                        "pickups" => cargo_ids(&checkpoint.pickup_cargo),
+                        "dropoffs" => cargo_ids(&checkpoint.dropoff_cargo),
+                        "available_weight_kg" => checkpoint
+                            .available_capacity
+                            .get(CapacityDimension::WEIGHT_KG)
+                            .to_string(),
+                        "available_teu" => checkpoint
+                            .available_capacity
+                            .get(CapacityDimension::TEU)
+                            .to_string(),
+                        _ => unreachable!(),
+                    })
+                    .map(|value| csv_field(&value))
+                    .collect();
+                out += &row.join(",");
+                out += "\n";
+            }
+        }
 
-        let earliest_checkpoint_time = prev_time + prev_duration + driving_time1;
-        let latest_checkpoint_time = next_time - driving_time2;
+        match path {
+            Some(path) => {
+                std::fs::write(path, out)?;
+                Ok(None)
+            }
+            None => Ok(Some(out)),
+        }
+    }
 
-        Interval::new(earliest_checkpoint_time, latest_checkpoint_time, ())
+    /// Loads a schedule saved by `to_json` for the trucks and cargo of `schedule_generator`.
+    /// Trucks that are not in the JSON get no checkpoints, and driving times are
+    /// recomputed from the driving times of `schedule_generator`.
+    /// Raises an error if cargo isn't picked up and later dropped off by the same truck,
+    /// but the schedule is not checked against other constraints, see `ScheduleGenerator.validate`
+    #[staticmethod]
+    pub fn from_json(schedule_generator: &ScheduleGenerator, json: &str) -> PyResult<Schedule> {
+        let data: ScheduleJson = serde_json::from_str(json)
+            .map_err(|err| PyTypeError::new_err(format!("Invalid schedule JSON: {err}")))?;
+        schedule_generator.schedule_from_json(data)
     }
 
-    /// Given a previous and next checkpoints, find
-    /// what terminals those correspond to. Handles cases when
-    /// there is no checkpoint, and suggests correct terminals
+    /// Returns the index of the checkpoint of truck `truck_id` with `handle`, from
+    /// `MoveDescription.checkpoint_handles`, or None if the checkpoint has been removed
+    pub fn get_checkpoint_index(
+        &self,
+        truck_id: PyTruckID,
+        handle: u64,
+        schedule_generator: &ScheduleGenerator,
+    ) -> PyResult<Option<usize>> {
+        let truck: Truck = schedule_generator
+            .truck_mapper
+            .reverse_map(&truck_id)
+            .filter(|truck| self.truck_checkpoints.contains_key(truck))
+            .ok_or_else(|| UnknownIdError::new_err(format!("Unknown truck {truck_id:?}")))?;
+        Ok(self
+            .truck_checkpoints
+            .get(&truck)
+            .unwrap()
+            .position(Handle::from_bits(handle)))
+    }
+
+    /// Returns the checkpoints of truck `truck_id` in order, see `PyRouteStop`
+    pub fn get_truck_route(
+        &self,
+        truck_id: PyTruckID,
+        schedule_generator: &ScheduleGenerator,
+    ) -> PyResult<Vec<PyRouteStop>> {
+        let truck: Truck = schedule_generator
+            .truck_mapper
+            .reverse_map(&truck_id)
+            .filter(|truck| self.truck_checkpoints.contains_key(truck))
+            .ok_or_else(|| UnknownIdError::new_err(format!("Unknown truck {truck_id:?}")))?;
+        let cargo_ids = |cargo: &CargoSet| {
+            cargo
+                .iter()
+                .map(|cargo| schedule_generator.cargo_mapper.map(cargo).unwrap())
+                .collect()
+        };
+        Ok(self
+            .truck_checkpoints
+            .get(&truck)
+            .unwrap()
+            .iter()
+            .map(|checkpoint| {
+                (
+                    schedule_generator.time_formats.time(checkpoint.time),
+                    schedule_generator
+                        .terminal_mapper
+                        .map(&checkpoint.terminal)
+                        .unwrap(),
+                    cargo_ids(&checkpoint.pickup_cargo),
+                    cargo_ids(&checkpoint.dropoff_cargo),
+                    schedule_generator.capacity_to_dict(truck, &checkpoint.available_capacity),
+                )
+            })
+            .collect())
+    }
+
+    /// Returns the weight and TEU truck `truck_id` has available over time, as a step function
+    /// (see `PyCapacityStep`) starting with its full capacity when it can start driving,
+    /// followed by the capacity left after each checkpoint
+    pub fn capacity_profile(
+        &self,
+        truck_id: PyTruckID,
+        schedule_generator: &ScheduleGenerator,
+    ) -> PyResult<Vec<PyCapacityStep>> {
+        schedule_generator.check_trucks(self)?;
+        let truck: Truck = schedule_generator
+            .truck_mapper
+            .reverse_map(&truck_id)
+            .filter(|truck| self.truck_checkpoints.contains_key(truck))
+            .ok_or_else(|| UnknownIdError::new_err(format!("Unknown truck {truck_id:?}")))?;
+        let step = |time: Time, capacity: &Capacity| {
+            (
+                schedule_generator.time_formats.time(time),
+                capacity.get(CapacityDimension::WEIGHT_KG),
+                capacity.get(CapacityDimension::TEU),
+            )
+        };
+
+        let full_capacity = &schedule_generator.truck_data.get(&truck).unwrap().capacity;
+        let mut out = vec![step(
+            schedule_generator.get_truck_start_time(truck),
+            full_capacity,
+        )];
+        out.extend(
+            self.truck_checkpoints
+                .get(&truck)
+                .unwrap()
+                .iter()
+                .map(|checkpoint| step(checkpoint.time, &checkpoint.available_capacity)),
+        );
+        Ok(out)
+    }
+
+    /// Returns the legs driven by each truck in order, starting from its starting
+    /// terminal, with their geometries set by `ScheduleGenerator.set_route_geometry`
+    pub fn get_route_legs(
+        &self,
+        schedule_generator: &ScheduleGenerator,
+    ) -> PyResult<Vec<PyRouteLeg>> {
+        schedule_generator.check_trucks(self)?;
+        let mut out = vec![];
+        for (truck, checkpoints) in self.truck_checkpoints.iter() {
+            let truck_id = schedule_generator.truck_mapper.map(truck).unwrap();
+            let mut terminal = schedule_generator
+                .truck_data
+                .get(truck)
+                .unwrap()
+                .starting_terminal;
+            for checkpoint in checkpoints.iter() {
+                let geometry = schedule_generator
+                    .route_geometries
+                    .get(&(terminal, checkpoint.terminal))
+                    .map(|points| {
+                        points
+                            .iter()
+                            .map(|point| (point.latitude, point.longitude))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                out.push((
+                    truck_id.clone(),
+                    schedule_generator.terminal_mapper.map(&terminal).unwrap(),
+                    schedule_generator
+                        .terminal_mapper
+                        .map(&checkpoint.terminal)
+                        .unwrap(),
+                    geometry,
+                ));
+                terminal = checkpoint.terminal;
+            }
+        }
+        Ok(out)
+    }
+
+    /// Returns how cargo `cargo_id` is delivered, see `PyCargoItinerary`,
+    /// or None if it isn't scheduled. The id of a booking with several containers
+    /// gives the itinerary of its first one
+    pub fn get_cargo_itinerary(
+        &self,
+        cargo_id: PyCargoID,
+        schedule_generator: &ScheduleGenerator,
+    ) -> PyResult<Option<PyCargoItinerary>> {
+        schedule_generator.check_trucks(self)?;
+        let cargo = schedule_generator.find_cargo(&cargo_id)?;
+        let Some(truck) = self.scheduled_cargo_truck.get(&cargo) else {
+            return Ok(None);
+        };
+        let (pickup_time, dropoff_time) = schedule_generator
+            .get_cargo_pickup_dropoff_times(self, cargo)
+            .ok_or_else(|| {
+                InfeasibleScheduleError::new_err(format!(
+                    "Cargo {cargo_id:?} is assigned to a truck which doesn't deliver it"
+                ))
+            })?;
+        let booking_info = schedule_generator.cargo_booking_info.get(&cargo).unwrap();
+        let terminal_mapper = &schedule_generator.terminal_mapper;
+        Ok(Some((
+            schedule_generator.truck_mapper.map(truck).unwrap(),
+            schedule_generator.time_formats.time(pickup_time),
+            terminal_mapper.map(&booking_info.from).unwrap(),
+            schedule_generator.time_formats.time(dropoff_time),
+            terminal_mapper.map(&booking_info.to).unwrap(),
+        )))
+    }
+
+    /// Computes aggregate numbers describing the schedule, see `ScheduleStatistics`
+    pub fn statistics(
+        &self,
+        schedule_generator: &ScheduleGenerator,
+    ) -> PyResult<ScheduleStatistics> {
+        schedule_generator.check_trucks(self)?;
+        let time_formats = schedule_generator.time_formats;
+        let mut out = ScheduleStatistics {
+            total_driving_time: time_formats.duration(
+                self.truck_driving_times
+                    .values()
+                    .sum::<NonNegativeTimeDelta>(),
+            ),
+            truck_driving_times: BTreeMap::new(),
+            truck_num_stops: BTreeMap::new(),
+            truck_utilisation: BTreeMap::new(),
+            truck_idle_times: BTreeMap::new(),
+            deliveries_by_terminal: BTreeMap::new(),
+        };
+
+        for (truck, checkpoints) in self.truck_checkpoints.iter() {
+            let truck_id = schedule_generator.truck_mapper.map(truck).unwrap();
+            let truck_capacity = &schedule_generator.truck_data.get(truck).unwrap().capacity;
+            let start_time = schedule_generator.get_truck_start_time(*truck);
+            let driving_time = *self.truck_driving_times.get(truck).unwrap();
+            let end_time = checkpoints.last().map_or(start_time, |checkpoint| {
+                checkpoint.time + checkpoint.duration
+            });
+            let working_time = end_time.saturating_sub(start_time);
+
+            // Capacity in use between each checkpoint and the next one, weighted by time
+            let mut used_capacity_time = BTreeMap::new();
+            for (checkpoint, next_checkpoint) in checkpoints.iter().zip(checkpoints.iter().skip(1))
+            {
+                let time = next_checkpoint.time - checkpoint.time;
+                for (dimension_id, available) in
+                    schedule_generator.capacity_to_dict(*truck, &checkpoint.available_capacity)
+                {
+                    let dimension: CapacityDimension = schedule_generator
+                        .capacity_dimension_mapper
+                        .reverse_map(&dimension_id)
+                        .unwrap();
+                    let used = truck_capacity.get(dimension) - available;
+                    *used_capacity_time.entry(dimension_id).or_insert(0.0) +=
+                        used as f64 * time as f64;
+                }
+            }
+            let utilisation = schedule_generator
+                .capacity_to_dict(*truck, truck_capacity)
+                .into_iter()
+                .map(|(dimension_id, capacity)| {
+                    let used_time = used_capacity_time
+                        .get(&dimension_id)
+                        .copied()
+                        .unwrap_or(0.0);
+                    let percentage = if capacity == 0 || working_time == 0 {
+                        0.0
+                    } else {
+                        100.0 * used_time / (capacity as f64 * working_time as f64)
+                    };
+                    (dimension_id, percentage)
+                })
+                .collect();
+
+            let busy_time = driving_time
+                + checkpoints
+                    .iter()
+                    .map(|checkpoint| checkpoint.duration)
+                    .sum::<NonNegativeTimeDelta>()
+                + self
+                    .truck_breaks
+                    .get(truck)
+                    .unwrap()
+                    .iter()
+                    .map(|break_| break_.duration)
+                    .sum::<NonNegativeTimeDelta>();
+
+            for checkpoint in checkpoints.iter() {
+                let deliveries = out
+                    .deliveries_by_terminal
+                    .entry(
+                        schedule_generator
+                            .terminal_mapper
+                            .map(&checkpoint.terminal)
+                            .unwrap(),
+                    )
+                    .or_default();
+                deliveries.0 += checkpoint.pickup_cargo.len();
+                deliveries.1 += checkpoint.dropoff_cargo.len();
+            }
+
+            out.truck_driving_times
+                .insert(truck_id.clone(), time_formats.duration(driving_time));
+            out.truck_num_stops
+                .insert(truck_id.clone(), checkpoints.len());
+            out.truck_utilisation.insert(truck_id.clone(), utilisation);
+            out.truck_idle_times.insert(
+                truck_id,
+                time_formats.duration(working_time.saturating_sub(busy_time)),
+            );
+        }
+        Ok(out)
+    }
+
+    /// Represents the schedule as a dict of columns "truck", "time", "terminal", "cargo"
+    /// and "action" ("pickup" or "dropoff"), with a row for each pickup and dropoff,
+    /// e.g. to make a `pandas.DataFrame` from it.
+    /// If `interned_ids` is set, the "truck", "terminal" and "cargo" columns hold integer
+    /// codes instead, which are indices into the lists of ids "truck_ids", "terminal_ids"
+    /// and "cargo_ids", e.g. for `pandas.Categorical.from_codes`
+    #[pyo3(signature = (schedule_generator, interned_ids=false))]
+    pub fn to_columns<'py>(
+        &self,
+        py: Python<'py>,
+        schedule_generator: &ScheduleGenerator,
+        interned_ids: bool,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let mut trucks = vec![];
+        let mut times = vec![];
+        let mut terminals = vec![];
+        let mut cargo = vec![];
+        let mut actions = vec![];
+        for (truck, time, terminal, item, action) in self.stop_rows() {
+            trucks.push(truck.get_id());
+            times.push(schedule_generator.time_formats.time(time));
+            terminals.push(terminal.get_id());
+            cargo.push(item.get_id());
+            actions.push(STOP_ACTIONS[action]);
+        }
+
+        let id_names = schedule_generator.get_id_names();
+        let out = PyDict::new(py);
+        if interned_ids {
+            out.set_item("truck", trucks)?;
+            out.set_item("time", times)?;
+            out.set_item("terminal", terminals)?;
+            out.set_item("cargo", cargo)?;
+            out.set_item("action", actions)?;
+            out.set_item("truck_ids", &id_names.trucks)?;
+            out.set_item("terminal_ids", &id_names.terminals)?;
+            out.set_item("cargo_ids", &id_names.cargo)?;
+        } else {
+            let names = |codes: Vec<usize>, names: &[String]| -> Vec<String> {
+                codes.into_iter().map(|code| names[code].clone()).collect()
+            };
+            out.set_item("truck", names(trucks, &id_names.trucks))?;
+            out.set_item("time", times)?;
+            out.set_item("terminal", names(terminals, &id_names.terminals))?;
+            out.set_item("cargo", names(cargo, &id_names.cargo))?;
+            out.set_item("action", actions)?;
+        }
+        Ok(out)
+    }
+
+    /// Represents the rows of `to_columns` as a numpy structured array with fields
+    /// "truck", "terminal" and "cargo" (uint32 codes as with `interned_ids`),
+    /// "time" (datetime64[s]) and "action" (uint8, 0 for pickup and 1 for dropoff).
+    /// The records are written straight into a python buffer, which numpy uses without
+    /// copying it.
+    /// Fails if the schedule has ids `schedule_generator` doesn't know about
+    pub fn to_numpy<'py>(
+        &self,
+        py: Python<'py>,
+        schedule_generator: &ScheduleGenerator,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let dtype = vec![
+            ("truck", "<u4"),
+            ("time", "<M8[s]"),
+            ("terminal", "<u4"),
+            ("cargo", "<u4"),
+            ("action", "u1"),
+        ];
+        let id_names = schedule_generator.get_id_names();
+        let to_code = |id: usize, names: &[String]| {
+            u32::try_from(id)
+                .ok()
+                .filter(|_| id < names.len())
+                .ok_or_else(|| {
+                    UnknownIdError::new_err("Schedule has ids unknown to the schedule generator")
+                })
+        };
+
+        // NOTE: numpy packs the fields of the dtype without padding
+        const RECORD_SIZE: usize = 4 + 8 + 4 + 4 + 1;
+        let num_records = self.stop_rows().count();
+        let records = PyByteArray::new_with(py, num_records * RECORD_SIZE, |buffer| {
+            for (record, (truck, time, terminal, cargo, action)) in
+                buffer.chunks_exact_mut(RECORD_SIZE).zip(self.stop_rows())
+            {
+                record[0..4]
+                    .copy_from_slice(&to_code(truck.get_id(), &id_names.trucks)?.to_le_bytes());
+                record[4..12].copy_from_slice(&(time as i64).to_le_bytes());
+                record[12..16].copy_from_slice(
+                    &to_code(terminal.get_id(), &id_names.terminals)?.to_le_bytes(),
+                );
+                record[16..20]
+                    .copy_from_slice(&to_code(cargo.get_id(), &id_names.cargo)?.to_le_bytes());
+                record[20] = action as u8;
+            }
+            Ok(())
+        })?;
+
+        let kwargs = PyDict::new(py);
+        kwargs.set_item("dtype", dtype)?;
+        py.import("numpy")?
+            .call_method("frombuffer", (records,), Some(&kwargs))
+    }
+
+    /// Represents what each truck is doing over time as bars of a Gantt chart,
+    /// see `PyGanttBar`. Trucks drive as early as possible, only stopping for breaks
+    pub fn to_gantt(&self, schedule_generator: &ScheduleGenerator) -> PyResult<Vec<PyGanttBar>> {
+        schedule_generator.check_trucks(self)?;
+        let mut out = vec![];
+        for truck in self.truck_checkpoints.keys() {
+            let truck_id = schedule_generator.truck_mapper.map(truck).unwrap();
+            for (kind, start_time, end_time, terminal) in schedule_generator
+                .get_gantt_bars(self, *truck)
+                .map_err(|missing| schedule_generator.driving_time_error(missing))?
+            {
+                out.push((
+                    truck_id.clone(),
+                    kind,
+                    schedule_generator.time_formats.time(start_time),
+                    schedule_generator.time_formats.time(end_time),
+                    terminal
+                        .map(|terminal| schedule_generator.terminal_mapper.map(&terminal).unwrap()),
+                ));
+            }
+        }
+        Ok(out)
+    }
+
+    /// Describes what each truck is doing over time as JSON for timeline charts, with
+    /// "groups" listing a lane for each truck and "items" the segments of the lanes, in the
+    /// format of vis-timeline. The items can also be given to plotly's `px.timeline` as a
+    /// data frame. Each segment has a "segment" type of "drive", "service" (at a terminal),
+    /// "wait" or "break", ISO 8601 "start" and "end" times and the "cargo" on the truck,
+    /// or handled at the terminal for service segments, to color segments by cargo
+    pub fn to_timeline_json(&self, schedule_generator: &ScheduleGenerator) -> PyResult<String> {
+        schedule_generator.check_trucks(self)?;
+        let to_iso = |time: Time| {
+            DateTime::from_timestamp(time as i64, 0)
+                .map(|datetime| datetime.to_rfc3339())
+                .unwrap_or_default()
+        };
+        let cargo_ids = |cargo: &BTreeSet<Cargo>| -> Vec<PyCargoID> {
+            cargo
+                .iter()
+                .map(|cargo| schedule_generator.cargo_mapper.map(cargo).unwrap())
+                .collect()
+        };
+
+        let mut groups = vec![];
+        let mut items = vec![];
+        for (truck, checkpoints) in self.truck_checkpoints.iter() {
+            let truck_id = schedule_generator.truck_mapper.map(truck).unwrap();
+            groups.push(json!({"id": truck_id, "content": truck_id}));
+
+            // Stops are in the order of the checkpoints
+            let mut checkpoints = checkpoints.iter();
+            let mut loaded_cargo = BTreeSet::new();
+            for (kind, start_time, end_time, terminal) in schedule_generator
+                .get_gantt_bars(self, *truck)
+                .map_err(|missing| schedule_generator.driving_time_error(missing))?
+            {
+                let (segment, cargo) = match kind {
+                    "stop" => {
+                        let checkpoint = checkpoints.next().unwrap();
+                        let handled_cargo: BTreeSet<Cargo> = checkpoint
+                            .pickup_cargo
+                            .iter()
+                            .chain(checkpoint.dropoff_cargo.iter())
+                            .copied()
+                            .collect();
+                        loaded_cargo.extend(checkpoint.pickup_cargo.iter().copied());
+                        loaded_cargo.retain(|cargo| !checkpoint.dropoff_cargo.contains(cargo));
+                        ("service", cargo_ids(&handled_cargo))
+                    }
+                    "driving" => ("drive", cargo_ids(&loaded_cargo)),
+                    "idle" => ("wait", cargo_ids(&loaded_cargo)),
+                    _ => ("break", cargo_ids(&loaded_cargo)),
+                };
+                let terminal_id = terminal
+                    .map(|terminal| schedule_generator.terminal_mapper.map(&terminal).unwrap());
+                items.push(json!({
+                    "id": items.len(),
+                    "group": truck_id,
+                    "type": "range",
+                    "segment": segment,
+                    "className": segment,
+                    "content": terminal_id.clone().unwrap_or_else(|| segment.to_string()),
+                    "start": to_iso(start_time),
+                    "end": to_iso(end_time),
+                    "terminal": terminal_id,
+                    "cargo": cargo,
+                }));
+            }
+        }
+
+        serde_json::to_string(&json!({"groups": groups, "items": items}))
+            .map_err(|err| PyTypeError::new_err(format!("Can't save timeline as JSON: {err}")))
+    }
+
+    /// Returns a map from drivers from the roster to the truck they drive
+    pub fn get_driver_assignments(
+        &self,
+        schedule_generator: &ScheduleGenerator,
+    ) -> BTreeMap<PyDriverID, PyTruckID> {
+        self.driver_trucks
+            .iter()
+            .map(|(driver, truck)| {
+                (
+                    schedule_generator.driver_mapper.map(driver).unwrap(),
+                    schedule_generator.truck_mapper.map(truck).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    /// Represents the breaks in the schedule as a list of tuples
+    /// (truck, datetime, duration)
+    pub fn breaks_to_list_of_tuples(
+        &self,
+        schedule_generator: &ScheduleGenerator,
+    ) -> Vec<(PyTruckID, FormattedTime, FormattedTimeDelta)> {
+        let mut out = Vec::new();
+        for (truck, breaks) in self.truck_breaks.iter() {
+            let truck_id = schedule_generator.truck_mapper.map(truck).unwrap();
+            for break_ in breaks.iter() {
+                out.push((
+                    truck_id.clone(),
+                    schedule_generator.time_formats.time(break_.time),
+                    schedule_generator.time_formats.duration(break_.duration),
+                ));
+            }
+        }
+        out
+    }
+}
+
+/// A change made to a schedule by a neighbour action, which can be undone
+/// to get back the schedule it was applied to
+#[derive(Clone, Debug)]
+#[allow(clippy::enum_variant_names)]
+enum Move {
+    /// Insert `checkpoint`, which has no pickups or dropoffs,
+    /// at `index` of the truck's checkpoints
+    AddCheckpoint {
+        truck: Truck,
+        index: usize,
+        checkpoint: Checkpoint,
+        old_driving_time: NonNegativeTimeDelta,
+        new_driving_time: NonNegativeTimeDelta,
+    },
+    /// Remove `checkpoint`, which has no pickups or dropoffs,
+    /// from `index` of the truck's checkpoints
+    RemoveCheckpoint {
+        truck: Truck,
+        index: usize,
+        checkpoint: Checkpoint,
+        old_driving_time: NonNegativeTimeDelta,
+        new_driving_time: NonNegativeTimeDelta,
+    },
+    /// Schedule `cargo` on the truck. The checkpoints from the pickup to the dropoff,
+    /// starting at `first_index`, are replaced with `new_checkpoints`
+    AddDelivery {
+        cargo: Cargo,
+        truck: Truck,
+        first_index: usize,
+        old_checkpoints: Vec<Checkpoint>,
+        new_checkpoints: Vec<Checkpoint>,
+    },
+    /// Unschedule `cargo` from the truck. The checkpoints from the pickup to the dropoff,
+    /// starting at `first_index`, are replaced with `new_checkpoints`
+    RemoveDelivery {
+        cargo: Cargo,
+        truck: Truck,
+        first_index: usize,
+        old_checkpoints: Vec<Checkpoint>,
+        new_checkpoints: Vec<Checkpoint>,
+    },
+    AddBreak {
+        truck: Truck,
+        index: usize,
+        break_: Break,
+    },
+    RemoveBreak {
+        truck: Truck,
+        index: usize,
+        break_: Break,
+    },
+    /// Replace the break at `old_index` of the truck's breaks with `new_break`,
+    /// which ends up at `new_index`
+    MoveBreak {
+        truck: Truck,
+        old_index: usize,
+        old_break: Break,
+        new_index: usize,
+        new_break: Break,
+    },
+    AssignDriver {
+        driver: Driver,
+        truck: Truck,
+    },
+    UnassignDriver {
+        driver: Driver,
+        truck: Truck,
+    },
+}
+
+impl Move {
+    /// Makes the change to `schedule`
+    fn apply(&self, schedule: &mut Schedule) {
+        match self {
+            Move::AddCheckpoint {
+                truck,
+                index,
+                checkpoint,
+                new_driving_time,
+                ..
+            } => {
+                schedule.insert_checkpoint(*truck, *index, checkpoint.clone());
+                schedule
+                    .truck_driving_times
+                    .insert(*truck, *new_driving_time);
+            }
+            Move::RemoveCheckpoint {
+                truck,
+                index,
+                new_driving_time,
+                ..
+            } => {
+                schedule.remove_checkpoint(*truck, *index);
+                schedule
+                    .truck_driving_times
+                    .insert(*truck, *new_driving_time);
+            }
+            Move::AddDelivery {
+                cargo,
+                truck,
+                first_index,
+                new_checkpoints,
+                ..
+            } => {
+                schedule.replace_checkpoints(*truck, *first_index, new_checkpoints);
+                schedule.assign_cargo(*cargo, *truck);
+            }
+            Move::RemoveDelivery {
+                cargo,
+                truck,
+                first_index,
+                new_checkpoints,
+                ..
+            } => {
+                schedule.replace_checkpoints(*truck, *first_index, new_checkpoints);
+                schedule.unassign_cargo(cargo);
+            }
+            Move::AddBreak {
+                truck,
+                index,
+                break_,
+            } => {
+                schedule
+                    .truck_breaks
+                    .get_mut(truck)
+                    .unwrap()
+                    .insert(*index, break_.clone());
+            }
+            Move::RemoveBreak { truck, index, .. } => {
+                schedule.truck_breaks.get_mut(truck).unwrap().remove(*index);
+            }
+            Move::MoveBreak {
+                truck,
+                old_index,
+                new_index,
+                new_break,
+                ..
+            } => {
+                let breaks = schedule.truck_breaks.get_mut(truck).unwrap();
+                breaks.remove(*old_index);
+                breaks.insert(*new_index, new_break.clone());
+            }
+            Move::AssignDriver { driver, truck } => {
+                schedule.driver_trucks.insert(*driver, *truck);
+            }
+            Move::UnassignDriver { driver, .. } => {
+                schedule.driver_trucks.remove(driver);
+            }
+        }
+    }
+
+    /// Reverts the change to `schedule`, which has to be as `apply` left it
+    fn undo(&self, schedule: &mut Schedule) {
+        match self {
+            Move::AddCheckpoint {
+                truck,
+                index,
+                old_driving_time,
+                ..
+            } => {
+                schedule.remove_checkpoint(*truck, *index);
+                schedule
+                    .truck_driving_times
+                    .insert(*truck, *old_driving_time);
+            }
+            Move::RemoveCheckpoint {
+                truck,
+                index,
+                checkpoint,
+                old_driving_time,
+                ..
+            } => {
+                schedule.insert_checkpoint(*truck, *index, checkpoint.clone());
+                schedule
+                    .truck_driving_times
+                    .insert(*truck, *old_driving_time);
+            }
+            Move::AddDelivery {
+                cargo,
+                truck,
+                first_index,
+                old_checkpoints,
+                ..
+            } => {
+                schedule.replace_checkpoints(*truck, *first_index, old_checkpoints);
+                schedule.unassign_cargo(cargo);
+            }
+            Move::RemoveDelivery {
+                cargo,
+                truck,
+                first_index,
+                old_checkpoints,
+                ..
+            } => {
+                schedule.replace_checkpoints(*truck, *first_index, old_checkpoints);
+                schedule.assign_cargo(*cargo, *truck);
+            }
+            Move::AddBreak { truck, index, .. } => {
+                schedule.truck_breaks.get_mut(truck).unwrap().remove(*index);
+            }
+            Move::RemoveBreak {
+                truck,
+                index,
+                break_,
+            } => {
+                schedule
+                    .truck_breaks
+                    .get_mut(truck)
+                    .unwrap()
+                    .insert(*index, break_.clone());
+            }
+            Move::MoveBreak {
+                truck,
+                old_index,
+                old_break,
+                new_index,
+                ..
+            } => {
+                let breaks = schedule.truck_breaks.get_mut(truck).unwrap();
+                breaks.remove(*new_index);
+                breaks.insert(*old_index, old_break.clone());
+            }
+            Move::AssignDriver { driver, .. } => {
+                schedule.driver_trucks.remove(driver);
+            }
+            Move::UnassignDriver { driver, truck } => {
+                schedule.driver_trucks.insert(*driver, *truck);
+            }
+        }
+    }
+}
+
+impl Move {
+    /// What the move does, named like the neighbour action making it
+    fn kind(&self) -> &'static str {
+        match self {
+            Move::AddCheckpoint { .. } => "add_checkpoint",
+            Move::RemoveCheckpoint { .. } => "remove_checkpoint",
+            Move::AddDelivery { .. } => "add_delivery",
+            Move::RemoveDelivery { .. } => "remove_delivery",
+            Move::AddBreak { .. } => "add_break",
+            Move::RemoveBreak { .. } => "remove_break",
+            Move::MoveBreak { .. } => "move_break",
+            Move::AssignDriver { .. } => "assign_driver",
+            Move::UnassignDriver { .. } => "unassign_driver",
+        }
+    }
+
+    /// The truck changed by the move
+    fn truck(&self) -> Truck {
+        match self {
+            Move::AddCheckpoint { truck, .. }
+            | Move::RemoveCheckpoint { truck, .. }
+            | Move::AddDelivery { truck, .. }
+            | Move::RemoveDelivery { truck, .. }
+            | Move::AddBreak { truck, .. }
+            | Move::RemoveBreak { truck, .. }
+            | Move::MoveBreak { truck, .. }
+            | Move::AssignDriver { truck, .. }
+            | Move::UnassignDriver { truck, .. } => *truck,
+        }
+    }
+
+    /// Describes the move for python, see `MoveDescription`.
+    /// `schedule` is the one the move was applied to
+    fn describe(
+        &self,
+        schedule_generator: &ScheduleGenerator,
+        schedule: &Schedule,
+    ) -> MoveDescription {
+        let time = |time| schedule_generator.time_formats.time(time);
+        let mut out = MoveDescription {
+            kind: self.kind().to_string(),
+            truck: schedule_generator.truck_mapper.map(&self.truck()).unwrap(),
+            cargo: None,
+            driver: None,
+            checkpoint_indices: Vec::new(),
+            checkpoint_handles: Vec::new(),
+            time_changes: Vec::new(),
+        };
+        match self {
+            Move::AddCheckpoint {
+                index, checkpoint, ..
+            } => {
+                out.checkpoint_indices.push(*index);
+                out.time_changes.push((None, Some(time(checkpoint.time))));
+            }
+            Move::RemoveCheckpoint {
+                index, checkpoint, ..
+            } => {
+                out.checkpoint_indices.push(*index);
+                out.time_changes.push((Some(time(checkpoint.time)), None));
+            }
+            Move::AddDelivery {
+                cargo,
+                first_index,
+                old_checkpoints,
+                new_checkpoints,
+                ..
+            }
+            | Move::RemoveDelivery {
+                cargo,
+                first_index,
+                old_checkpoints,
+                new_checkpoints,
+                ..
+            } => {
+                out.cargo = Some(schedule_generator.cargo_mapper.map(cargo).unwrap());
+                // The pickup and dropoff checkpoints
+                out.checkpoint_indices =
+                    vec![*first_index, first_index + new_checkpoints.len() - 1];
+                out.time_changes = old_checkpoints
+                    .iter()
+                    .zip(new_checkpoints.iter())
+                    .filter(|(old, new)| old.time != new.time)
+                    .map(|(old, new)| (Some(time(old.time)), Some(time(new.time))))
+                    .collect();
+            }
+            Move::AddBreak { break_, .. } => {
+                out.time_changes.push((None, Some(time(break_.time))));
+            }
+            Move::RemoveBreak { break_, .. } => {
+                out.time_changes.push((Some(time(break_.time)), None));
+            }
+            Move::MoveBreak {
+                old_break,
+                new_break,
+                ..
+            } => {
+                out.time_changes
+                    .push((Some(time(old_break.time)), Some(time(new_break.time))));
+            }
+            Move::AssignDriver { driver, .. } | Move::UnassignDriver { driver, .. } => {
+                out.driver = Some(schedule_generator.driver_mapper.map(driver).unwrap());
+            }
+        }
+        if !matches!(self, Move::RemoveCheckpoint { .. }) {
+            let handles = schedule
+                .truck_checkpoints
+                .get(&self.truck())
+                .unwrap()
+                .handles();
+            out.checkpoint_handles = out
+                .checkpoint_indices
+                .iter()
+                .map(|index| handles[*index].to_bits())
+                .collect();
+        }
+        out
+    }
+}
+
+/// A move made by `ScheduleGenerator.get_schedule_neighbour`
+#[pyclass]
+pub struct MoveDescription {
+    /// What was done: "add_checkpoint", "remove_checkpoint", "add_delivery",
+    /// "remove_delivery", "add_break", "remove_break", "move_break",
+    /// "assign_driver" or "unassign_driver"
+    #[pyo3(get)]
+    kind: String,
+    /// The truck whose checkpoints, breaks or drivers changed
+    #[pyo3(get)]
+    truck: PyTruckID,
+    /// The cargo whose delivery was added or removed
+    #[pyo3(get)]
+    cargo: Option<PyCargoID>,
+    /// The driver who was assigned or unassigned
+    #[pyo3(get)]
+    driver: Option<PyDriverID>,
+    /// Indices of the truck's checkpoints that were added or removed, or of the pickup
+    /// and dropoff checkpoints of the delivery
+    #[pyo3(get)]
+    checkpoint_indices: Vec<usize>,
+    /// Handles of the checkpoints at `checkpoint_indices`, unless they were removed.
+    /// Unlike indices, they stay the same while other checkpoints are added or removed,
+    /// see `Schedule.get_checkpoint_index`. They are unique among the checkpoints of
+    /// the truck, including removed ones
+    #[pyo3(get)]
+    checkpoint_handles: Vec<u64>,
+    /// Changed times of the truck's checkpoints or breaks, as (old time, new time).
+    /// The old time is None for ones that were added, the new time for ones that were removed
+    #[pyo3(get)]
+    time_changes: Vec<(Option<FormattedTime>, Option<FormattedTime>)>,
+}
+
+/// What `ScheduleGenerator.get_schedule_neighbour` returns
+#[derive(IntoPyObject)]
+pub enum PyNeighbour {
+    Schedule(Schedule),
+    /// The schedule and the move made to get it, if it was asked for
+    WithMove((Schedule, MoveDescription)),
+}
+
+/// A way of randomly changing a schedule in place, returning the move made,
+/// or None if it failed, in which case the schedule is left unchanged
+type NeighbourAction = fn(&mut ScheduleGenerator, &mut Schedule) -> DrivingTimeResult<Option<Move>>;
+
+/// Attempts and time spent on one type of move, see `MoveStats`
+#[derive(Default, Clone, Debug)]
+struct MoveTypeStats {
+    attempts: usize,
+    successes: usize,
+    time: Duration,
+    /// Number of failed attempts for each reason, see `ScheduleGenerator.diagnose_moves`
+    rejections: BTreeMap<&'static str, usize>,
+}
+
+impl MoveTypeStats {
+    /// As a dict of "attempts", "successes", "seconds" and "rejections"
+    fn to_py_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let out = PyDict::new(py);
+        out.set_item("attempts", self.attempts)?;
+        out.set_item("successes", self.successes)?;
+        out.set_item("seconds", self.time.as_secs_f64())?;
+        out.set_item("rejections", &self.rejections)?;
+        Ok(out)
+    }
+}
+
+/// Where the time making moves goes, recorded when instrumentation is enabled
+/// with `ScheduleGenerator.set_instrumentation`
+#[derive(Default, Clone, Debug)]
+struct MoveStats {
+    /// Stats of each type of move, by the name of its action
+    moves: BTreeMap<&'static str, MoveTypeStats>,
+    /// Time spent in each phase of making moves, see `ScheduleGenerator.get_move_stats`
+    phases: BTreeMap<&'static str, Duration>,
+    /// Time spent in `try_move` during the current attempt
+    attempt_check_time: Duration,
+}
+
+/// What to do with bookings that can't be done within the planning period
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum InfeasibleBookingPolicy {
+    /// Raise an `InfeasibleBookingError`
+    Raise,
+    /// Leave the booking out, logging a warning
+    Skip,
+    /// Keep the booking with relaxed time windows, at a penalty in the scores.
+    /// Bookings whose empty return or stops can't be done are still left out
+    Relax,
+}
+
+impl InfeasibleBookingPolicy {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "raise" => Some(Self::Raise),
+            "skip" => Some(Self::Skip),
+            "relax" => Some(Self::Relax),
+            _ => None,
+        }
+    }
+}
+
+/// The data a generator was created from, kept to be able to save it with `to_json`
+#[derive(Clone, Serialize, Deserialize)]
+struct GeneratorInputs {
+    terminal_data: BTreeMap<PyTerminalID, (PyTime, PyTime)>,
+    truck_data: BTreeMap<PyTruckID, PyTruckData>,
+    booking_data: Vec<PyBooking>,
+    planning_period: (PyTime, PyTime),
+    #[serde(default)]
+    relax_time_windows: bool,
+    #[serde(default)]
+    calendars: BTreeMap<PyRegionID, PyCalendar>,
+    #[serde(default)]
+    terminal_regions: BTreeMap<PyTerminalID, PyRegionID>,
+    #[serde(default)]
+    terminal_locations: BTreeMap<PyTerminalID, (f64, f64)>,
+    #[serde(default)]
+    terminal_timezones: BTreeMap<PyTerminalID, String>,
+    #[serde(default)]
+    terminal_opening_hours: BTreeMap<PyTerminalID, (NaiveTime, NaiveTime)>,
+    #[serde(default)]
+    driving_time_providers: Option<Vec<String>>,
+    #[serde(default)]
+    infeasible_bookings: Option<String>,
+}
+
+impl HeapSize for GeneratorInputs {
+    fn heap_size(&self) -> usize {
+        self.terminal_data.heap_size()
+            + self.truck_data.heap_size()
+            + self.booking_data.heap_size()
+            + self.calendars.heap_size()
+            + self.terminal_regions.heap_size()
+            + self.terminal_locations.heap_size()
+            + self.terminal_timezones.heap_size()
+            + self.terminal_opening_hours.heap_size()
+            + self.driving_time_providers.heap_size()
+            + self.infeasible_bookings.heap_size()
+    }
+}
+
+/// Driving times as a list of (from, to, driving time)
+type PyDrivingTimes = Vec<(PyTerminalID, PyTerminalID, NonNegativeTimeDelta)>;
+
+/// Time-of-day dependent driving times, see `ScheduleGenerator.set_driving_time_profile`
+type PyDrivingTimeProfile = Vec<(NonNegativeTimeDelta, NonNegativeTimeDelta)>;
+
+/// Points along a road as (latitude, longitude), see `ScheduleGenerator.set_route_geometry`
+type PyRouteGeometry = Vec<(f64, f64)>;
+
+/// A generator as saved by `ScheduleGenerator.to_json`
+#[derive(Serialize, Deserialize)]
+struct GeneratorJson {
+    #[serde(flatten)]
+    inputs: GeneratorInputs,
+    /// Driving times used for all vehicles
+    #[serde(default)]
+    driving_times: PyDrivingTimes,
+    #[serde(default)]
+    vehicle_class_driving_times: BTreeMap<PyVehicleClassID, PyDrivingTimes>,
+    /// (from, to, profile) for driving times depending on the time of day
+    #[serde(default)]
+    driving_time_profiles: Vec<(PyTerminalID, PyTerminalID, PyDrivingTimeProfile)>,
+    /// (from, to, factor, valid interval), in the order they were applied
+    #[serde(default)]
+    traffic_factors: Vec<(PyTerminalID, PyTerminalID, f64, (Time, Time))>,
+    /// Standard deviations of driving times
+    #[serde(default)]
+    driving_time_spreads: PyDrivingTimes,
+    /// (from, to, geometry) of the roads between terminals
+    #[serde(default)]
+    route_geometries: Vec<(PyTerminalID, PyTerminalID, PyRouteGeometry)>,
+}
+
+/// State of an optimization shared with the thread running it
+#[derive(Default)]
+struct OptimizationState {
+    progress: Mutex<Option<AnnealingProgress>>,
+    cancelled: AtomicBool,
+    finished: AtomicBool,
+}
+
+/// The best schedule found by an optimization and its scores
+type OptimizationResult = PyResult<(Schedule, Vec<f64>)>;
+
+/// An optimization running on a separate thread, see `ScheduleGenerator.optimize_async`
+#[pyclass]
+pub struct OptimizationHandle {
+    state: Arc<OptimizationState>,
+    /// The thread running the optimization, until it has been joined
+    thread: Mutex<Option<JoinHandle<OptimizationResult>>>,
+    /// What the optimization returned, once the thread has been joined
+    result: Mutex<Option<OptimizationResult>>,
+}
+
+#[pymethods]
+impl OptimizationHandle {
+    /// The latest (iteration, num_iterations, temperature, best scores so far)
+    /// reported, or None if the optimization hasn't reported any progress yet
+    pub fn progress(&self) -> Option<(usize, usize, f64, Vec<f64>)> {
+        self.state
+            .progress
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|progress| {
+                (
+                    progress.iteration,
+                    progress.num_iterations,
+                    progress.temperature,
+                    progress.best_scores.clone(),
+                )
+            })
+    }
+
+    /// Whether the optimization has finished, so that `result` won't block
+    pub fn done(&self) -> bool {
+        self.state.finished.load(Ordering::Acquire)
+    }
+
+    /// Asks the optimization to stop at the next progress report. It still
+    /// returns the best schedule found so far
+    pub fn cancel(&self) {
+        self.state.cancelled.store(true, Ordering::Release);
+    }
+
+    /// Waits for the optimization to finish, without holding the GIL, and returns
+    /// the best schedule found and its scores
+    pub fn result(&self, py: Python<'_>) -> OptimizationResult {
+        py.allow_threads(|| {
+            if let Some(thread) = self.thread.lock().unwrap().take() {
+                let result = thread.join().unwrap_or_else(|_| {
+                    Err(PyRuntimeError::new_err("The optimization thread panicked"))
+                });
+                *self.result.lock().unwrap() = Some(result);
+            }
+        });
+        match self.result.lock().unwrap().as_ref().unwrap() {
+            Ok(result) => Ok(result.clone()),
+            Err(err) => Err(err.clone_ref(py)),
+        }
+    }
+
+    /// Allows `await handle` in asyncio, waiting for `result` in the default executor
+    pub fn __await__<'py>(slf: &Bound<'py, Self>) -> PyResult<Bound<'py, PyAny>> {
+        let event_loop = slf
+            .py()
+            .import("asyncio")?
+            .call_method0("get_running_loop")?;
+        event_loop
+            .call_method1("run_in_executor", (slf.py().None(), slf.getattr("result")?))?
+            .call_method0("__await__")
+    }
+}
+
+/// The data of a `ScheduleGenerator` that doesn't change while making schedules,
+/// shared by generators made with `fork` so that they can run on other threads.
+/// Changing it, e.g. adding a booking, first copies it if it is shared
+#[derive(Clone)]
+pub struct GeneratorData {
+    /// What the generator was created from, updated when bookings and trucks change
+    inputs: GeneratorInputs,
+
+    /// A map from (from_terminal, to_terminal) to cached driving times
+    driving_times_cache: DrivingTimesCache,
+
+    // A map from (start_terminal, end_terminal) to collection of cargo
+    // that can be delivered from start_terminal to end_terminal
+    cargo_by_terminals: BTreeMap<(Terminal, Terminal), BTreeSet<Cargo>>,
+
+    /// Times during which pickup can occur. Takes into account e.g. terminals
+    /// closing overnight
+    pickup_times: IntervalsByCargoMap,
+
+    /// Times during which dropoff can occur. Takes into account e.g. terminals
+    /// closing overnight
+    dropoff_times: IntervalsByCargoMap,
+
+    /// A map from cargo to information about delivering it
+    cargo_booking_info: IdMap<Cargo, BookingInformation>,
+
+    /// A map from cargo to cargo that can only be picked up after it is dropped off;
+    /// the reverse of `BookingInformation::predecessor`
+    cargo_successors: IdMap<Cargo, BTreeSet<Cargo>>,
+
+    /// For the main cargo of each booking with additional stops, the cargo picked up
+    /// or dropped off (true if picked up) at each stop, in the order of stops.
+    /// All of these need to be delivered by the same truck, in this order
+    cargo_stops: BTreeMap<Cargo, Vec<Vec<(Cargo, bool)>>>,
+
+    /// A map from all cargo in `cargo_stops` to the main cargo of its booking;
+    /// the reverse of `cargo_stops`
+    stop_main_cargo: IdMap<Cargo, Cargo>,
+
+    /// A map from the cargo id of each booking to all the cargo generated
+    /// from it, i.e. its containers and their empty returns
+    booking_cargo: BTreeMap<PyCargoID, Vec<Cargo>>,
+
+    terminals: BTreeSet<Terminal>,
+
+    /// Times during which each terminal is open
+    terminal_open_intervals: BTreeMap<Terminal, IntervalChain>,
+
+    /// Geographic locations of the terminals for which they are known
+    terminal_locations: BTreeMap<Terminal, Location>,
+
+    /// Points along the roads driven from one terminal to another, for drawing routes
+    route_geometries: BTreeMap<(Terminal, Terminal), Vec<Location>>,
+
+    trucks: BTreeSet<Truck>,
+
+    /// Terminals when and where the trucks start at
+    truck_data: IdMap<Truck, TruckData>,
+
+    /// Trucks that have some cargo locked to them, and so are
+    /// always considered to be in use
+    trucks_with_locked_cargo: BTreeSet<Truck>,
+
+    /// Time in which we are allowed to schedule trucks
+    planning_period: Interval,
+
+    /// What to do with bookings that can't be done within the planning period
+    infeasible_booking_policy: InfeasibleBookingPolicy,
+
+    /// Bookings which were left out, with the reason why, see `rejected_bookings`
+    rejected_bookings: BTreeMap<PyCargoID, String>,
+
+    /// The maximum number of checkpoints a truck can have within a single day, if any
+    max_checkpoints_per_day: Option<usize>,
+
+    /// Limits on continuous driving, if any. Breaks are only scheduled
+    /// when these are set
+    driving_hour_rules: Option<DrivingHourRules>,
+
+    /// Days on which each region is closed
+    calendars: BTreeMap<PyRegionID, Calendar>,
+
+    /// Slack left on top of driving time before each checkpoint, e.g. for gate queues
+    buffer_time: NonNegativeTimeDelta,
+
+    /// Terminals that need a different `buffer_time`
+    terminal_buffer_times: BTreeMap<Terminal, NonNegativeTimeDelta>,
+
+    /// Drivers that can be assigned to trucks, who need the qualifications of the cargo
+    /// they handle on top of their truck. If there are none, each truck is assumed
+    /// to always have a driver with the truck's qualifications
+    drivers: BTreeMap<Driver, DriverData>,
+
+    /// Number of chassis available at each terminal at the start of the planning period.
+    /// Each delivery takes a chassis from the pickup terminal and leaves it at the
+    /// dropoff terminal. Terminals not in this map have an unlimited number of chassis
+    chassis_pools: BTreeMap<Terminal, usize>,
+
+    /// How times and durations are given back to python, see `set_time_formats`
+    time_formats: TimeFormats,
+
+    terminal_mapper: CounterMapper<String>,
+    cargo_mapper: CounterMapper<String>,
+    truck_mapper: CounterMapper<String>,
+    qualification_mapper: CounterMapper<String>,
+    vehicle_class_mapper: CounterMapper<String>,
+    capacity_dimension_mapper: CounterMapper<String>,
+    driver_mapper: CounterMapper<String>,
+}
+
+/// Class with logic and data needed to create schedules
+#[pyclass]
+pub struct ScheduleGenerator {
+    data: Arc<GeneratorData>,
+
+    rng: Xoshiro256PlusPlus,
+
+    /// Time spent making moves, if instrumentation is enabled
+    move_stats: Option<MoveStats>,
+
+    /// Why the current attempt at making a move failed, if known
+    rejection_reason: Option<&'static str>,
+
+    /// Whether to check the invariants of schedules after changing them,
+    /// which takes time linear in the number of checkpoints of a truck
+    debug_checks: bool,
+
+    /// Ids of trucks, terminals and cargo shared with schedules,
+    /// rebuilt when new ones are added
+    id_names: RwLock<Arc<IdNames>>,
+}
+
+// NOTE: only the data can be read through this; changing it needs `data_mut`
+impl std::ops::Deref for ScheduleGenerator {
+    type Target = GeneratorData;
+
+    fn deref(&self) -> &GeneratorData {
+        &self.data
+    }
+}
+
+impl ScheduleGenerator {
+    /// The data of the generator to change, copied first if it is shared with a fork
+    fn data_mut(&mut self) -> &mut GeneratorData {
+        Arc::make_mut(&mut self.data)
+    }
+
+    /// Ids of all trucks, terminals and cargo so far
+    fn get_id_names(&self) -> Arc<IdNames> {
+        {
+            let id_names = self.id_names.read().unwrap();
+            // Ids are never removed from the mappers, so the names are up to date
+            // if there are as many of them
+            if id_names.trucks.len() == self.truck_mapper.len()
+                && id_names.terminals.len() == self.terminal_mapper.len()
+                && id_names.cargo.len() == self.cargo_mapper.len()
+                && id_names.capacity_dimensions.len() == self.capacity_dimension_mapper.len()
+                && id_names.time_formats == self.time_formats
+            {
+                return id_names.clone();
+            }
+        }
+        let names = |mapper: &CounterMapper<String>| {
+            // The type of the internal ids doesn't matter, as they are in order
+            mapper.iter::<Truck>().map(|(_, id)| id.clone()).collect()
+        };
+        let id_names = Arc::new(IdNames {
+            trucks: names(&self.truck_mapper),
+            terminals: names(&self.terminal_mapper),
+            cargo: names(&self.cargo_mapper),
+            capacity_dimensions: names(&self.capacity_dimension_mapper),
+            truck_capacities: self
+                .truck_mapper
+                .iter()
+                .map(|(truck, _)| {
+                    self.truck_data
+                        .get(&truck)
+                        .map(|truck_data| truck_data.capacity.clone())
+                })
+                .collect(),
+            time_formats: self.time_formats,
+        });
+        *self.id_names.write().unwrap() = id_names.clone();
+        id_names
+    }
+
+    /// Updates the ids `schedule` is displayed with if trucks, terminals or cargo
+    /// were added since it was made, so that it can show the ones it gains
+    fn refresh_id_names(&self, schedule: &mut Schedule) {
+        let id_names = &schedule.id_names.0;
+        if id_names.trucks.len() < self.truck_mapper.len()
+            || id_names.terminals.len() < self.terminal_mapper.len()
+            || id_names.cargo.len() < self.cargo_mapper.len()
+            || id_names.time_formats != self.time_formats
+        {
+            schedule.id_names = SharedIdNames(self.get_id_names());
+        }
+    }
+
+    /// Makes sure that checkpoints for a certain truck have a correct format after
+    /// the checkpoints at `changed` were changed, added or removed (then `changed` is
+    /// empty). Only looks at these checkpoints and their neighbours, unless
+    /// `debug_checks` is set, in which case the whole route and its indices are checked
+    fn assert_truck_checkpoints_invariant(
+        &self,
+        schedule: &Schedule,
+        truck: Truck,
+        changed: Range<usize>,
+    ) {
+        let checkpoints = schedule.truck_checkpoints.get(&truck).unwrap();
+        let checked = if self.debug_checks {
+            checkpoints
+        } else {
+            let end = (changed.end + 1).min(checkpoints.len());
+            checkpoints.slice(changed.start.saturating_sub(1).min(end)..end)
+        };
+        // Make sure that we don't have 2 checkpoints in the same terminal
+        // together
+        assert!(checked.pairs().all(|(c1, c2)| c1.terminal != c2.terminal));
+
+        // Also check the starting terminal
+        if self.debug_checks || changed.start == 0 {
+            if let Some(first_checkpoint) = checkpoints.first() {
+                assert!(
+                    first_checkpoint.terminal
+                        != self.truck_data.get(&truck).unwrap().starting_terminal
+                );
+            }
+        }
+
+        // Make sure that the times are still in strictly ascending order of time
+        // https://stackoverflow.com/questions/51272571/how-do-i-check-if-a-slice-is-sorted
+        assert!(checked
+            .pairs()
+            .all(|(c1, c2)| c1.time + c1.duration < c2.time));
+
+        if !self.debug_checks {
+            return;
+        }
+
+        // The positions of checkpoints at each terminal have to be up to date
+        assert!(
+            schedule.truck_terminal_positions.get(&truck) == Some(&terminal_positions(checkpoints))
+        );
+        assert!(schedule.truck_empty_positions.get(&truck) == Some(&empty_positions(checkpoints)));
+        assert!(
+            schedule.num_checkpoints
+                == schedule
+                    .truck_checkpoints
+                    .values()
+                    .map(|route| route.len())
+                    .sum::<usize>()
+        );
+        assert!(
+            schedule.num_empty_checkpoints
+                == schedule
+                    .truck_empty_positions
+                    .values()
+                    .map(Vec::len)
+                    .sum::<usize>()
+        );
+    }
+
+    /// Describes a driving time that couldn't be found as a python exception
+    fn driving_time_error(&self, missing: MissingDrivingTime) -> PyErr {
+        MissingDrivingTimeError::new_err(format!(
+            "Driving time from terminal {:?} to terminal {:?} is not known, and {}",
+            self.terminal_mapper.map(&missing.from).unwrap(),
+            self.terminal_mapper.map(&missing.to).unwrap(),
+            missing.reason
+        ))
+    }
+
+    /// Raise an error unless driving times are asked from `source`, since
+    /// setting them would have no effect
+    fn check_driving_time_source(&self, source: DrivingTimeSource) -> PyResult<()> {
+        if self.driving_times_cache.has_source(source) {
+            Ok(())
+        } else {
+            Err(PyTypeError::new_err(format!(
+                "The {:?} driving time provider was not selected in driving_time_providers",
+                source.name()
+            )))
+        }
+    }
+
+    /// Replace the driving time provider of kind `source`, which needs to have
+    /// been selected when creating the generator
+    fn replace_driving_time_provider(
+        &mut self,
+        source: DrivingTimeSource,
+        provider: Box<dyn DrivingTimeProvider>,
+    ) -> PyResult<()> {
+        self.check_driving_time_source(source)?;
+        self.data_mut()
+            .driving_times_cache
+            .set_provider(source, provider);
+        Ok(())
+    }
+
+    /// A random neighbour of `schedule`, like `get_schedule_neighbour` but without python
+    pub fn make_schedule_neighbour(
+        &mut self,
+        schedule: &Schedule,
+        num_tries_per_action: usize,
+    ) -> DrivingTimeResult<Schedule> {
+        let mut out = schedule.clone();
+        self.find_schedule_neighbour(&mut out, num_tries_per_action)?;
+        Ok(out)
+    }
+
+    /// Changes `schedule` in place to a random neighbour, returning the move made,
+    /// see `get_schedule_neighbour`
+    fn find_schedule_neighbour(
+        &mut self,
+        schedule: &mut Schedule,
+        num_tries_per_action: usize,
+    ) -> DrivingTimeResult<Move> {
+        loop {
+            // Randomly decide what we want to do
+            // Prioritise adding and updating checkpoints because we want to explore more of those
+            // options, and also because adding a checkpoint might fail, but removing is a lot less likely to fail
+            let action_types = self.get_action_types();
+            let (name, action) = action_types[self.rng.random_range(0..action_types.len())];
+
+            // Try executing this action type a few times
+            for attempt in 1..=num_tries_per_action {
+                let started = self.start_timer();
+                let mv = action(self, schedule)?;
+                self.record_attempt(name, mv.is_some(), started);
+                if let Some(mv) = mv {
+                    trace!("Move {name} succeeded after {attempt} attempts");
+                    self.refresh_id_names(schedule);
+                    return Ok(mv);
+                }
+            }
+            trace!("Move {name} was rejected {num_tries_per_action} times");
+        }
+    }
+
+    /// The types of moves `find_schedule_neighbour` picks from, by name
+    fn get_action_types(&self) -> Vec<(&'static str, NeighbourAction)> {
+        let mut action_types: Vec<(&'static str, NeighbourAction)> = vec![
+            ("remove_random_checkpoint", Self::remove_random_checkpoint),
+            ("add_random_checkpoint", Self::add_random_checkpoint),
+            ("remove_random_delivery", Self::remove_random_delivery),
+            ("add_random_delivery", Self::add_random_delivery),
+        ];
+        // Breaks are only needed when there are driving-hour rules
+        if self.driving_hour_rules.is_some() {
+            action_types.extend([
+                (
+                    "add_random_break",
+                    Self::add_random_break as NeighbourAction,
+                ),
+                ("remove_random_break", Self::remove_random_break),
+                ("move_random_break", Self::move_random_break),
+            ]);
+        }
+        // Drivers only need to be assigned if there is a roster
+        if !self.drivers.is_empty() {
+            action_types.extend([
+                (
+                    "assign_random_driver",
+                    Self::assign_random_driver as NeighbourAction,
+                ),
+                ("unassign_random_driver", Self::unassign_random_driver),
+            ]);
+        }
+        action_types
+    }
+
+    /// Applies `mv` to `schedule` and undoes it again if `find_violation` finds
+    /// a constraint the changed schedule breaks, which is recorded as the reason
+    /// the move failed. Returns the move if it was kept
+    fn try_move(
+        &mut self,
+        schedule: &mut Schedule,
+        mv: Move,
+        find_violation: impl FnOnce(&Self, &Schedule) -> DrivingTimeResult<Option<&'static str>>,
+    ) -> DrivingTimeResult<Option<Move>> {
+        let started = self.start_timer();
+        mv.apply(schedule);
+        let violation = find_violation(self, schedule);
+        if !matches!(violation, Ok(None)) {
+            mv.undo(schedule);
+            if let Ok(Some(constraint)) = violation {
+                self.rejection_reason = Some(constraint);
+            }
+        }
+        if let (Some(move_stats), Some(started)) = (&mut self.move_stats, started) {
+            move_stats.attempt_check_time += started.elapsed();
+        }
+        Ok(violation?.is_none().then_some(mv))
+    }
+
+    /// Records why the current attempt at making a move failed, returning no move
+    fn reject(&mut self, reason: &'static str) -> Option<Move> {
+        self.rejection_reason = Some(reason);
+        None
+    }
+
+    /// Why the last attempt at making a move failed, clearing it for the next one
+    fn take_rejection_reason(&mut self) -> &'static str {
+        self.rejection_reason.take().unwrap_or("unknown")
+    }
+
+    /// The current time if instrumentation is enabled, to pass to
+    /// `record_phase` or `record_attempt` afterwards
+    fn start_timer(&self) -> Option<Instant> {
+        self.move_stats.as_ref().map(|_| Instant::now())
+    }
+
+    /// Adds the time since `started` to the time spent in `phase`
+    fn record_phase(&mut self, phase: &'static str, started: Option<Instant>) {
+        if let (Some(move_stats), Some(started)) = (&mut self.move_stats, started) {
+            *move_stats.phases.entry(phase).or_default() += started.elapsed();
+        }
+    }
+
+    /// Records an attempt at making a move of type `move_type` that started at
+    /// `started`. Time spent in `try_move` counts as checking feasibility, the rest
+    /// as enumerating candidates
+    fn record_attempt(
+        &mut self,
+        move_type: &'static str,
+        succeeded: bool,
+        started: Option<Instant>,
+    ) {
+        let rejection_reason = self.take_rejection_reason();
+        let (Some(move_stats), Some(started)) = (&mut self.move_stats, started) else {
+            return;
+        };
+        let time = started.elapsed();
+        let check_time = std::mem::take(&mut move_stats.attempt_check_time);
+        *move_stats.phases.entry("feasibility_check").or_default() += check_time;
+        *move_stats
+            .phases
+            .entry("candidate_enumeration")
+            .or_default() += time.saturating_sub(check_time);
+
+        let stats = move_stats.moves.entry(move_type).or_default();
+        stats.attempts += 1;
+        stats.successes += usize::from(succeeded);
+        stats.time += time;
+        if !succeeded {
+            *stats.rejections.entry(rejection_reason).or_default() += 1;
+        }
+    }
+
+    /// Tries each type of move `num_attempts` times on `schedule`,
+    /// undoing the moves that succeed, see `diagnose_moves`. Leaves the random
+    /// number generator as it was, so that the moves made later stay the same
+    fn dry_run_moves(
+        &mut self,
+        schedule: &Schedule,
+        num_attempts: usize,
+    ) -> DrivingTimeResult<BTreeMap<&'static str, MoveTypeStats>> {
+        let rng = self.rng.clone();
+        let mut schedule = schedule.clone();
+        let out = self
+            .get_action_types()
+            .into_iter()
+            .map(|(name, action)| {
+                let mut stats = MoveTypeStats::default();
+                for _ in 0..num_attempts {
+                    let started = Instant::now();
+                    let mv = action(self, &mut schedule)?;
+                    stats.attempts += 1;
+                    stats.time += started.elapsed();
+                    let rejection_reason = self.take_rejection_reason();
+                    if let Some(mv) = mv {
+                        stats.successes += 1;
+                        mv.undo(&mut schedule);
+                    } else {
+                        *stats.rejections.entry(rejection_reason).or_default() += 1;
+                    }
+                }
+                Ok((name, stats))
+            })
+            .collect();
+        self.rng = rng;
+        out
+    }
+
+    /// Scores of `schedule`, see `scores`
+    pub fn compute_scores(&self, schedule: &Schedule) -> DrivingTimeResult<Vec<f64>> {
+        // Minimise the number of trucks required.
+        // Trucks with locked cargo count as used, since they can't be freed
+        let num_free_trucks: usize = schedule
+            .truck_checkpoints
+            .iter()
+            .filter(|(truck, checkpoints)| {
+                checkpoints.is_empty() && !self.trucks_with_locked_cargo.contains(truck)
+            })
+            .count();
+
+        // Sum of minimal driving times needed to deliver each piece of cargo that
+        // has been delivered;
+        // this is a very simplistic lower bound
+        let min_driving_time: NonNegativeTimeDelta = schedule
+            .scheduled_cargo_truck
+            .iter()
+            .map(|(cargo, truck)| {
+                let booking_info = self.cargo_booking_info.get(cargo).unwrap();
+                Ok(self.scale_driving_time(
+                    *truck,
+                    self.driving_times_cache.get_driving_time(
+                        booking_info.from,
+                        booking_info.to,
+                        self.truck_data.get(truck).unwrap().vehicle_class,
+                    )?,
+                ))
+            })
+            .sum::<DrivingTimeResult<NonNegativeTimeDelta>>()?;
+
+        // Total driving time
+        let total_driving_time: NonNegativeTimeDelta =
+            schedule.truck_driving_times.values().copied().sum();
+
+        // Deliveries outside the requested time windows are heavily penalised,
+        // but are still better than not delivering the cargo at all.
+        // Higher priority cargo counts for more
+        let delivered_weight: f64 = schedule
+            .scheduled_cargo_truck
+            .keys()
+            .map(|cargo| {
+                let booking_info = self.cargo_booking_info.get(cargo).unwrap();
+                if booking_info.relaxed_time_windows {
+                    booking_info.delivery_weight() * (1.0 - RELAXED_TIME_WINDOWS_PENALTY)
+                } else {
+                    booking_info.delivery_weight()
+                }
+            })
+            .sum();
+        let total_weight: f64 = self
+            .cargo_booking_info
+            .values()
+            .map(|booking_info| booking_info.delivery_weight())
+            .sum();
+
+        // Proportion of deliveries made
+        let deliveries_proportion = delivered_weight / total_weight;
+
+        // Proportion of trucks that are free
+        let free_trucks_proportion = (num_free_trucks as f64) / (self.trucks.len() as f64);
+
+        // The smaller the total driving time, the larger this is
+        // This can become more than 1 if 2 pieces of cargo are moved at once
+        // Prevent division by 0
+        let driving_time_score = (min_driving_time as f64) / (max(total_driving_time, 1) as f64);
+
+        // Each missing mandatory delivery makes the schedule infeasible
+        let constraint_penalty = -(self.num_missing_mandatory_deliveries(schedule) as f64);
+
+        Ok(vec![
+            deliveries_proportion,
+            free_trucks_proportion,
+            driving_time_score,
+            constraint_penalty,
+        ])
+    }
+
+    /// Improves `schedule` with simulated annealing, see `optimize`. Calls `on_progress`
+    /// every `options.progress_interval` iterations, stopping early if it returns false.
+    /// Returns the best schedule found and its scores
+    pub fn anneal(
+        &mut self,
+        schedule: &Schedule,
+        options: &AnnealingOptions,
+        on_progress: &mut dyn FnMut(AnnealingProgress) -> PyResult<bool>,
+    ) -> PyResult<(Schedule, Vec<f64>)> {
+        let mut current_schedule = schedule.clone();
+        let mut current_scores = self
+            .compute_scores(&current_schedule)
+            .map_err(|missing| self.driving_time_error(missing))?;
+        let mut best_schedule = current_schedule.clone();
+        let mut best_scores = current_scores.clone();
+        let deltas = |new: &[f64], old: &[f64]| -> Vec<f64> {
+            new.iter()
+                .zip(old.iter())
+                .map(|(new, old)| new - old)
+                .collect()
+        };
+
+        let mut temperature = options.initial_temperature;
+        for iteration in 0..options.num_iterations {
+            if temperature <= options.final_temperature {
+                break;
+            }
+            if iteration % options.progress_interval == 0
+                && !on_progress(AnnealingProgress {
+                    iteration,
+                    num_iterations: options.num_iterations,
+                    temperature,
+                    best_scores: best_scores.clone(),
+                })?
+            {
+                info!("Optimization stopped early at iteration {iteration}");
+                break;
+            }
+
+            // Allow randomly restarting from the best schedule found
+            if self.rng.random::<f64>() <= options.restart_probability {
+                debug!("Iteration {iteration}: restarting from the best schedule");
+                let started = self.start_timer();
+                current_schedule = best_schedule.clone();
+                self.record_phase("cloning", started);
+                current_scores = best_scores.clone();
+            }
+
+            // Try out a neighbour in place, undoing the move if it is rejected
+            let mv = self
+                .find_schedule_neighbour(&mut current_schedule, options.num_tries_per_action)
+                .map_err(|missing| self.driving_time_error(missing))?;
+            let started = self.start_timer();
+            let new_scores = self
+                .compute_scores(&current_schedule)
+                .map_err(|missing| self.driving_time_error(missing))?;
+            self.record_phase("scoring", started);
+
+            let new_deltas = deltas(&new_scores, &current_scores);
+            if is_better(&new_deltas)
+                || self.rng.random::<f64>() < acceptance_probability(&new_deltas, temperature)
+            {
+                debug!("Iteration {iteration}: accepted schedule with scores {new_scores:?}");
+                current_scores = new_scores;
+                if is_better(&deltas(&current_scores, &best_scores)) {
+                    let started = self.start_timer();
+                    best_schedule = current_schedule.clone();
+                    self.record_phase("cloning", started);
+                    best_scores = current_scores.clone();
+                }
+            } else {
+                mv.undo(&mut current_schedule);
+            }
+
+            temperature = temperature_at(
+                options,
+                (iteration + 1) as f64 / options.num_iterations as f64,
+            );
+        }
+        info!("Finished optimizing with best scores {best_scores:?}");
+        Ok((best_schedule, best_scores))
+    }
+
+    /// Points along the road from `from` to `to`, as set with `set_route_geometry`, or the
+    /// straight line between them if not set. Empty if a terminal's location isn't known
+    fn get_leg_geometry(&self, from: Terminal, to: Terminal) -> Vec<Location> {
+        if let Some(points) = self.route_geometries.get(&(from, to)) {
+            return points.clone();
+        }
+        match (
+            self.terminal_locations.get(&from),
+            self.terminal_locations.get(&to),
+        ) {
+            (Some(from_location), Some(to_location)) => vec![*from_location, *to_location],
+            _ => vec![],
+        }
+    }
+
+    /// Pairs of distinct terminals between which trucks might need to drive,
+    /// see `required_terminal_pairs`
+    fn get_required_terminal_pairs(&self) -> BTreeSet<(Terminal, Terminal)> {
+        let cargo_terminals: BTreeSet<Terminal> = self
+            .cargo_by_terminals
+            .keys()
+            .flat_map(|(from, to)| [*from, *to])
+            .collect();
+        let starting_terminals = self
+            .truck_data
+            .values()
+            .map(|truck_data| truck_data.starting_terminal);
+
+        let mut out = BTreeSet::new();
+        for from in cargo_terminals.iter().copied().chain(starting_terminals) {
+            for to in cargo_terminals.iter() {
+                if from != *to {
+                    out.insert((from, *to));
+                }
+            }
+        }
+        out
+    }
+
+    /// The earliest time at which `truck` can start driving
+    fn get_truck_start_time(&self, truck: Truck) -> Time {
+        max(
+            self.truck_data.get(&truck).unwrap().start_time,
+            self.planning_period.get_start_time(),
+        )
+    }
+
+    /// Slack to leave between arriving at `terminal` and the checkpoint there
+    fn get_buffer_time(&self, terminal: Terminal) -> NonNegativeTimeDelta {
+        *self
+            .terminal_buffer_times
+            .get(&terminal)
+            .unwrap_or(&self.buffer_time)
+    }
+
+    /// Whether `truck` is allowed to deliver cargo described by `booking_info`.
+    /// With a driver roster, the driver on duty also needs the qualifications,
+    /// see `respects_driver_roster`
+    fn can_carry(&self, truck: Truck, booking_info: &BookingInformation) -> bool {
+        let truck_data = self.truck_data.get(&truck).unwrap();
+        booking_info
+            .locked_truck
+            .is_none_or(|locked_truck| locked_truck == truck)
+            && booking_info
+                .required_qualifications
+                .is_subset(&truck_data.qualifications)
+    }
+
+    /// For each checkpoint of `truck`, finds a driver of the truck who can drive to it
+    /// from the previous checkpoint and stay for its duration within their shift,
+    /// if there is one
+    fn get_checkpoint_drivers(
+        &self,
+        schedule: &Schedule,
+        truck: Truck,
+    ) -> DrivingTimeResult<Vec<Option<Driver>>> {
+        let truck_data = self.truck_data.get(&truck).unwrap();
+        let drivers = schedule
+            .driver_trucks
+            .iter()
+            .filter(|(_driver, driver_truck)| **driver_truck == truck)
+            .map(|(driver, _driver_truck)| *driver)
+            .collect::<Vec<_>>();
+
+        let mut out = vec![];
+        let mut terminal = truck_data.starting_terminal;
+        let mut departure_time = self.get_truck_start_time(truck);
+        for checkpoint in schedule.truck_checkpoints.get(&truck).unwrap().iter() {
+            let mut checkpoint_driver = None;
+            for driver in drivers.iter() {
+                // Only the shift during which the checkpoint starts can cover it
+                let shifts = &self.drivers.get(driver).unwrap().shifts;
+                let Some(shift) = shifts.find_interval_at(checkpoint.time) else {
+                    continue;
+                };
+                // The truck can wait for the driver's shift to start
+                let start_time = max(departure_time, shift.get_start_time());
+                if start_time
+                    + self.get_truck_driving_time_at(
+                        truck,
+                        terminal,
+                        checkpoint.terminal,
+                        start_time,
+                    )?
+                    + self.get_buffer_time(checkpoint.terminal)
+                    <= checkpoint.time
+                    && checkpoint.time + checkpoint.duration <= shift.get_end_time()
+                {
+                    checkpoint_driver = Some(*driver);
+                    break;
+                }
+            }
+            out.push(checkpoint_driver);
+            terminal = checkpoint.terminal;
+            departure_time = checkpoint.time + checkpoint.duration;
+        }
+        Ok(out)
+    }
+
+    /// Finds the (cargo, qualification) pairs for cargo picked up or dropped off
+    /// at `checkpoint` that require a qualification `driver` doesn't have
+    fn find_missing_driver_qualifications(
+        &self,
+        driver: Driver,
+        checkpoint: &Checkpoint,
+    ) -> Vec<(Cargo, Qualification)> {
+        let driver_data = self.drivers.get(&driver).unwrap();
+        checkpoint
+            .pickup_cargo
+            .iter()
+            .chain(checkpoint.dropoff_cargo.iter())
+            .flat_map(|cargo| {
+                self.cargo_booking_info
+                    .get(cargo)
+                    .unwrap()
+                    .required_qualifications
+                    .difference(&driver_data.qualifications)
+                    .map(|qualification| (*cargo, *qualification))
+            })
+            .collect()
+    }
+
+    /// Whether every checkpoint of `truck` is handled by a driver of the truck
+    /// who is on duty and has the qualifications its cargo requires.
+    /// Always true if there is no driver roster
+    fn respects_driver_roster(&self, schedule: &Schedule, truck: Truck) -> DrivingTimeResult<bool> {
+        if self.drivers.is_empty() {
+            return Ok(true);
+        }
+        Ok(self
+            .get_checkpoint_drivers(schedule, truck)?
+            .into_iter()
+            .zip(schedule.truck_checkpoints.get(&truck).unwrap().iter())
+            .all(|(driver, checkpoint)| {
+                driver.is_some_and(|driver| {
+                    self.find_missing_driver_qualifications(driver, checkpoint)
+                        .is_empty()
+                })
+            }))
+    }
+
+    /// Whether `driver` is allowed to drive `truck`, given their home terminal
+    fn can_drive(&self, driver: Driver, truck: Truck) -> bool {
+        self.drivers
+            .get(&driver)
+            .unwrap()
+            .home_terminal
+            .is_none_or(|home_terminal| {
+                home_terminal == self.truck_data.get(&truck).unwrap().starting_terminal
+            })
+    }
+
+    /// Whether the shifts of the drivers overlap
+    fn shifts_overlap(&self, driver1: Driver, driver2: Driver) -> bool {
+        let shifts1 = &self.drivers.get(&driver1).unwrap().shifts;
+        let shifts2 = &self.drivers.get(&driver2).unwrap().shifts;
+        !shifts1.intersect(shifts2).is_empty()
+    }
+
+    /// Finds the days (counted from the unix epoch) in which `checkpoints`
+    /// have more checkpoints than allowed by `max_checkpoints_per_day`
+    fn days_over_checkpoint_limit(&self, checkpoints: Route<'_, Checkpoint>) -> Vec<u64> {
+        let Some(max_checkpoints_per_day) = self.max_checkpoints_per_day else {
+            return vec![];
+        };
+
+        let mut num_checkpoints_by_day = BTreeMap::new();
+        for checkpoint in checkpoints.iter() {
+            *num_checkpoints_by_day
+                .entry(checkpoint.time / SECONDS_PER_DAY)
+                .or_insert(0) += 1;
+        }
+        num_checkpoints_by_day
+            .into_iter()
+            .filter(|(_day, num_checkpoints)| *num_checkpoints > max_checkpoints_per_day)
+            .map(|(day, _num_checkpoints)| day)
+            .collect()
+    }
+
+    /// Whether a day of the checkpoints at `changed` has more checkpoints than allowed
+    /// by `max_checkpoints_per_day`. Unlike `days_over_checkpoint_limit`, only looks
+    /// at the checkpoints of these days
+    fn exceeds_checkpoint_limit(
+        &self,
+        checkpoints: Route<'_, Checkpoint>,
+        changed: Range<usize>,
+    ) -> bool {
+        let Some(max_checkpoints_per_day) = self.max_checkpoints_per_day else {
+            return false;
+        };
+
+        let day = |index: usize| checkpoints[index].time / SECONDS_PER_DAY;
+        changed.into_iter().any(|index| {
+            // Checkpoints are in order of time, so those of a day are next to each other
+            let num_before = (0..index)
+                .rev()
+                .take_while(|other| day(*other) == day(index))
+                .count();
+            let num_after = (index + 1..checkpoints.len())
+                .take_while(|other| day(*other) == day(index))
+                .count();
+            num_before + 1 + num_after > max_checkpoints_per_day
+        })
+    }
+
+    /// For each terminal with a chassis pool, calculates how many chassis
+    /// are available there over time, as a list of (time, number of chassis
+    /// available from that time onwards). The number is negative if the
+    /// schedule uses more chassis than are available
+    fn get_chassis_timelines(&self, schedule: &Schedule) -> BTreeMap<Terminal, Vec<(Time, i64)>> {
+        self.chassis_pools
+            .keys()
+            .map(|terminal| (*terminal, self.get_chassis_timeline(schedule, *terminal)))
+            .collect()
+    }
+
+    /// The chassis timeline of `terminal`, which needs to have a chassis pool,
+    /// see `get_chassis_timelines`
+    fn get_chassis_timeline(&self, schedule: &Schedule, terminal: Terminal) -> Vec<(Time, i64)> {
+        // Changes in number of available chassis, from the checkpoints at the terminal
+        let mut events = vec![];
+        for (truck, terminal_positions) in schedule.truck_terminal_positions.iter() {
+            let Some(positions) = terminal_positions.get(&terminal) else {
+                continue;
+            };
+            let checkpoints = schedule.truck_checkpoints.get(truck).unwrap();
+            for position in positions {
+                let checkpoint = &checkpoints[*position];
+                let change =
+                    checkpoint.dropoff_cargo.len() as i64 - checkpoint.pickup_cargo.len() as i64;
+                if change != 0 {
+                    events.push((checkpoint.time, change));
+                }
+            }
+        }
+        // If chassis are returned and taken at the same time, assume they
+        // are returned first
+        events.sort_by_key(|(time, change)| (*time, -change));
+
+        let mut available = *self.chassis_pools.get(&terminal).unwrap() as i64;
+        let mut timeline = vec![(self.planning_period.get_start_time(), available)];
+        for (time, change) in events {
+            available += change;
+            timeline.push((time, available));
+        }
+        timeline
+    }
+
+    /// Whether no terminal runs out of chassis under `schedule`
+    fn has_enough_chassis(&self, schedule: &Schedule) -> bool {
+        self.has_enough_chassis_at(schedule, self.chassis_pools.keys().copied())
+    }
+
+    /// Whether none of `terminals` runs out of chassis under `schedule`.
+    /// Unlike `has_enough_chassis`, only looks at the checkpoints at these terminals
+    fn has_enough_chassis_at(
+        &self,
+        schedule: &Schedule,
+        terminals: impl IntoIterator<Item = Terminal>,
+    ) -> bool {
+        terminals
+            .into_iter()
+            .filter(|terminal| self.chassis_pools.contains_key(terminal))
+            .all(|terminal| {
+                self.get_chassis_timeline(schedule, terminal)
+                    .iter()
+                    .all(|(_time, available)| *available >= 0)
+            })
+    }
+
+    /// The cargo with id `cargo_id`, or the first container of the booking with
+    /// that id, so that bookings of several containers can be looked up by their id
+    fn find_cargo(&self, cargo_id: &PyCargoID) -> PyResult<Cargo> {
+        self.cargo_mapper
+            .reverse_map(cargo_id)
+            .filter(|cargo| self.cargo_booking_info.contains_key(cargo))
+            .or_else(|| {
+                self.booking_cargo
+                    .get(cargo_id)
+                    .and_then(|all_cargo| all_cargo.first().copied())
+            })
+            .ok_or_else(|| UnknownIdError::new_err(format!("Unknown cargo {cargo_id:?}")))
+    }
+
+    /// Finds the times at which `cargo` is picked up and dropped off, if it is scheduled
+    fn get_cargo_pickup_dropoff_times(
+        &self,
+        schedule: &Schedule,
+        cargo: Cargo,
+    ) -> Option<(Time, Time)> {
+        let truck = schedule.scheduled_cargo_truck.get(&cargo)?;
+        let checkpoints = schedule.truck_checkpoints.get(truck).unwrap();
+        let pickup = checkpoints
+            .iter()
+            .find(|checkpoint| checkpoint.pickup_cargo.contains(&cargo))?;
+        let dropoff = checkpoints
+            .iter()
+            .find(|checkpoint| checkpoint.dropoff_cargo.contains(&cargo))?;
+        Some((pickup.time, dropoff.time))
+    }
+
+    /// Finds scheduled cargo that is picked up before its predecessor is dropped off
+    fn find_precedence_violations(&self, schedule: &Schedule) -> Vec<(Cargo, Cargo)> {
+        let mut out = vec![];
+        for (predecessor, successors) in self.cargo_successors.iter() {
+            for successor in successors.iter() {
+                if !self.is_precedence_respected(schedule, *predecessor, *successor) {
+                    out.push((*predecessor, *successor));
+                }
+            }
+        }
+        out
+    }
+
+    /// Whether `successor` is either unscheduled, or picked up after `predecessor`
+    /// is dropped off
+    fn is_precedence_respected(
+        &self,
+        schedule: &Schedule,
+        predecessor: Cargo,
+        successor: Cargo,
+    ) -> bool {
+        let Some((successor_pickup_time, _)) =
+            self.get_cargo_pickup_dropoff_times(schedule, successor)
+        else {
+            return true;
+        };
+        self.get_cargo_pickup_dropoff_times(schedule, predecessor)
+            .is_some_and(|(_, predecessor_dropoff_time)| {
+                predecessor_dropoff_time < successor_pickup_time
+            })
+    }
+
+    /// Whether `cargo` is delivered in order with its predecessor and successors.
+    /// Unlike `find_precedence_violations`, only looks at the pairs involving `cargo`
+    fn respects_precedence_of(&self, schedule: &Schedule, cargo: Cargo) -> bool {
+        let predecessor = self.cargo_booking_info.get(&cargo).unwrap().predecessor;
+        predecessor
+            .is_none_or(|predecessor| self.is_precedence_respected(schedule, predecessor, cargo))
+            && self.cargo_successors.get(&cargo).is_none_or(|successors| {
+                successors
+                    .iter()
+                    .all(|successor| self.is_precedence_respected(schedule, cargo, *successor))
+            })
+    }
+
+    /// Whether the scheduled cargo in `stops` is all delivered by the same truck,
+    /// visiting the stops in order
+    fn are_stops_in_order(&self, schedule: &Schedule, stops: &[Vec<(Cargo, bool)>]) -> bool {
+        let mut truck = None;
+        let mut prev_stop_time = Time::MIN;
+        for stop in stops.iter() {
+            let mut stop_times = vec![];
+            for (cargo, is_pickup) in stop.iter() {
+                let Some(cargo_truck) = schedule.scheduled_cargo_truck.get(cargo) else {
+                    continue;
+                };
+                if *truck.get_or_insert(*cargo_truck) != *cargo_truck {
+                    return false;
+                }
+                let (pickup_time, dropoff_time) = self
+                    .get_cargo_pickup_dropoff_times(schedule, *cargo)
+                    .unwrap();
+                stop_times.push(if *is_pickup {
+                    pickup_time
+                } else {
+                    dropoff_time
+                });
+            }
+            if stop_times.iter().any(|time| *time < prev_stop_time) {
+                return false;
+            }
+            prev_stop_time = stop_times.into_iter().max().unwrap_or(prev_stop_time);
+        }
+        true
+    }
+
+    /// Finds the main cargo of bookings whose additional stops
+    /// are not visited in order by a single truck
+    fn find_stop_order_violations(&self, schedule: &Schedule) -> Vec<Cargo> {
+        self.cargo_stops
+            .iter()
+            .filter(|(_cargo, stops)| !self.are_stops_in_order(schedule, stops))
+            .map(|(cargo, _stops)| *cargo)
+            .collect()
+    }
+
+    /// Whether the stops of the booking of `cargo`, if it has any, are visited in order.
+    /// Unlike `find_stop_order_violations`, only looks at this booking
+    fn respects_stop_order_of(&self, schedule: &Schedule, cargo: Cargo) -> bool {
+        self.stop_main_cargo.get(&cargo).is_none_or(|main_cargo| {
+            self.are_stops_in_order(schedule, self.cargo_stops.get(main_cargo).unwrap())
+        })
+    }
+
+    /// Weight of `truck` with its tractor, trailer and cargo
+    /// after the pickups and dropoffs at `checkpoint`
+    fn get_gross_weight_kg(&self, truck: Truck, checkpoint: &Checkpoint) -> usize {
+        let truck_data = self.truck_data.get(&truck).unwrap();
+        let cargo_weight_kg = truck_data.capacity.get(CapacityDimension::WEIGHT_KG)
+            - checkpoint
+                .available_capacity
+                .get(CapacityDimension::WEIGHT_KG);
+        truck_data.tare_weight_kg + cargo_weight_kg
+    }
+
+    /// Whether `truck` is within its legal gross weight after the pickups
+    /// and dropoffs at `checkpoint`
+    fn is_legal_weight(&self, truck: Truck, checkpoint: &Checkpoint) -> bool {
+        self.truck_data
+            .get(&truck)
+            .unwrap()
+            .max_gross_weight_kg
+            .is_none_or(|max_gross_weight_kg| {
+                self.get_gross_weight_kg(truck, checkpoint) <= max_gross_weight_kg
+            })
+    }
+
+    /// Splits driving for `driving_time` in one go into stretches no longer than
+    /// allowed by `driving_hour_rules`, with a break in between each of them.
+    /// Returns the number of breaks needed and the length of the first stretch;
+    /// the other stretches are as long as allowed
+    fn split_long_leg(
+        &self,
+        driving_time: NonNegativeTimeDelta,
+    ) -> (NonNegativeTimeDelta, NonNegativeTimeDelta) {
+        match &self.driving_hour_rules {
+            Some(rules) if driving_time > rules.max_continuous_driving_time => {
+                let num_breaks = (driving_time - 1) / rules.max_continuous_driving_time;
+                (
+                    num_breaks,
+                    driving_time - num_breaks * rules.max_continuous_driving_time,
+                )
+            }
+            _ => (0, driving_time),
+        }
+    }
+
+    /// Time spent on the breaks needed to drive for `driving_time` in one go,
+    /// see `split_long_leg`
+    fn get_long_leg_break_time(&self, driving_time: NonNegativeTimeDelta) -> NonNegativeTimeDelta {
+        let (num_breaks, _) = self.split_long_leg(driving_time);
+        self.driving_hour_rules
+            .as_ref()
+            .map_or(0, |rules| num_breaks * rules.min_break_duration)
+    }
+
+    /// Walks the route of `truck`, assuming that it drives as early as possible and
+    /// only stops driving for breaks. Legs too long to drive without a break
+    /// are split by breaks, see `split_long_leg`. Returns None if breaks overlap with each other or
+    /// with checkpoints, or don't leave enough time to drive between checkpoints.
+    /// Otherwise, returns (end time, length) of each stretch of continuous driving
+    /// between breaks that are long enough to satisfy `driving_hour_rules`
+    fn get_continuous_driving_times(
+        &self,
+        truck: Truck,
+        checkpoints: Route<'_, Checkpoint>,
+        breaks: &[Break],
+    ) -> DrivingTimeResult<Option<Vec<(Time, NonNegativeTimeDelta)>>> {
+        let truck_data = self.truck_data.get(&truck).unwrap();
+        let (min_break_duration, max_continuous_driving_time) =
+            self.driving_hour_rules.as_ref().map_or((0, 0), |rules| {
+                (rules.min_break_duration, rules.max_continuous_driving_time)
+            });
+
+        let mut out = vec![];
+        let mut continuous_driving_time = 0;
+        let mut breaks = breaks.iter().peekable();
+        let mut terminal = truck_data.starting_terminal;
+        // NOTE: the time until which the truck can't drive yet
+        // because its shift hasn't started, or it is at a checkpoint or on a break
+        let mut time = self.get_truck_start_time(truck);
+
+        for checkpoint in checkpoints.iter() {
+            let mut remaining_driving_time =
+                self.get_truck_driving_time_at(truck, terminal, checkpoint.terminal, time)?;
+            while let Some(break_) = breaks.next_if(|break_| break_.time < checkpoint.time) {
+                if break_.time < time || break_.time + break_.duration > checkpoint.time {
+                    return Ok(None);
+                }
+                let driving_time = remaining_driving_time.min(break_.time - time);
+                continuous_driving_time += driving_time;
+                remaining_driving_time -= driving_time;
+                if break_.duration >= min_break_duration {
+                    out.push((break_.time, continuous_driving_time));
+                    continuous_driving_time = 0;
+                }
+                time = break_.time + break_.duration;
+            }
+
+            let (num_leg_breaks, mut stretch) = self.split_long_leg(remaining_driving_time);
+            for _ in 0..num_leg_breaks {
+                continuous_driving_time += stretch;
+                remaining_driving_time -= stretch;
+                time += stretch;
+                out.push((time, continuous_driving_time));
+                continuous_driving_time = 0;
+                time += min_break_duration;
+                stretch = remaining_driving_time.min(max_continuous_driving_time);
+            }
+
+            if time + remaining_driving_time + self.get_buffer_time(checkpoint.terminal)
+                > checkpoint.time
+            {
+                return Ok(None);
+            }
+            continuous_driving_time += remaining_driving_time;
+            terminal = checkpoint.terminal;
+            time = checkpoint.time + checkpoint.duration;
+        }
+
+        // The truck doesn't drive after its last checkpoint,
+        // but the breaks still can't overlap
+        for break_ in breaks {
+            if break_.time < time {
+                return Ok(None);
+            }
+            time = break_.time + break_.duration;
+        }
+        out.push((time, continuous_driving_time));
+        Ok(Some(out))
+    }
+
+    /// Walks the route of `truck` like `get_continuous_driving_times`, returning
+    /// (kind, start time, end time, terminal) of what the truck is doing, see `PyGanttBar`
+    fn get_gantt_bars(
+        &self,
+        schedule: &Schedule,
+        truck: Truck,
+    ) -> DrivingTimeResult<Vec<GanttBar>> {
+        let mut out = vec![];
+        let mut breaks = schedule.truck_breaks.get(&truck).unwrap().iter().peekable();
+        let mut terminal = self.truck_data.get(&truck).unwrap().starting_terminal;
+        let mut time = self.get_truck_start_time(truck);
+
+        for checkpoint in schedule.truck_checkpoints.get(&truck).unwrap().iter() {
+            let mut remaining_driving_time =
+                self.get_truck_driving_time_at(truck, terminal, checkpoint.terminal, time)?;
+            while let Some(break_) = breaks.next_if(|break_| break_.time < checkpoint.time) {
+                let driving_time = remaining_driving_time.min(break_.time.saturating_sub(time));
+                if driving_time > 0 {
+                    out.push((
+                        "driving",
+                        time,
+                        time + driving_time,
+                        Some(checkpoint.terminal),
+                    ));
+                }
+                if break_.time > time + driving_time {
+                    out.push(("idle", time + driving_time, break_.time, None));
+                }
+                out.push(("break", break_.time, break_.time + break_.duration, None));
+                remaining_driving_time -= driving_time;
+                time = max(time + driving_time, break_.time + break_.duration);
+            }
+
+            let (num_leg_breaks, mut stretch) = self.split_long_leg(remaining_driving_time);
+            for _ in 0..num_leg_breaks {
+                let rules = self.driving_hour_rules.as_ref().unwrap();
+                out.push(("driving", time, time + stretch, Some(checkpoint.terminal)));
+                remaining_driving_time -= stretch;
+                time += stretch;
+                out.push(("break", time, time + rules.min_break_duration, None));
+                time += rules.min_break_duration;
+                stretch = remaining_driving_time.min(rules.max_continuous_driving_time);
+            }
+
+            if remaining_driving_time > 0 {
+                out.push((
+                    "driving",
+                    time,
+                    time + remaining_driving_time,
+                    Some(checkpoint.terminal),
+                ));
+            }
+            let arrival_time = time + remaining_driving_time;
+            if checkpoint.time > arrival_time {
+                out.push(("idle", arrival_time, checkpoint.time, None));
+            }
+            out.push((
+                "stop",
+                checkpoint.time,
+                checkpoint.time + checkpoint.duration,
+                Some(checkpoint.terminal),
+            ));
+            terminal = checkpoint.terminal;
+            time = checkpoint.time + checkpoint.duration;
+        }
+
+        // The truck doesn't drive after its last checkpoint
+        for break_ in breaks {
+            out.push(("break", break_.time, break_.time + break_.duration, None));
+        }
+        Ok(out)
+    }
+
+    /// Whether the checkpoints and breaks of `truck` fit together
+    /// and satisfy `driving_hour_rules`
+    fn respects_driving_hours(&self, schedule: &Schedule, truck: Truck) -> DrivingTimeResult<bool> {
+        let breaks = schedule.truck_breaks.get(&truck).unwrap();
+        if self.driving_hour_rules.is_none() && breaks.is_empty() {
+            return Ok(true);
+        }
+        let checkpoints = schedule.truck_checkpoints.get(&truck).unwrap();
+        let Some(continuous_driving_times) =
+            self.get_continuous_driving_times(truck, checkpoints, breaks)?
+        else {
+            return Ok(false);
+        };
+        Ok(self.driving_hour_rules.as_ref().is_none_or(|rules| {
+            continuous_driving_times
+                .iter()
+                .all(|(_time, driving_time)| *driving_time <= rules.max_continuous_driving_time)
+        }))
+    }
+
+    /// Finds a terminal referenced by `booking`, together with the times it is open
+    fn get_booking_terminal(
+        &self,
+        booking: &PyBooking,
+        terminal_id: &PyTerminalID,
+    ) -> PyResult<(Terminal, IntervalChain)> {
+        self.terminal_mapper
+            .reverse_map(terminal_id)
+            .and_then(|terminal: Terminal| {
+                Some((
+                    terminal,
+                    self.terminal_open_intervals.get(&terminal)?.clone(),
+                ))
+            })
+            .ok_or_else(|| {
+                UnknownTerminalError::new_err(format!(
+                    "Cargo {:?} has unknown terminal {terminal_id:?}",
+                    booking.cargo
+                ))
+            })
+    }
+
+    /// Adds the truck `truck_id` to the fleet
+    fn register_truck(&mut self, truck_id: &PyTruckID, data: &PyTruckData) -> PyResult<()> {
+        data.validate(&format!("Truck {truck_id:?}"))?;
+
+        let starting_terminal: Terminal = self
+            .terminal_mapper
+            .reverse_map(&data.starting_terminal)
+            .ok_or_else(|| {
+                UnknownTerminalError::new_err(format!(
+                    "Truck {truck_id:?} has unknown starting terminal {:?}",
+                    data.starting_terminal
+                ))
+            })?;
+        let truck: Truck = self.data_mut().truck_mapper.add_or_find(truck_id);
+
+        let start_time = data.shift_start_time.map(Time::from).unwrap_or_else(|| {
+            self.terminal_open_intervals
+                .get(&starting_terminal)
+                .and_then(|intervals| intervals.get_intervals().first())
+                .map_or(self.planning_period.get_start_time(), |interval| {
+                    interval.get_start_time()
+                })
+        });
+
+        let truck_data = TruckData {
+            starting_terminal,
+            start_time,
+            // The truck is not limited in dimensions it doesn't list
+            capacity: make_capacity(
+                &self.capacity_dimension_mapper,
+                data.max_weight_kg,
+                data.max_teu,
+                &data.capacities,
+                usize::MAX,
+            )?,
+            tare_weight_kg: data.tractor_tare_weight_kg + data.trailer_tare_weight_kg,
+            max_gross_weight_kg: data.max_gross_weight_kg,
+            rear_loading: data.rear_loading,
+            qualifications: data
+                .qualifications
+                .iter()
+                .map(|qualification| {
+                    self.data_mut()
+                        .qualification_mapper
+                        .add_or_find(qualification)
+                })
+                .collect(),
+            vehicle_class: data.vehicle_class.as_ref().map(|vehicle_class| {
+                self.data_mut()
+                    .vehicle_class_mapper
+                    .add_or_find(vehicle_class)
+            }),
+            driving_time_per_mille: (1000.0 / data.speed_factor).round() as NonNegativeTimeDelta,
+        };
+
+        let generator_data = self.data_mut();
+        generator_data.trucks.insert(truck);
+        generator_data.terminals.insert(starting_terminal);
+        generator_data.truck_data.insert(truck, truck_data);
+        generator_data
+            .inputs
+            .truck_data
+            .insert(truck_id.clone(), data.clone());
+        Ok(())
+    }
+
+    /// Adds the cargo of `booking` to the cargo that can be scheduled.
+    /// Bookings that can't be done within the planning period are ignored
+    fn register_booking(&mut self, booking: &PyBooking) -> PyResult<()> {
+        // Bookings read from tables or JSON don't go through `PyBooking::new`
+        booking.validate()?;
+        // Check all the terminals up front, since the booking might be dropped
+        // before some of them are looked up
+        for terminal_id in booking.terminal_ids() {
+            self.get_booking_terminal(booking, terminal_id)?;
+        }
+        // Make sure the cargo ids generated for the booking don't clash with those
+        // of another booking, e.g. "A#1" with the second container of "A"
+        if let Some(cargo_id) = booking.generated_cargo_ids().into_iter().find(|cargo_id| {
+            self.cargo_mapper
+                .reverse_map(cargo_id)
+                .is_some_and(|cargo: Cargo| self.cargo_booking_info.contains_key(&cargo))
+        }) {
+            return Err(PyTypeError::new_err(format!(
+                "Booking for cargo {:?} uses cargo id {cargo_id:?}, which is already used by another booking",
+                booking.cargo
+            )));
+        }
+
+        // Remove irrelevant bookings
+        // Note that this also includes the bookings that are too far in the future -
+        // we are not anticipating anything after the planning period ends.
+        // We want to run this algorithm with a relatively large look-ahead,
+        // so that all relevant bookings are within the planning_period. In
+        // this case, if our plan near the end of the period is suboptimal
+        // because we didn't anticipate bookings after the end of
+        // planning_period, that is not an issue: any plans for that time
+        // become stale as the situation changes
+
+        // TODO: we still might want to consider this in order to e.g.
+        // handle scheduling not-urgent containers more frequently
+
+        // To do that, first shrink the intervals, and then remove the empty ones
+        let (from_terminal, from_open_intervals) =
+            self.get_booking_terminal(booking, &booking.from_terminal)?;
+        let (to_terminal, to_open_intervals) =
+            self.get_booking_terminal(booking, &booking.to_terminal)?;
+
+        // Windows that aren't given don't restrict anything
+        let booking_windows = |windows: &Option<PyIntervalChain>| {
+            windows.as_ref().map_or_else(
+                || IntervalChain::from_interval(self.planning_period.clone()),
+                |windows| windows.chain.clone(),
+            )
+        };
+
+        // Fill in the parts of the time windows that weren't given
+        let pickup_open_time = booking
+            .pickup_open_time
+            .map(Time::from)
+            .unwrap_or(self.planning_period.get_start_time());
+        let pickup_close_time = booking
+            .pickup_close_time
+            .map(Time::from)
+            .unwrap_or(self.planning_period.get_end_time());
+        let dropoff_open_time = booking
+            .dropoff_open_time
+            .map(Time::from)
+            .unwrap_or(pickup_open_time);
+        let dropoff_close_time = booking
+            .dropoff_close_time
+            .map(Time::from)
+            .unwrap_or(self.planning_period.get_end_time());
+
+        let mut pickup_intervals = [
+            from_open_intervals.clone(),
+            IntervalChain::from_interval(interval_or_error(pickup_open_time, pickup_close_time)?),
+            booking_windows(&booking.pickup_windows),
+        ]
+        .iter()
+        .intersect_all()
+        .clamp(&self.planning_period);
+
+        let mut dropoff_intervals = [
+            to_open_intervals.clone(),
+            IntervalChain::from_interval(interval_or_error(dropoff_open_time, dropoff_close_time)?),
+            booking_windows(&booking.dropoff_windows),
+        ]
+        .iter()
+        .intersect_all()
+        .clamp(&self.planning_period);
+
+        // Bookings requested outside the planning period are irrelevant rather than
+        // infeasible, see above, so they are left out even if asked to raise
+        let is_outside_planning_period = [
+            (pickup_open_time, pickup_close_time, &booking.pickup_windows),
+            (
+                dropoff_open_time,
+                dropoff_close_time,
+                &booking.dropoff_windows,
+            ),
+        ]
+        .iter()
+        .any(|(open_time, close_time, windows)| {
+            Interval::new(
+                (*open_time).max(self.planning_period.get_start_time()),
+                (*close_time).min(self.planning_period.get_end_time()),
+                (),
+            )
+            .is_none()
+                || booking_windows(windows)
+                    .clamp(&self.planning_period)
+                    .is_empty()
+        });
+
+        // Keep the deliveries we can't do as requested if asked to, ignoring
+        // the requested window, and then the terminal hours if that isn't enough
+        let relaxed_time_windows = self.infeasible_booking_policy == InfeasibleBookingPolicy::Relax
+            && (pickup_intervals.is_empty() || dropoff_intervals.is_empty());
+        if relaxed_time_windows {
+            let relax = |intervals: IntervalChain, open_intervals: &IntervalChain| {
+                if !intervals.is_empty() {
+                    return intervals;
+                }
+                let within_terminal_hours = open_intervals.clamp(&self.planning_period);
+                if within_terminal_hours.is_empty() {
+                    IntervalChain::from_interval(self.planning_period.clone())
+                } else {
+                    within_terminal_hours
+                }
+            };
+            pickup_intervals = relax(pickup_intervals, &from_open_intervals);
+            dropoff_intervals = relax(dropoff_intervals, &to_open_intervals);
+        }
+
+        // Remove the deliveries we can't do
+        let reject_booking = if is_outside_planning_period {
+            Self::skip_booking
+        } else {
+            Self::reject_booking
+        };
+        if pickup_intervals.is_empty() {
+            return reject_booking(
+                self,
+                booking,
+                "it can't be picked up within its pickup window, the opening hours of its \
+                 terminal and the planning period"
+                    .to_string(),
+            );
+        }
+        if dropoff_intervals.is_empty() {
+            return reject_booking(
+                self,
+                booking,
+                "it can't be dropped off within its dropoff window, the opening hours of its \
+                 terminal and the planning period"
+                    .to_string(),
+            );
+        }
+
+        let locked_truck = if let Some(truck_id) = &booking.locked_truck {
+            let truck: Truck = self.truck_mapper.reverse_map(truck_id).ok_or_else(|| {
+                UnknownIdError::new_err(format!(
+                    "Cargo {:?} is locked to unknown truck {truck_id:?}",
+                    booking.cargo
+                ))
+            })?;
+            Some(truck)
+        } else {
+            None
+        };
+
+        // Find when the empty container can be returned, if it needs to be
+        let empty_return = if let Some(depot_id) = &booking.empty_return_terminal {
+            let (depot, depot_open_intervals) = self.get_booking_terminal(booking, depot_id)?;
+            let return_open_time = booking
+                .empty_return_open_time
+                .map(Time::from)
+                .unwrap_or(dropoff_open_time);
+            let return_close_time = booking
+                .empty_return_close_time
+                .map(Time::from)
+                .unwrap_or(self.planning_period.get_end_time());
+
+            // The empty container can be picked up as soon as the full one can
+            // be dropped off
+            let empty_pickup_intervals = [
+                to_open_intervals.clone(),
+                IntervalChain::from_interval(interval_or_error(
+                    dropoff_open_time,
+                    return_close_time,
+                )?),
+            ]
+            .iter()
+            .intersect_all()
+            .clamp(&self.planning_period);
+            let empty_dropoff_intervals = [
+                depot_open_intervals,
+                IntervalChain::from_interval(interval_or_error(
+                    return_open_time,
+                    return_close_time,
+                )?),
+            ]
+            .iter()
+            .intersect_all()
+            .clamp(&self.planning_period);
+
+            // Don't schedule a container whose empty we can't return
+            if empty_pickup_intervals.is_empty() || empty_dropoff_intervals.is_empty() {
+                return self.reject_booking(
+                    booking,
+                    format!(
+                        "its empty container can't be returned to terminal {depot_id:?} in time"
+                    ),
+                );
+            }
+            Some((depot, empty_pickup_intervals, empty_dropoff_intervals))
+        } else {
+            None
+        };
+
+        // Find when each additional stop can be visited
+        let mut stops = vec![];
+        for (terminal_id, open_time, close_time, is_pickup) in booking.stops.iter() {
+            let (terminal, open_intervals) = self.get_booking_terminal(booking, terminal_id)?;
+            let intervals = [
+                open_intervals,
+                IntervalChain::from_interval(interval_or_error(open_time.0, close_time.0)?),
+            ]
+            .iter()
+            .intersect_all()
+            .clamp(&self.planning_period);
+
+            // The booking can't be done if one of its stops can't be visited
+            if intervals.is_empty() {
+                return self.reject_booking(
+                    booking,
+                    format!("its stop at terminal {terminal_id:?} can't be visited in time"),
+                );
+            }
+            stops.push((terminal, intervals, *is_pickup));
+        }
+
+        // Update delivery info
+        let booking_info = BookingInformation {
+            from: from_terminal,
+            to: to_terminal,
+            demand: make_capacity(
+                &self.capacity_dimension_mapper,
+                booking.cargo_weight_kg,
+                booking.cargo_teu,
+                &booking.demands,
+                0,
+            )?,
+            mandatory: booking.mandatory,
+            locked_truck,
+            required_qualifications: booking
+                .required_qualifications
+                .iter()
+                .map(|qualification| {
+                    self.data_mut()
+                        .qualification_mapper
+                        .add_or_find(qualification)
+                })
+                .collect(),
+            predecessor: None,
+            relaxed_time_windows,
+            priority: booking.priority,
+            requested_by: booking.requested_by.clone(),
+        };
+
+        let data = self.data_mut();
+        // Only add terminals which are referenced in a relevant booking
+        data.terminals.insert(from_terminal);
+        data.terminals.insert(to_terminal);
+        if let Some(locked_truck) = locked_truck {
+            data.trucks_with_locked_cargo.insert(locked_truck);
+        }
+
+        let mut all_cargo = vec![];
+
+        // Each container is scheduled separately, so that containers
+        // of the same booking can be split between trucks
+        for container_index in 0..booking.num_containers {
+            let cargo_id = booking.container_cargo_id(container_index);
+            let cargo: Cargo = data.cargo_mapper.add_or_find(&cargo_id);
+            data.pickup_times.insert(cargo, pickup_intervals.clone());
+            data.dropoff_times.insert(cargo, dropoff_intervals.clone());
+
+            data.cargo_by_terminals
+                .entry((booking_info.from, booking_info.to))
+                .or_default()
+                .insert(cargo);
+            data.cargo_booking_info.insert(cargo, booking_info.clone());
+            all_cargo.push(cargo);
+
+            // Generate the delivery returning the empty container
+            if let Some((depot, empty_pickup_intervals, empty_dropoff_intervals)) = &empty_return {
+                data.terminals.insert(*depot);
+                let empty_cargo: Cargo =
+                    data.cargo_mapper.add_or_find(&format!("{cargo_id}/empty"));
+                data.pickup_times
+                    .insert(empty_cargo, empty_pickup_intervals.clone());
+                data.dropoff_times
+                    .insert(empty_cargo, empty_dropoff_intervals.clone());
+
+                let mut empty_booking_info = BookingInformation {
+                    from: to_terminal,
+                    to: *depot,
+                    predecessor: Some(cargo),
+                    relaxed_time_windows: false,
+                    ..booking_info.clone()
+                };
+                // NOTE: we don't know the tare weight of the container
+                empty_booking_info
+                    .demand
+                    .set(CapacityDimension::WEIGHT_KG, 0);
+                data.cargo_by_terminals
+                    .entry((empty_booking_info.from, empty_booking_info.to))
+                    .or_default()
+                    .insert(empty_cargo);
+                data.cargo_booking_info
+                    .insert(empty_cargo, empty_booking_info);
+                data.cargo_successors
+                    .get_or_insert_with(cargo, BTreeSet::new)
+                    .insert(empty_cargo);
+                all_cargo.push(empty_cargo);
+            }
+
+            // Generate the deliveries to and from the additional stops
+            if !stops.is_empty() {
+                let mut cargo_stops = vec![vec![(cargo, true)]];
+                let mut last_stop = vec![(cargo, false)];
+                for (stop_index, (terminal, intervals, is_pickup)) in stops.iter().enumerate() {
+                    let stop_cargo: Cargo = data
+                        .cargo_mapper
+                        .add_or_find(&format!("{cargo_id}@{stop_index}"));
+                    let stop_booking_info = if *is_pickup {
+                        data.pickup_times.insert(stop_cargo, intervals.clone());
+                        data.dropoff_times
+                            .insert(stop_cargo, dropoff_intervals.clone());
+                        last_stop.push((stop_cargo, false));
+                        BookingInformation {
+                            from: *terminal,
+                            ..booking_info.clone()
+                        }
+                    } else {
+                        data.pickup_times
+                            .insert(stop_cargo, pickup_intervals.clone());
+                        data.dropoff_times.insert(stop_cargo, intervals.clone());
+                        cargo_stops[0].push((stop_cargo, true));
+                        BookingInformation {
+                            to: *terminal,
+                            ..booking_info.clone()
+                        }
+                    };
+                    cargo_stops.push(vec![(stop_cargo, *is_pickup)]);
+
+                    data.terminals.insert(*terminal);
+                    data.cargo_by_terminals
+                        .entry((stop_booking_info.from, stop_booking_info.to))
+                        .or_default()
+                        .insert(stop_cargo);
+                    data.cargo_booking_info
+                        .insert(stop_cargo, stop_booking_info);
+                    all_cargo.push(stop_cargo);
+                }
+                cargo_stops.push(last_stop);
+                for (stop_cargo, _is_pickup) in cargo_stops.iter().flatten() {
+                    data.stop_main_cargo.insert(*stop_cargo, cargo);
+                }
+                data.cargo_stops.insert(cargo, cargo_stops);
+            }
+        }
+
+        data.booking_cargo.insert(booking.cargo.clone(), all_cargo);
+        data.rejected_bookings.remove(&booking.cargo);
+        // Only kept for `to_json` once it is valid, so that the JSON can be loaded again
+        data.inputs.booking_data.push(booking.clone());
+        Ok(())
+    }
+
+    /// Handles a booking that can't be done for `reason`, according to
+    /// `infeasible_booking_policy`
+    fn reject_booking(&mut self, booking: &PyBooking, reason: String) -> PyResult<()> {
+        if self.infeasible_booking_policy == InfeasibleBookingPolicy::Raise {
+            return Err(InfeasibleBookingError::new_err(format!(
+                "Booking for cargo {:?} can't be done, since {reason}",
+                booking.cargo
+            )));
+        }
+        self.skip_booking(booking, reason)
+    }
+
+    /// Leaves out a booking that can't be done for `reason`, listing it
+    /// in `rejected_bookings`
+    fn skip_booking(&mut self, booking: &PyBooking, reason: String) -> PyResult<()> {
+        warn!(
+            "Leaving out booking for cargo {:?}, since {reason}",
+            booking.cargo
+        );
+        let data = self.data_mut();
+        data.rejected_bookings.insert(booking.cargo.clone(), reason);
+        // Rejected again when loading `to_json`
+        data.inputs.booking_data.push(booking.clone());
+        Ok(())
+    }
+
+    /// Removes `cargo` from the cargo that can be scheduled.
+    /// It needs to have been removed from all schedules already
+    fn forget_cargo(&mut self, cargo: Cargo) {
+        let data = self.data_mut();
+        data.pickup_times.remove(&cargo);
+        data.dropoff_times.remove(&cargo);
+        let booking_info = data.cargo_booking_info.remove(&cargo).unwrap();
+        let terminal_cargo = data
+            .cargo_by_terminals
+            .get_mut(&(booking_info.from, booking_info.to))
+            .unwrap();
+        terminal_cargo.remove(&cargo);
+        if terminal_cargo.is_empty() {
+            data.cargo_by_terminals
+                .remove(&(booking_info.from, booking_info.to));
+        }
+
+        data.cargo_successors.remove(&cargo);
+        data.cargo_stops.remove(&cargo);
+        data.stop_main_cargo.remove(&cargo);
+        if let Some(predecessor) = booking_info.predecessor {
+            if let Some(successors) = data.cargo_successors.get_mut(&predecessor) {
+                successors.remove(&cargo);
+            }
+        }
+
+        data.trucks_with_locked_cargo = data
+            .cargo_booking_info
+            .values()
+            .filter_map(|booking_info| booking_info.locked_truck)
+            .collect();
+    }
+
+    /// Makes sure that `schedule` only has trucks of the generator, which it
+    /// doesn't if it was made before one of them was removed
+    fn check_trucks(&self, schedule: &Schedule) -> PyResult<()> {
+        match schedule
+            .truck_checkpoints
+            .keys()
+            .find(|truck| !self.trucks.contains(truck))
+        {
+            Some(truck) => Err(PyTypeError::new_err(format!(
+                "Schedule has truck {:?}, which was removed; use the schedule returned by remove_truck",
+                self.truck_mapper.map(truck).unwrap()
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Unschedules cargo whose predecessor or other stops aren't scheduled as they
+    /// need to be anymore, e.g. because the truck delivering them was removed
+    fn unschedule_dependent_cargo(&self, schedule: &mut Schedule) {
+        loop {
+            let mut cargo_to_remove: BTreeSet<Cargo> = self
+                .find_precedence_violations(schedule)
+                .into_iter()
+                .map(|(_predecessor, successor)| successor)
+                .collect();
+            for cargo in self.find_stop_order_violations(schedule) {
+                let stops = self.cargo_stops.get(&cargo).unwrap();
+                cargo_to_remove.extend(stops.iter().flatten().map(|(cargo, _)| *cargo));
+            }
+            if cargo_to_remove.is_empty() {
+                return;
+            }
+            for cargo in cargo_to_remove {
+                self.remove_delivery(schedule, cargo);
+            }
+        }
+    }
+
+    /// Removes the pickup and dropoff of `cargo` from `schedule` if it is scheduled,
+    /// freeing up the capacity it took up
+    fn remove_delivery(&self, schedule: &mut Schedule, cargo: Cargo) {
+        if let Some(mv) = self.delivery_removal(schedule, cargo) {
+            mv.apply(schedule);
+        }
+    }
+
+    /// The move removing the pickup and dropoff of `cargo` from `schedule`,
+    /// see `remove_delivery`. None if it isn't scheduled
+    fn delivery_removal(&self, schedule: &Schedule, cargo: Cargo) -> Option<Move> {
+        let &truck = schedule.scheduled_cargo_truck.get(&cargo)?;
+        let checkpoints = schedule.truck_checkpoints.get(&truck).unwrap();
+
+        // Find all references to this cargo in truck
+        let start_checkpoint_index = checkpoints
+            .iter()
+            .position(|checkpoint| checkpoint.pickup_cargo.contains(&cargo))
+            .unwrap();
+        let end_checkpoint_index = checkpoints
+            .iter()
+            .position(|checkpoint| checkpoint.dropoff_cargo.contains(&cargo))
+            .unwrap();
+        if self.debug_checks {
+            assert!(
+                checkpoints
+                    .iter()
+                    .filter(|checkpoint| checkpoint.pickup_cargo.contains(&cargo))
+                    .count()
+                    == 1
+            );
+            assert!(
+                checkpoints
+                    .iter()
+                    .filter(|checkpoint| checkpoint.dropoff_cargo.contains(&cargo))
+                    .count()
+                    == 1
+            );
+        }
+
+        let old_checkpoints = checkpoints
+            .slice(start_checkpoint_index..=end_checkpoint_index)
+            .to_vec();
+        let mut new_checkpoints = old_checkpoints.clone();
+        let last = new_checkpoints.len() - 1;
+        assert!(new_checkpoints[0].pickup_cargo.remove(&cargo));
+        assert!(new_checkpoints[last].dropoff_cargo.remove(&cargo));
+
+        // Modify the weights and sizes
+        let booking_info = self.cargo_booking_info.get(&cargo).unwrap();
+        let truck_data = self.truck_data.get(&truck).unwrap();
+        for checkpoint in &mut new_checkpoints[..last] {
+            checkpoint.available_capacity.add(&booking_info.demand);
+            if self.debug_checks {
+                assert!(checkpoint
+                    .available_capacity
+                    .fits_within(&truck_data.capacity));
+            }
+        }
+
+        Some(Move::RemoveDelivery {
+            cargo,
+            truck,
+            first_index: start_checkpoint_index,
+            old_checkpoints,
+            new_checkpoints,
+        })
+    }
+
+    /// Adds a description of each hard constraint violated by `truck` in `schedule`
+    /// to `out`, see `find_violations`
+    fn find_truck_violations(
+        &self,
+        schedule: &Schedule,
+        truck: &Truck,
+        out: &mut Vec<String>,
+    ) -> DrivingTimeResult<()> {
+        let checkpoints = schedule.truck_checkpoints.get(truck).unwrap();
+        if let Some(first_checkpoint) = checkpoints.first() {
+            let start_time = self.get_truck_start_time(*truck);
+            if first_checkpoint.time < start_time {
+                out.push(format!(
+                    "Truck {:?} has a checkpoint at time {} before its shift starts at {start_time}",
+                    self.truck_mapper.map(truck).unwrap(),
+                    first_checkpoint.time
+                ));
+            }
+        }
+
+        for checkpoint in checkpoints.iter() {
+            if !self.is_legal_weight(*truck, checkpoint) {
+                out.push(format!(
+                    "Truck {:?} has gross weight {}kg at time {}, over the legal maximum of {}kg",
+                    self.truck_mapper.map(truck).unwrap(),
+                    self.get_gross_weight_kg(*truck, checkpoint),
+                    checkpoint.time,
+                    self.truck_data
+                        .get(truck)
+                        .unwrap()
+                        .max_gross_weight_kg
+                        .unwrap()
+                ));
+            }
+        }
+
+        if self.truck_data.get(truck).unwrap().rear_loading && !is_last_in_first_out(checkpoints) {
+            out.push(format!(
+                "Rear-loaded truck {:?} doesn't drop off cargo in reverse order of pickup",
+                self.truck_mapper.map(truck).unwrap()
+            ));
+        }
+
+        let breaks = schedule.truck_breaks.get(truck).unwrap();
+        match self.get_continuous_driving_times(*truck, checkpoints, breaks)? {
+            None => out.push(format!(
+                "Truck {:?} doesn't have time to drive between its checkpoints around its breaks and buffer times",
+                self.truck_mapper.map(truck).unwrap()
+            )),
+            Some(continuous_driving_times) => {
+                if let Some(rules) = &self.driving_hour_rules {
+                    for (time, driving_time) in continuous_driving_times {
+                        if driving_time > rules.max_continuous_driving_time {
+                            out.push(format!(
+                                "Driver of truck {:?} drives for {driving_time} without a break before time {time}, over the maximum of {}",
+                                self.truck_mapper.map(truck).unwrap(),
+                                rules.max_continuous_driving_time
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+
+        if !self.drivers.is_empty() {
+            let truck_id = self.truck_mapper.map(truck).unwrap();
+            for (driver, checkpoint) in self
+                .get_checkpoint_drivers(schedule, *truck)?
+                .into_iter()
+                .zip(checkpoints.iter())
+            {
+                let Some(driver) = driver else {
+                    out.push(format!(
+                        "Truck {truck_id:?} has no driver on duty to drive to its checkpoint at time {}",
+                        checkpoint.time
+                    ));
+                    continue;
+                };
+                for (cargo, qualification) in
+                    self.find_missing_driver_qualifications(driver, checkpoint)
+                {
+                    out.push(format!(
+                        "Cargo {:?} requires qualification {:?}, which driver {:?} of truck {truck_id:?} doesn't have",
+                        self.cargo_mapper.map(&cargo).unwrap(),
+                        self.qualification_mapper.map(&qualification).unwrap(),
+                        self.driver_mapper.map(&driver).unwrap(),
+                    ));
+                }
+            }
+        }
+
+        for day in self.days_over_checkpoint_limit(checkpoints) {
+            out.push(format!(
+                "Truck {:?} has more than {} checkpoints on the day starting at {}",
+                self.truck_mapper.map(truck).unwrap(),
+                self.max_checkpoints_per_day.unwrap(),
+                day * SECONDS_PER_DAY
+            ));
+        }
+        Ok(())
+    }
+
+    /// Lists all the ways in which `schedule` violates hard constraints
+    fn find_violations(&self, schedule: &Schedule) -> DrivingTimeResult<Vec<String>> {
+        let mut out = vec![];
+        for (cargo, booking_info) in self.cargo_booking_info.iter() {
+            let cargo_id = self.cargo_mapper.map(cargo).unwrap();
+            let Some(truck) = schedule.scheduled_cargo_truck.get(cargo) else {
+                if booking_info.mandatory {
+                    out.push(format!("Mandatory cargo {cargo_id:?} is not delivered"));
+                }
+                continue;
+            };
+            let truck_id = self.truck_mapper.map(truck).unwrap();
+
+            if let Some(locked_truck) = booking_info.locked_truck {
+                if locked_truck != *truck {
+                    out.push(format!(
+                        "Cargo {cargo_id:?} is locked to truck {:?}, but is delivered by truck {truck_id:?}",
+                        self.truck_mapper.map(&locked_truck).unwrap()
+                    ));
+                }
+            }
+
+            // With a driver roster, the drivers' qualifications are also checked
+            // for each checkpoint
+            let truck_data = self.truck_data.get(truck).unwrap();
+            for qualification in booking_info
+                .required_qualifications
+                .difference(&truck_data.qualifications)
+            {
+                out.push(format!(
+                    "Cargo {cargo_id:?} requires qualification {:?}, which truck {truck_id:?} doesn't have",
+                    self.qualification_mapper.map(qualification).unwrap()
+                ));
+            }
+        }
+
+        for truck in schedule.truck_checkpoints.keys() {
+            self.find_truck_violations(schedule, truck, &mut out)?;
+        }
+
+        for (driver, truck) in schedule.driver_trucks.iter() {
+            if !self.can_drive(*driver, *truck) {
+                out.push(format!(
+                    "Driver {:?} drives truck {:?}, which doesn't start at the driver's home terminal",
+                    self.driver_mapper.map(driver).unwrap(),
+                    self.truck_mapper.map(truck).unwrap(),
+                ));
+            }
+        }
+
+        for (driver1, truck1) in schedule.driver_trucks.iter() {
+            for (driver2, truck2) in schedule.driver_trucks.iter() {
+                if driver1 < driver2 && truck1 == truck2 && self.shifts_overlap(*driver1, *driver2)
+                {
+                    out.push(format!(
+                        "Drivers {:?} and {:?} of truck {:?} have overlapping shifts",
+                        self.driver_mapper.map(driver1).unwrap(),
+                        self.driver_mapper.map(driver2).unwrap(),
+                        self.truck_mapper.map(truck1).unwrap(),
+                    ));
+                }
+            }
+        }
+
+        for (predecessor, successor) in self.find_precedence_violations(schedule) {
+            out.push(format!(
+                "Cargo {:?} is picked up before cargo {:?} is dropped off",
+                self.cargo_mapper.map(&successor).unwrap(),
+                self.cargo_mapper.map(&predecessor).unwrap(),
+            ));
+        }
+
+        for cargo in self.find_stop_order_violations(schedule) {
+            out.push(format!(
+                "Stops of the booking for cargo {:?} are not visited in order by a single truck",
+                self.cargo_mapper.map(&cargo).unwrap()
+            ));
+        }
+
+        for (terminal, timeline) in self.get_chassis_timelines(schedule) {
+            if let Some((time, available)) =
+                timeline.iter().find(|(_time, available)| *available < 0)
+            {
+                out.push(format!(
+                    "Terminal {:?} is short of {} chassis at time {time}",
+                    self.terminal_mapper.map(&terminal).unwrap(),
+                    -available
+                ));
+            }
+        }
+        Ok(out)
+    }
+
+    /// Adjusts a driving time from the driving times given
+    /// to how fast `truck` drives
+    fn scale_driving_time(
+        &self,
+        truck: Truck,
+        driving_time: NonNegativeTimeDelta,
+    ) -> NonNegativeTimeDelta {
+        let driving_time_per_mille = self.truck_data.get(&truck).unwrap().driving_time_per_mille;
+        if driving_time_per_mille == 1000 {
+            return driving_time;
+        }
+        driving_time
+            .saturating_mul(driving_time_per_mille)
+            .div_ceil(1000)
+    }
+
+    /// Whether all cargo in `schedule` is still picked up and dropped off within its
+    /// time windows when the driving times are sampled around their means, see
+    /// `set_driving_time_spreads`. Trucks leave each checkpoint once they are done there
+    /// and take their breaks on the way, while arriving early means waiting for the
+    /// scheduled time, so delays are made up for by the slack before later checkpoints
+    fn is_on_time_when_sampled(
+        &self,
+        schedule: &Schedule,
+        rng: &mut Xoshiro256PlusPlus,
+    ) -> DrivingTimeResult<bool> {
+        for (truck, checkpoints) in schedule.truck_checkpoints.iter() {
+            let breaks = schedule.truck_breaks.get(truck).unwrap();
+            let mut terminal = self.truck_data.get(truck).unwrap().starting_terminal;
+            let mut scheduled_departure_time = self.get_truck_start_time(*truck);
+            let mut departure_time = scheduled_departure_time;
+            for checkpoint in checkpoints.iter() {
+                let mut driving_time = self.get_truck_driving_time_at(
+                    *truck,
+                    terminal,
+                    checkpoint.terminal,
+                    departure_time,
+                )?;
+                let spread = self.scale_driving_time(
+                    *truck,
+                    self.driving_times_cache
+                        .get_spread(terminal, checkpoint.terminal),
+                );
+                if spread > 0 {
+                    driving_time = (driving_time as f64
+                        + spread as f64 * sample_standard_normal(rng))
+                    .max(0.0)
+                    .round() as NonNegativeTimeDelta;
+                }
+                let break_duration: NonNegativeTimeDelta = breaks
+                    .iter()
+                    .filter(|break_| {
+                        scheduled_departure_time <= break_.time && break_.time < checkpoint.time
+                    })
+                    .map(|break_| break_.duration)
+                    .sum();
+
+                let time = max(
+                    checkpoint.time,
+                    departure_time + break_duration + driving_time,
+                );
+                let is_on_time = checkpoint
+                    .pickup_cargo
+                    .iter()
+                    .all(|cargo| self.pickup_times.get(cargo).unwrap().contains(time))
+                    && checkpoint
+                        .dropoff_cargo
+                        .iter()
+                        .all(|cargo| self.dropoff_times.get(cargo).unwrap().contains(time));
+                if !is_on_time {
+                    return Ok(false);
+                }
+                terminal = checkpoint.terminal;
+                scheduled_departure_time = checkpoint.time + checkpoint.duration;
+                departure_time = time + checkpoint.duration;
+            }
+        }
+        Ok(true)
+    }
+
+    /// Get the driving time of `truck` from `from` to `to` when departing at `departure_time`
+    fn get_truck_driving_time_at(
+        &self,
+        truck: Truck,
+        from: Terminal,
+        to: Terminal,
+        departure_time: Time,
+    ) -> DrivingTimeResult<NonNegativeTimeDelta> {
+        let vehicle_class = self.truck_data.get(&truck).unwrap().vehicle_class;
+        Ok(self.scale_driving_time(
+            truck,
+            self.driving_times_cache.get_driving_time_at(
+                from,
+                to,
+                vehicle_class,
+                departure_time,
+            )?,
+        ))
+    }
+
+    /// Finds the latest time `truck` can depart from `from` to arrive at `to`
+    /// before `arrival_time`
+    fn get_truck_latest_departure_time(
+        &self,
+        truck: Truck,
+        from: Terminal,
+        to: Terminal,
+        arrival_time: Time,
+    ) -> DrivingTimeResult<Time> {
+        let vehicle_class = self.truck_data.get(&truck).unwrap().vehicle_class;
+        let latest_departure_time = self.driving_times_cache.get_latest_departure_time(
+            from,
+            to,
+            vehicle_class,
+            arrival_time,
+        )?;
+        // NOTE: approximate for time-dependent driving times, since
+        // the driving time is scaled at the latest departure time
+        Ok(arrival_time.saturating_sub(
+            self.scale_driving_time(truck, arrival_time.saturating_sub(latest_departure_time)),
+        ))
+    }
+
+    /// Builds a schedule from its JSON representation, recomputing the capacity
+    /// left at each checkpoint and the driving times. Each cargo needs to be
+    /// dropped off by the truck that picked it up, at a later checkpoint
+    fn schedule_from_json(&self, data: ScheduleJson) -> PyResult<Schedule> {
+        let find_truck = |truck_id: &PyTruckID| {
+            self.truck_mapper
+                .reverse_map(truck_id)
+                .filter(|truck| self.trucks.contains(truck))
+                .ok_or_else(|| UnknownIdError::new_err(format!("Unknown truck {truck_id:?}")))
+        };
+        let find_cargo = |cargo_id: &PyCargoID| {
+            self.cargo_mapper
+                .reverse_map(cargo_id)
+                .filter(|cargo| self.cargo_booking_info.contains_key(cargo))
+                .ok_or_else(|| UnknownIdError::new_err(format!("Unknown cargo {cargo_id:?}")))
+        };
+
+        let mut out = self.empty_schedule();
+        for (truck_id, checkpoints_data) in data.truck_checkpoints.iter() {
+            let truck: Truck = find_truck(truck_id)?;
+            let mut available_capacity = self.truck_data.get(&truck).unwrap().capacity.clone();
+            let mut loaded_cargo = BTreeSet::new();
+            let mut checkpoints = vec![];
+            for checkpoint_data in checkpoints_data.iter() {
+                let terminal: Terminal = self
+                    .terminal_mapper
+                    .reverse_map(&checkpoint_data.terminal)
+                    .ok_or_else(|| {
+                        UnknownTerminalError::new_err(format!(
+                            "Unknown terminal {:?}",
+                            checkpoint_data.terminal
+                        ))
+                    })?;
+                let pickup_cargo = checkpoint_data
+                    .pickup_cargo
+                    .iter()
+                    .map(find_cargo)
+                    .collect::<PyResult<CargoSet>>()?;
+                let dropoff_cargo = checkpoint_data
+                    .dropoff_cargo
+                    .iter()
+                    .map(find_cargo)
+                    .collect::<PyResult<CargoSet>>()?;
+
+                for cargo in dropoff_cargo.iter() {
+                    if !loaded_cargo.remove(cargo) {
+                        return Err(InfeasibleScheduleError::new_err(format!(
+                            "Truck {truck_id:?} drops off cargo {:?} without having picked it up",
+                            self.cargo_mapper.map(cargo).unwrap()
+                        )));
+                    }
+                    available_capacity.add(&self.cargo_booking_info.get(cargo).unwrap().demand);
+                }
+                for cargo in pickup_cargo.iter() {
+                    loaded_cargo.insert(*cargo);
+                    available_capacity = available_capacity
+                        .checked_sub(&self.cargo_booking_info.get(cargo).unwrap().demand)
+                        .ok_or_else(|| {
+                            InfeasibleScheduleError::new_err(format!(
+                                "Truck {truck_id:?} doesn't have the capacity to pick up cargo {:?}",
+                                self.cargo_mapper.map(cargo).unwrap()
+                            ))
+                        })?;
+                    if out.assign_cargo(*cargo, truck).is_some() {
+                        return Err(InfeasibleScheduleError::new_err(format!(
+                            "Cargo {:?} is picked up more than once",
+                            self.cargo_mapper.map(cargo).unwrap()
+                        )));
+                    }
+                }
+
+                checkpoints.push(Checkpoint {
+                    time: checkpoint_data.time,
+                    terminal,
+                    pickup_cargo,
+                    dropoff_cargo,
+                    available_capacity: available_capacity.clone(),
+                    duration: checkpoint_data.duration,
+                });
+            }
+            if let Some(cargo) = loaded_cargo.first() {
+                return Err(InfeasibleScheduleError::new_err(format!(
+                    "Truck {truck_id:?} never drops off cargo {:?}",
+                    self.cargo_mapper.map(cargo).unwrap()
+                )));
+            }
+            out.truck_checkpoints.insert(truck, checkpoints);
+            out.index_checkpoints(truck);
+            let driving_time = self
+                .get_route_driving_time(&out, truck)
+                .map_err(|missing| self.driving_time_error(missing))?;
+            out.truck_driving_times.insert(truck, driving_time);
+        }
+
+        for (truck_id, breaks) in data.truck_breaks.iter() {
+            let truck: Truck = find_truck(truck_id)?;
+            out.truck_breaks.insert(
+                truck,
+                breaks
+                    .iter()
+                    .map(|(time, duration)| Break {
+                        time: *time,
+                        duration: *duration,
+                    })
+                    .collect(),
+            );
+        }
+
+        for (driver_id, truck_id) in data.driver_trucks.iter() {
+            let driver: Driver = self
+                .driver_mapper
+                .reverse_map(driver_id)
+                .filter(|driver| self.drivers.contains_key(driver))
+                .ok_or_else(|| UnknownIdError::new_err(format!("Unknown driver {driver_id:?}")))?;
+            out.driver_trucks.insert(driver, find_truck(truck_id)?);
+        }
+        Ok(out)
+    }
+
+    /// Describes how much of each capacity dimension is in `capacity`,
+    /// leaving out the dimensions `truck` isn't limited in
+    fn capacity_to_dict(
+        &self,
+        truck: Truck,
+        capacity: &Capacity,
+    ) -> BTreeMap<PyCapacityDimensionID, usize> {
+        let truck_capacity = &self.truck_data.get(&truck).unwrap().capacity;
+        self.capacity_dimension_mapper
+            .iter()
+            .filter(|(dimension, _)| truck_capacity.get(*dimension) != usize::MAX)
+            .map(|(dimension, dimension_id)| (dimension_id.clone(), capacity.get(dimension)))
+            .collect()
+    }
+
+    /// Total driving time of `truck` from its starting terminal through its checkpoints
+    fn get_route_driving_time(
+        &self,
+        schedule: &Schedule,
+        truck: Truck,
+    ) -> DrivingTimeResult<NonNegativeTimeDelta> {
+        let mut driving_time = 0;
+        let mut terminal = None;
+        for checkpoint in schedule.truck_checkpoints.get(&truck).unwrap().iter() {
+            driving_time += self.get_driving_time(terminal, Some(checkpoint.terminal), truck)?;
+            terminal = Some(checkpoint.terminal);
+        }
+        Ok(driving_time)
+    }
+
+    /// Get driving time between `from` and `to`.
+    /// If `from` is None, assume it is the starting terminal
+    /// If `to` is None, assume that there is no restriction
+    /// on what `to` is, and so we can stay at `from` for 0 driving time
+    fn get_driving_time(
+        &self,
+        from: Option<Terminal>,
+        to: Option<Terminal>,
+        truck: Truck,
+    ) -> DrivingTimeResult<NonNegativeTimeDelta> {
+        let truck_data = self.truck_data.get(&truck).unwrap();
+        let from = from.unwrap_or(truck_data.starting_terminal);
+        if let Some(to) = to {
+            let out =
+                self.driving_times_cache
+                    .get_driving_time(from, to, truck_data.vehicle_class)?;
+            Ok(self.scale_driving_time(truck, out))
+        } else {
+            Ok(0)
+        }
+    }
+
+    /// Find the interval between `prev_checkpoint.time` and `next_checkpoint.time`
+    /// containing the times during which we can put a checkpoint in `new_terminal`
+    /// and have time to drive from `prev_checkpoint.terminal` to `new_terminal` and
+    /// from `new_terminal` to `next_checkpoint.terminal`.
+    /// Driving times are looked up for the time of departure, since they
+    /// might depend on the time of day
+    fn get_transit_time_constraints(
+        &mut self,
+        truck: Truck,
+        prev_checkpoint: Option<&Checkpoint>,
+        next_checkpoint: Option<&Checkpoint>,
+        new_terminal: Terminal,
+    ) -> DrivingTimeResult<Option<Interval>> {
+        let (prev_terminal, prev_time, prev_duration) =
+            if let Some(prev_checkpoint) = prev_checkpoint {
+                (
+                    Some(prev_checkpoint.terminal),
+                    prev_checkpoint.time,
+                    prev_checkpoint.duration,
+                )
+            } else {
+                // Don't need to wait any time if at starting terminal, so 0 duration
+                (None, self.get_truck_start_time(truck), 0)
+            };
+
+        let (next_terminal, next_time) = if let Some(next_checkpoint) = next_checkpoint {
+            (Some(next_checkpoint.terminal), next_checkpoint.time)
+        } else {
+            // TODO: add proper bound on time
+            (None, self.planning_period.get_end_time())
+        };
+
+        let prev_terminal =
+            prev_terminal.unwrap_or(self.truck_data.get(&truck).unwrap().starting_terminal);
+        let departure_time = prev_time + prev_duration;
+        // Leave some slack after driving, e.g. for queueing at the gate,
+        // and time for breaks during legs too long to drive in one go
+        let driving_time =
+            self.get_truck_driving_time_at(truck, prev_terminal, new_terminal, departure_time)?;
+        let earliest_checkpoint_time = departure_time
+            + driving_time
+            + self.get_long_leg_break_time(driving_time)
+            + self.get_buffer_time(new_terminal);
+
+        let latest_checkpoint_time = if let Some(next_terminal) = next_terminal {
+            let arrival_time = next_time.saturating_sub(self.get_buffer_time(next_terminal));
+            let latest_departure_time = self.get_truck_latest_departure_time(
+                truck,
+                new_terminal,
+                next_terminal,
+                arrival_time,
+            )?;
+            latest_departure_time
+                .saturating_sub(self.get_long_leg_break_time(arrival_time - latest_departure_time))
+        } else {
+            next_time
+        };
+
+        Ok(Interval::new(
+            earliest_checkpoint_time,
+            latest_checkpoint_time,
+            (),
+        ))
+    }
+
+    /// Given a previous and next checkpoints, find
+    /// what terminals those correspond to. Handles cases when
+    /// there is no checkpoint, and suggests correct terminals
     fn get_gap_terminals(
         &self,
-        truck: Truck,
-        prev_checkpoint: Option<&Checkpoint>,
-        next_checkpoint: Option<&Checkpoint>,
-    ) -> (Terminal, Option<Terminal>) {
-        let prev_terminal = if let Some(prev) = prev_checkpoint {
-            prev.terminal
+        truck: Truck,
+        prev_checkpoint: Option<&Checkpoint>,
+        next_checkpoint: Option<&Checkpoint>,
+    ) -> (Terminal, Option<Terminal>) {
+        let prev_terminal = if let Some(prev) = prev_checkpoint {
+            prev.terminal
+        } else {
+            // Before first interval
+            self.truck_data.get(&truck).unwrap().starting_terminal
+        };
+
+        let next_terminal = next_checkpoint.map(|next| next.terminal);
+        (prev_terminal, next_terminal)
+    }
+
+    /// Return (`truck`, index in `checkpoints`) for a random checkpoint
+    /// without pickups or dropoffs
+    fn get_random_empty_checkpoint<'a>(
+        &mut self,
+        schedule: &'a Schedule,
+    ) -> Option<(&'a Checkpoint, Truck, usize)> {
+        // Pick a random empty checkpoint, uniformly, across trucks
+        if schedule.num_empty_checkpoints == 0 {
+            return None;
+        }
+
+        let mut checkpoint_index = self.rng.random_range(0..schedule.num_empty_checkpoints);
+        // Find a truck, weighted by number of empty checkpoints in it
+        let (chosen_truck, chosen_index) = schedule
+            .truck_empty_positions
+            .iter()
+            .find_map(|(truck, positions)| {
+                if checkpoint_index < positions.len() {
+                    Some((*truck, positions[checkpoint_index]))
+                } else {
+                    checkpoint_index -= positions.len();
+                    None
+                }
+            })
+            .unwrap();
+
+        let checkpoint = schedule
+            .truck_checkpoints
+            .get(&chosen_truck)
+            .unwrap()
+            .get(chosen_index)
+            .unwrap();
+        Some((checkpoint, chosen_truck, chosen_index))
+    }
+
+    /// Try to add a random direct delivery; return the move if succeeded
+    fn add_random_checkpoint(
+        &mut self,
+        schedule: &mut Schedule,
+    ) -> DrivingTimeResult<Option<Move>> {
+        // TODO: pick so that empty trucks have a higher chance of being picked
+        let Some(&truck) = self.data.trucks.iter().choose(&mut self.rng) else {
+            return Ok(self.reject("no_truck"));
+        };
+
+        // We want to pick an interval between checkpoints to which we will add a new checkpoint
+        // Pick a time uniformly at random and pick the interval containing that time,
+        // so that large intervals are more likely to be chosen, breaking up large intervals.
+        let planning_start_time = self.planning_period.get_start_time();
+        let planning_end_time = self.planning_period.get_end_time();
+        let Some(time_to_identify_gap) =
+            (planning_start_time..planning_end_time).choose(&mut self.rng)
+        else {
+            return Ok(self.reject("empty_planning_period"));
+        };
+        let (prev_checkpoint, next_checkpoint) =
+            schedule.get_checkpoints_around_gap(truck, time_to_identify_gap);
+        let (prev_terminal, next_terminal) =
+            self.get_gap_terminals(truck, prev_checkpoint, next_checkpoint);
+
+        // NOTE: the optimal solution doesn't visit a terminal and do nothing at it.
+        // Each visited terminal should either have a pickup or a dropoff
+        // associated with it
+        // TODO: explore automatically adding that pickup/dropoff
+        let mut possible_terminals = BTreeSet::new();
+
+        for cargo in schedule
+            .scheduled_cargo
+            .iter_missing(self.cargo_mapper.len())
+        {
+            let Some(booking_info) = self.cargo_booking_info.get(&cargo) else {
+                continue;
+            };
+            if !self.can_carry(truck, booking_info) {
+                continue;
+            }
+            // disallow picking same terminal as the one before or after, since we want to associate
+            // gaps between checkpoints with driving
+            if booking_info.from != prev_terminal && Some(booking_info.from) != next_terminal {
+                possible_terminals.insert(booking_info.from);
+            }
+            if booking_info.to != prev_terminal && Some(booking_info.to) != next_terminal {
+                // Only schedule the `to` terminal if this truck has visited the
+                // `from` terminal before and so can deliver
+                if let Some(first_from_checkpoint) = schedule
+                    .truck_checkpoints
+                    .get(&truck)
+                    .unwrap()
+                    .iter()
+                    .find(|checkpoint| checkpoint.terminal == booking_info.from)
+                {
+                    if first_from_checkpoint.time < time_to_identify_gap {
+                        possible_terminals.insert(booking_info.to);
+                    }
+                };
+            }
+        }
+
+        let Some(&new_terminal) = possible_terminals.iter().choose(&mut self.rng) else {
+            return Ok(self.reject("no_candidate_terminal"));
+        };
+
+        let Some(allowed_time_interval) = self.get_transit_time_constraints(
+            truck,
+            prev_checkpoint,
+            next_checkpoint,
+            new_terminal,
+        )?
+        else {
+            return Ok(self.reject("empty_allowed_interval"));
+        };
+
+        // Otherwise, schedule a checkpoint in this time, if we can
+        let new_time = allowed_time_interval.random_time(&mut self.rng);
+
+        // Insert in place of first element after it,
+        // or if all elements are before it, insert it at the end
+        let checkpoints = schedule.truck_checkpoints.get(&truck).unwrap();
+        let new_checkpoint_index = checkpoints
+            .iter()
+            .position(|checkpoint| checkpoint.time > new_time)
+            .unwrap_or(checkpoints.len());
+
+        // Since we are not loading or unloading anything,
+        // the size/weight are the same
+        let prev_available_capacity = if let Some(prev_checkpoint) = prev_checkpoint {
+            prev_checkpoint.available_capacity.clone()
+        } else {
+            // Starting capacity
+            self.truck_data.get(&truck).unwrap().capacity.clone()
+        };
+
+        // Increase the cached driving time
+        // We are replacing driving A->C with driving A->B->C
+        let old_driving_time = *schedule.truck_driving_times.get(&truck).unwrap();
+        let prev_terminal = Some(prev_terminal);
+        let terminal = Some(new_terminal);
+
+        let time_a_to_c = self.get_driving_time(prev_terminal, next_terminal, truck)?;
+        let time_a_to_b = self.get_driving_time(prev_terminal, terminal, truck)?;
+        let time_b_to_c = self.get_driving_time(terminal, next_terminal, truck)?;
+
+        let mv = Move::AddCheckpoint {
+            truck,
+            index: new_checkpoint_index,
+            checkpoint: Checkpoint {
+                time: new_time,
+                terminal: new_terminal,
+                pickup_cargo: CargoSet::new(),
+                dropoff_cargo: CargoSet::new(),
+                available_capacity: prev_available_capacity,
+                duration: 0,
+            },
+            old_driving_time,
+            new_driving_time: old_driving_time + time_a_to_b + time_b_to_c - time_a_to_c,
+        };
+        let changed = new_checkpoint_index..new_checkpoint_index + 1;
+        self.try_move(schedule, mv, |generator, schedule| {
+            generator.assert_truck_checkpoints_invariant(schedule, truck, changed.clone());
+
+            // Respect the limit on the number of stops per day, and
+            // the new checkpoint needs to fit around the driver's breaks and shift
+            Ok(
+                if generator.exceeds_checkpoint_limit(
+                    schedule.truck_checkpoints.get(&truck).unwrap(),
+                    changed,
+                ) {
+                    Some("checkpoint_limit")
+                } else if !generator.respects_driving_hours(schedule, truck)? {
+                    Some("driving_hours")
+                } else if !generator.respects_driver_roster(schedule, truck)? {
+                    Some("driver_roster")
+                } else {
+                    None
+                },
+            )
+        })
+    }
+
+    /// Pick a random checkpoint and remove it
+    fn remove_random_checkpoint(
+        &mut self,
+        schedule: &mut Schedule,
+    ) -> DrivingTimeResult<Option<Move>> {
+        // To avoid easily undoing progress, only allow removing checkpoint if there is no cargo
+        // pickup or dropoff in it
+        let Some((checkpoint, chosen_truck, chosen_index)) =
+            self.get_random_empty_checkpoint(schedule)
+        else {
+            return Ok(self.reject("no_empty_checkpoint"));
+        };
+
+        // Check that removing this checkpoint won't leave us
+        // with 2 consecutive checkpoints with the same terminals
+        let (prev_checkpoint, next_checkpoint) =
+            schedule.get_prev_and_next_checkpoints(chosen_truck, checkpoint);
+        let (prev_terminal, next_terminal) =
+            self.get_gap_terminals(chosen_truck, prev_checkpoint, next_checkpoint);
+        if Some(prev_terminal) == next_terminal {
+            return Ok(self.reject("repeated_terminal"));
+        }
+
+        // Reduce the cached driving time
+        // We are replacing driving A->B->C with driving A->C
+        let old_driving_time = *schedule.truck_driving_times.get(&chosen_truck).unwrap();
+        let prev_terminal = prev_checkpoint.map(|c| c.terminal);
+        let terminal = Some(checkpoint.terminal);
+        let next_terminal = next_checkpoint.map(|c| c.terminal);
+
+        let time_a_to_c = self.get_driving_time(prev_terminal, next_terminal, chosen_truck)?;
+        let time_a_to_b = self.get_driving_time(prev_terminal, terminal, chosen_truck)?;
+        let time_b_to_c = self.get_driving_time(terminal, next_terminal, chosen_truck)?;
+
+        let mv = Move::RemoveCheckpoint {
+            truck: chosen_truck,
+            index: chosen_index,
+            checkpoint: checkpoint.clone(),
+            old_driving_time,
+            new_driving_time: old_driving_time + time_a_to_c - time_a_to_b - time_b_to_c,
+        };
+        self.try_move(schedule, mv, |generator, schedule| {
+            generator.assert_truck_checkpoints_invariant(
+                schedule,
+                chosen_truck,
+                chosen_index..chosen_index,
+            );
+
+            // Driving directly to the next checkpoint might take longer,
+            // be too long without a break or not fit in a driver's shift
+            Ok(
+                if !generator.respects_driving_hours(schedule, chosen_truck)? {
+                    Some("driving_hours")
+                } else if !generator.respects_driver_roster(schedule, chosen_truck)? {
+                    Some("driver_roster")
+                } else {
+                    None
+                },
+            )
+        })
+    }
+
+    /// Try to add a break at a random time for a random truck
+    fn add_random_break(&mut self, schedule: &mut Schedule) -> DrivingTimeResult<Option<Move>> {
+        let Some(rules) = &self.driving_hour_rules else {
+            return Ok(self.reject("no_driving_hour_rules"));
+        };
+        let duration = rules.min_break_duration;
+        let Some(&truck) = self.data.trucks.iter().choose(&mut self.rng) else {
+            return Ok(self.reject("no_truck"));
+        };
+        let Some(time) = (self.get_truck_start_time(truck)..self.planning_period.get_end_time())
+            .choose(&mut self.rng)
+        else {
+            return Ok(self.reject("empty_planning_period"));
+        };
+
+        let index = schedule
+            .truck_breaks
+            .get(&truck)
+            .unwrap()
+            .iter()
+            .position(|break_| break_.time > time)
+            .unwrap_or(schedule.truck_breaks.get(&truck).unwrap().len());
+        let mv = Move::AddBreak {
+            truck,
+            index,
+            break_: Break { time, duration },
+        };
+        self.try_move(schedule, mv, |generator, schedule| {
+            Ok((!generator.respects_driving_hours(schedule, truck)?).then_some("driving_hours"))
+        })
+    }
+
+    /// Pick a random break and remove it
+    fn remove_random_break(&mut self, schedule: &mut Schedule) -> DrivingTimeResult<Option<Move>> {
+        let Some((truck, index)) = schedule
+            .truck_breaks
+            .iter()
+            .flat_map(|(truck, breaks)| (0..breaks.len()).map(move |index| (*truck, index)))
+            .choose(&mut self.rng)
+        else {
+            return Ok(self.reject("no_break"));
+        };
+
+        let mv = Move::RemoveBreak {
+            truck,
+            index,
+            break_: schedule.truck_breaks.get(&truck).unwrap()[index].clone(),
+        };
+        // The driver might now be driving for too long without a break
+        self.try_move(schedule, mv, |generator, schedule| {
+            Ok((!generator.respects_driving_hours(schedule, truck)?).then_some("driving_hours"))
+        })
+    }
+
+    /// Pick a random break and move it to a random time
+    /// between the same two checkpoints
+    fn move_random_break(&mut self, schedule: &mut Schedule) -> DrivingTimeResult<Option<Move>> {
+        let Some((truck, index)) = schedule
+            .truck_breaks
+            .iter()
+            .flat_map(|(truck, breaks)| (0..breaks.len()).map(move |index| (*truck, index)))
+            .choose(&mut self.rng)
+        else {
+            return Ok(self.reject("no_break"));
+        };
+        let breaks = schedule.truck_breaks.get(&truck).unwrap();
+        let old_break = breaks.get(index).unwrap();
+
+        let (prev_checkpoint, next_checkpoint) =
+            schedule.get_checkpoints_around_gap(truck, old_break.time);
+        let gap_start =
+            prev_checkpoint.map_or(self.get_truck_start_time(truck), |c| c.time + c.duration);
+        let gap_end = next_checkpoint.map_or(self.planning_period.get_end_time(), |c| c.time);
+        let Some(new_time) = gap_end
+            .checked_sub(old_break.duration)
+            .and_then(|latest_time| (gap_start..latest_time).choose(&mut self.rng))
+        else {
+            return Ok(self.reject("empty_allowed_interval"));
+        };
+
+        // Keep the breaks sorted
+        let new_break = Break {
+            time: new_time,
+            duration: old_break.duration,
+        };
+        let new_index = breaks
+            .iter()
+            .enumerate()
+            .filter(|(other_index, other_break)| *other_index != index && **other_break < new_break)
+            .count();
+        let mv = Move::MoveBreak {
+            truck,
+            old_index: index,
+            old_break: old_break.clone(),
+            new_index,
+            new_break,
+        };
+        self.try_move(schedule, mv, |generator, schedule| {
+            Ok((!generator.respects_driving_hours(schedule, truck)?).then_some("driving_hours"))
+        })
+    }
+
+    /// Assign a random driver who isn't driving yet to a random truck
+    fn assign_random_driver(&mut self, schedule: &mut Schedule) -> DrivingTimeResult<Option<Move>> {
+        let Some(&driver) = self
+            .data
+            .drivers
+            .keys()
+            .filter(|driver| !schedule.driver_trucks.contains_key(driver))
+            .choose(&mut self.rng)
+        else {
+            return Ok(self.reject("no_unassigned_driver"));
+        };
+        let Some(&truck) = self.data.trucks.iter().choose(&mut self.rng) else {
+            return Ok(self.reject("no_truck"));
+        };
+        if !self.can_drive(driver, truck) {
+            return Ok(self.reject("unqualified_driver"));
+        }
+
+        // A truck can only be driven by one driver at a time
+        if schedule
+            .driver_trucks
+            .iter()
+            .any(|(other_driver, other_truck)| {
+                *other_truck == truck && self.shifts_overlap(driver, *other_driver)
+            })
+        {
+            return Ok(self.reject("overlapping_shifts"));
+        }
+
+        let mv = Move::AssignDriver { driver, truck };
+        mv.apply(schedule);
+        Ok(Some(mv))
+    }
+
+    /// Stop a random driver from driving their truck,
+    /// if the truck's checkpoints can be handled without them
+    fn unassign_random_driver(
+        &mut self,
+        schedule: &mut Schedule,
+    ) -> DrivingTimeResult<Option<Move>> {
+        let Some((&driver, &truck)) = schedule.driver_trucks.iter().choose(&mut self.rng) else {
+            return Ok(self.reject("no_assigned_driver"));
+        };
+
+        let mv = Move::UnassignDriver { driver, truck };
+        self.try_move(schedule, mv, |generator, schedule| {
+            Ok((!generator.respects_driver_roster(schedule, truck)?).then_some("driver_roster"))
+        })
+    }
+
+    /// Remove pickup and dropoff for a piece of cargo.
+    /// Mandatory cargo is never removed, since nothing guarantees
+    /// that it would be re-inserted later
+    fn remove_random_delivery(
+        &mut self,
+        schedule: &mut Schedule,
+    ) -> DrivingTimeResult<Option<Move>> {
+        let Some((&cargo, _truck)) = schedule
+            .scheduled_cargo_truck
+            .iter()
+            .filter(|(cargo, _truck)| !self.data.cargo_booking_info.get(cargo).unwrap().mandatory)
+            // Cargo that has to be delivered before some scheduled cargo has to stay
+            .filter(|(cargo, _truck)| {
+                self.data
+                    .cargo_successors
+                    .get(cargo)
+                    .is_none_or(|successors| {
+                        successors
+                            .iter()
+                            .all(|successor| !schedule.scheduled_cargo.contains(successor))
+                    })
+            })
+            .choose(&mut self.rng)
+        else {
+            return Ok(self.reject("no_removable_delivery"));
+        };
+        let mv = self.delivery_removal(schedule, cargo).unwrap();
+
+        // Not returning the chassis at the dropoff terminal might leave
+        // later pickups there without one
+        self.try_move(schedule, mv, |generator, schedule| {
+            Ok((!generator.has_enough_chassis(schedule)).then_some("chassis"))
+        })
+    }
+
+    /// Given a checkpoint with its new pickup and dropoff and the checkpoints
+    /// around it, finds a random time it can be rescheduled to. Keeps the relative
+    /// order of all checkpoints the same
+    fn find_random_reschedule_time(
+        &mut self,
+        truck: Truck,
+        checkpoint_before: Option<&Checkpoint>,
+        checkpoint: &Checkpoint,
+        checkpoint_after: Option<&Checkpoint>,
+    ) -> DrivingTimeResult<Option<Time>> {
+        let started = self.start_timer();
+        let pickup_restriction_intervals = checkpoint
+            .pickup_cargo
+            .iter()
+            .map(|cargo| self.pickup_times.get(cargo).unwrap())
+            .intersect_all();
+        let dropoff_restriction_intervals = checkpoint
+            .dropoff_cargo
+            .iter()
+            .map(|cargo| self.dropoff_times.get(cargo).unwrap())
+            .intersect_all();
+
+        let Some(driving_restriction_interval) = self.get_transit_time_constraints(
+            truck,
+            checkpoint_before,
+            checkpoint_after,
+            checkpoint.terminal,
+        )?
+        else {
+            self.record_phase("interval_intersection", started);
+            return Ok(None);
+        };
+        let driving_restriction_intervals =
+            IntervalWithDataChain::from_interval(driving_restriction_interval);
+
+        let allowed_intervals = [
+            pickup_restriction_intervals,
+            dropoff_restriction_intervals,
+            driving_restriction_intervals,
+        ]
+        .iter()
+        .intersect_all()
+        .clamp(&self.planning_period);
+        self.record_phase("interval_intersection", started);
+
+        // Pick a time in the allowed intervals uniformly, so that the sub-interval
+        // that is larger (and so offers more flexibility) is more likely to be picked
+        Ok(allowed_intervals.random_time(&mut self.rng))
+    }
+
+    /// Add a random cargo pickup-dropoff pair to two checkpoints.
+    /// If necessary, move checkpoints to allow this to be done
+    fn add_random_delivery(&mut self, schedule: &mut Schedule) -> DrivingTimeResult<Option<Move>> {
+        // Pick a random truck, see what cargo it can deliver based on what terminals
+        // it is visiting
+        let Some((&truck, checkpoints)) = schedule.truck_checkpoints.iter().choose(&mut self.rng)
+        else {
+            return Ok(self.reject("no_truck"));
+        };
+
+        // See what undelivered cargo can be delivered between these terminals
+
+        // TODO: limit the gap between (from, to) as a heuristic: it is unlikely
+        // that a truck will pick up a cargo, drive for a very long time,
+        // then drop it off
+
+        // Unscheduled cargo which can be taken by this truck, with the positions of
+        // the truck's checkpoints at its pickup and dropoff terminals
+        let terminal_positions = schedule.truck_terminal_positions.get(&truck).unwrap();
+        let mut available_cargo = Vec::new();
+        for (start_terminal, start_positions) in terminal_positions.iter() {
+            for (end_terminal, end_positions) in terminal_positions.iter() {
+                // The truck needs to visit the end terminal after the start terminal
+                if end_positions.last() <= start_positions.first() {
+                    continue;
+                }
+
+                // If we found some,
+                if let Some(cargo_collection) = self
+                    .cargo_by_terminals
+                    .get(&(*start_terminal, *end_terminal))
+                {
+                    // Record all cargo that hasn't been scheduled yet
+                    // and that this truck is allowed to take
+                    for cargo in cargo_collection.iter() {
+                        if !schedule.scheduled_cargo.contains(cargo)
+                            && self.can_carry(truck, self.cargo_booking_info.get(cargo).unwrap())
+                        {
+                            available_cargo.push((*cargo, start_positions, end_positions));
+                        }
+                    }
+                }
+            }
+        }
+
+        // Pick random cargo, preferring urgent cargo, and a random pair of checkpoints
+        // to deliver between
+        let Some(&(chosen_cargo, start_positions, end_positions)) = available_cargo
+            .choose_weighted(&mut self.rng, |(cargo, _, _)| {
+                self.data
+                    .cargo_booking_info
+                    .get(cargo)
+                    .unwrap()
+                    .delivery_weight()
+            })
+            .ok()
+        else {
+            return Ok(self.reject("no_candidate_cargo"));
+        };
+        assert!(!schedule.scheduled_cargo.contains(&chosen_cargo));
+        // TODO: if the same start_checkpoint/end_checkpoint appears multiple times,
+        // then the shortest delivery is always optimal, so disregard others.
+        // E.g. if the truck goes A->B->C->A->B, and we want to deliver A->B,
+        // it is always better to drive A->B than A->B->C->A->B
+        // We will want to implement this in the future
+        let (start_checkpoint_index, end_checkpoint_index) = start_positions
+            .iter()
+            .flat_map(|&start| {
+                end_positions
+                    .iter()
+                    .filter(move |&&end| start < end)
+                    .map(move |&end| (start, end))
+            })
+            .choose(&mut self.rng)
+            .unwrap();
+
+        // Find the intervals when these checkpoints can be moved to
+        // Consider restrictions due to being able to pick up all items,
+        // drop off all items and drive to and from checkpoint
+        // TODO: it might make sense to cache this
+
+        // TODO: add an operation that randomly reschedules some checkpoint
+
+        // Create copies of the checkpoints from the pickup to the dropoff and operate on them
+        let old_checkpoints = checkpoints
+            .slice(start_checkpoint_index..=end_checkpoint_index)
+            .to_vec();
+        let mut new_checkpoints = old_checkpoints.clone();
+        let last = new_checkpoints.len() - 1;
+        new_checkpoints[0].pickup_cargo.insert(chosen_cargo);
+        new_checkpoints[last].dropoff_cargo.insert(chosen_cargo);
+
+        // NOTE: reschedule them one-by-one. If we reschedule them at the same time and
+        // the end checkpoint is directly after the start checkpoint,
+        // the end checkpoint might be rescheduled to before the new start
+        // checkpoint time
+        let Some(new_start_checkpoint_time) = self.find_random_reschedule_time(
+            truck,
+            start_checkpoint_index
+                .checked_sub(1)
+                .map(|index| &checkpoints[index]),
+            &new_checkpoints[0],
+            new_checkpoints.get(1),
+        )?
+        else {
+            return Ok(self.reject("empty_allowed_interval"));
+        };
+        new_checkpoints[0].time = new_start_checkpoint_time;
+
+        let Some(new_end_checkpoint_time) = self.find_random_reschedule_time(
+            truck,
+            Some(&new_checkpoints[last - 1]),
+            &new_checkpoints[last],
+            checkpoints.get(end_checkpoint_index + 1),
+        )?
+        else {
+            return Ok(self.reject("empty_allowed_interval"));
+        };
+        new_checkpoints[last].time = new_end_checkpoint_time;
+
+        // Try to modify the weights and sizes
+        let booking_info = self.cargo_booking_info.get(&chosen_cargo).unwrap();
+        for checkpoint in &mut new_checkpoints[..last] {
+            // Immediately fail if capacity constraint is failed in any dimension
+            let Some(available_capacity) = checkpoint
+                .available_capacity
+                .checked_sub(&booking_info.demand)
+            else {
+                return Ok(self.reject("capacity_underflow"));
+            };
+            checkpoint.available_capacity = available_capacity;
+        }
+
+        // The truck also needs to stay within the legal weight limits
+        if !new_checkpoints[..last]
+            .iter()
+            .all(|checkpoint| self.is_legal_weight(truck, checkpoint))
+        {
+            return Ok(self.reject("over_weight_limit"));
+        }
+
+        let mv = Move::AddDelivery {
+            cargo: chosen_cargo,
+            truck,
+            first_index: start_checkpoint_index,
+            old_checkpoints,
+            new_checkpoints,
+        };
+        let changed = start_checkpoint_index..end_checkpoint_index + 1;
+        self.try_move(schedule, mv, |generator, schedule| {
+            let checkpoints = schedule.truck_checkpoints.get(&truck).unwrap();
+            generator.assert_truck_checkpoints_invariant(schedule, truck, changed.clone());
+
+            // Only the checkpoints at `changed` are different, and of those only the
+            // first and last were rescheduled, so only the chassis at their terminals
+            // and the order of the cargo handled at the rescheduled ones can be affected
+            let changed_terminals = checkpoints
+                .slice(changed.clone())
+                .iter()
+                .map(|checkpoint| checkpoint.terminal)
+                .collect::<BTreeSet<_>>();
+            let rescheduled_cargo = [&checkpoints[changed.start], &checkpoints[changed.end - 1]]
+                .into_iter()
+                .flat_map(|checkpoint| {
+                    checkpoint
+                        .pickup_cargo
+                        .iter()
+                        .chain(checkpoint.dropoff_cargo.iter())
+                })
+                .copied()
+                .collect::<BTreeSet<_>>();
+
+            // Rescheduling might have moved a checkpoint into a day which
+            // already has too many, into a break or out of a driver's shift
+            let violation = if generator.exceeds_checkpoint_limit(checkpoints, changed) {
+                Some("checkpoint_limit")
+            } else if !generator.respects_driving_hours(schedule, truck)? {
+                Some("driving_hours")
+            } else if generator.truck_data.get(&truck).unwrap().rear_loading
+                && !is_last_in_first_out(checkpoints)
+            {
+                // Rear-loaded trucks can only unload the cargo loaded last
+                Some("rear_loading")
+            } else if !generator.respects_driver_roster(schedule, truck)? {
+                // The drivers on duty need to be qualified for the new cargo
+                Some("driver_roster")
+            } else if !generator.has_enough_chassis_at(schedule, changed_terminals) {
+                Some("chassis")
+            } else if !generator.cargo_successors.is_empty()
+                && !rescheduled_cargo
+                    .iter()
+                    .all(|cargo| generator.respects_precedence_of(schedule, *cargo))
+            {
+                // Rescheduling checkpoints might have changed the order of dependent deliveries
+                Some("precedence")
+            } else if !generator.cargo_stops.is_empty()
+                && !rescheduled_cargo
+                    .iter()
+                    .all(|cargo| generator.respects_stop_order_of(schedule, *cargo))
+            {
+                // Stops of a booking need to be visited in order by the same truck
+                Some("stop_order")
+            } else {
+                None
+            };
+            Ok(violation)
+        })
+    }
+
+    /// Why `truck` can't deliver `cargo` in `schedule`, as (constraint, reason),
+    /// or None if it can, see `explain_infeasibility`
+    fn explain_truck_infeasibility(
+        &mut self,
+        schedule: &Schedule,
+        truck: Truck,
+        cargo: Cargo,
+    ) -> DrivingTimeResult<Option<(&'static str, String)>> {
+        let booking_info = self.cargo_booking_info.get(&cargo).unwrap().clone();
+        let truck_data = self.truck_data.get(&truck).unwrap().clone();
+
+        if let Some(locked_truck) = booking_info
+            .locked_truck
+            .filter(|locked_truck| *locked_truck != truck)
+        {
+            return Ok(Some((
+                "incompatibility",
+                format!(
+                    "The cargo can only be delivered by truck {:?}",
+                    self.truck_mapper.map(&locked_truck).unwrap()
+                ),
+            )));
+        }
+        if let Some(qualification) = booking_info
+            .required_qualifications
+            .difference(&truck_data.qualifications)
+            .next()
+        {
+            return Ok(Some((
+                "incompatibility",
+                format!(
+                    "The cargo requires qualification {:?}, which the truck doesn't have",
+                    self.qualification_mapper.map(qualification).unwrap()
+                ),
+            )));
+        }
+
+        if !booking_info.demand.fits_within(&truck_data.capacity) {
+            return Ok(Some((
+                "capacity",
+                "The cargo takes up more than the truck can carry".to_string(),
+            )));
+        }
+        let cargo_weight_kg = booking_info.demand.get(CapacityDimension::WEIGHT_KG);
+        if truck_data
+            .max_gross_weight_kg
+            .is_some_and(|max_gross_weight_kg| {
+                truck_data.tare_weight_kg + cargo_weight_kg > max_gross_weight_kg
+            })
+        {
+            return Ok(Some((
+                "capacity",
+                "The truck would exceed its legal gross weight with the cargo".to_string(),
+            )));
+        }
+
+        // The terminals to visit in order with their windows: the pickup,
+        // any other stops of the booking, and the dropoff
+        let mut visits = vec![(
+            booking_info.from,
+            self.pickup_times.get(&cargo).unwrap().clone(),
+        )];
+        if let Some(stops) = self.cargo_stops.get(&cargo) {
+            for stop in &stops[1..stops.len() - 1] {
+                let (stop_cargo, is_pickup) = stop[0];
+                let stop_info = self.cargo_booking_info.get(&stop_cargo).unwrap();
+                visits.push(if is_pickup {
+                    (
+                        stop_info.from,
+                        self.pickup_times.get(&stop_cargo).unwrap().clone(),
+                    )
+                } else {
+                    (
+                        stop_info.to,
+                        self.dropoff_times.get(&stop_cargo).unwrap().clone(),
+                    )
+                });
+            }
+        }
+        visits.push((
+            booking_info.to,
+            self.dropoff_times.get(&cargo).unwrap().clone(),
+        ));
+
+        let availability = Interval::new(
+            self.get_truck_start_time(truck),
+            self.planning_period.get_end_time(),
+            (),
+        );
+        let is_available_for = |times: &IntervalChain| {
+            availability
+                .as_ref()
+                .is_some_and(|availability| !times.clamp(availability).is_empty())
+        };
+        if !visits.iter().all(|(_, times)| is_available_for(times)) {
+            return Ok(Some((
+                "time_window",
+                "The cargo's pickup, dropoff or stop windows are over before the truck is available"
+                    .to_string(),
+            )));
+        }
+
+        // Cargo can only be picked up after its predecessor, e.g. the full container
+        // of an empty container return, is dropped off
+        if let Some(predecessor) = booking_info.predecessor {
+            let predecessor_dropoff_time = self
+                .get_cargo_pickup_dropoff_times(schedule, predecessor)
+                .map_or_else(
+                    || {
+                        let dropoff_times = self.dropoff_times.get(&predecessor).unwrap();
+                        dropoff_times
+                            .get_intervals()
+                            .first()
+                            .map_or(Time::MAX, |interval| interval.get_start_time())
+                    },
+                    |(_, dropoff_time)| dropoff_time,
+                );
+            let pickup_times = Interval::new(
+                predecessor_dropoff_time.saturating_add(1),
+                self.planning_period.get_end_time(),
+                (),
+            )
+            .map_or_else(IntervalChain::new, |after| visits[0].1.clamp(&after));
+            if !is_available_for(&pickup_times) {
+                return Ok(Some((
+                    "precedence",
+                    format!(
+                        "The cargo can't be picked up within its windows after cargo {:?} is dropped off",
+                        self.cargo_mapper.map(&predecessor).unwrap()
+                    ),
+                )));
+            }
+            visits[0].1 = pickup_times;
+        }
+        let route = if visits.len() > 2 {
+            "the pickup terminal, the other stops and then the dropoff terminal"
+        } else {
+            "the pickup and then the dropoff terminal"
+        };
+
+        // Try visiting the terminals right after each other
+        // in each gap between the truck's checkpoints
+        let checkpoints = schedule.truck_checkpoints.get(&truck).unwrap().to_vec();
+        let mut is_full_everywhere = true;
+        for index in 0..=checkpoints.len() {
+            let prev_checkpoint = index.checked_sub(1).map(|index| &checkpoints[index]);
+            let available_capacity = prev_checkpoint.map_or(&truck_data.capacity, |checkpoint| {
+                &checkpoint.available_capacity
+            });
+            if !booking_info.demand.fits_within(available_capacity) {
+                continue;
+            }
+            is_full_everywhere = false;
+
+            // Visiting each terminal as early as possible leaves the most time for the rest
+            let mut prev_checkpoint = prev_checkpoint.cloned();
+            let mut reaches_all = true;
+            for (visit_index, (terminal, times)) in visits.iter().enumerate() {
+                let next_checkpoint = if visit_index + 1 == visits.len() {
+                    checkpoints.get(index)
+                } else {
+                    None
+                };
+                let Some(time) = self.find_earliest_insertion_time(
+                    truck,
+                    prev_checkpoint.as_ref(),
+                    next_checkpoint,
+                    *terminal,
+                    times,
+                )?
+                else {
+                    reaches_all = false;
+                    break;
+                };
+                prev_checkpoint = Some(Checkpoint {
+                    time,
+                    terminal: *terminal,
+                    pickup_cargo: CargoSet::new(),
+                    dropoff_cargo: CargoSet::new(),
+                    available_capacity: available_capacity.clone(),
+                    duration: 0,
+                });
+            }
+            if reaches_all {
+                return Ok(None);
+            }
+        }
+
+        Ok(Some(if is_full_everywhere {
+            (
+                "capacity",
+                "The truck has no room for the cargo between any of its checkpoints".to_string(),
+            )
+        } else if checkpoints.is_empty() {
+            (
+                "driving_time",
+                format!("The truck can't drive to {route} within their windows"),
+            )
+        } else {
+            (
+                "driving_time",
+                format!(
+                    "The truck's other checkpoints leave no time to drive to {route} within their windows"
+                ),
+            )
+        }))
+    }
+
+    /// The earliest time within `times` at which a checkpoint at `terminal` could be
+    /// put between `prev_checkpoint` and `next_checkpoint` of `truck`, if any
+    fn find_earliest_insertion_time(
+        &mut self,
+        truck: Truck,
+        prev_checkpoint: Option<&Checkpoint>,
+        next_checkpoint: Option<&Checkpoint>,
+        terminal: Terminal,
+        times: &IntervalChain,
+    ) -> DrivingTimeResult<Option<Time>> {
+        let Some(transit_interval) =
+            self.get_transit_time_constraints(truck, prev_checkpoint, next_checkpoint, terminal)?
+        else {
+            return Ok(None);
+        };
+        Ok(times
+            .clamp(&transit_interval)
+            .iter()
+            .next()
+            .map(|interval| interval.get_start_time()))
+    }
+
+    /// Number of mandatory pieces of cargo that `schedule` doesn't deliver
+    fn num_missing_mandatory_deliveries(&self, schedule: &Schedule) -> usize {
+        self.cargo_booking_info
+            .iter()
+            .filter(|(cargo, booking_info)| {
+                booking_info.mandatory && !schedule.scheduled_cargo.contains(cargo)
+            })
+            .count()
+    }
+}
+
+/// Creates a capacity from its weight, TEU and other dimensions,
+/// using `default` for the dimensions not listed
+fn make_capacity(
+    capacity_dimension_mapper: &CounterMapper<String>,
+    weight_kg: usize,
+    teu: usize,
+    other_dimensions: &BTreeMap<PyCapacityDimensionID, usize>,
+    default: usize,
+) -> PyResult<Capacity> {
+    let mut amounts = BTreeMap::new();
+    amounts.insert(CapacityDimension::WEIGHT_KG, weight_kg);
+    amounts.insert(CapacityDimension::TEU, teu);
+    for (name, amount) in other_dimensions.iter() {
+        let dimension = capacity_dimension_mapper
+            .reverse_map(name)
+            .ok_or_else(|| PyTypeError::new_err(format!("Unknown capacity dimension {name:?}")))?;
+        amounts.insert(dimension, *amount);
+    }
+    Ok(Capacity::from_map(
+        &amounts,
+        capacity_dimension_mapper.len(),
+        default,
+    ))
+}
+
+/// Samples from the standard normal distribution, using the Box-Muller transform
+fn sample_standard_normal(rng: &mut Xoshiro256PlusPlus) -> f64 {
+    // In (0, 1], to avoid taking the logarithm of 0
+    let radius_sample = 1.0 - rng.random::<f64>();
+    let angle_sample = rng.random::<f64>();
+    (-2.0 * radius_sample.ln()).sqrt() * (2.0 * std::f64::consts::PI * angle_sample).cos()
+}
+
+/// Checks the parameters of simulated annealing, see `ScheduleGenerator.optimize`
+fn annealing_options(
+    num_iterations: usize,
+    initial_temperature: f64,
+    final_temperature: f64,
+    num_tries_per_action: usize,
+    restart_probability: f64,
+    progress_interval: usize,
+) -> PyResult<AnnealingOptions> {
+    if !(initial_temperature > 0.0 && final_temperature > 0.0) {
+        return Err(PyTypeError::new_err(format!(
+            "Temperatures need to be positive, got {initial_temperature} and {final_temperature}"
+        )));
+    }
+    // Progress is also where cancellation is checked
+    if progress_interval == 0 {
+        return Err(PyTypeError::new_err(
+            "progress_interval needs to be positive",
+        ));
+    }
+    Ok(AnnealingOptions {
+        num_iterations,
+        initial_temperature,
+        final_temperature,
+        num_tries_per_action,
+        restart_probability,
+        progress_interval,
+    })
+}
+
+/// Creates an interval [start_time, end_time] and returns an error
+/// if invalid
+fn interval_or_error(start_time: Time, end_time: Time) -> PyResult<Interval> {
+    if let Some(interval) = Interval::new(start_time, end_time, ()) {
+        Ok(interval)
+    } else {
+        Err(InvalidIntervalError::new_err(format!(
+            "Invalid interval starting at {start_time}, ending at {end_time}"
+        )))
+    }
+}
+
+#[pymethods]
+impl ScheduleGenerator {
+    #[new]
+    /// Create a new schedule generator
+    /// terminal_data is a dict sending a terminal id to (opening_time, closing_time)
+    /// truck_data is a dict sending truck id to its `PyTruckData`, and booking_data is a list
+    /// of `PyBooking`s. All three can also be given as a pandas DataFrame or pyarrow Table
+    /// whose columns are named after the fields ("opening_time" and "closing_time" for
+    /// terminals), with the terminal and truck ids in an "id" column, see `PyRows`
+    /// infeasible_bookings says what to do with bookings that can't be done within the planning
+    /// period: "raise" an `InfeasibleBookingError`, "skip" them with a warning, or "relax" their
+    /// time windows, keeping them at a penalty (see `get_relaxed_cargo_ids`). With "raise",
+    /// bookings whose windows are outside the planning period are still skipped, since they
+    /// don't matter for it. Bookings that are left out are listed by `rejected_bookings`.
+    /// Setting relax_time_windows is the same as "relax", and otherwise defaults to "skip"
+    /// calendars is a dict sending a region id to the days on which the region is closed,
+    /// and terminal_regions sends a terminal id to the region whose calendar it follows.
+    /// To give a terminal its own calendar, put it in a region of its own
+    /// terminal_locations optionally sends a terminal id to its (latitude, longitude)
+    /// terminal_timezones optionally sends a terminal id to its timezone, e.g. "Europe/Berlin",
+    /// in which its calendar's days and its opening hours are taken. Defaults to UTC
+    /// terminal_opening_hours optionally sends a terminal id to the (opening, closing) local
+    /// time of day between which it is open each day, within its opening and closing times
+    /// driving_time_providers optionally lists the sources asked in order for driving times,
+    /// out of "time_dependent" (see `set_driving_time_profile`), "static" (see
+    /// `set_driving_times`), "callback" (see `set_driving_time_provider`), "shortest_path"
+    /// (routes through terminals with known driving times) and "haversine" (estimates from
+    /// terminal_locations). Defaults to all of them, in this order
+    /// Invalid inputs raise an error naming the terminal, truck or booking at fault
+    #[pyo3(signature = (
+        terminal_data,
+        truck_data,
+        booking_data,
+        planning_period,
+        relax_time_windows=false,
+        calendars=BTreeMap::new(),
+        terminal_regions=BTreeMap::new(),
+        terminal_locations=BTreeMap::new(),
+        terminal_timezones=BTreeMap::new(),
+        terminal_opening_hours=BTreeMap::new(),
+        driving_time_providers=None,
+        infeasible_bookings=None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        terminal_data: PyKeyedRows<PyTerminalTimes>,
+        truck_data: PyKeyedRows<PyTruckData>,
+        booking_data: PyRows<PyBooking>,
+        planning_period: (PyTime, PyTime),
+        relax_time_windows: bool,
+        calendars: BTreeMap<PyRegionID, PyCalendar>,
+        terminal_regions: BTreeMap<PyTerminalID, PyRegionID>,
+        terminal_locations: BTreeMap<PyTerminalID, (f64, f64)>,
+        terminal_timezones: BTreeMap<PyTerminalID, String>,
+        terminal_opening_hours: BTreeMap<PyTerminalID, (NaiveTime, NaiveTime)>,
+        driving_time_providers: Option<Vec<String>>,
+        infeasible_bookings: Option<String>,
+    ) -> PyResult<Self> {
+        let (truck_data, booking_data) = (truck_data.0, booking_data.0);
+        let terminal_data: BTreeMap<PyTerminalID, (PyTime, PyTime)> = terminal_data
+            .0
+            .into_iter()
+            .map(
+                |(terminal_id, PyTerminalTimes(opening_time, closing_time))| {
+                    (terminal_id, (opening_time, closing_time))
+                },
+            )
+            .collect();
+
+        let infeasible_booking_policy = match &infeasible_bookings {
+            Some(name) => InfeasibleBookingPolicy::from_name(name).ok_or_else(|| {
+                PyTypeError::new_err(format!(
+                    "Unknown policy {name:?} for infeasible bookings, expected \"raise\", \"skip\" or \"relax\""
+                ))
+            })?,
+            None if relax_time_windows => InfeasibleBookingPolicy::Relax,
+            None => InfeasibleBookingPolicy::Skip,
+        };
+
+        // Bookings with the same cargo id would otherwise be merged
+        let mut cargo_ids = BTreeSet::new();
+        let duplicate_cargo_ids: BTreeSet<&PyCargoID> = booking_data
+            .iter()
+            .map(|booking| &booking.cargo)
+            .filter(|cargo_id| !cargo_ids.insert(*cargo_id))
+            .collect();
+        if !duplicate_cargo_ids.is_empty() {
+            return Err(PyTypeError::new_err(format!(
+                "There is more than one booking for cargo {duplicate_cargo_ids:?}"
+            )));
+        }
+
+        // We want to map between the internally-used
+        // integer ids and the externally-used String ids.
+        // This is done because it is easier to deal with
+        // integers and ownership, while Strings would make
+        // maintenance a bit more tricky
+        let mut terminal_mapper = CounterMapper::new();
+        let cargo_mapper = CounterMapper::new();
+        let truck_mapper = CounterMapper::new();
+        let qualification_mapper = CounterMapper::new();
+        let vehicle_class_mapper = CounterMapper::new();
+
+        // Find all the capacity dimensions, making sure that the
+        // built-in ones get their fixed ids
+        let mut capacity_dimension_mapper = CounterMapper::new();
+        for name in CapacityDimension::BUILTIN_NAMES
+            .iter()
+            .map(|name| name.to_string())
+            .chain(
+                truck_data
+                    .values()
+                    .flat_map(|data| data.capacities.keys().cloned()),
+            )
+            .chain(
+                booking_data
+                    .iter()
+                    .flat_map(|booking| booking.demands.keys().cloned()),
+            )
+        {
+            let _: CapacityDimension = capacity_dimension_mapper.add_or_find(&name);
+        }
+
+        let inputs = GeneratorInputs {
+            terminal_data: terminal_data.clone(),
+            // Trucks and bookings are added when registering them
+            truck_data: BTreeMap::new(),
+            booking_data: vec![],
+            planning_period,
+            relax_time_windows,
+            calendars: calendars.clone(),
+            terminal_regions: terminal_regions.clone(),
+            terminal_locations: terminal_locations.clone(),
+            terminal_timezones: terminal_timezones.clone(),
+            terminal_opening_hours: terminal_opening_hours.clone(),
+            driving_time_providers: driving_time_providers.clone(),
+            infeasible_bookings,
+        };
+
+        let planning_period = interval_or_error(planning_period.0.into(), planning_period.1.into())
+            .map_err(|_| {
+                InvalidIntervalError::new_err(format!(
+                    "Planning period starts at {} and ends at {}, which is not after it starts",
+                    planning_period.0 .0, planning_period.1 .0
+                ))
+            })?;
+
+        let calendars: BTreeMap<PyRegionID, Calendar> = calendars
+            .iter()
+            .map(|(region_id, calendar)| {
+                (
+                    region_id.clone(),
+                    Calendar::new(
+                        &calendar.closed_weekdays,
+                        &calendar
+                            .holidays
+                            .iter()
+                            .map(|date| date.0)
+                            .collect::<Vec<_>>(),
+                    ),
+                )
+            })
+            .collect();
+
+        // Calculate terminal_open_intervals
+        let mut terminal_open_intervals = BTreeMap::new();
+        for (terminal_id, (opening_time, closing_time)) in terminal_data.iter() {
+            let terminal: Terminal = terminal_mapper.add_or_find(terminal_id);
+            // If it is a valid interval, create
+            let interval = interval_or_error(opening_time.0, closing_time.0).map_err(|_| {
+                InvalidIntervalError::new_err(format!(
+                    "Terminal {terminal_id:?} opens at {} and closes at {}, which is not after it opens",
+                    opening_time.0, closing_time.0
+                ))
+            })?;
+            // TODO: make opening and closing times repeat day on day
+            // TODO: if you do that, be sure to set the starting point to be sane (and
+            // not e.g. 0 unix time) to avoid considering really old time intervals
+            let mut intervals = IntervalChain::from_interval(interval.clone());
+            // Opening hours and calendars are expanded day by day, so only within
+            // the planning period, outside of which terminals are never used
+            let expand = |pattern: RecurringPattern| {
+                Interval::new(
+                    interval
+                        .get_start_time()
+                        .max(planning_period.get_start_time()),
+                    interval.get_end_time().min(planning_period.get_end_time()),
+                    (),
+                )
+                .map_or_else(IntervalChain::new, |horizon| pattern.expand(&horizon))
+            };
+
+            let timezone = match terminal_timezones.get(terminal_id) {
+                Some(timezone_name) => Some(timezone_name.parse::<Tz>().map_err(|_| {
+                    PyTypeError::new_err(format!(
+                        "Terminal {terminal_id:?} has unknown timezone {timezone_name:?}"
+                    ))
+                })?),
+                None => None,
+            };
+            // Keep only the daily opening hours
+            if let Some((opening_time, closing_time)) = terminal_opening_hours.get(terminal_id) {
+                intervals = [
+                    intervals,
+                    expand(RecurringPattern::daily(
+                        timezone.unwrap_or(Tz::UTC),
+                        *opening_time,
+                        *closing_time,
+                    )),
+                ]
+                .iter()
+                .intersect_all();
+            }
+            // Remove the days on which the terminal is closed
+            if let Some(region_id) = terminal_regions.get(terminal_id) {
+                let calendar = calendars.get(region_id).ok_or_else(|| {
+                    PyTypeError::new_err(format!(
+                        "Terminal {terminal_id:?} is in region {region_id:?}, which has no calendar"
+                    ))
+                })?;
+                intervals = match timezone {
+                    Some(timezone) => [intervals, expand(calendar.to_pattern(timezone))]
+                        .iter()
+                        .intersect_all(),
+                    None => calendar.restrict(&intervals),
+                };
+            }
+            terminal_open_intervals.insert(terminal, intervals);
+        }
+        for terminal_id in terminal_timezones
+            .keys()
+            .chain(terminal_opening_hours.keys())
+        {
+            if !terminal_data.contains_key(terminal_id) {
+                return Err(UnknownTerminalError::new_err(format!(
+                    "Unknown terminal {terminal_id:?}"
+                )));
+            }
+        }
+
+        let mut locations = BTreeMap::new();
+        for (terminal_id, (latitude, longitude)) in terminal_locations.iter() {
+            let terminal: Terminal = terminal_mapper.reverse_map(terminal_id).ok_or_else(|| {
+                UnknownTerminalError::new_err(format!("Unknown terminal {terminal_id:?}"))
+            })?;
+            let location = Location::new(*latitude, *longitude).ok_or_else(|| {
+                PyTypeError::new_err(format!(
+                    "Terminal {terminal_id:?} has invalid location ({latitude}, {longitude})"
+                ))
+            })?;
+            locations.insert(terminal, location);
+        }
+
+        let sources = match driving_time_providers {
+            Some(names) => names
+                .iter()
+                .map(|name| {
+                    DrivingTimeSource::from_name(name).ok_or_else(|| {
+                        PyTypeError::new_err(format!("Unknown driving time provider {name:?}"))
+                    })
+                })
+                .collect::<PyResult<Vec<_>>>()?,
+            None => DrivingTimeSource::DEFAULT_ORDER.to_vec(),
+        };
+        let providers = sources
+            .into_iter()
+            .map(|source| {
+                let provider: Option<Box<dyn DrivingTimeProvider>> = match source {
+                    DrivingTimeSource::Static | DrivingTimeSource::TimeDependent => None,
+                    DrivingTimeSource::Callback => Some(Box::new(CallbackProvider::new(None))),
+                    DrivingTimeSource::ShortestPath => Some(Box::new(ShortestPathProvider)),
+                    DrivingTimeSource::Haversine => Some(Box::new(HaversineProvider::new(
+                        locations.clone(),
+                        DEFAULT_ESTIMATE_SPEED_KMH,
+                    ))),
+                };
+                (source, provider)
+            })
+            .collect();
+        let terminal_ids = terminal_mapper
+            .iter()
+            .map(|(terminal, terminal_id)| (terminal, terminal_id.clone()))
+            .collect();
+        let driving_times_cache = DrivingTimesCache::new(providers, terminal_ids);
+
+        let data = GeneratorData {
+            inputs,
+            driving_times_cache,
+            cargo_by_terminals: BTreeMap::new(),
+            pickup_times: IdMap::new(),
+            dropoff_times: IdMap::new(),
+            cargo_booking_info: IdMap::new(),
+            cargo_successors: IdMap::new(),
+            cargo_stops: BTreeMap::new(),
+            stop_main_cargo: IdMap::new(),
+            booking_cargo: BTreeMap::new(),
+            terminals: BTreeSet::new(),
+            terminal_open_intervals,
+            terminal_locations: locations,
+            route_geometries: BTreeMap::new(),
+            trucks: BTreeSet::new(),
+            truck_data: IdMap::new(),
+            trucks_with_locked_cargo: BTreeSet::new(),
+            planning_period,
+            infeasible_booking_policy,
+            rejected_bookings: BTreeMap::new(),
+            max_checkpoints_per_day: None,
+            driving_hour_rules: None,
+            calendars,
+            buffer_time: 0,
+            terminal_buffer_times: BTreeMap::new(),
+            drivers: BTreeMap::new(),
+            chassis_pools: BTreeMap::new(),
+            time_formats: TimeFormats::default(),
+            terminal_mapper,
+            cargo_mapper,
+            truck_mapper,
+            qualification_mapper,
+            vehicle_class_mapper,
+            capacity_dimension_mapper,
+            driver_mapper: CounterMapper::new(),
+        };
+        let mut out = Self {
+            data: Arc::new(data),
+            rng: Xoshiro256PlusPlus::seed_from_u64(0),
+            move_stats: None,
+            rejection_reason: None,
+            debug_checks: cfg!(debug_assertions),
+            id_names: RwLock::new(Arc::new(IdNames {
+                trucks: vec![],
+                terminals: vec![],
+                cargo: vec![],
+                capacity_dimensions: vec![],
+                truck_capacities: vec![],
+                time_formats: TimeFormats::default(),
+            })),
+        };
+
+        for (truck_id, data) in truck_data.iter() {
+            out.register_truck(truck_id, data)?;
+        }
+        for booking in booking_data.iter() {
+            out.register_booking(booking)?;
+        }
+
+        Ok(out)
+    }
+
+    /// Creates a generator from JSON saved by `to_json`. Besides the arguments of
+    /// the constructor, it can contain `driving_times`, `vehicle_class_driving_times`,
+    /// `driving_time_spreads` (all as lists of [from, to, driving_time]),
+    /// `driving_time_profiles` (as a list of [from, to, profile]), `traffic_factors`
+    /// (as a list of [from, to, factor, [start, end]]) and `route_geometries`
+    /// (as a list of [from, to, [[latitude, longitude], ...]])
+    #[staticmethod]
+    pub fn from_json(json: &str) -> PyResult<Self> {
+        let data: GeneratorJson = serde_json::from_str(json)
+            .map_err(|err| PyTypeError::new_err(format!("Invalid generator JSON: {err}")))?;
+        let inputs = data.inputs;
+        let mut out = Self::new(
+            PyKeyedRows(
+                inputs
+                    .terminal_data
+                    .into_iter()
+                    .map(|(terminal_id, times)| (terminal_id, times.into()))
+                    .collect(),
+            ),
+            PyKeyedRows(inputs.truck_data),
+            PyRows(inputs.booking_data),
+            inputs.planning_period,
+            inputs.relax_time_windows,
+            inputs.calendars,
+            inputs.terminal_regions,
+            inputs.terminal_locations,
+            inputs.terminal_timezones,
+            inputs.terminal_opening_hours,
+            inputs.driving_time_providers,
+            inputs.infeasible_bookings,
+        )?;
+
+        let to_map = |driving_times: PyDrivingTimes| {
+            driving_times
+                .into_iter()
+                .map(|(from_id, to_id, driving_time)| ((from_id, to_id), driving_time.into()))
+                .collect()
+        };
+        if !data.driving_times.is_empty() {
+            out.update_driving_times(to_map(data.driving_times), None)?;
+        }
+        for (vehicle_class_id, driving_times) in data.vehicle_class_driving_times {
+            out.update_driving_times(to_map(driving_times), Some(vehicle_class_id))?;
+        }
+        for (from_id, to_id, profile) in data.driving_time_profiles {
+            let profile = profile
+                .into_iter()
+                .map(|(bucket_start, driving_time)| (bucket_start.into(), driving_time.into()))
+                .collect();
+            out.set_driving_time_profile(from_id, to_id, profile)?;
+        }
+        for (from_id, to_id, factor, (start, end)) in data.traffic_factors {
+            out.apply_traffic_factor(from_id, to_id, factor, (start.into(), end.into()), None)?;
+        }
+        out.set_driving_time_spreads(
+            data.driving_time_spreads
+                .into_iter()
+                .map(|(from_id, to_id, spread)| ((from_id, to_id), spread.into()))
+                .collect(),
+        )?;
+        for (from_id, to_id, geometry) in data.route_geometries {
+            out.set_route_geometry(from_id, to_id, geometry)?;
+        }
+        Ok(out)
+    }
+
+    /// Saves the terminals, trucks, bookings, planning period, driving times with their
+    /// traffic factors and spreads, and route geometries as JSON, to be loaded with
+    /// `from_json`. Other settings, such as the driver roster or driving hour rules,
+    /// are not saved
+    pub fn to_json(&self) -> PyResult<String> {
+        let from_map = |driving_times: &BTreeMap<(Terminal, Terminal), NonNegativeTimeDelta>| {
+            driving_times
+                .iter()
+                .map(|((from, to), driving_time)| {
+                    (
+                        self.terminal_mapper.map(from).unwrap(),
+                        self.terminal_mapper.map(to).unwrap(),
+                        *driving_time,
+                    )
+                })
+                .collect()
+        };
+        let data = GeneratorJson {
+            inputs: self.inputs.clone(),
+            driving_times: from_map(
+                self.driving_times_cache
+                    .get_static_driving_times(None)
+                    .unwrap(),
+            ),
+            vehicle_class_driving_times: self
+                .driving_times_cache
+                .get_vehicle_classes()
+                .map(|vehicle_class| {
+                    (
+                        self.vehicle_class_mapper.map(&vehicle_class).unwrap(),
+                        from_map(
+                            self.driving_times_cache
+                                .get_static_driving_times(Some(vehicle_class))
+                                .unwrap(),
+                        ),
+                    )
+                })
+                .collect(),
+            driving_time_profiles: self
+                .driving_times_cache
+                .get_profiles()
+                .iter()
+                .map(|((from, to), profile)| {
+                    (
+                        self.terminal_mapper.map(from).unwrap(),
+                        self.terminal_mapper.map(to).unwrap(),
+                        profile.clone(),
+                    )
+                })
+                .collect(),
+            traffic_factors: self
+                .driving_times_cache
+                .get_traffic_factors()
+                .iter()
+                .flat_map(|((from, to), factors)| {
+                    factors.iter().map(|(interval, factor)| {
+                        (
+                            self.terminal_mapper.map(from).unwrap(),
+                            self.terminal_mapper.map(to).unwrap(),
+                            *factor,
+                            (interval.get_start_time(), interval.get_end_time()),
+                        )
+                    })
+                })
+                .collect(),
+            driving_time_spreads: from_map(self.driving_times_cache.get_spreads()),
+            route_geometries: self
+                .route_geometries
+                .iter()
+                .map(|((from, to), points)| {
+                    (
+                        self.terminal_mapper.map(from).unwrap(),
+                        self.terminal_mapper.map(to).unwrap(),
+                        points
+                            .iter()
+                            .map(|location| (location.latitude, location.longitude))
+                            .collect(),
+                    )
+                })
+                .collect(),
+        };
+        serde_json::to_string(&data)
+            .map_err(|err| PyTypeError::new_err(format!("Can't save generator as JSON: {err}")))
+    }
+
+    /// Rebuilds a schedule from the list of tuples
+    /// (truck, time, terminal, cargo, was_picked_up) given by `Schedule.to_list_of_tuples`,
+    /// e.g. to start optimising from a plan made by hand.
+    /// With driving-hour rules or a driver roster, the plan also needs the breaks
+    /// as given by `Schedule.breaks_to_list_of_tuples`, and the drivers of the trucks
+    /// as given by `Schedule.get_driver_assignments`. Checkpoints get no duration,
+    /// like the ones made while optimising.
+    /// Raises an error if the plan is not feasible
+    #[pyo3(signature = (plan, breaks=vec![], driver_assignments=BTreeMap::new()))]
+    pub fn schedule_from_plan(
+        &self,
+        plan: Vec<(PyTruckID, PyTime, PyTerminalID, PyCargoID, bool)>,
+        breaks: Vec<(PyTruckID, PyTime, PyTimeDelta)>,
+        driver_assignments: BTreeMap<PyDriverID, PyTruckID>,
+    ) -> PyResult<Schedule> {
+        let mut truck_plans: BTreeMap<PyTruckID, Vec<_>> = BTreeMap::new();
+        for (truck_id, time, terminal_id, cargo_id, was_picked_up) in plan {
+            truck_plans.entry(truck_id).or_default().push((
+                time,
+                terminal_id,
+                cargo_id,
+                was_picked_up,
+            ));
+        }
+
+        let mut truck_checkpoints = BTreeMap::new();
+        for (truck_id, mut truck_plan) in truck_plans {
+            // Stable sort, so that cargo handled at the same time stays in order
+            truck_plan.sort_by_key(|(time, _, _, _)| *time);
+
+            let mut loaded_cargo = BTreeSet::new();
+            let mut checkpoints: Vec<CheckpointJson> = vec![];
+            for (PyTime(time), terminal_id, cargo_id, was_picked_up) in truck_plan {
+                if was_picked_up {
+                    loaded_cargo.insert(cargo_id.clone());
+                } else if !loaded_cargo.remove(&cargo_id) {
+                    return Err(InfeasibleScheduleError::new_err(format!(
+                        "Truck {truck_id:?} drops off cargo {cargo_id:?} at time {time} without having picked it up"
+                    )));
+                }
+
+                let checkpoint = match checkpoints.last_mut() {
+                    Some(last) if last.time == time && last.terminal == terminal_id => last,
+                    Some(last) if last.time == time => {
+                        return Err(InfeasibleScheduleError::new_err(format!(
+                            "Truck {truck_id:?} is at both terminal {:?} and {terminal_id:?} at time {time}",
+                            last.terminal
+                        )));
+                    }
+                    _ => {
+                        checkpoints.push(CheckpointJson {
+                            terminal: terminal_id,
+                            time,
+                            duration: 0,
+                            pickup_cargo: vec![],
+                            dropoff_cargo: vec![],
+                        });
+                        checkpoints.last_mut().unwrap()
+                    }
+                };
+                if was_picked_up {
+                    checkpoint.pickup_cargo.push(cargo_id);
+                } else {
+                    checkpoint.dropoff_cargo.push(cargo_id);
+                }
+            }
+
+            if let Some(cargo_id) = loaded_cargo.first() {
+                return Err(InfeasibleScheduleError::new_err(format!(
+                    "Truck {truck_id:?} never drops off cargo {cargo_id:?}"
+                )));
+            }
+            truck_checkpoints.insert(truck_id, checkpoints);
+        }
+
+        let mut truck_breaks: BTreeMap<PyTruckID, Vec<_>> = BTreeMap::new();
+        for (truck_id, PyTime(time), PyTimeDelta(duration)) in breaks {
+            truck_breaks
+                .entry(truck_id)
+                .or_default()
+                .push((time, duration));
+        }
+        for breaks in truck_breaks.values_mut() {
+            breaks.sort();
+        }
+
+        let out = self.schedule_from_json(ScheduleJson {
+            truck_checkpoints,
+            truck_breaks,
+            driver_trucks: driver_assignments,
+            truck_driving_times: BTreeMap::new(),
+        })?;
+
+        let violations = self
+            .find_violations(&out)
+            .map_err(|missing| self.driving_time_error(missing))?;
+        if !violations.is_empty() {
+            return Err(InfeasibleScheduleError::new_err(format!(
+                "Plan is not feasible: {}",
+                violations.join("; ")
+            )));
+        }
+        Ok(out)
+    }
+
+    /// Adds the truck `truck_id` to the fleet, e.g. when a truck is rented mid-horizon.
+    /// Returns `schedule` with the new truck not doing anything yet.
+    /// Driving times need to be set for its starting terminal if it is new
+    pub fn add_truck(
+        &mut self,
+        truck_id: PyTruckID,
+        data: PyTruckData,
+        schedule: &Schedule,
+    ) -> PyResult<Schedule> {
+        if self
+            .truck_mapper
+            .reverse_map(&truck_id)
+            .is_some_and(|truck: Truck| self.trucks.contains(&truck))
+        {
+            return Err(PyTypeError::new_err(format!(
+                "There is already a truck {truck_id:?}"
+            )));
+        }
+        self.register_truck(&truck_id, &data)?;
+
+        let truck: Truck = self.truck_mapper.reverse_map(&truck_id).unwrap();
+        let mut out = schedule.clone();
+        out.truck_checkpoints.insert(truck, vec![]);
+        out.index_checkpoints(truck);
+        out.truck_driving_times.insert(truck, 0);
+        out.truck_breaks.insert(truck, vec![]);
+        self.refresh_id_names(&mut out);
+        Ok(out)
+    }
+
+    /// Removes the truck `truck_id` from the fleet, e.g. when it breaks down.
+    /// Returns `schedule` without the truck, with the cargo it delivered unscheduled,
+    /// along with cargo on other trucks that depended on it, e.g. empty container returns.
+    /// Cargo locked to this truck can no longer be scheduled. Schedules made before
+    /// can't be used with the generator anymore
+    pub fn remove_truck(&mut self, truck_id: PyTruckID, schedule: &Schedule) -> PyResult<Schedule> {
+        self.check_trucks(schedule)?;
+        let truck: Truck = self
+            .truck_mapper
+            .reverse_map(&truck_id)
+            .filter(|truck| self.trucks.contains(truck))
+            .ok_or_else(|| UnknownIdError::new_err(format!("Unknown truck {truck_id:?}")))?;
+
+        let mut out = schedule.clone();
+        out.truck_checkpoints.remove(&truck);
+        out.index_checkpoints(truck);
+        out.truck_driving_times.remove(&truck);
+        out.truck_breaks.remove(&truck);
+        out.scheduled_cargo_truck.retain(|cargo, cargo_truck| {
+            let keep = *cargo_truck != truck;
+            if !keep {
+                out.scheduled_cargo.remove(cargo);
+            }
+            keep
+        });
+        out.driver_trucks
+            .retain(|_driver, driver_truck| *driver_truck != truck);
+        self.unschedule_dependent_cargo(&mut out);
+
+        let data = self.data_mut();
+        data.trucks.remove(&truck);
+        data.truck_data.remove(&truck);
+        data.trucks_with_locked_cargo.remove(&truck);
+        data.inputs.truck_data.remove(&truck_id);
+        Ok(out)
+    }
+
+    /// Adds a booking after the generator has been created, keeping the driving times
+    /// and existing schedules valid. Returns whether the booking can be scheduled at all.
+    /// Driving times need to be set for any terminals this adds to `get_terminal_ids`
+    pub fn add_booking(&mut self, booking: PyBooking) -> PyResult<bool> {
+        if self.booking_cargo.contains_key(&booking.cargo) {
+            return Err(PyTypeError::new_err(format!(
+                "There is already a booking for cargo {:?}",
+                booking.cargo
+            )));
+        }
+        self.register_booking(&booking)?;
+        Ok(self.booking_cargo.contains_key(&booking.cargo))
+    }
+
+    /// Cancels the booking for cargo `cargo_id`, so that it is no longer scheduled.
+    /// Returns `schedule` with its deliveries removed
+    pub fn cancel_booking(
+        &mut self,
+        cargo_id: PyCargoID,
+        schedule: &Schedule,
+    ) -> PyResult<Schedule> {
+        self.check_trucks(schedule)?;
+        let all_cargo = self
+            .data_mut()
+            .booking_cargo
+            .remove(&cargo_id)
+            .ok_or_else(|| {
+                UnknownIdError::new_err(format!("No schedulable booking for cargo {cargo_id:?}"))
+            })?;
+        let mut out = schedule.clone();
+        for cargo in all_cargo.iter() {
+            self.remove_delivery(&mut out, *cargo);
+        }
+        for cargo in all_cargo {
+            self.forget_cargo(cargo);
+        }
+        self.data_mut()
+            .inputs
+            .booking_data
+            .retain(|booking| booking.cargo != cargo_id);
+        Ok(out)
+    }
+
+    /// Removes the booking for cargo `cargo_id`, like `cancel_booking`, and also removes
+    /// the checkpoints of `schedule` that were only there for its deliveries,
+    /// where that doesn't break other constraints
+    pub fn remove_booking(
+        &mut self,
+        cargo_id: PyCargoID,
+        schedule: &Schedule,
+    ) -> PyResult<Schedule> {
+        self.check_trucks(schedule)?;
+        let all_cargo = self
+            .booking_cargo
+            .get(&cargo_id)
+            .cloned()
+            .unwrap_or_default();
+        let mut out = self.cancel_booking(cargo_id, schedule)?;
+
+        for (truck, checkpoints) in schedule.truck_checkpoints.iter() {
+            // Walk backwards, so that removing a checkpoint doesn't shift the ones to visit
+            for index in (0..checkpoints.len()).rev() {
+                let checkpoint = &checkpoints[index];
+                let served_booking = checkpoint
+                    .pickup_cargo
+                    .iter()
+                    .chain(checkpoint.dropoff_cargo.iter())
+                    .any(|cargo| all_cargo.contains(cargo));
+                let new_checkpoint = &out.truck_checkpoints.get(truck).unwrap()[index];
+                if !served_booking
+                    || !new_checkpoint.pickup_cargo.is_empty()
+                    || !new_checkpoint.dropoff_cargo.is_empty()
+                {
+                    continue;
+                }
+
+                let mut candidate = out.clone();
+                candidate.remove_checkpoint(*truck, index);
+                let new_checkpoints = candidate.truck_checkpoints.get(truck).unwrap();
+                let prev_terminal = index.checked_sub(1).map_or(
+                    self.truck_data.get(truck).unwrap().starting_terminal,
+                    |prev| new_checkpoints[prev].terminal,
+                );
+                let next_terminal = new_checkpoints.get(index).map(|next| next.terminal);
+
+                // Keep the checkpoint if removing it leaves 2 consecutive checkpoints
+                // with the same terminal or breaks driving rules
+                if Some(prev_terminal) == next_terminal
+                    || !self
+                        .respects_driving_hours(&candidate, *truck)
+                        .map_err(|missing| self.driving_time_error(missing))?
+                    || !self
+                        .respects_driver_roster(&candidate, *truck)
+                        .map_err(|missing| self.driving_time_error(missing))?
+                {
+                    continue;
+                }
+                out = candidate;
+            }
+
+            let driving_time = self
+                .get_route_driving_time(&out, *truck)
+                .map_err(|missing| self.driving_time_error(missing))?;
+            out.truck_driving_times.insert(*truck, driving_time);
+        }
+        Ok(out)
+    }
+
+    /// Replaces the booking for cargo `booking.cargo` with `booking`.
+    /// Returns `schedule` with the deliveries of the old booking removed,
+    /// so that the amended booking can be scheduled again.
+    /// If `booking` is invalid, the old booking is kept
+    pub fn amend_booking(&mut self, booking: PyBooking, schedule: &Schedule) -> PyResult<Schedule> {
+        booking.validate()?;
+        let old_booking = self
+            .inputs
+            .booking_data
+            .iter()
+            .find(|old_booking| old_booking.cargo == booking.cargo)
+            .cloned();
+        let out = self.cancel_booking(booking.cargo.clone(), schedule)?;
+        if let Err(err) = self.register_booking(&booking) {
+            // The old booking gets its cargo ids back, so `schedule` stays valid
+            if let Some(old_booking) = old_booking {
+                self.register_booking(&old_booking)?;
+            }
+            return Err(err);
+        }
+        Ok(out)
+    }
+
+    /// Creates an empty schedule
+    pub fn empty_schedule(&self) -> Schedule {
+        Schedule {
+            // Create empty checkpoints for each truck
+            truck_checkpoints: self.trucks.iter().map(|truck| (*truck, vec![])).collect(),
+            truck_terminal_positions: self
+                .trucks
+                .iter()
+                .map(|truck| (*truck, BTreeMap::new()))
+                .collect(),
+            num_checkpoints: 0,
+            truck_empty_positions: self.trucks.iter().map(|truck| (*truck, vec![])).collect(),
+            num_empty_checkpoints: 0,
+            scheduled_cargo_truck: IdMap::new(),
+            scheduled_cargo: IdSet::with_capacity(self.cargo_mapper.len()),
+            // Each truck drives 0 distance by default, simply staying where it is
+            truck_driving_times: self.trucks.iter().map(|truck| (*truck, 0)).collect(),
+            truck_breaks: self.trucks.iter().map(|truck| (*truck, vec![])).collect(),
+            driver_trucks: BTreeMap::new(),
+            id_names: SharedIdNames(self.get_id_names()),
+        }
+    }
+
+    /// Limits the number of checkpoints each truck can have within a single
+    /// (UTC) day, e.g. to reflect dispatcher policies. `None` removes the limit
+    pub fn set_max_checkpoints_per_day(&mut self, max_checkpoints_per_day: Option<usize>) {
+        self.data_mut().max_checkpoints_per_day = max_checkpoints_per_day;
+    }
+
+    /// Limits how long drivers can drive without a break of at least `min_break_duration`,
+    /// after which breaks are scheduled as part of the schedule. Legs between terminals
+    /// longer than `max_continuous_driving_time` always include the breaks they need
+    pub fn set_driving_hour_rules(
+        &mut self,
+        max_continuous_driving_time: PyTimeDelta,
+        min_break_duration: PyTimeDelta,
+    ) -> PyResult<()> {
+        let (max_continuous_driving_time, min_break_duration) =
+            (max_continuous_driving_time.0, min_break_duration.0);
+        if max_continuous_driving_time == 0 {
+            return Err(PyTypeError::new_err(
+                "Maximum continuous driving time needs to be positive",
+            ));
+        }
+        self.data_mut().driving_hour_rules = Some(DrivingHourRules {
+            max_continuous_driving_time,
+            min_break_duration,
+        });
+        Ok(())
+    }
+
+    /// Sets the slack left on top of driving time before each checkpoint, e.g. for
+    /// queueing at the gate. terminal_buffer_times overrides it for some terminals
+    #[pyo3(signature = (buffer_time, terminal_buffer_times=BTreeMap::new()))]
+    pub fn set_buffer_times(
+        &mut self,
+        buffer_time: PyTimeDelta,
+        terminal_buffer_times: BTreeMap<PyTerminalID, PyTimeDelta>,
+    ) -> PyResult<()> {
+        let mut new_terminal_buffer_times = BTreeMap::new();
+        for (terminal_id, terminal_buffer_time) in terminal_buffer_times.iter() {
+            let terminal: Terminal =
+                self.terminal_mapper
+                    .reverse_map(terminal_id)
+                    .ok_or_else(|| {
+                        UnknownTerminalError::new_err(format!("Unknown terminal {terminal_id:?}"))
+                    })?;
+            new_terminal_buffer_times.insert(terminal, terminal_buffer_time.0);
+        }
+        self.data_mut().buffer_time = buffer_time.0;
+        self.data_mut().terminal_buffer_times = new_terminal_buffer_times;
+        Ok(())
+    }
+
+    /// Sets the drivers that can be assigned to trucks. Each truck can only
+    /// be driven by its assigned drivers during their shifts, and the drivers need the
+    /// qualifications of the cargo they handle as well as the trucks.
+    /// Schedules made before this have no drivers assigned
+    pub fn set_driver_roster(&mut self, roster: Vec<PyDriver>) -> PyResult<()> {
+        let mut drivers = BTreeMap::new();
+        for py_driver in roster.iter() {
+            let driver_id = &py_driver.id;
+            if self
+                .driver_mapper
+                .reverse_map(driver_id)
+                .is_some_and(|driver: Driver| drivers.contains_key(&driver))
+            {
+                return Err(PyTypeError::new_err(format!(
+                    "Driver {driver_id:?} is in the roster more than once"
+                )));
+            }
+
+            let home_terminal = match &py_driver.home_terminal {
+                Some(terminal_id) => Some(
+                    self.terminal_mapper
+                        .reverse_map(terminal_id)
+                        .ok_or_else(|| {
+                            UnknownTerminalError::new_err(format!(
+                                "Unknown terminal {terminal_id:?}"
+                            ))
+                        })?,
+                ),
+                None => None,
+            };
+            let mut shifts = IntervalChain::from_interval(interval_or_error(
+                py_driver.shift_start_time.0,
+                py_driver.shift_end_time.0,
+            )?);
+            if let Some(region_id) = &py_driver.region {
+                let calendar = self.calendars.get(region_id).ok_or_else(|| {
+                    PyTypeError::new_err(format!(
+                        "Driver {driver_id:?} is in region {region_id:?}, which has no calendar"
+                    ))
+                })?;
+                shifts = calendar.restrict(&shifts);
+            }
+            let driver: Driver = self.data_mut().driver_mapper.add_or_find(driver_id);
+            let driver_data = DriverData {
+                home_terminal,
+                shifts,
+                qualifications: py_driver
+                    .qualifications
+                    .iter()
+                    .map(|qualification| {
+                        self.data_mut()
+                            .qualification_mapper
+                            .add_or_find(qualification)
+                    })
+                    .collect(),
+            };
+            drivers.insert(driver, driver_data);
+        }
+        self.data_mut().drivers = drivers;
+        Ok(())
+    }
+
+    /// Returns the ids of the drivers in the roster
+    pub fn get_driver_ids(&self) -> Vec<PyDriverID> {
+        self.drivers
+            .keys()
+            .map(|driver| self.driver_mapper.map(driver).unwrap())
+            .collect()
+    }
+
+    /// Sets the number of chassis available at each terminal at the start of
+    /// the planning period. Each delivery takes a chassis from its pickup terminal
+    /// and leaves it at its dropoff terminal. Terminals that are not given
+    /// have an unlimited number of chassis
+    pub fn set_chassis_pools(
+        &mut self,
+        chassis_pools: BTreeMap<PyTerminalID, usize>,
+    ) -> PyResult<()> {
+        let mut new_chassis_pools = BTreeMap::new();
+        for (terminal_id, num_chassis) in chassis_pools.iter() {
+            let terminal: Terminal =
+                self.terminal_mapper
+                    .reverse_map(terminal_id)
+                    .ok_or_else(|| {
+                        UnknownTerminalError::new_err(format!("Unknown terminal {terminal_id:?}"))
+                    })?;
+            new_chassis_pools.insert(terminal, *num_chassis);
+        }
+        self.data_mut().chassis_pools = new_chassis_pools;
+        Ok(())
+    }
+
+    /// For each terminal with a chassis pool, returns how many chassis are
+    /// available there over time under `schedule`, as a list of (time, number
+    /// of chassis available from that time onwards)
+    pub fn get_chassis_availability(
+        &self,
+        schedule: &Schedule,
+    ) -> BTreeMap<PyTerminalID, Vec<(FormattedTime, i64)>> {
+        self.get_chassis_timelines(schedule)
+            .into_iter()
+            .map(|(terminal, timeline)| {
+                (
+                    self.terminal_mapper.map(&terminal).unwrap(),
+                    timeline
+                        .into_iter()
+                        .map(|(time, available)| (self.time_formats.time(time), available))
+                        .collect(),
+                )
+            })
+            .collect()
+    }
+
+    /// Returns when terminal `terminal_id` is open, after applying its calendar,
+    /// timezone and daily opening hours
+    pub fn get_terminal_open_intervals(
+        &self,
+        terminal_id: PyTerminalID,
+    ) -> PyResult<PyIntervalChain> {
+        self.terminal_mapper
+            .reverse_map(&terminal_id)
+            .and_then(|terminal| self.terminal_open_intervals.get(&terminal))
+            .map(|chain| PyIntervalChain {
+                chain: chain.clone(),
+            })
+            .ok_or_else(|| {
+                UnknownTerminalError::new_err(format!("Unknown terminal {terminal_id:?}"))
+            })
+    }
+
+    /// Returns the (pickup, dropoff) time windows of cargo `cargo_id`, within the
+    /// planning period and the opening hours of its terminals. The id of a booking
+    /// with several containers gives the windows of its first one
+    pub fn get_cargo_time_windows(
+        &self,
+        cargo_id: PyCargoID,
+    ) -> PyResult<(PyIntervalChain, PyIntervalChain)> {
+        let cargo = self.find_cargo(&cargo_id)?;
+        Ok((
+            PyIntervalChain {
+                chain: self.pickup_times.get(&cargo).unwrap().clone(),
+            },
+            PyIntervalChain {
+                chain: self.dropoff_times.get(&cargo).unwrap().clone(),
+            },
+        ))
+    }
+
+    /// Returns a map from each terminal with a known location to its (latitude, longitude)
+    pub fn get_terminal_locations(&self) -> BTreeMap<PyTerminalID, (f64, f64)> {
+        self.terminal_locations
+            .iter()
+            .map(|(terminal, location)| {
+                (
+                    self.terminal_mapper.map(terminal).unwrap(),
+                    (location.latitude, location.longitude),
+                )
+            })
+            .collect()
+    }
+
+    /// Sets the points along the road driven from `from_id` to `to_id`, as
+    /// (latitude, longitude), e.g. from a routing service, for drawing routes on a map
+    pub fn set_route_geometry(
+        &mut self,
+        from_id: PyTerminalID,
+        to_id: PyTerminalID,
+        geometry: Vec<(f64, f64)>,
+    ) -> PyResult<()> {
+        let from_terminal: Terminal =
+            self.terminal_mapper.reverse_map(&from_id).ok_or_else(|| {
+                UnknownTerminalError::new_err(format!("Unknown terminal {from_id:?}"))
+            })?;
+        let to_terminal: Terminal = self
+            .terminal_mapper
+            .reverse_map(&to_id)
+            .ok_or_else(|| UnknownTerminalError::new_err(format!("Unknown terminal {to_id:?}")))?;
+        let points = geometry
+            .iter()
+            .map(|(latitude, longitude)| {
+                Location::new(*latitude, *longitude).ok_or_else(|| {
+                    PyTypeError::new_err(format!(
+                        "Invalid location ({latitude}, {longitude}) in route from {from_id:?} to {to_id:?}"
+                    ))
+                })
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+        self.data_mut()
+            .route_geometries
+            .insert((from_terminal, to_terminal), points);
+        Ok(())
+    }
+
+    /// Sets how this generator and its schedules give times and durations back to python:
+    /// `times` as "seconds" since the unix epoch (the default) or "datetime" (in UTC),
+    /// and `durations` as "seconds" (the default) or "timedelta". Times and durations
+    /// can be given in either form regardless. Iterating over a schedule uses the formats
+    /// from when it was last changed
+    #[pyo3(signature = (times="seconds", durations="seconds"))]
+    pub fn set_time_formats(&mut self, times: &str, durations: &str) -> PyResult<()> {
+        let times_as_datetimes = match times {
+            "datetime" => true,
+            "seconds" => false,
+            _ => {
+                return Err(PyTypeError::new_err(format!(
+                    "Unknown time format {times:?}, expected \"datetime\" or \"seconds\""
+                )))
+            }
+        };
+        let durations_as_timedeltas = match durations {
+            "timedelta" => true,
+            "seconds" => false,
+            _ => {
+                return Err(PyTypeError::new_err(format!(
+                    "Unknown duration format {durations:?}, expected \"timedelta\" or \"seconds\""
+                )))
+            }
+        };
+        self.data_mut().time_formats = TimeFormats {
+            times_as_datetimes,
+            durations_as_timedeltas,
+        };
+        Ok(())
+    }
+
+    /// Reseeds internal RNG
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = Xoshiro256PlusPlus::seed_from_u64(seed);
+    }
+
+    /// A generator sharing the bookings, trucks and driving times of this one without
+    /// copying them, with its own random number generator seeded with `seed`. Each fork
+    /// can run `optimize` on its own thread at the same time, e.g. in a
+    /// `concurrent.futures.ThreadPoolExecutor`, and schedules can be used with any of
+    /// them. Changing a generator afterwards, e.g. adding a booking, copies its data
+    /// first, so it doesn't affect the others
+    pub fn fork(&self, seed: u64) -> Self {
+        Self {
+            data: self.data.clone(),
+            rng: Xoshiro256PlusPlus::seed_from_u64(seed),
+            move_stats: self.move_stats.as_ref().map(|_| MoveStats::default()),
+            rejection_reason: None,
+            debug_checks: self.debug_checks,
+            id_names: RwLock::new(self.get_id_names()),
+        }
+    }
+
+    /// Sets whether to check the internal invariants of schedules after each move,
+    /// which is slower. On by default in debug builds, and off in release builds
+    pub fn set_debug_checks(&mut self, enabled: bool) {
+        self.debug_checks = enabled;
+    }
+
+    /// Starts or stops recording where time goes when making moves, see `get_move_stats`.
+    /// Starting again resets the stats. Timing slows down making moves a little
+    pub fn set_instrumentation(&mut self, enabled: bool) {
+        self.move_stats = enabled.then(MoveStats::default);
+    }
+
+    /// What was recorded since `set_instrumentation(True)`, as a dict with
+    /// - "moves": for each move type, a dict of "attempts", "successes", "seconds" and
+    ///   "rejections" (the number of failed attempts for each reason, see `diagnose_moves`)
+    /// - "phases": seconds spent in each phase: "candidate_enumeration" (picking moves),
+    ///   "interval_intersection" (finding times a checkpoint can move to, which is part of
+    ///   candidate enumeration), "feasibility_check", "cloning" and "scoring" (in `optimize`)
+    ///
+    /// Returns None if instrumentation is disabled
+    pub fn get_move_stats<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyDict>>> {
+        let Some(move_stats) = &self.move_stats else {
+            return Ok(None);
+        };
+        let moves = PyDict::new(py);
+        for (move_type, stats) in move_stats.moves.iter() {
+            moves.set_item(*move_type, stats.to_py_dict(py)?)?;
+        }
+        let phases = PyDict::new(py);
+        for (phase, time) in move_stats.phases.iter() {
+            phases.set_item(*phase, time.as_secs_f64())?;
+        }
+
+        let out = PyDict::new(py);
+        out.set_item("moves", moves)?;
+        out.set_item("phases", phases)?;
+        Ok(Some(out))
+    }
+
+    /// Dry run of the moves `get_schedule_neighbour` can make, to see why they fail:
+    /// tries each type of move `num_attempts` times on `schedule` without changing it.
+    /// Returns a dict from move type to a dict of "attempts", "successes", "seconds"
+    /// and "rejections", the number of failed attempts for each reason, e.g.
+    /// "no_candidate_terminal", "empty_allowed_interval", "capacity_underflow", or the
+    /// constraint the move broke once made: "checkpoint_limit", "driving_hours",
+    /// "driver_roster", "rear_loading", "chassis", "precedence" or "stop_order".
+    /// Doesn't change the random moves made afterwards.
+    /// Other python threads can run meanwhile
+    #[pyo3(signature = (schedule, num_attempts=100))]
+    pub fn diagnose_moves<'py>(
+        &mut self,
+        py: Python<'py>,
+        schedule: &Schedule,
+        num_attempts: usize,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        self.check_trucks(schedule)?;
+        let moves = py
+            .allow_threads(|| self.dry_run_moves(schedule, num_attempts))
+            .map_err(|missing| self.driving_time_error(missing))?;
+        let out = PyDict::new(py);
+        for (move_type, stats) in moves.iter() {
+            out.set_item(*move_type, stats.to_py_dict(py)?)?;
+        }
+        Ok(out)
+    }
+
+    /// Approximate heap usage of the generator's data in bytes, as a dict with
+    /// "inputs" (the data it was created from, kept for `to_json`), "driving_times_cache",
+    /// "interval_chains" (time windows of cargo and terminals), "bookings" (including
+    /// rejected ones), "trucks", "drivers", "route_geometries", "id_mappers" and their
+    /// "total".
+    /// Forks of the generator share this data until one of them changes it
+    pub fn memory_stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let stats = [
+            ("inputs", self.inputs.heap_size()),
+            ("driving_times_cache", self.driving_times_cache.heap_size()),
+            (
+                "interval_chains",
+                self.pickup_times.heap_size()
+                    + self.dropoff_times.heap_size()
+                    + self.terminal_open_intervals.heap_size(),
+            ),
+            (
+                "bookings",
+                self.cargo_booking_info.heap_size()
+                    + self.cargo_by_terminals.heap_size()
+                    + self.cargo_successors.heap_size()
+                    + self.cargo_stops.heap_size()
+                    + self.stop_main_cargo.heap_size()
+                    + self.booking_cargo.heap_size()
+                    + self.rejected_bookings.heap_size(),
+            ),
+            (
+                "trucks",
+                self.trucks.heap_size()
+                    + self.truck_data.heap_size()
+                    + self.trucks_with_locked_cargo.heap_size(),
+            ),
+            ("drivers", self.drivers.heap_size()),
+            ("route_geometries", self.route_geometries.heap_size()),
+            (
+                "id_mappers",
+                [
+                    &self.terminal_mapper,
+                    &self.cargo_mapper,
+                    &self.truck_mapper,
+                    &self.qualification_mapper,
+                    &self.vehicle_class_mapper,
+                    &self.capacity_dimension_mapper,
+                    &self.driver_mapper,
+                ]
+                .iter()
+                .map(|mapper| mapper.heap_size())
+                .sum(),
+            ),
+        ];
+
+        let out = PyDict::new(py);
+        for (name, size) in stats {
+            out.set_item(name, size)?;
+        }
+        out.set_item(
+            "total",
+            stats.iter().map(|(_name, size)| size).sum::<usize>(),
+        )?;
+        Ok(out)
+    }
+
+    /// Gets a random neighbour for a schedule.
+    /// Note that the neighbours might not be sampled uniformly.
+    /// Pick an action type and try to execute it randomly up to
+    /// `num_tries_per_action` times. If this fails, pick another action type and repeat.
+    /// This helps to keep frequency of selecting each action type similar to what is expected,
+    /// despite some action types failing more often than others.
+    /// If `describe_move` is set, returns (neighbour, `MoveDescription`) instead.
+    /// Other python threads can run meanwhile
+    #[pyo3(signature = (schedule, num_tries_per_action, describe_move=false))]
+    pub fn get_schedule_neighbour(
+        &mut self,
+        py: Python<'_>,
+        schedule: &Schedule,
+        num_tries_per_action: usize,
+        describe_move: bool,
+    ) -> PyResult<PyNeighbour> {
+        self.check_trucks(schedule)?;
+        let started = self.start_timer();
+        let mut out = schedule.clone();
+        self.record_phase("cloning", started);
+        let mv = py
+            .allow_threads(|| self.find_schedule_neighbour(&mut out, num_tries_per_action))
+            .map_err(|missing| self.driving_time_error(missing))?;
+        if describe_move {
+            let description = mv.describe(self, &out);
+            Ok(PyNeighbour::WithMove((out, description)))
         } else {
-            // Before first interval
-            self.truck_data.get(&truck).unwrap().starting_terminal
+            Ok(PyNeighbour::Schedule(out))
+        }
+    }
+
+    /// Returns a score representing how good the Schedule is
+    /// The score is a vector of numbers, where each
+    /// represent a different criterion by which the solution can be judged.
+    /// Higher score is better
+    /// The last score is a non-positive penalty for violated hard constraints
+    /// (such as missing mandatory deliveries); a schedule is only feasible
+    /// if it is 0. Other python threads can run meanwhile
+    pub fn scores(&mut self, py: Python<'_>, schedule: &Schedule) -> PyResult<Vec<f64>> {
+        self.check_trucks(schedule)?;
+        py.allow_threads(|| self.compute_scores(schedule))
+            .map_err(|missing| self.driving_time_error(missing))
+    }
+
+    /// Improves `schedule` with simulated annealing over `get_schedule_neighbour`, like
+    /// `sa_solve` in `src/metaheuristic/sa.py` but without leaving Rust, releasing the GIL
+    /// meanwhile. Every `progress_interval` iterations, `progress_callback` is called with
+    /// (iteration, num_iterations, temperature, best scores so far), and optimization
+    /// stops early if it returns False or if `cancel_event` (e.g. a `threading.Event`)
+    /// is set. Returns the best schedule found and its scores
+    #[pyo3(signature = (
+        schedule,
+        num_iterations,
+        initial_temperature=10.0,
+        final_temperature=0.1,
+        num_tries_per_action=10,
+        restart_probability=0.001,
+        progress_callback=None,
+        progress_interval=100,
+        cancel_event=None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn optimize(
+        &mut self,
+        py: Python<'_>,
+        schedule: &Schedule,
+        num_iterations: usize,
+        initial_temperature: f64,
+        final_temperature: f64,
+        num_tries_per_action: usize,
+        restart_probability: f64,
+        progress_callback: Option<Py<PyAny>>,
+        progress_interval: usize,
+        cancel_event: Option<Py<PyAny>>,
+    ) -> PyResult<(Schedule, Vec<f64>)> {
+        self.check_trucks(schedule)?;
+        let options = annealing_options(
+            num_iterations,
+            initial_temperature,
+            final_temperature,
+            num_tries_per_action,
+            restart_probability,
+            progress_interval,
+        )?;
+        let mut on_progress = |progress: AnnealingProgress| {
+            Python::with_gil(|py| {
+                if let Some(cancel_event) = &cancel_event {
+                    if cancel_event
+                        .call_method0(py, "is_set")?
+                        .extract::<bool>(py)?
+                    {
+                        return Ok(false);
+                    }
+                }
+                let Some(progress_callback) = &progress_callback else {
+                    return Ok(true);
+                };
+                let result = progress_callback.call1(
+                    py,
+                    (
+                        progress.iteration,
+                        progress.num_iterations,
+                        progress.temperature,
+                        progress.best_scores,
+                    ),
+                )?;
+                // Anything other than False, e.g. None, means carrying on
+                let result = result.bind(py);
+                Ok(!result.is_instance_of::<PyBool>() || result.extract::<bool>()?)
+            })
+        };
+        py.allow_threads(|| self.anneal(schedule, &options, &mut on_progress))
+    }
+
+    /// Like `optimize`, but runs on a separate thread and returns straight away with an
+    /// `OptimizationHandle`, whose progress can be polled with `progress`, which can be
+    /// stopped with `cancel`, and whose result can be waited for with `result` or awaited
+    /// from asyncio. The optimization runs on a fork of the generator (see `fork`), so the
+    /// generator can be used meanwhile, but changes to it don't affect the optimization
+    #[pyo3(signature = (
+        schedule,
+        num_iterations,
+        initial_temperature=10.0,
+        final_temperature=0.1,
+        num_tries_per_action=10,
+        restart_probability=0.001,
+        progress_interval=100,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn optimize_async(
+        slf: &Bound<'_, Self>,
+        schedule: &Schedule,
+        num_iterations: usize,
+        initial_temperature: f64,
+        final_temperature: f64,
+        num_tries_per_action: usize,
+        restart_probability: f64,
+        progress_interval: usize,
+    ) -> PyResult<OptimizationHandle> {
+        slf.borrow().check_trucks(schedule)?;
+        let options = annealing_options(
+            num_iterations,
+            initial_temperature,
+            final_temperature,
+            num_tries_per_action,
+            restart_probability,
+            progress_interval,
+        )?;
+        let mut generator = {
+            let mut generator = slf.borrow_mut();
+            let seed = generator.rng.random();
+            generator.fork(seed)
+        };
+        let schedule = schedule.clone();
+        let state = Arc::new(OptimizationState::default());
+
+        let thread_state = state.clone();
+        let thread = std::thread::spawn(move || {
+            let mut on_progress = |progress: AnnealingProgress| {
+                *thread_state.progress.lock().unwrap() = Some(progress);
+                Ok(!thread_state.cancelled.load(Ordering::Acquire))
+            };
+            let result = generator.anneal(&schedule, &options, &mut on_progress);
+            thread_state.finished.store(true, Ordering::Release);
+            result
+        });
+
+        Ok(OptimizationHandle {
+            state,
+            thread: Mutex::new(Some(thread)),
+            result: Mutex::new(None),
+        })
+    }
+
+    /// Ids of cargo whose requested time windows could not be met and were relaxed
+    /// when creating this generator with infeasible_bookings="relax"
+    pub fn get_relaxed_cargo_ids(&self) -> Vec<PyCargoID> {
+        self.cargo_booking_info
+            .iter()
+            .filter(|(_, booking_info)| booking_info.relaxed_time_windows)
+            .map(|(cargo, _)| self.cargo_mapper.map(cargo).unwrap().clone())
+            .collect()
+    }
+
+    /// Bookings which were left out since they can't be done within the planning period,
+    /// as (cargo id of the booking, reason), see infeasible_bookings in the constructor
+    pub fn rejected_bookings(&self) -> Vec<(PyCargoID, String)> {
+        self.rejected_bookings
+            .iter()
+            .map(|(cargo_id, reason)| (cargo_id.clone(), reason.clone()))
+            .collect()
+    }
+
+    /// Returns a map from each cargo id, as used in `Schedule.to_list_of_tuples`,
+    /// to the (priority, requested_by) of its booking
+    pub fn get_cargo_priorities(&self) -> BTreeMap<PyCargoID, (usize, Option<String>)> {
+        self.cargo_booking_info
+            .iter()
+            .map(|(cargo, booking_info)| {
+                (
+                    self.cargo_mapper.map(cargo).unwrap(),
+                    (booking_info.priority, booking_info.requested_by.clone()),
+                )
+            })
+            .collect()
+    }
+
+    /// Whether the schedule satisfies all hard constraints,
+    /// e.g. delivers all mandatory cargo
+    pub fn is_feasible(&self, schedule: &Schedule) -> PyResult<bool> {
+        self.check_trucks(schedule)?;
+        Ok(self.validate(schedule)?.is_empty())
+    }
+
+    /// Returns a description of each hard constraint `schedule` violates,
+    /// e.g. mandatory cargo not being delivered or cargo being delivered
+    /// by a driver without the required qualifications
+    pub fn validate(&self, schedule: &Schedule) -> PyResult<Vec<String>> {
+        self.check_trucks(schedule)?;
+        self.find_violations(schedule)
+            .map_err(|missing| self.driving_time_error(missing))
+    }
+
+    /// For each truck, why it can't deliver `cargo_id` in `schedule`, as (constraint,
+    /// reason) where constraint is "incompatibility" (locked to another truck or missing
+    /// qualifications), "capacity", "time_window" (windows over before the truck is
+    /// available), "precedence" (can't be picked up after the cargo it depends on, e.g.
+    /// for an empty container return), "driving_time" or "rejected" (the booking was
+    /// left out, see `rejected_bookings`), or None if it can. The id of a booking with
+    /// several containers gives the explanations for its first one.
+    /// Only considers visiting the pickup, any other stops and the dropoff right after
+    /// each other between two of the truck's checkpoints, without checking driving hours
+    /// or the driver roster
+    pub fn explain_infeasibility(
+        &mut self,
+        cargo_id: PyCargoID,
+        schedule: &Schedule,
+    ) -> PyResult<BTreeMap<PyTruckID, Option<(&'static str, String)>>> {
+        self.check_trucks(schedule)?;
+        if let Some(reason) = self.rejected_bookings.get(&cargo_id) {
+            return Ok(schedule
+                .truck_checkpoints
+                .keys()
+                .map(|truck| {
+                    (
+                        self.truck_mapper.map(truck).unwrap(),
+                        Some(("rejected", reason.clone())),
+                    )
+                })
+                .collect());
+        }
+        let cargo = self.find_cargo(&cargo_id)?;
+
+        let mut out = BTreeMap::new();
+        for truck in schedule.truck_checkpoints.keys() {
+            let explanation = self
+                .explain_truck_infeasibility(schedule, *truck, cargo)
+                .map_err(|missing| self.driving_time_error(missing))?;
+            out.insert(self.truck_mapper.map(truck).unwrap(), explanation);
+        }
+        Ok(out)
+    }
+
+    pub fn __repr__(&self) -> String {
+        format!(
+            "ScheduleGenerator({} terminals, {} trucks, {} cargo, planning period [{}, {}))",
+            self.terminals.len(),
+            self.trucks.len(),
+            self.cargo_booking_info.len(),
+            self.planning_period.get_start_time(),
+            self.planning_period.get_end_time()
+        )
+    }
+
+    pub fn get_terminal_ids(&self) -> Vec<PyTerminalID> {
+        self.terminals
+            .iter()
+            .map(|terminal| self.terminal_mapper.map(terminal).unwrap())
+            .collect()
+    }
+
+    /// Reset the driving times used by the algorithm
+    /// terminal_id_order gives the order of terminals in `driving_times`
+    /// `driving_times` are the mappings of terminal ids to driving times to all
+    /// the terminals (including itself), in the order given in `terminal_id_order`
+    /// It can also be a square 2-D numpy array of integer or float driving times, whose
+    /// rows and columns are both in the order given in `terminal_id_order`, which is
+    /// read directly rather than through python lists
+    /// If `vehicle_class` is given, these driving times are only used for trucks
+    /// of this class, and override the driving times set without a vehicle class.
+    /// Otherwise, "static" needs to be selected in driving_time_providers
+    #[pyo3(signature = (terminal_id_order, driving_times, vehicle_class=None))]
+    pub fn set_driving_times(
+        &mut self,
+        terminal_id_order: Vec<PyTerminalID>,
+        driving_times: PyDrivingTimeTable,
+        vehicle_class: Option<PyVehicleClassID>,
+    ) -> PyResult<()> {
+        if vehicle_class.is_none() {
+            self.check_driving_time_source(DrivingTimeSource::Static)?;
+        }
+        let find_terminal = |terminal_id: &PyTerminalID| {
+            self.terminal_mapper
+                .reverse_map(terminal_id)
+                .ok_or_else(|| {
+                    UnknownTerminalError::new_err(format!("Unknown terminal {terminal_id:?}"))
+                })
         };
+        let terminal_order: Vec<Terminal> = terminal_id_order
+            .iter()
+            .map(find_terminal)
+            .collect::<PyResult<_>>()?;
+
+        let mut driving_times_reformatted = BTreeMap::new();
+        match driving_times {
+            PyDrivingTimeTable::Rows(rows) => {
+                for (from_id, times) in rows.iter() {
+                    let from_terminal = find_terminal(from_id)?;
+                    if times.len() > terminal_order.len() {
+                        return Err(PyTypeError::new_err(format!(
+                            "Terminal {from_id:?} has {} driving times, but there are only {} terminals",
+                            times.len(),
+                            terminal_order.len()
+                        )));
+                    }
+                    for (to_terminal, time) in terminal_order.iter().zip(times.iter()) {
+                        driving_times_reformatted.insert((from_terminal, *to_terminal), time.0);
+                    }
+                }
+            }
+            PyDrivingTimeTable::Matrix(matrix) => {
+                let num_terminals = terminal_order.len();
+                if (matrix.num_rows, matrix.num_columns) != (num_terminals, num_terminals) {
+                    return Err(PyTypeError::new_err(format!(
+                        "Driving time matrix has shape ({}, {}), but there are {num_terminals} terminals",
+                        matrix.num_rows, matrix.num_columns
+                    )));
+                }
+                for (from_index, from_terminal) in terminal_order.iter().enumerate() {
+                    for (to_index, to_terminal) in terminal_order.iter().enumerate() {
+                        driving_times_reformatted.insert(
+                            (*from_terminal, *to_terminal),
+                            matrix.get(from_index, to_index),
+                        );
+                    }
+                }
+            }
+        }
+
+        let vehicle_class = vehicle_class.map(|vehicle_class| {
+            self.data_mut()
+                .vehicle_class_mapper
+                .add_or_find(&vehicle_class)
+        });
+        self.data_mut()
+            .driving_times_cache
+            .set_static_driving_times(driving_times_reformatted, vehicle_class);
+        Ok(())
+    }
+
+    /// Replace the driving times between some pairs of terminals, keeping the others.
+    /// `driving_times` sends (from_id, to_id) to the new driving time.
+    /// NOTE: the total driving times of existing schedules are not updated
+    #[pyo3(signature = (driving_times, vehicle_class=None))]
+    pub fn update_driving_times(
+        &mut self,
+        driving_times: BTreeMap<(PyTerminalID, PyTerminalID), PyTimeDelta>,
+        vehicle_class: Option<PyVehicleClassID>,
+    ) -> PyResult<()> {
+        if vehicle_class.is_none() {
+            self.check_driving_time_source(DrivingTimeSource::Static)?;
+        }
+        let mut driving_times_reformatted = BTreeMap::new();
+        for ((from_id, to_id), time) in driving_times.iter() {
+            let from_terminal: Terminal =
+                self.terminal_mapper.reverse_map(from_id).ok_or_else(|| {
+                    UnknownTerminalError::new_err(format!("Unknown terminal {from_id:?}"))
+                })?;
+            let to_terminal: Terminal =
+                self.terminal_mapper.reverse_map(to_id).ok_or_else(|| {
+                    UnknownTerminalError::new_err(format!("Unknown terminal {to_id:?}"))
+                })?;
+            driving_times_reformatted.insert((from_terminal, to_terminal), time.0);
+        }
 
-        let next_terminal = if let Some(next) = next_checkpoint {
-            Some(next.terminal)
-        } else {
-            None
+        let vehicle_class = vehicle_class.map(|vehicle_class| {
+            self.data_mut()
+                .vehicle_class_mapper
+                .add_or_find(&vehicle_class)
+        });
+        self.data_mut()
+            .driving_times_cache
+            .update_static_driving_times(driving_times_reformatted, vehicle_class);
+        Ok(())
+    }
+
+    /// Set the standard deviations of the driving times between pairs of terminals,
+    /// keeping the ones of other pairs, for `estimate_on_time_probability`.
+    /// The driving times set with `set_driving_times` are their means
+    pub fn set_driving_time_spreads(
+        &mut self,
+        spreads: BTreeMap<(PyTerminalID, PyTerminalID), PyTimeDelta>,
+    ) -> PyResult<()> {
+        let mut spreads_reformatted = BTreeMap::new();
+        for ((from_id, to_id), spread) in spreads.iter() {
+            let from_terminal: Terminal =
+                self.terminal_mapper.reverse_map(from_id).ok_or_else(|| {
+                    UnknownTerminalError::new_err(format!("Unknown terminal {from_id:?}"))
+                })?;
+            let to_terminal: Terminal =
+                self.terminal_mapper.reverse_map(to_id).ok_or_else(|| {
+                    UnknownTerminalError::new_err(format!("Unknown terminal {to_id:?}"))
+                })?;
+            spreads_reformatted.insert((from_terminal, to_terminal), spread.0);
+        }
+        self.data_mut()
+            .driving_times_cache
+            .update_spreads(spreads_reformatted);
+        Ok(())
+    }
+
+    /// Estimates the probability that all cargo in `schedule` is picked up and dropped off
+    /// within its time windows, by sampling normally-distributed driving times with the
+    /// spreads set by `set_driving_time_spreads` `num_samples` times. Delays carry over
+    /// to later checkpoints, except for what the slack before them makes up for
+    #[pyo3(signature = (schedule, num_samples=1000, seed=0))]
+    pub fn estimate_on_time_probability(
+        &self,
+        schedule: &Schedule,
+        num_samples: usize,
+        seed: u64,
+    ) -> PyResult<f64> {
+        self.check_trucks(schedule)?;
+        if num_samples == 0 {
+            return Err(PyTypeError::new_err("Need at least one sample"));
+        }
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(seed);
+        let mut num_on_time = 0;
+        for _ in 0..num_samples {
+            if self
+                .is_on_time_when_sampled(schedule, &mut rng)
+                .map_err(|missing| self.driving_time_error(missing))?
+            {
+                num_on_time += 1;
+            }
+        }
+        Ok(num_on_time as f64 / num_samples as f64)
+    }
+
+    /// Replace the driving time from `from_id` to `to_id`, see `update_driving_times`
+    #[pyo3(signature = (from_id, to_id, driving_time, vehicle_class=None))]
+    pub fn set_driving_time(
+        &mut self,
+        from_id: PyTerminalID,
+        to_id: PyTerminalID,
+        driving_time: PyTimeDelta,
+        vehicle_class: Option<PyVehicleClassID>,
+    ) -> PyResult<()> {
+        self.update_driving_times(
+            BTreeMap::from([((from_id, to_id), driving_time)]),
+            vehicle_class,
+        )
+    }
+
+    /// Set a function `provider(from_id, to_id) -> seconds` used by the "callback" driving
+    /// time provider for driving times which were not set with `set_driving_times`,
+    /// e.g. to query a routing service such as OSRM. Its results are cached.
+    /// Pass None to remove it.
+    #[pyo3(signature = (provider))]
+    pub fn set_driving_time_provider(
+        &mut self,
+        py: Python<'_>,
+        provider: Option<Py<PyAny>>,
+    ) -> PyResult<()> {
+        let Some(provider) = provider else {
+            self.replace_driving_time_provider(
+                DrivingTimeSource::Callback,
+                Box::new(CallbackProvider::new(None)),
+            )?;
+            return Ok(());
         };
-        (prev_terminal, next_terminal)
+        if !provider.bind(py).is_callable() {
+            return Err(PyTypeError::new_err(format!(
+                "Driving time provider {provider} is not callable"
+            )));
+        }
+
+        let callback = CallbackProvider::new(Some(Box::new(move |from_id, to_id| {
+            Python::with_gil(|py| {
+                provider
+                    .call1(py, (from_id, to_id))
+                    .and_then(|driving_time| driving_time.extract::<PyTimeDelta>(py))
+                    .map(|driving_time| driving_time.0)
+                    .map_err(|err| err.to_string())
+            })
+        })));
+        self.replace_driving_time_provider(DrivingTimeSource::Callback, Box::new(callback))
     }
 
-    /// Return (`truck`, index in `checkpoints`) for a random checkpoint
-    fn get_random_checkpoint<'a>(
+    /// Every pair of distinct terminals (from_id, to_id) between which trucks might need
+    /// to drive, from their starting terminals or terminals where cargo (including empty
+    /// containers returned to depots) is picked up or dropped off, to the latter.
+    /// Useful to find all the driving times needed at once, e.g. in a single OSRM request
+    pub fn required_terminal_pairs(&self) -> Vec<(PyTerminalID, PyTerminalID)> {
+        self.get_required_terminal_pairs()
+            .into_iter()
+            .map(|(from, to)| {
+                (
+                    self.terminal_mapper.map(&from).unwrap(),
+                    self.terminal_mapper.map(&to).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    /// Pairs of terminals (from_id, to_id) between which trucks might need to drive,
+    /// see `required_terminal_pairs`, but whose driving time is not known yet. When needed,
+    /// these are found with the driving time providers, and an error is raised if none
+    /// of them can find it
+    pub fn missing_driving_times(&self) -> Vec<(PyTerminalID, PyTerminalID)> {
+        self.get_required_terminal_pairs()
+            .into_iter()
+            .filter(|(from, to)| !self.driving_times_cache.is_known(*from, *to))
+            .map(|(from, to)| {
+                (
+                    self.terminal_mapper.map(&from).unwrap(),
+                    self.terminal_mapper.map(&to).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    /// Finds triples (a_id, b_id, c_id) of terminals for which the driving time set with
+    /// `set_driving_times` from a to c is more than `tolerance` longer than driving
+    /// from a to c through b, which usually means that the driving times are wrong
+    #[pyo3(signature = (tolerance=PyTimeDelta(0)))]
+    pub fn find_triangle_violations(
+        &self,
+        tolerance: PyTimeDelta,
+    ) -> Vec<(PyTerminalID, PyTerminalID, PyTerminalID)> {
+        self.driving_times_cache
+            .find_triangle_violations(tolerance.0)
+            .into_iter()
+            .map(|(a, b, c)| {
+                (
+                    self.terminal_mapper.map(&a).unwrap(),
+                    self.terminal_mapper.map(&b).unwrap(),
+                    self.terminal_mapper.map(&c).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    /// Finds pairs (a_id, b_id) of terminals for which the driving time set with
+    /// `set_driving_times` from a to b is more than `tolerance` longer than from b to a
+    #[pyo3(signature = (tolerance=PyTimeDelta(0)))]
+    pub fn find_asymmetric_driving_times(
+        &self,
+        tolerance: PyTimeDelta,
+    ) -> Vec<(PyTerminalID, PyTerminalID)> {
+        self.driving_times_cache
+            .find_asymmetries(tolerance.0)
+            .into_iter()
+            .map(|(a, b)| {
+                (
+                    self.terminal_mapper.map(&a).unwrap(),
+                    self.terminal_mapper.map(&b).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    /// Set the average speed in km/h used to estimate driving times that are not
+    /// known from the great-circle distance between the terminals' locations.
+    /// Since roads are not straight, this should be lower than the trucks' actual speed.
+    /// If "haversine" was not selected in driving_time_providers, it is asked last
+    pub fn set_estimated_driving_speed(&mut self, speed_kmh: f64) -> PyResult<()> {
+        if !(speed_kmh.is_finite() && speed_kmh > 0.0) {
+            return Err(PyTypeError::new_err(format!(
+                "Estimated driving speed needs to be positive, got {speed_kmh}"
+            )));
+        }
+        let provider = HaversineProvider::new(self.terminal_locations.clone(), speed_kmh);
+        self.data_mut()
+            .driving_times_cache
+            .set_provider(DrivingTimeSource::Haversine, Box::new(provider));
+        Ok(())
+    }
+
+    /// Driving times which were not known, and so were estimated from the distance
+    /// between the terminals so far, as (from_id, to_id, estimated driving time)
+    pub fn get_estimated_driving_times(
+        &self,
+    ) -> Vec<(PyTerminalID, PyTerminalID, FormattedTimeDelta)> {
+        self.driving_times_cache
+            .get_found_driving_times(DrivingTimeSource::Haversine)
+            .into_iter()
+            .map(|((from, to), driving_time)| {
+                (
+                    self.terminal_mapper.map(&from).unwrap(),
+                    self.terminal_mapper.map(&to).unwrap(),
+                    self.time_formats.duration(driving_time),
+                )
+            })
+            .collect()
+    }
+
+    /// Driving times which were not known, and so were derived from the shortest route
+    /// through terminals with known driving times so far, as (from_id, to_id, driving time)
+    pub fn get_derived_driving_times(
+        &self,
+    ) -> Vec<(PyTerminalID, PyTerminalID, FormattedTimeDelta)> {
+        self.driving_times_cache
+            .get_found_driving_times(DrivingTimeSource::ShortestPath)
+            .into_iter()
+            .map(|((from, to), driving_time)| {
+                (
+                    self.terminal_mapper.map(&from).unwrap(),
+                    self.terminal_mapper.map(&to).unwrap(),
+                    self.time_formats.duration(driving_time),
+                )
+            })
+            .collect()
+    }
+
+    /// Make the driving time from `from_id` to `to_id` depend on the time of day.
+    /// `profile` is a list of (start of time bucket in seconds since midnight UTC,
+    /// driving time when departing within this bucket); each bucket lasts until the
+    /// next one starts, and the last one lasts until the first one on the next day.
+    /// Both can also be given as timedeltas.
+    /// "time_dependent" needs to be selected in driving_time_providers.
+    /// NOTE: the total driving time used for scoring still uses the
+    /// driving times set by `set_driving_times`
+    pub fn set_driving_time_profile(
         &mut self,
-        schedule: &'a Schedule,
-    ) -> Option<(&'a Checkpoint, Truck, usize)> {
-        // Pick a random checkpoint, uniformly, across trucks
-        // TODO: keep track of this number to make it faster
-        let total_num_checkpoints = schedule
-            .truck_checkpoints
+        from_id: PyTerminalID,
+        to_id: PyTerminalID,
+        profile: Vec<(PyTimeDelta, PyTimeDelta)>,
+    ) -> PyResult<()> {
+        let profile: PyDrivingTimeProfile = profile
+            .into_iter()
+            .map(|(bucket_start, driving_time)| (bucket_start.0, driving_time.0))
+            .collect();
+        let from_terminal: Terminal =
+            self.terminal_mapper.reverse_map(&from_id).ok_or_else(|| {
+                UnknownTerminalError::new_err(format!("Unknown terminal {from_id:?}"))
+            })?;
+        let to_terminal: Terminal = self
+            .terminal_mapper
+            .reverse_map(&to_id)
+            .ok_or_else(|| UnknownTerminalError::new_err(format!("Unknown terminal {to_id:?}")))?;
+
+        self.check_driving_time_source(DrivingTimeSource::TimeDependent)?;
+        if profile.is_empty() {
+            return Err(PyTypeError::new_err("Driving time profile can't be empty"));
+        }
+        if let Some((bucket_start, _)) = profile
             .iter()
-            .map(|(_truck, checkpoints)| checkpoints.len())
-            .sum();
+            .find(|(bucket_start, _)| *bucket_start >= SECONDS_PER_DAY)
+        {
+            return Err(PyTypeError::new_err(format!(
+                "Time bucket start {bucket_start} is not within a day"
+            )));
+        }
 
-        if total_num_checkpoints == 0 {
-            return None;
+        self.data_mut()
+            .driving_times_cache
+            .set_profile(from_terminal, to_terminal, profile);
+        Ok(())
+    }
+
+    /// Scale the driving time from `from_id` to `to_id` by `factor` when departing within
+    /// `valid_interval`, e.g. to account for live traffic. Factors applied later take priority
+    /// over earlier ones where they overlap, so apply a factor of 1 to undo one.
+    /// If `schedule` is given, the driving times of the trucks in it which might drive
+    /// this way during `valid_interval` are computed again, and the trucks validated again,
+    /// returning their violations (see `validate`)
+    #[pyo3(signature = (from_id, to_id, factor, valid_interval, schedule=None))]
+    pub fn apply_traffic_factor(
+        &mut self,
+        from_id: PyTerminalID,
+        to_id: PyTerminalID,
+        factor: f64,
+        valid_interval: (PyTime, PyTime),
+        schedule: Option<PyRefMut<'_, Schedule>>,
+    ) -> PyResult<Vec<String>> {
+        let from_terminal: Terminal =
+            self.terminal_mapper.reverse_map(&from_id).ok_or_else(|| {
+                UnknownTerminalError::new_err(format!("Unknown terminal {from_id:?}"))
+            })?;
+        let to_terminal: Terminal = self
+            .terminal_mapper
+            .reverse_map(&to_id)
+            .ok_or_else(|| UnknownTerminalError::new_err(format!("Unknown terminal {to_id:?}")))?;
+        if !(factor.is_finite() && factor > 0.0) {
+            return Err(PyTypeError::new_err(format!(
+                "Traffic factor needs to be positive, got {factor}"
+            )));
         }
+        let interval = interval_or_error(valid_interval.0.into(), valid_interval.1.into())?;
 
-        let checkpoint_index = self.rng.random_range(0..total_num_checkpoints);
-        let mut num_checkpoints_considered = 0;
-        // Find a truck, weighted by number of checkpoints in it
-        let (chosen_truck, chosen_index) = schedule
-            .truck_checkpoints
+        self.data_mut().driving_times_cache.add_traffic_factor(
+            from_terminal,
+            to_terminal,
+            factor,
+            interval.clone(),
+        );
+
+        let Some(mut schedule) = schedule else {
+            return Ok(vec![]);
+        };
+        self.check_trucks(&schedule)?;
+        let mut affected_trucks = vec![];
+        for (truck, checkpoints) in schedule.truck_checkpoints.iter() {
+            // Each leg is driven some time between the previous checkpoint and the next one
+            let mut terminal = self.truck_data.get(truck).unwrap().starting_terminal;
+            let mut time = self.get_truck_start_time(*truck);
+            let drives_leg = checkpoints.iter().any(|checkpoint| {
+                let is_affected = terminal == from_terminal
+                    && checkpoint.terminal == to_terminal
+                    && time < interval.get_end_time()
+                    && interval.get_start_time() < checkpoint.time;
+                terminal = checkpoint.terminal;
+                time = checkpoint.time;
+                is_affected
+            });
+            if drives_leg {
+                affected_trucks.push(*truck);
+            }
+        }
+
+        let mut out = vec![];
+        for truck in affected_trucks {
+            let driving_time = self
+                .get_route_driving_time(&schedule, truck)
+                .map_err(|missing| self.driving_time_error(missing))?;
+            schedule.truck_driving_times.insert(truck, driving_time);
+            self.find_truck_violations(&schedule, &truck, &mut out)
+                .map_err(|missing| self.driving_time_error(missing))?;
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use proptest::prelude::*;
+    use pyo3::types::PyInt;
+
+    use super::super::common_types::IsID;
+    use super::*;
+    use serde_json::json;
+
+    const PLANNING_START: u64 = 1_735_689_600;
+    const HOUR: u64 = 60 * 60;
+
+    type PlannedStop = (PyTruckID, PyTime, PyTerminalID, PyCargoID, bool);
+
+    /// A booking for cargo `cargo_id` from terminal "A" to "B", which
+    /// can be delivered at any time during the planning period
+    fn booking(cargo_id: &str) -> PyBooking {
+        let end = PLANNING_START + 24 * HOUR;
+        PyBooking::new(
+            cargo_id.to_string(),
+            1_000,
+            1,
+            "A".to_string(),
+            "B".to_string(),
+            Some(PyTime(PLANNING_START)),
+            Some(PyTime(end)),
+            Some(PyTime(PLANNING_START)),
+            Some(PyTime(end)),
+            false,
+            None,
+            vec![],
+            1,
+            None,
+            None,
+            None,
+            BTreeMap::new(),
+            vec![],
+            0,
+            None,
+            None,
+            None,
+        )
+        .unwrap()
+    }
+
+    fn truck(starting_terminal: &str) -> PyTruckData {
+        PyTruckData::new(
+            starting_terminal.to_string(),
+            30_000,
+            2,
+            vec![],
+            None,
+            BTreeMap::new(),
+            0,
+            0,
+            None,
+            false,
+            None,
+            1.0,
+        )
+        .unwrap()
+    }
+
+    /// A generator for a day with terminals "A", "B" and "C" at known locations,
+    /// half an hour apart, trucks "K0" and "K1" starting at "C" and `bookings`
+    fn generator(bookings: Vec<PyBooking>) -> ScheduleGenerator {
+        generator_with_trucks(
+            bookings,
+            BTreeMap::from([
+                ("K0".to_string(), truck("C")),
+                ("K1".to_string(), truck("C")),
+            ]),
+        )
+    }
+
+    /// A generator like `generator` with `trucks` instead
+    fn generator_with_trucks(
+        bookings: Vec<PyBooking>,
+        trucks: BTreeMap<PyTruckID, PyTruckData>,
+    ) -> ScheduleGenerator {
+        generator_with_options(bookings, trucks, false)
+    }
+
+    /// A generator like `generator_with_trucks`, relaxing the time windows
+    /// of bookings if `relax_time_windows` is set
+    fn generator_with_options(
+        bookings: Vec<PyBooking>,
+        trucks: BTreeMap<PyTruckID, PyTruckData>,
+        relax_time_windows: bool,
+    ) -> ScheduleGenerator {
+        let planning_period = (PyTime(PLANNING_START), PyTime(PLANNING_START + 24 * HOUR));
+        let terminals = ["A", "B", "C"];
+        let mut generator = ScheduleGenerator::new(
+            PyKeyedRows(
+                terminals
+                    .iter()
+                    .map(|terminal| (terminal.to_string(), planning_period.into()))
+                    .collect(),
+            ),
+            PyKeyedRows(trucks),
+            PyRows(bookings),
+            planning_period,
+            relax_time_windows,
+            BTreeMap::new(),
+            BTreeMap::new(),
+            terminals
+                .iter()
+                .zip([(52.0, 5.0), (52.2, 5.0), (52.0, 5.3)])
+                .map(|(terminal, location)| (terminal.to_string(), location))
+                .collect(),
+            BTreeMap::new(),
+            BTreeMap::new(),
+            None,
+            None,
+        )
+        .unwrap();
+        let terminal_ids: Vec<_> = terminals.iter().map(|id| id.to_string()).collect();
+        let driving_times = terminal_ids
             .iter()
-            .find_map(|(truck, checkpoints)| {
-                if checkpoint_index - num_checkpoints_considered < checkpoints.len() {
-                    return Some((truck, checkpoint_index - num_checkpoints_considered));
-                } else {
-                    num_checkpoints_considered += checkpoints.len();
-                    return None;
-                }
+            .map(|from| {
+                let times = terminal_ids
+                    .iter()
+                    .map(|to| PyTimeDelta(if from == to { 0 } else { HOUR / 2 }))
+                    .collect();
+                (from.clone(), times)
             })
+            .collect();
+        generator
+            .set_driving_times(terminal_ids, PyDrivingTimeTable::Rows(driving_times), None)
             .unwrap();
+        generator
+    }
 
-        let checkpoint = schedule
-            .truck_checkpoints
-            .get(&chosen_truck)
-            .unwrap()
-            .get(chosen_index)
+    /// A generator with terminals "A", "B" and "C" at known locations, truck "K0"
+    /// starting at "C" and `bookings`, asking the driving time sources `providers`
+    fn generator_with_providers(bookings: Vec<PyBooking>, providers: &[&str]) -> ScheduleGenerator {
+        let planning_period = (PyTime(PLANNING_START), PyTime(PLANNING_START + 24 * HOUR));
+        let terminals = ["A", "B", "C"];
+        ScheduleGenerator::new(
+            PyKeyedRows(
+                terminals
+                    .iter()
+                    .map(|terminal| (terminal.to_string(), planning_period.into()))
+                    .collect(),
+            ),
+            PyKeyedRows(BTreeMap::from([("K0".to_string(), truck("C"))])),
+            PyRows(bookings),
+            planning_period,
+            false,
+            BTreeMap::new(),
+            BTreeMap::new(),
+            terminals
+                .iter()
+                .zip([(52.0, 5.0), (52.2, 5.0), (52.0, 5.3)])
+                .map(|(terminal, location)| (terminal.to_string(), location))
+                .collect(),
+            BTreeMap::new(),
+            BTreeMap::new(),
+            Some(providers.iter().map(|name| name.to_string()).collect()),
+            None,
+        )
+        .unwrap()
+    }
+
+    /// The schedules visited by taking `num_neighbours` random steps
+    /// from an empty schedule
+    fn random_walk(generator: &mut ScheduleGenerator, num_neighbours: usize) -> Vec<Schedule> {
+        let mut schedule = generator.empty_schedule();
+        let mut out = vec![];
+        for _ in 0..num_neighbours {
+            generator
+                .find_schedule_neighbour(&mut schedule, 10)
+                .unwrap();
+            let violations = generator.find_violations(&schedule).unwrap();
+            assert!(violations.is_empty(), "{violations:?}");
+            out.push(schedule.clone());
+        }
+        out
+    }
+
+    /// A generator like `generator` with several bookings, driving-hour rules
+    /// and a driver roster, so that every type of move can be made
+    fn generator_with_all_moves() -> ScheduleGenerator {
+        let mut generator = generator((0..6).map(|i| booking(&format!("C{i}"))).collect());
+        generator
+            .set_driving_hour_rules(PyTimeDelta(2 * HOUR), PyTimeDelta(HOUR / 2))
+            .unwrap();
+        generator
+            .set_driver_roster(
+                ["D0", "D1", "D2"]
+                    .map(|driver_id| {
+                        PyDriver::new(
+                            driver_id.to_string(),
+                            PyTime(PLANNING_START),
+                            PyTime(PLANNING_START + 24 * HOUR),
+                            vec![],
+                            None,
+                            None,
+                        )
+                    })
+                    .into(),
+            )
             .unwrap();
-        Some((checkpoint, *chosen_truck, chosen_index))
+        generator
     }
 
-    /// Try to add a random direct delivery; return new schedule if succeeded
-    fn add_random_checkpoint(&mut self, schedule: &Schedule) -> Option<Schedule> {
-        // TODO: pick so that empty trucks have a higher chance of being picked
-        let truck = *self.trucks.iter().choose(&mut self.rng)?;
+    /// `schedule` with its indices rebuilt from its checkpoints
+    fn reindexed(schedule: &Schedule) -> Schedule {
+        let mut out = schedule.clone();
+        out.scheduled_cargo = IdSet::with_capacity(0);
+        out.scheduled_cargo_truck = IdMap::new();
+        for (truck, checkpoints) in schedule.truck_checkpoints.iter() {
+            for checkpoint in checkpoints.iter() {
+                for cargo in checkpoint.pickup_cargo.iter() {
+                    out.assign_cargo(*cargo, *truck);
+                }
+            }
+        }
+        let trucks: Vec<Truck> = schedule.truck_checkpoints.keys().copied().collect();
+        for truck in trucks {
+            out.index_checkpoints(truck);
+        }
+        out
+    }
 
-        // We want to pick an interval between checkpoints to which we will add a new checkpoint
-        // Pick a time uniformly at random and pick the interval containing that time,
-        // so that large intervals are more likely to be chosen, breaking up large intervals.
-        let planning_start_time = self.planning_period.get_start_time();
-        let planning_end_time = self.planning_period.get_end_time();
-        let time_to_identify_gap =
-            (planning_start_time..planning_end_time).choose(&mut self.rng)?;
-        let (prev_checkpoint, next_checkpoint) =
-            schedule.get_checkpoints_around_gap(truck, time_to_identify_gap);
-        let (prev_terminal, next_terminal) =
-            self.get_gap_terminals(truck, prev_checkpoint, next_checkpoint);
+    /// Applies random moves to a schedule, checking after each one that undoing it
+    /// gives back the schedule from before, and that the indices are up to date.
+    /// Returns the types of moves made
+    fn check_undoing_moves(seed: u64, num_moves: usize) -> BTreeSet<&'static str> {
+        let mut generator = generator_with_all_moves().fork(seed);
+        let mut schedule = generator.empty_schedule();
+        let mut kinds = BTreeSet::new();
+        for _ in 0..num_moves {
+            let original = schedule.clone();
+            let mv = generator
+                .find_schedule_neighbour(&mut schedule, 10)
+                .unwrap();
+            kinds.insert(mv.kind());
+            assert!(reindexed(&schedule) == schedule);
 
-        // NOTE: the optimal solution doesn't visit a terminal and do nothing at it.
-        // Each visited terminal should either have a pickup or a dropoff
-        // associated with it
-        // TODO: explore automatically adding that pickup/dropoff
-        let mut possible_terminals = BTreeSet::new();
+            let changed = schedule.clone();
+            mv.undo(&mut schedule);
+            assert!(
+                schedule == original,
+                "undoing {} changed the schedule",
+                mv.kind()
+            );
+            assert!(reindexed(&schedule) == schedule);
+            schedule = changed;
+        }
+        kinds
+    }
 
-        for (cargo, booking_info) in self.cargo_booking_info.iter() {
-            if schedule.scheduled_cargo_truck.contains_key(cargo) {
-                continue;
+    #[test]
+    fn moves_of_every_type_can_be_undone() {
+        assert_eq!(check_undoing_moves(0, 2000).len(), 9);
+    }
+
+    #[test]
+    fn checkpoint_handles_outlive_other_moves() {
+        let mut generator = generator_with_all_moves();
+        let mut schedule = generator.empty_schedule();
+        let (truck_id, handle, terminal) = loop {
+            let mv = generator
+                .find_schedule_neighbour(&mut schedule, 10)
+                .unwrap();
+            if let Move::AddCheckpoint { checkpoint, .. } = &mv {
+                let description = mv.describe(&generator, &schedule);
+                break (
+                    description.truck,
+                    description.checkpoint_handles[0],
+                    checkpoint.terminal,
+                );
             }
-            // disallow picking same terminal as the one before or after, since we want to associate
-            // gaps between checkpoints with driving
-            if booking_info.from != prev_terminal && Some(booking_info.from) != next_terminal {
-                possible_terminals.insert(booking_info.from);
+        };
+
+        let mut was_removed = false;
+        for _ in 0..500 {
+            generator
+                .find_schedule_neighbour(&mut schedule, 10)
+                .unwrap();
+            let index = schedule
+                .get_checkpoint_index(truck_id.clone(), handle, &generator)
+                .unwrap();
+            match index {
+                Some(index) => {
+                    // Handles of removed checkpoints aren't reused
+                    assert!(!was_removed);
+                    let truck = generator.truck_mapper.reverse_map(&truck_id).unwrap();
+                    let checkpoints = schedule.truck_checkpoints.get(&truck).unwrap();
+                    assert_eq!(checkpoints[index].terminal, terminal);
+                }
+                None => was_removed = true,
             }
-            if booking_info.to != prev_terminal && Some(booking_info.to) != next_terminal {
-                // Only schedule the `to` terminal if this truck has visited the
-                // `from` terminal before and so can deliver
-                if let Some(first_from_checkpoint) = schedule
-                    .truck_checkpoints
-                    .get(&truck)
-                    .unwrap()
-                    .iter()
-                    .find(|checkpoint| checkpoint.terminal == booking_info.from)
-                {
-                    if first_from_checkpoint.time < time_to_identify_gap {
-                        possible_terminals.insert(booking_info.to);
-                    }
-                };
+        }
+    }
+
+    #[test]
+    fn dry_runs_leave_the_generator_as_it_was() {
+        let mut generator = generator_with_all_moves().fork(0);
+        let mut schedule = generator.empty_schedule();
+        for _ in 0..50 {
+            generator
+                .find_schedule_neighbour(&mut schedule, 10)
+                .unwrap();
+        }
+
+        let rng = generator.rng.clone();
+        let moves = generator.dry_run_moves(&schedule, 20).unwrap();
+        assert!(generator.rng == rng);
+        assert_eq!(moves.len(), 9);
+        for stats in moves.values() {
+            assert_eq!(stats.attempts, 20);
+            let rejections = stats.rejections.values().sum::<usize>();
+            assert_eq!(stats.successes + rejections, 20);
+        }
+        let successes = |moves: &BTreeMap<&str, MoveTypeStats>| {
+            moves
+                .values()
+                .map(|stats| stats.successes)
+                .collect::<Vec<_>>()
+        };
+        let again = generator.dry_run_moves(&schedule, 20).unwrap();
+        assert_eq!(successes(&again), successes(&moves));
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn undoing_moves_restores_schedules(seed in any::<u64>(), num_moves in 0..100usize) {
+            check_undoing_moves(seed, num_moves);
+        }
+    }
+
+    /// Takes neighbours of `schedule` until `cargo_id` is delivered
+    fn schedule_cargo(
+        generator: &mut ScheduleGenerator,
+        schedule: &Schedule,
+        cargo_id: &str,
+    ) -> Schedule {
+        let cargo: Cargo = generator
+            .cargo_mapper
+            .reverse_map(&cargo_id.to_string())
+            .unwrap();
+        let mut schedule = schedule.clone();
+        for _ in 0..10_000 {
+            if schedule.scheduled_cargo_truck.contains_key(&cargo) {
+                return schedule;
+            }
+            generator
+                .find_schedule_neighbour(&mut schedule, 10)
+                .unwrap();
+        }
+        panic!("Cargo {cargo_id:?} was never scheduled");
+    }
+
+    /// A plan for `schedule_from_plan` from (truck, minutes into the planning period,
+    /// terminal, cargo, is_pickup) tuples
+    fn plan(stops: &[(&str, u64, &str, &str, bool)]) -> Vec<PlannedStop> {
+        stops
+            .iter()
+            .map(|(truck_id, minutes, terminal_id, cargo_id, is_pickup)| {
+                (
+                    truck_id.to_string(),
+                    PyTime(PLANNING_START + minutes * 60),
+                    terminal_id.to_string(),
+                    cargo_id.to_string(),
+                    *is_pickup,
+                )
+            })
+            .collect()
+    }
+
+    /// The error message of making a schedule from `plan`
+    fn plan_error(generator: &ScheduleGenerator, plan: Vec<PlannedStop>) -> String {
+        pyo3::prepare_freethreaded_python();
+        generator
+            .schedule_from_plan(plan, vec![], BTreeMap::new())
+            .err()
+            .unwrap()
+            .to_string()
+    }
+
+    /// A generator with booking "C0", and a schedule in which "K0" picks it up at "A"
+    /// an hour into the planning period and drops it off at "B" an hour later
+    fn planned_delivery() -> (ScheduleGenerator, Schedule) {
+        let generator = generator(vec![booking("C0")]);
+        let schedule = generator
+            .schedule_from_plan(
+                plan(&[("K0", 60, "A", "C0", true), ("K0", 120, "B", "C0", false)]),
+                vec![],
+                BTreeMap::new(),
+            )
+            .unwrap();
+        (generator, schedule)
+    }
+
+    /// Most cargo `truck` carries at once in `schedule`
+    fn max_on_board(schedule: &Schedule, truck: Truck) -> usize {
+        let mut on_board = 0;
+        let mut out = 0;
+        for checkpoint in schedule.truck_checkpoints.get(&truck).unwrap().iter() {
+            on_board -= checkpoint.dropoff_cargo.len();
+            on_board += checkpoint.pickup_cargo.len();
+            out = out.max(on_board);
+        }
+        out
+    }
+
+    #[test]
+    fn locked_cargo_is_only_delivered_by_its_truck() {
+        let mut locked = booking("C1");
+        locked.locked_truck = Some("K1".to_string());
+        let mut generator = generator(vec![booking("C0"), locked]);
+        let k1 = generator
+            .truck_mapper
+            .reverse_map(&"K1".to_string())
+            .unwrap();
+        let c1 = generator
+            .cargo_mapper
+            .reverse_map(&"C1".to_string())
+            .unwrap();
+
+        let schedules = random_walk(&mut generator, 500);
+        assert!(schedules
+            .iter()
+            .any(|schedule| schedule.scheduled_cargo_truck.contains_key(&c1)));
+        for schedule in schedules {
+            if let Some(truck) = schedule.scheduled_cargo_truck.get(&c1) {
+                assert_eq!(*truck, k1);
             }
         }
 
-        let new_terminal = *possible_terminals.iter().choose(&mut self.rng)?;
+        let error = plan_error(
+            &generator,
+            plan(&[("K0", 60, "A", "C1", true), ("K0", 120, "B", "C1", false)]),
+        );
+        assert!(error.contains("locked to truck \"K1\""), "{error}");
+    }
+
+    #[test]
+    fn trucks_keep_to_the_checkpoint_limit_per_day() {
+        let mut generator = generator((0..4).map(|i| booking(&format!("C{i}"))).collect());
+        generator.set_max_checkpoints_per_day(Some(2));
+        let schedules = random_walk(&mut generator, 500);
+        assert!(schedules
+            .iter()
+            .any(|schedule| !schedule.scheduled_cargo_truck.is_empty()));
+        for schedule in schedules {
+            for checkpoints in schedule.truck_checkpoints.values() {
+                assert!(checkpoints.len() <= 2);
+            }
+        }
+
+        let error = plan_error(
+            &generator,
+            plan(&[
+                ("K0", 60, "A", "C0", true),
+                ("K0", 120, "B", "C0", false),
+                ("K0", 180, "A", "C1", true),
+                ("K0", 240, "B", "C1", false),
+            ]),
+        );
+        assert!(error.contains("more than 2 checkpoints"), "{error}");
+    }
+
+    #[test]
+    fn latest_departure_times_can_be_at_the_start_of_a_bucket() {
+        let mut generator = generator(vec![]);
+        generator
+            .set_driving_time_profile(
+                "A".to_string(),
+                "B".to_string(),
+                vec![
+                    (PyTimeDelta(0), PyTimeDelta(2 * HOUR)),
+                    (PyTimeDelta(8 * HOUR), PyTimeDelta(HOUR)),
+                ],
+            )
+            .unwrap();
+        let [a, b]: [Terminal; 2] = ["A", "B"].map(|terminal_id| {
+            generator
+                .terminal_mapper
+                .reverse_map(&terminal_id.to_string())
+                .unwrap()
+        });
+        let cache = &generator.driving_times_cache;
+        // Departing at 08:00 gets there at 09:00, while departing just before takes 2 hours
+        assert_eq!(
+            cache.get_latest_departure_time(a, b, None, PLANNING_START + 9 * HOUR),
+            Ok(PLANNING_START + 8 * HOUR)
+        );
+        assert_eq!(
+            cache.get_latest_departure_time(a, b, None, PLANNING_START + 9 * HOUR - 1),
+            Ok(PLANNING_START + 7 * HOUR - 1)
+        );
+    }
+
+    #[test]
+    fn containers_of_a_booking_can_be_split_across_trucks() {
+        let mut containers = booking("C0");
+        containers.num_containers = 3;
+        let mut generator = generator(vec![containers]);
+        let mut schedule = generator.empty_schedule();
+        for cargo_id in ["C0#0", "C0#1", "C0#2"] {
+            schedule = schedule_cargo(&mut generator, &schedule, cargo_id);
+        }
+        assert!(generator.find_violations(&schedule).unwrap().is_empty());
+        // Each truck can only carry 2 of them at once
+        for schedule in random_walk(&mut generator, 500) {
+            for truck in schedule.truck_checkpoints.keys() {
+                assert!(max_on_board(&schedule, *truck) <= 2);
+            }
+        }
+
+        let split = plan(&[
+            ("K0", 60, "A", "C0#0", true),
+            ("K0", 60, "A", "C0#1", true),
+            ("K0", 120, "B", "C0#0", false),
+            ("K0", 120, "B", "C0#1", false),
+            ("K1", 60, "A", "C0#2", true),
+            ("K1", 120, "B", "C0#2", false),
+        ]);
+        let schedule = generator
+            .schedule_from_plan(split, vec![], BTreeMap::new())
+            .unwrap();
+        assert_eq!(schedule.scheduled_cargo_truck.len(), 3);
+        let error = plan_error(
+            &generator,
+            plan(&[
+                ("K0", 60, "A", "C0#0", true),
+                ("K0", 60, "A", "C0#1", true),
+                ("K0", 60, "A", "C0#2", true),
+                ("K0", 120, "B", "C0#0", false),
+                ("K0", 120, "B", "C0#1", false),
+                ("K0", 120, "B", "C0#2", false),
+            ]),
+        );
+        assert!(error.contains("doesn't have the capacity"), "{error}");
+    }
+
+    #[test]
+    fn deliveries_take_chassis_from_the_pools() {
+        let mut generator = generator(vec![booking("C0"), booking("C1")]);
+        let empty_schedule = generator.empty_schedule();
+        let mut both_delivered = empty_schedule.clone();
+        while both_delivered.scheduled_cargo_truck.len() < 2 {
+            generator
+                .find_schedule_neighbour(&mut both_delivered, 10)
+                .unwrap();
+        }
+        assert!(generator.validate(&both_delivered).unwrap().is_empty());
+
+        // Chassis taken from "A" are left at "B", so only one delivery can be made
+        generator
+            .set_chassis_pools(BTreeMap::from([("A".to_string(), 1)]))
+            .unwrap();
+        let violations = generator.validate(&both_delivered).unwrap();
+        assert_eq!(violations.len(), 1);
+        assert!(violations[0].contains("short of 1 chassis"));
+
+        let mut schedule = schedule_cargo(&mut generator, &empty_schedule, "C0");
+        let availability = generator.get_chassis_availability(&schedule);
+        let available = availability["A"]
+            .iter()
+            .map(|(_time, available)| *available);
+        assert_eq!(available.collect::<Vec<_>>(), vec![1, 0]);
+        for _ in 0..1000 {
+            generator
+                .find_schedule_neighbour(&mut schedule, 10)
+                .unwrap();
+            assert!(schedule.scheduled_cargo_truck.len() <= 1);
+        }
+    }
 
-        let allowed_time_interval = self.get_transit_time_constraints(
-            truck,
-            prev_checkpoint,
-            next_checkpoint,
-            new_terminal,
-        )?;
+    #[test]
+    fn empty_containers_are_returned_after_their_dropoff() {
+        let mut with_empty_return = booking("C0");
+        with_empty_return.empty_return_terminal = Some("C".to_string());
+        let mut generator = generator(vec![with_empty_return]);
+        assert_eq!(generator.booking_cargo["C0"].len(), 2);
+        let schedule = generator.empty_schedule();
+        let schedule = schedule_cargo(&mut generator, &schedule, "C0/empty");
+
+        let cargo = |cargo_id: &str| -> Cargo {
+            generator
+                .cargo_mapper
+                .reverse_map(&cargo_id.to_string())
+                .unwrap()
+        };
+        let terminal = |terminal_id: &str| -> Terminal {
+            generator
+                .terminal_mapper
+                .reverse_map(&terminal_id.to_string())
+                .unwrap()
+        };
+        let (_, full_dropoff_time) = generator
+            .get_cargo_pickup_dropoff_times(&schedule, cargo("C0"))
+            .unwrap();
+        let (empty_pickup_time, _) = generator
+            .get_cargo_pickup_dropoff_times(&schedule, cargo("C0/empty"))
+            .unwrap();
+        assert!(full_dropoff_time < empty_pickup_time);
+        let empty_info = generator
+            .cargo_booking_info
+            .get(&cargo("C0/empty"))
+            .unwrap();
+        assert_eq!(
+            (empty_info.from, empty_info.to),
+            (terminal("B"), terminal("C"))
+        );
 
-        // Otherwise, schedule a checkpoint in this time, if we can
-        let new_time = allowed_time_interval.random_time(&mut self.rng);
+        // Cancelling the booking cancels returning its empty container too
+        let schedule = generator
+            .cancel_booking("C0".to_string(), &schedule)
+            .unwrap();
+        assert!(schedule.scheduled_cargo_truck.is_empty());
+        assert!(generator.validate(&schedule).unwrap().is_empty());
+    }
 
-        let mut out = schedule.clone();
-        let new_deliveries = out.truck_checkpoints.get_mut(&truck).unwrap();
+    #[test]
+    fn trucks_are_limited_in_every_capacity_dimension() {
+        let bookings = (0..3)
+            .map(|i| {
+                let mut pallets = booking(&format!("C{i}"));
+                pallets.demands = BTreeMap::from([("pallets".to_string(), 6)]);
+                pallets
+            })
+            .collect();
+        let mut limited = truck("C");
+        limited.capacities = BTreeMap::from([("pallets".to_string(), 10)]);
+        let mut generator = generator_with_trucks(
+            bookings,
+            BTreeMap::from([("K0".to_string(), truck("C")), ("K1".to_string(), limited)]),
+        );
+        let [k0, k1] = ["K0", "K1"].map(|truck_id| {
+            generator
+                .truck_mapper
+                .reverse_map(&truck_id.to_string())
+                .unwrap()
+        });
+
+        // K1 has weight and TEU for 2 of them, but not the pallets
+        let schedules = random_walk(&mut generator, 500);
+        assert!(schedules
+            .iter()
+            .any(|schedule| max_on_board(schedule, k0) == 2));
+        for schedule in schedules {
+            assert!(max_on_board(&schedule, k1) <= 1);
+        }
+        let together = |truck_id| {
+            plan(&[
+                (truck_id, 60, "A", "C0", true),
+                (truck_id, 60, "A", "C1", true),
+                (truck_id, 120, "B", "C0", false),
+                (truck_id, 120, "B", "C1", false),
+            ])
+        };
+        let error = plan_error(&generator, together("K1"));
+        assert!(error.contains("doesn't have the capacity"), "{error}");
+        // K0 isn't limited in pallets
+        assert!(generator
+            .schedule_from_plan(together("K0"), vec![], BTreeMap::new())
+            .is_ok());
+    }
 
-        // Insert in place of first element after it,
-        // or if all elements are before it, insert it at the end
-        let new_checkpoint_index = new_deliveries
+    #[test]
+    fn trucks_stay_under_their_legal_gross_weight() {
+        let bookings = (0..3)
+            .map(|i| {
+                let mut heavy = booking(&format!("C{i}"));
+                heavy.cargo_weight_kg = 10_000;
+                heavy
+            })
+            .collect();
+        let mut legal = truck("C");
+        legal.tractor_tare_weight_kg = 8_000;
+        legal.trailer_tare_weight_kg = 7_000;
+        legal.max_gross_weight_kg = Some(28_000);
+        let mut generator = generator_with_trucks(
+            bookings,
+            BTreeMap::from([("K0".to_string(), truck("C")), ("K1".to_string(), legal)]),
+        );
+        let [k0, k1] = ["K0", "K1"].map(|truck_id| {
+            generator
+                .truck_mapper
+                .reverse_map(&truck_id.to_string())
+                .unwrap()
+        });
+
+        // K1 can carry 2 of them, but would then be over its legal gross weight
+        let schedules = random_walk(&mut generator, 500);
+        assert!(schedules
             .iter()
-            .position(|checkpoint| checkpoint.time > new_time)
-            .unwrap_or(new_deliveries.len());
+            .any(|schedule| max_on_board(schedule, k0) == 2));
+        for schedule in schedules {
+            assert!(max_on_board(&schedule, k1) <= 1);
+        }
+        let error = plan_error(
+            &generator,
+            plan(&[
+                ("K1", 60, "A", "C0", true),
+                ("K1", 60, "A", "C1", true),
+                ("K1", 120, "B", "C0", false),
+                ("K1", 120, "B", "C1", false),
+            ]),
+        );
+        assert!(error.contains("gross weight 35000kg"), "{error}");
+    }
 
-        // Since we are not loading or unloading anything,
-        // the size/weight are the same
-        let (prev_available_teu, prev_available_weight_kg) =
-            if let Some(prev_checkpoint) = prev_checkpoint {
-                (
-                    prev_checkpoint.available_teu,
-                    prev_checkpoint.available_weight_kg,
-                )
-            } else {
-                // Starting size, weight
-                let truck_data = self.truck_data.get(&truck).unwrap();
-                (truck_data.max_teu, truck_data.max_weight_kg)
-            };
+    #[test]
+    fn cargo_loaded_together_can_be_unloaded_in_any_order() {
+        let checkpoint = |pickup_cargo: &[usize], dropoff_cargo: &[usize]| Checkpoint {
+            time: PLANNING_START,
+            terminal: Terminal::from_id(0),
+            pickup_cargo: pickup_cargo.iter().map(|id| Cargo::from_id(*id)).collect(),
+            dropoff_cargo: dropoff_cargo.iter().map(|id| Cargo::from_id(*id)).collect(),
+            available_capacity: Capacity::from_map(&BTreeMap::new(), 0, 0),
+            duration: 0,
+        };
+        let is_lifo = |checkpoints: Vec<Checkpoint>| {
+            let truck = Truck::from_id(0);
+            let routes: Routes<Truck, Checkpoint> = [(truck, checkpoints)].into_iter().collect();
+            is_last_in_first_out(routes.get(&truck).unwrap())
+        };
+        // Loaded together, so either can be unloaded first
+        assert!(is_lifo(vec![
+            checkpoint(&[0, 1], &[]),
+            checkpoint(&[], &[0]),
+            checkpoint(&[], &[1]),
+        ]));
+        // 2 blocks 0 and 1 until it is unloaded
+        assert!(!is_lifo(vec![
+            checkpoint(&[0, 1], &[]),
+            checkpoint(&[2], &[]),
+            checkpoint(&[], &[0]),
+            checkpoint(&[], &[1, 2]),
+        ]));
+        assert!(is_lifo(vec![
+            checkpoint(&[0, 1], &[]),
+            checkpoint(&[2], &[]),
+            checkpoint(&[], &[0, 2]),
+            checkpoint(&[], &[1]),
+        ]));
+        // Cargo that isn't on the truck can't be unloaded
+        assert!(!is_lifo(vec![
+            checkpoint(&[0], &[]),
+            checkpoint(&[], &[0, 1])
+        ]));
+    }
 
-        new_deliveries.insert(
-            new_checkpoint_index,
-            Checkpoint {
-                time: new_time,
-                terminal: new_terminal,
-                pickup_cargo: BTreeSet::new(),
-                dropoff_cargo: BTreeSet::new(),
-                available_teu: prev_available_teu,
-                available_weight_kg: prev_available_weight_kg,
-                duration: 0,
-            },
+    #[test]
+    fn bookings_with_conflicting_windows_are_relaxed_at_a_penalty() {
+        // Its pickup window is after the end of the planning period
+        let conflicting = || {
+            let mut conflicting = booking("C0");
+            conflicting.pickup_open_time = Some(PyTime(PLANNING_START + 30 * HOUR));
+            conflicting.pickup_close_time = Some(PyTime(PLANNING_START + 31 * HOUR));
+            conflicting
+        };
+        let trucks = || BTreeMap::from([("K0".to_string(), truck("C"))]);
+
+        let skipping = generator_with_options(vec![conflicting()], trucks(), false);
+        assert!(skipping.get_relaxed_cargo_ids().is_empty());
+
+        let mut generator = generator_with_options(vec![conflicting()], trucks(), true);
+        assert_eq!(generator.get_relaxed_cargo_ids(), ["C0"]);
+        let mut schedule = generator.empty_schedule();
+        while schedule.scheduled_cargo_truck.is_empty() {
+            generator
+                .find_schedule_neighbour(&mut schedule, 10)
+                .unwrap();
+        }
+        let deliveries_proportion = generator.compute_scores(&schedule).unwrap()[0];
+        assert!((deliveries_proportion - (1.0 - RELAXED_TIME_WINDOWS_PENALTY)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn breaks_are_checked_against_driving_hour_rules() {
+        // "C" to "A" and "A" to "B" are each half an hour, too long to drive in one go
+        let mut generator = generator_with_trucks(
+            vec![booking("C0")],
+            BTreeMap::from([("K0".to_string(), truck("C"))]),
         );
+        generator
+            .set_driving_hour_rules(PyTimeDelta(40 * 60), PyTimeDelta(HOUR / 2))
+            .unwrap();
+
+        // So every delivery needs a break in between the legs
+        let delivered: Vec<_> = random_walk(&mut generator, 1000)
+            .into_iter()
+            .filter(|schedule| !schedule.scheduled_cargo_truck.is_empty())
+            .collect();
+        assert!(!delivered.is_empty());
+        for schedule in delivered {
+            let breaks = schedule.breaks_to_list_of_tuples(&generator);
+            assert!(!breaks.is_empty());
+            assert!(breaks
+                .iter()
+                .all(|(_, _, duration)| duration.duration == HOUR / 2));
+        }
 
-        self.assert_truck_checkpoints_invariant(&out, truck);
+        let plan = plan(&[("K0", 120, "A", "C0", true), ("K0", 240, "B", "C0", false)]);
+        let with_break = |time: u64| vec![("K0".to_string(), PyTime(time), PyTimeDelta(HOUR / 2))];
+        assert!(generator
+            .schedule_from_plan(plan.clone(), vec![], BTreeMap::new())
+            .is_err());
+
+        // A break in between the legs splits the driving
+        let schedule = generator
+            .schedule_from_plan(
+                plan.clone(),
+                with_break(PLANNING_START + HOUR),
+                BTreeMap::new(),
+            )
+            .unwrap();
+        assert!(generator.validate(&schedule).unwrap().is_empty());
+        let breaks = schedule.breaks_to_list_of_tuples(&generator);
+        assert_eq!(breaks.len(), 1);
+        assert_eq!(breaks[0].1.time, PLANNING_START + HOUR);
+        assert_eq!(breaks[0].2.duration, HOUR / 2);
+
+        // Breaks can't overlap with checkpoints
+        assert!(generator
+            .schedule_from_plan(
+                plan,
+                with_break(PLANNING_START + 2 * HOUR - 10 * 60),
+                BTreeMap::new()
+            )
+            .is_err());
+    }
 
-        // Increase the cached driving time
-        // We are replacing driving A->C with driving A->B->C
-        let mut driving_time = *out.truck_driving_times.get(&truck).unwrap();
-        let prev_terminal = Some(prev_terminal);
-        let terminal = Some(new_terminal);
+    #[test]
+    fn trucks_only_start_driving_when_their_shift_starts() {
+        let mut late = truck("C");
+        late.shift_start_time = Some(PyTime(PLANNING_START + 5 * HOUR));
+        let mut generator = generator_with_trucks(
+            vec![booking("C0"), booking("C1")],
+            BTreeMap::from([("K0".to_string(), truck("C")), ("K1".to_string(), late)]),
+        );
+        let k1 = generator
+            .truck_mapper
+            .reverse_map(&"K1".to_string())
+            .unwrap();
+
+        let schedules = random_walk(&mut generator, 500);
+        assert!(schedules.iter().any(|schedule| !schedule
+            .truck_checkpoints
+            .get(&k1)
+            .unwrap()
+            .is_empty()));
+        for schedule in schedules {
+            if let Some(first_checkpoint) = schedule.truck_checkpoints.get(&k1).unwrap().first() {
+                // It still needs to drive from its starting terminal
+                assert!(first_checkpoint.time > PLANNING_START + 5 * HOUR);
+            }
+        }
+        let error = plan_error(
+            &generator,
+            plan(&[("K1", 60, "A", "C0", true), ("K1", 120, "B", "C0", false)]),
+        );
+        assert!(error.contains("before its shift starts"), "{error}");
+    }
 
-        let time_a_to_c = self.get_driving_time(prev_terminal, next_terminal, truck);
-        let time_a_to_b = self.get_driving_time(prev_terminal, terminal, truck);
-        let time_b_to_c = self.get_driving_time(terminal, next_terminal, truck);
+    #[test]
+    fn qualifications_are_needed_by_trucks_and_drivers() {
+        let mut adr_booking = booking("C0");
+        adr_booking.required_qualifications = vec!["ADR".to_string()];
+        let mut adr_truck = truck("C");
+        adr_truck.qualifications = vec!["ADR".to_string()];
+        let mut generator = generator_with_trucks(
+            vec![adr_booking],
+            BTreeMap::from([
+                ("K0".to_string(), truck("C")),
+                ("K1".to_string(), adr_truck),
+            ]),
+        );
+        let driver = |qualifications: Vec<String>| {
+            PyDriver::new(
+                "D0".to_string(),
+                PyTime(PLANNING_START),
+                PyTime(PLANNING_START + 24 * HOUR),
+                qualifications,
+                None,
+                None,
+            )
+        };
+        generator
+            .set_driver_roster(vec![driver(vec!["ADR".to_string()])])
+            .unwrap();
 
-        driving_time -= time_a_to_c;
-        driving_time += time_a_to_b + time_b_to_c;
-        out.truck_driving_times.insert(truck, driving_time);
+        // Only the truck with the qualification can carry the cargo,
+        // even though the driver has it
+        let [k0, k1]: [Truck; 2] = ["K0", "K1"].map(|truck_id| {
+            generator
+                .truck_mapper
+                .reverse_map(&truck_id.to_string())
+                .unwrap()
+        });
+        let cargo: Cargo = generator
+            .cargo_mapper
+            .reverse_map(&"C0".to_string())
+            .unwrap();
+        let booking_info = generator.cargo_booking_info.get(&cargo).unwrap().clone();
+        assert!(!generator.can_carry(k0, &booking_info));
+        assert!(generator.can_carry(k1, &booking_info));
+        let schedule = random_walk(&mut generator, 1000)
+            .into_iter()
+            .find(|schedule| schedule.scheduled_cargo_truck.contains_key(&cargo))
+            .unwrap();
 
-        return Some(out);
+        // The driver on duty needs it as well
+        generator.set_driver_roster(vec![driver(vec![])]).unwrap();
+        assert!(generator
+            .find_violations(&schedule)
+            .unwrap()
+            .iter()
+            .any(|violation| violation.contains("driver \"D0\"")));
     }
 
-    /// Pick a random checkpoint and remove it
-    fn remove_random_checkpoint(&mut self, schedule: &Schedule) -> Option<Schedule> {
-        let (checkpoint, chosen_truck, chosen_index) = self.get_random_checkpoint(schedule)?;
-        // To avoid easily undoing progress, only allow removing checkpoint if there is no cargo
-        // pickup or dropoff in it
+    #[test]
+    fn checkpoints_leave_buffer_time_after_driving() {
+        let mut generator = generator((0..3).map(|i| booking(&format!("C{i}"))).collect());
+        generator
+            .set_buffer_times(
+                PyTimeDelta(HOUR / 2),
+                BTreeMap::from([("B".to_string(), PyTimeDelta(2 * HOUR))]),
+            )
+            .unwrap();
+        let b = generator
+            .terminal_mapper
+            .reverse_map(&"B".to_string())
+            .unwrap();
 
-        // TODO: maybe it is faster to list all checkpoints without pickups or dropoffs and
-        // then pick randomly among them
-        if !checkpoint.pickup_cargo.is_empty() || !checkpoint.dropoff_cargo.is_empty() {
-            return None;
+        for schedule in random_walk(&mut generator, 500) {
+            for (truck, checkpoints) in schedule.truck_checkpoints.iter() {
+                let mut terminal = generator.truck_data.get(truck).unwrap().starting_terminal;
+                let mut departure_time = generator.get_truck_start_time(*truck);
+                for checkpoint in checkpoints.iter() {
+                    let driving_time = if terminal == checkpoint.terminal {
+                        0
+                    } else {
+                        HOUR / 2
+                    };
+                    let buffer_time = if checkpoint.terminal == b {
+                        2 * HOUR
+                    } else {
+                        HOUR / 2
+                    };
+                    assert!(departure_time + driving_time + buffer_time <= checkpoint.time);
+                    terminal = checkpoint.terminal;
+                    departure_time = checkpoint.time + checkpoint.duration;
+                }
+            }
         }
 
-        // TODO: make the clones cheaper
-        let mut out = schedule.clone();
+        // Driving from C to A and from A to B takes half an hour
+        let with_arrival_at_b = |minutes| {
+            plan(&[
+                ("K0", 60, "A", "C0", true),
+                ("K0", minutes, "B", "C0", false),
+            ])
+        };
+        let error = plan_error(&generator, with_arrival_at_b(120));
+        assert!(error.contains("buffer times"), "{error}");
+        assert!(generator
+            .schedule_from_plan(with_arrival_at_b(210), vec![], BTreeMap::new())
+            .is_ok());
+    }
 
-        // Check that removing this checkpoint won't leave us
-        // with 2 consecutive checkpoints with the same terminals
-        let (prev_checkpoint, next_checkpoint) =
-            schedule.get_prev_and_next_checkpoints(chosen_truck, checkpoint);
-        let (prev_terminal, next_terminal) =
-            self.get_gap_terminals(chosen_truck, prev_checkpoint, next_checkpoint);
-        if Some(prev_terminal) == next_terminal {
-            return None;
+    #[test]
+    fn schedules_round_trip_through_json() {
+        let mut generator = generator(vec![booking("C0"), booking("C1")]);
+        let mut schedule = generator.empty_schedule();
+        while schedule.scheduled_cargo_truck.len() < 2 {
+            generator
+                .find_schedule_neighbour(&mut schedule, 10)
+                .unwrap();
         }
 
-        // Remove the checkpoint
-        out.truck_checkpoints
-            .get_mut(&chosen_truck)
-            .unwrap()
-            .remove(chosen_index);
+        let json = schedule.to_json(&generator).unwrap();
+        let loaded = Schedule::from_json(&generator, &json).unwrap();
+        assert_eq!(loaded.to_json(&generator).unwrap(), json);
+        assert_eq!(loaded.scheduled_cargo_truck, schedule.scheduled_cargo_truck);
+    }
 
-        self.assert_truck_checkpoints_invariant(&out, chosen_truck);
+    #[test]
+    fn schedules_without_pickups_are_rejected() {
+        let generator = generator(vec![booking("C0")]);
+        let checkpoint = |terminal: &str, time: u64, pickups: &[&str], dropoffs: &[&str]| {
+            json!({
+                "terminal": terminal,
+                "time": time,
+                "duration": 0,
+                "pickup_cargo": pickups,
+                "dropoff_cargo": dropoffs,
+            })
+        };
+        let schedule_json = |checkpoints: Vec<serde_json::Value>| {
+            json!({ "truck_checkpoints": { "K0": checkpoints } }).to_string()
+        };
 
-        // Reduce the cached driving time
-        // We are replacing driving A->B->C with driving A->C
-        let mut driving_time = *out.truck_driving_times.get(&chosen_truck).unwrap();
-        let (prev_checkpoint, next_checkpoint) =
-            schedule.get_prev_and_next_checkpoints(chosen_truck, checkpoint);
-        let prev_terminal = prev_checkpoint.map(|c| c.terminal);
-        let terminal = Some(checkpoint.terminal);
-        let next_terminal = next_checkpoint.map(|c| c.terminal);
+        let dropoff_only =
+            schedule_json(vec![checkpoint("B", PLANNING_START + HOUR, &[], &["C0"])]);
+        assert!(Schedule::from_json(&generator, &dropoff_only).is_err());
+        let pickup_only = schedule_json(vec![checkpoint("A", PLANNING_START + HOUR, &["C0"], &[])]);
+        assert!(Schedule::from_json(&generator, &pickup_only).is_err());
+        let dropoff_first = schedule_json(vec![
+            checkpoint("B", PLANNING_START + HOUR, &[], &["C0"]),
+            checkpoint("A", PLANNING_START + 2 * HOUR, &["C0"], &[]),
+        ]);
+        assert!(Schedule::from_json(&generator, &dropoff_first).is_err());
+        let delivery = schedule_json(vec![
+            checkpoint("A", PLANNING_START + HOUR, &["C0"], &[]),
+            checkpoint("B", PLANNING_START + 2 * HOUR, &[], &["C0"]),
+        ]);
+        assert!(Schedule::from_json(&generator, &delivery).is_ok());
+    }
 
-        let time_a_to_c = self.get_driving_time(prev_terminal, next_terminal, chosen_truck);
-        let time_a_to_b = self.get_driving_time(prev_terminal, terminal, chosen_truck);
-        let time_b_to_c = self.get_driving_time(terminal, next_terminal, chosen_truck);
+    #[test]
+    fn on_time_probability_accounts_for_slack_and_durations() {
+        // Picks up "C0" at "A" for `duration`, and drops it off at "B" at the last
+        // second of its time window, `slack` later than the truck can get there
+        let probability = |duration: u64, slack: i64| {
+            let pickup_time = PLANNING_START + 2 * HOUR;
+            let mut generator = generator(vec![]);
+            let [a, b] = ["A", "B"].map(|terminal_id| {
+                generator
+                    .terminal_mapper
+                    .reverse_map(&terminal_id.to_string())
+                    .unwrap()
+            });
+            let truck = generator
+                .truck_mapper
+                .reverse_map(&"K0".to_string())
+                .unwrap();
+            let driving_time = generator
+                .get_truck_driving_time_at(truck, a, b, pickup_time + duration)
+                .unwrap();
+            let dropoff_time = (pickup_time + duration + driving_time)
+                .checked_add_signed(slack)
+                .unwrap();
 
-        driving_time += time_a_to_c;
-        driving_time -= time_a_to_b + time_b_to_c;
-        out.truck_driving_times.insert(chosen_truck, driving_time);
+            let mut tight_booking = booking("C0");
+            tight_booking.dropoff_close_time = Some(PyTime(dropoff_time + 1));
+            generator.add_booking(tight_booking).unwrap();
+            generator
+                .set_driving_time_spreads(BTreeMap::from([(
+                    ("A".to_string(), "B".to_string()),
+                    PyTimeDelta(600),
+                )]))
+                .unwrap();
+            let schedule = Schedule::from_json(
+                &generator,
+                &json!({
+                    "truck_checkpoints": { "K0": [
+                        {
+                            "terminal": "A",
+                            "time": pickup_time,
+                            "duration": duration,
+                            "pickup_cargo": ["C0"],
+                            "dropoff_cargo": [],
+                        },
+                        {
+                            "terminal": "B",
+                            "time": dropoff_time,
+                            "duration": 0,
+                            "pickup_cargo": [],
+                            "dropoff_cargo": ["C0"],
+                        },
+                    ]},
+                })
+                .to_string(),
+            )
+            .unwrap();
+            generator
+                .estimate_on_time_probability(&schedule, 100, 0)
+                .unwrap()
+        };
 
-        return Some(out);
+        // An hour of slack makes up for a driving time that is 10 minutes off
+        assert_eq!(probability(30 * 60, HOUR as i64), 1.0);
+        // The truck only leaves after the checkpoint's duration
+        assert_eq!(probability(2 * HOUR, -(HOUR as i64)), 0.0);
     }
 
-    /// Remove pickup and dropoff for a piece of cargo
-    fn remove_random_delivery(&mut self, schedule: &Schedule) -> Option<Schedule> {
-        let (cargo, truck) = schedule
-            .scheduled_cargo_truck
-            .iter()
-            .choose(&mut self.rng)?;
-        let mut out = schedule.clone();
+    #[test]
+    fn generators_can_be_created_from_tables() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            // Like a pyarrow table with `columns`, which only need python literals
+            let table = |columns: serde_json::Value| {
+                let code = format!("type('Table', (), {{'to_pydict': lambda self: {columns}}})()");
+                py.eval(&std::ffi::CString::new(code).unwrap(), None, None)
+                    .unwrap()
+            };
+            let terminals = table(json!({
+                "id": ["A", "B", "C"],
+                "opening_time": [PLANNING_START, PLANNING_START, PLANNING_START],
+                "closing_time": [PLANNING_START + 12 * HOUR, PLANNING_START + 24 * HOUR, PLANNING_START + 24 * HOUR],
+            }));
+            let trucks = table(json!({
+                "id": ["K0"],
+                "starting_terminal": ["C"],
+                "max_weight_kg": [30_000],
+                "max_teu": [2],
+            }));
+            let bookings = table(json!({
+                "cargo": ["C0"],
+                "from_terminal": ["A"],
+                "to_terminal": ["B"],
+            }));
+
+            let generator = ScheduleGenerator::new(
+                terminals.extract().unwrap(),
+                trucks.extract().unwrap(),
+                bookings.extract().unwrap(),
+                (PyTime(PLANNING_START), PyTime(PLANNING_START + 24 * HOUR)),
+                false,
+                BTreeMap::new(),
+                BTreeMap::new(),
+                BTreeMap::new(),
+                BTreeMap::new(),
+                BTreeMap::new(),
+                None,
+                None,
+            )
+            .unwrap();
+            assert!(generator.booking_cargo.contains_key("C0"));
+            let terminal = generator
+                .terminal_mapper
+                .reverse_map(&"A".to_string())
+                .unwrap();
+            assert_eq!(
+                generator
+                    .terminal_open_intervals
+                    .get(&terminal)
+                    .unwrap()
+                    .get_intervals()[0]
+                    .get_end_time(),
+                PLANNING_START + 12 * HOUR
+            );
 
-        let checkpoints = out.truck_checkpoints.get_mut(&truck).unwrap();
+            // Dicts of terminals work as before
+            let terminals = py.eval(c"{'A': (0, 60)}", None, None).unwrap();
+            let terminals: PyKeyedRows<PyTerminalTimes> = terminals.extract().unwrap();
+            assert_eq!(terminals.0.len(), 1);
+        });
+    }
 
-        // Remove all references to this cargo in truck
-        let (start_checkpoint_index, start_checkpoint) = checkpoints
-            .iter_mut()
-            .enumerate()
-            .find(|(_, checkpoint)| checkpoint.pickup_cargo.contains(cargo))
+    #[test]
+    fn invalid_bookings_are_not_saved() {
+        let mut generator = generator(vec![booking("C0")]);
+        let mut locked_booking = booking("C1");
+        locked_booking.locked_truck = Some("unknown".to_string());
+        assert!(generator.add_booking(locked_booking).is_err());
+
+        let json = generator.to_json().unwrap();
+        assert!(!json.contains("\"C1\""));
+        let loaded = ScheduleGenerator::from_json(&json).unwrap();
+        assert_eq!(loaded.to_json().unwrap(), json);
+    }
+
+    #[test]
+    fn invalid_amendments_keep_the_old_booking() {
+        let mut generator = generator(vec![booking("C0")]);
+        let cargo: Cargo = generator
+            .cargo_mapper
+            .reverse_map(&"C0".to_string())
             .unwrap();
-        assert!(start_checkpoint.pickup_cargo.remove(cargo));
-        assert!(
-            checkpoints
-                .iter()
-                .filter(|checkpoint| checkpoint.pickup_cargo.contains(cargo))
-                .count()
-                == 0
+        let schedule = random_walk(&mut generator, 1000)
+            .into_iter()
+            .find(|schedule| schedule.scheduled_cargo_truck.contains_key(&cargo))
+            .unwrap();
+        let scores = generator.compute_scores(&schedule).unwrap();
+
+        let mut amended_booking = booking("C0");
+        amended_booking.locked_truck = Some("unknown".to_string());
+        assert!(generator.amend_booking(amended_booking, &schedule).is_err());
+        assert!(generator.booking_cargo.contains_key("C0"));
+        assert_eq!(generator.compute_scores(&schedule).unwrap(), scores);
+        assert!(generator.find_violations(&schedule).unwrap().is_empty());
+
+        let mut amended_booking = booking("C0");
+        amended_booking.to_terminal = "C".to_string();
+        let amended = generator.amend_booking(amended_booking, &schedule).unwrap();
+        assert!(amended.scheduled_cargo_truck.is_empty());
+        let cargo = generator.booking_cargo.get("C0").unwrap()[0];
+        assert_eq!(
+            generator.cargo_booking_info.get(&cargo).unwrap().to,
+            generator
+                .terminal_mapper
+                .reverse_map(&"C".to_string())
+                .unwrap()
         );
+    }
 
-        let (end_checkpoint_index, end_checkpoint) = checkpoints
-            .iter_mut()
-            .enumerate()
-            .find(|(_, checkpoint)| checkpoint.dropoff_cargo.contains(cargo))
-            .unwrap();
-        assert!(end_checkpoint.dropoff_cargo.remove(cargo));
+    #[test]
+    fn stops_of_a_booking_are_visited_in_order() {
+        // Picks up at "A", then at "C", before dropping everything off at "B"
+        let mut with_stop = booking("C0");
+        with_stop.stops = vec![(
+            "C".to_string(),
+            PyTime(PLANNING_START),
+            PyTime(PLANNING_START + 24 * HOUR),
+            true,
+        )];
+        let mut generator = generator(vec![with_stop]);
+        assert_eq!(generator.booking_cargo["C0"].len(), 2);
+        let [main_cargo, stop_cargo]: [Cargo; 2] = ["C0", "C0@0"].map(|cargo_id| {
+            generator
+                .cargo_mapper
+                .reverse_map(&cargo_id.to_string())
+                .unwrap()
+        });
+
+        // Delivering both of them needs a single truck visiting "A" before "C"
+        let schedules: Vec<_> = random_walk(&mut generator, 1000)
+            .into_iter()
+            .filter(|schedule| schedule.scheduled_cargo_truck.len() == 2)
+            .collect();
+        assert!(!schedules.is_empty());
+        for schedule in schedules {
+            assert!(generator.find_stop_order_violations(&schedule).is_empty());
+            assert_eq!(
+                schedule.scheduled_cargo_truck.get(&main_cargo),
+                schedule.scheduled_cargo_truck.get(&stop_cargo)
+            );
+            let (main_pickup_time, _) = generator
+                .get_cargo_pickup_dropoff_times(&schedule, main_cargo)
+                .unwrap();
+            let (stop_pickup_time, _) = generator
+                .get_cargo_pickup_dropoff_times(&schedule, stop_cargo)
+                .unwrap();
+            assert!(main_pickup_time < stop_pickup_time);
+        }
+
+        // Visiting the stop before the pickup of the booking breaks the order
+        let error = plan_error(
+            &generator,
+            plan(&[
+                ("K0", 60, "C", "C0@0", true),
+                ("K0", 120, "A", "C0", true),
+                ("K0", 180, "B", "C0", false),
+                ("K0", 180, "B", "C0@0", false),
+            ]),
+        );
         assert!(
-            checkpoints
-                .iter()
-                .filter(|checkpoint| checkpoint.dropoff_cargo.contains(cargo))
-                .count()
-                == 0
+            error.contains("Stops of the booking for cargo \"C0\" are not visited in order"),
+            "{error}"
         );
+        let in_order = plan(&[
+            ("K0", 60, "A", "C0", true),
+            ("K0", 120, "C", "C0@0", true),
+            ("K0", 180, "B", "C0", false),
+            ("K0", 180, "B", "C0@0", false),
+        ]);
+        let schedule = generator
+            .schedule_from_plan(in_order, vec![], BTreeMap::new())
+            .unwrap();
+        assert!(generator.find_stop_order_violations(&schedule).is_empty());
+    }
 
-        // Modify the weights and sizes
-        let checkpoints = out.truck_checkpoints.get_mut(truck).unwrap();
-        let booking_info = self.cargo_booking_info.get(&cargo).unwrap();
-        let truck_data = self.truck_data.get(truck).unwrap();
-        for checkpoint in &mut checkpoints[start_checkpoint_index..end_checkpoint_index] {
-            checkpoint.available_weight_kg += booking_info.weight_kg;
-            assert!(checkpoint.available_weight_kg <= truck_data.max_weight_kg);
+    #[test]
+    fn cargo_of_the_highest_priority_can_be_scheduled() {
+        let mut urgent = booking("C1");
+        urgent.priority = usize::MAX;
+        let mut generator = generator(vec![booking("C0"), urgent]);
+        let schedule = random_walk(&mut generator, 1000)
+            .into_iter()
+            .find(|schedule| schedule.scheduled_cargo_truck.len() == 2)
+            .unwrap();
+        assert_eq!(generator.compute_scores(&schedule).unwrap()[0], 1.0);
+    }
 
-            checkpoint.available_teu += booking_info.teu;
-            assert!(checkpoint.available_teu <= truck_data.max_teu);
+    #[test]
+    fn slow_trucks_take_longer_to_drive() {
+        let mut slow = truck("C");
+        slow.speed_factor = 0.5;
+        let mut generator = generator_with_trucks(
+            vec![booking("C0")],
+            BTreeMap::from([("K0".to_string(), truck("C")), ("K1".to_string(), slow)]),
+        );
+        let truck = |truck_id: &str| {
+            generator
+                .truck_mapper
+                .reverse_map(&truck_id.to_string())
+                .unwrap()
+        };
+        let terminal = |terminal_id: &str| {
+            generator
+                .terminal_mapper
+                .reverse_map(&terminal_id.to_string())
+                .unwrap()
+        };
+        let driving_time = |truck_id| {
+            generator.get_truck_driving_time_at(
+                truck(truck_id),
+                terminal("C"),
+                terminal("A"),
+                PLANNING_START,
+            )
+        };
+        assert_eq!(driving_time("K0"), Ok(HOUR / 2));
+        assert_eq!(driving_time("K1"), Ok(HOUR));
+
+        // The schedules found by the neighbour search leave the slow truck time to drive
+        let (k1, c) = (truck("K1"), terminal("C"));
+        for schedule in random_walk(&mut generator, 500) {
+            let checkpoints = schedule.truck_checkpoints.get(&k1).unwrap();
+            if let Some(first_checkpoint) = checkpoints
+                .iter()
+                .find(|checkpoint| checkpoint.terminal != c)
+            {
+                assert!(first_checkpoint.time >= PLANNING_START + HOUR);
+            }
         }
 
-        out.scheduled_cargo_truck.remove(cargo);
-
-        Some(out)
+        let arriving_at_a = |truck_id| {
+            plan(&[
+                (truck_id, 40, "A", "C0", true),
+                (truck_id, 180, "B", "C0", false),
+            ])
+        };
+        assert!(generator
+            .schedule_from_plan(arriving_at_a("K0"), vec![], BTreeMap::new())
+            .is_ok());
+        let error = plan_error(&generator, arriving_at_a("K1"));
+        assert!(error.contains("doesn't have time to drive"), "{error}");
     }
 
-    /// Given an  old checkpoint and new pickup and dropoff for it,
-    /// finds a random time it can be rescheduled to. Keeps the relative
-    /// order of all checkpoints the same
-    fn find_random_reschedule_time(
-        &mut self,
-        schedule: &Schedule,
-        truck: Truck,
-        old_checkpoint_index: usize,
-        new_pickup: &BTreeSet<Cargo>,
-        new_dropoff: &BTreeSet<Cargo>,
-    ) -> Option<Time> {
-        let old_checkpoint = schedule
-            .truck_checkpoints
-            .get(&truck)
-            .unwrap()
-            .get(old_checkpoint_index)
+    #[test]
+    fn bookings_added_later_can_be_scheduled() {
+        let mut generator = generator(vec![]);
+        let schedule = generator.empty_schedule();
+        assert!(generator.add_booking(booking("C0")).unwrap());
+        let c0 = generator
+            .cargo_mapper
+            .reverse_map(&"C0".to_string())
             .unwrap();
-        let pickup_restriction_intervals = new_pickup
-            .iter()
-            .map(|cargo| self.pickup_times.get(cargo).unwrap())
-            .intersect_all();
-        let dropoff_restriction_intervals = new_dropoff
+        let (a, b) = (
+            generator
+                .terminal_mapper
+                .reverse_map(&"A".to_string())
+                .unwrap(),
+            generator
+                .terminal_mapper
+                .reverse_map(&"B".to_string())
+                .unwrap(),
+        );
+        assert!(generator.cargo_by_terminals[&(a, b)].contains(&c0));
+        assert!(generator
+            .inputs
+            .booking_data
             .iter()
-            .map(|cargo| self.dropoff_times.get(cargo).unwrap())
-            .intersect_all();
+            .any(|booking| booking.cargo == "C0"));
 
-        let (checkpoint_before, checkpoint_after) =
-            schedule.get_prev_and_next_checkpoints(truck, old_checkpoint);
+        // Schedules made before the booking was added can deliver it
+        let schedule = schedule_cargo(&mut generator, &schedule, "C0");
+        assert!(generator.find_violations(&schedule).unwrap().is_empty());
 
-        let driving_restriction_intervals =
-            IntervalWithDataChain::from_interval(self.get_transit_time_constraints(
-                truck,
-                checkpoint_before,
-                checkpoint_after,
-                old_checkpoint.terminal,
-            )?);
+        assert!(generator.add_booking(booking("C0")).is_err());
+        let mut unknown_terminal = booking("C1");
+        unknown_terminal.to_terminal = "X".to_string();
+        assert!(generator.add_booking(unknown_terminal).is_err());
+        assert!(!generator.booking_cargo.contains_key("C1"));
+    }
 
-        let allowed_intervals = [
-            pickup_restriction_intervals,
-            dropoff_restriction_intervals,
-            driving_restriction_intervals,
-            IntervalWithDataChain::from_interval(self.planning_period.clone()),
-        ]
-        .iter()
-        .intersect_all();
+    #[test]
+    fn removing_a_booking_drops_checkpoints_only_serving_it() {
+        let mut to_c = booking("C1");
+        to_c.to_terminal = "C".to_string();
+        let mut generator = generator(vec![booking("C0"), to_c]);
+        // Both are picked up together, but only C0 is dropped off at B
+        let plan = plan(&[
+            ("K0", 60, "A", "C0", true),
+            ("K0", 60, "A", "C1", true),
+            ("K0", 120, "B", "C0", false),
+            ("K0", 180, "C", "C1", false),
+        ]);
+        let schedule = generator
+            .schedule_from_plan(plan, vec![], BTreeMap::new())
+            .unwrap();
+        let truck = generator
+            .truck_mapper
+            .reverse_map(&"K0".to_string())
+            .unwrap();
+        let old_driving_time = *schedule.truck_driving_times.get(&truck).unwrap();
 
-        let new_interval = allowed_intervals
-            .get_intervals()
+        let out = generator
+            .remove_booking("C0".to_string(), &schedule)
+            .unwrap();
+        let terminals: Vec<_> = out
+            .truck_checkpoints
+            .get(&truck)
+            .unwrap()
             .iter()
-            .choose(&mut self.rng)?;
-        let new_time =
-            (new_interval.get_start_time()..new_interval.get_end_time()).choose(&mut self.rng)?;
-
-        // TODO: implement this instead
-        // // Pick a time in the allowed intervals uniformly,
-        // // so that the sub-interval that is larger (and so offers more flexibility)
-        // // is more likely to be picked
-        //
-        // // This is a measure of "how much we are away from the start",
-        // // only measuring the time contained in the intervals. For example,
-        // // if for intervals [1, 3), [10, 4), this value is 5, then
-        // // we have "moved past" the 2 timesteps in the first interval,
-        // // and are on the 3rd time step in the second interval
-        // // We will then convert this to actual time.
-        // let new_time_index = (0..allowed_intervals.total_length()).choose(&mut self.rng);
-        Some(new_time)
+            .map(|checkpoint| generator.terminal_mapper.map(&checkpoint.terminal).unwrap())
+            .collect();
+        assert_eq!(terminals, ["A", "C"]);
+        let first = out.truck_checkpoints.get(&truck).unwrap()[0].clone();
+        assert_eq!(first.pickup_cargo.len(), 1);
+        assert!(!generator.booking_cargo.contains_key("C0"));
+
+        // The truck no longer drives via B
+        let driving_time = *out.truck_driving_times.get(&truck).unwrap();
+        assert!(driving_time < old_driving_time);
+        assert_eq!(
+            driving_time,
+            generator.get_route_driving_time(&out, truck).unwrap()
+        );
+        assert!(generator.find_violations(&out).unwrap().is_empty());
     }
 
-    /// Add a random cargo pickup-dropoff pair to two checkpoints.
-    /// If necessary, move checkpoints to allow this to be done
-    fn add_random_delivery(&mut self, schedule: &Schedule) -> Option<Schedule> {
-        // Pick a random truck, see what cargo it can deliver based on what terminals
-        // it is visiting
-        let (truck, checkpoints) = schedule.truck_checkpoints.iter().choose(&mut self.rng)?;
-
-        // See what undelivered cargo can be delivered between these terminals
-
-        // TODO: limit the gap between (from, to) as a heuristic: it is unlikely
-        // that a truck will pick up a cargo, drive for a very long time,
-        // then drop it off
+    #[test]
+    fn traffic_spreads_and_routes_are_saved() {
+        let mut generator = generator(vec![booking("C0")]);
+        let (a, b) = ("A".to_string(), "B".to_string());
+        let morning = (PLANNING_START.into(), (PLANNING_START + 2 * HOUR).into());
+        let evening = (
+            (PLANNING_START + 16 * HOUR).into(),
+            (PLANNING_START + 18 * HOUR).into(),
+        );
+        generator
+            .apply_traffic_factor(a.clone(), b.clone(), 1.5, morning, None)
+            .unwrap();
+        generator
+            .apply_traffic_factor(a.clone(), b.clone(), 2.0, evening, None)
+            .unwrap();
+        generator
+            .set_driving_time_spreads(BTreeMap::from([((a.clone(), b.clone()), PyTimeDelta(60))]))
+            .unwrap();
+        generator
+            .set_route_geometry(a, b, vec![(52.0, 4.0), (52.5, 4.5)])
+            .unwrap();
 
-        // A map from unscheduled cargo which can be taken by this truck
-        // to a collection of (pickup_checkpoint, dropoff_checkpoint)
-        let mut available_cargo_checkpoints = BTreeMap::new();
-        for (start_checkpoint_index, start_checkpoint) in checkpoints.iter().enumerate() {
-            // Look at all terminals after this
-            for end_checkpoint_index in (start_checkpoint_index + 1)..checkpoints.len() {
-                let end_checkpoint = checkpoints.get(end_checkpoint_index).unwrap();
-                let start_terminal = start_checkpoint.terminal;
-                let end_terminal = end_checkpoint.terminal;
+        let json = generator.to_json().unwrap();
+        let loaded = ScheduleGenerator::from_json(&json).unwrap();
+        assert_eq!(loaded.to_json().unwrap(), json);
+        let cache = &loaded.driving_times_cache;
+        assert_eq!(
+            cache.get_traffic_factors(),
+            generator.driving_times_cache.get_traffic_factors()
+        );
+        assert_eq!(
+            cache.get_spreads(),
+            generator.driving_times_cache.get_spreads()
+        );
+        assert_eq!(loaded.route_geometries, generator.route_geometries);
+    }
 
-                // If we found some,
-                if let Some(cargo_collection) =
-                    self.cargo_by_terminals.get(&(start_terminal, end_terminal))
-                {
-                    // Record all cargo that hasn't been scheduled yet
-                    for cargo in cargo_collection.iter() {
-                        if !schedule.scheduled_cargo_truck.contains_key(&cargo) {
-                            available_cargo_checkpoints
-                                .entry(*cargo)
-                                .or_insert(BTreeSet::new())
-                                .insert((
-                                    start_checkpoint,
-                                    end_checkpoint,
-                                    start_checkpoint_index,
-                                    end_checkpoint_index,
-                                ));
-                        }
-                    }
-                }
-            }
-        }
+    #[test]
+    fn trucks_at_unknown_terminals_are_rejected() {
+        let mut generator = generator(vec![booking("C0")]);
+        let schedule = generator.empty_schedule();
+        assert!(generator
+            .add_truck("K2".to_string(), truck("D"), &schedule)
+            .is_err());
+        let terminal: Option<Terminal> = generator.terminal_mapper.reverse_map(&"D".to_string());
+        assert!(terminal.is_none());
+        let truck: Option<Truck> = generator.truck_mapper.reverse_map(&"K2".to_string());
+        assert!(truck.is_none());
+    }
 
-        // Pick random cargo and a random pair of checkpoints to deliver between
-        let (chosen_cargo, chosen_checkpoint_pairs) =
-            available_cargo_checkpoints.iter().choose(&mut self.rng)?;
-        assert!(!schedule.scheduled_cargo_truck.contains_key(chosen_cargo));
-        // TODO: if the same start_checkpoint/end_checkpoint appears multiple times,
-        // then the shortest delivery is always optimal, so disregard others.
-        // E.g. if the truck goes A->B->C->A->B, and we want to deliver A->B,
-        // it is always better to drive A->B than A->B->C->A->B
-        // We will want to implement this in the future
-        let (start_checkpoint, end_checkpoint, start_checkpoint_index, end_checkpoint_index) =
-            chosen_checkpoint_pairs
-                .iter()
-                .choose(&mut self.rng)
+    #[test]
+    fn removing_a_truck_unschedules_dependent_cargo() {
+        let mut returned_booking = booking("C0");
+        returned_booking.empty_return_terminal = Some("C".to_string());
+        let mut generator = generator(vec![returned_booking]);
+        let [full, empty] = ["C0", "C0/empty"].map(|cargo_id| -> Cargo {
+            generator
+                .cargo_mapper
+                .reverse_map(&cargo_id.to_string())
+                .unwrap()
+        });
+
+        // Find a schedule where another truck returns the empty container
+        let mut schedule = generator.empty_schedule();
+        while schedule.scheduled_cargo_truck.get(&full).is_none()
+            || schedule.scheduled_cargo_truck.get(&empty).is_none()
+            || schedule.scheduled_cargo_truck.get(&full)
+                == schedule.scheduled_cargo_truck.get(&empty)
+        {
+            generator
+                .find_schedule_neighbour(&mut schedule, 10)
                 .unwrap();
+        }
+        let truck = *schedule.scheduled_cargo_truck.get(&full).unwrap();
+        let truck_id = generator.truck_mapper.map(&truck).unwrap().clone();
+
+        let removed = generator.remove_truck(truck_id.clone(), &schedule).unwrap();
+        assert!(removed.scheduled_cargo_truck.is_empty());
+        assert!(generator.validate(&removed).unwrap().is_empty());
+
+        // The old schedule still has the removed truck
+        assert!(generator.remove_truck(truck_id, &schedule).is_err());
+        assert!(generator
+            .cancel_booking("C0".to_string(), &schedule)
+            .is_err());
+    }
 
-        let chosen_cargo = *chosen_cargo;
-        let start_checkpoint_index = *start_checkpoint_index;
-        let end_checkpoint_index = *end_checkpoint_index;
-
-        // Find the intervals when these checkpoints can be moved to
-        // Consider restrictions due to being able to pick up all items,
-        // drop off all items and drive to and from checkpoint
-        // TODO: it might make sense to cache this
-
-        // TODO: add an operation that randomly reschedules some checkpoint
+    #[test]
+    fn bookings_of_several_containers_can_be_looked_up_by_their_id() {
+        let mut two_containers = booking("C0");
+        two_containers.num_containers = 2;
+        let mut generator = generator(vec![two_containers]);
+        let mut schedule = generator.empty_schedule();
+        assert!(generator.get_cargo_time_windows("C0".to_string()).is_ok());
+        assert!(generator
+            .explain_infeasibility("C0".to_string(), &schedule)
+            .unwrap()
+            .values()
+            .all(Option::is_none));
+        assert!(schedule
+            .get_cargo_itinerary("C0".to_string(), &generator)
+            .unwrap()
+            .is_none());
 
-        // Create copies and operate on them
-        let mut new_start_checkpoint_pickup = start_checkpoint.pickup_cargo.clone();
-        new_start_checkpoint_pickup.insert(chosen_cargo);
+        let first_container: Cargo = generator
+            .cargo_mapper
+            .reverse_map(&"C0#0".to_string())
+            .unwrap();
+        while !schedule
+            .scheduled_cargo_truck
+            .contains_key(&first_container)
+        {
+            generator
+                .find_schedule_neighbour(&mut schedule, 10)
+                .unwrap();
+        }
+        let (truck_id, ..) = schedule
+            .get_cargo_itinerary("C0".to_string(), &generator)
+            .unwrap()
+            .unwrap();
+        let truck = schedule
+            .scheduled_cargo_truck
+            .get(&first_container)
+            .unwrap();
+        assert_eq!(truck_id, *generator.truck_mapper.map(truck).unwrap());
+        assert!(generator.get_cargo_time_windows("C2".to_string()).is_err());
+    }
 
-        let mut new_end_checkpoint_dropoff = end_checkpoint.dropoff_cargo.clone();
-        new_end_checkpoint_dropoff.insert(chosen_cargo);
+    #[test]
+    fn itineraries_of_stale_schedules_raise() {
+        let mut generator = generator(vec![booking("C0")]);
+        let mut schedule = generator.empty_schedule();
+        while schedule.scheduled_cargo_truck.is_empty() {
+            generator
+                .find_schedule_neighbour(&mut schedule, 10)
+                .unwrap();
+        }
+        let (truck_id, ..) = schedule
+            .get_cargo_itinerary("C0".to_string(), &generator)
+            .unwrap()
+            .unwrap();
 
-        let mut out = schedule.clone();
+        generator.remove_truck(truck_id, &schedule).unwrap();
+        assert!(schedule
+            .get_cargo_itinerary("C0".to_string(), &generator)
+            .is_err());
+    }
 
-        // NOTE: reschedule them one-by-one. If we reschedule them at the same time and
-        // the end checkpoint is directly after the start checkpoint,
-        // the end checkpoint might be rescheduled to before the new start
-        // checkpoint time
-        let new_start_checkpoint_time = self.find_random_reschedule_time(
-            &out,
-            *truck,
-            start_checkpoint_index,
-            &new_start_checkpoint_pickup,
-            &start_checkpoint.dropoff_cargo,
-        )?;
-        let new_start_checkpoint = out
-            .get_checkpoint_mut(*truck, start_checkpoint_index)
+    #[test]
+    fn plans_keep_their_breaks_and_drivers() {
+        let mut generator = generator(vec![booking("C0")]);
+        generator
+            .set_driving_hour_rules(PyTimeDelta(40 * 60), PyTimeDelta(HOUR / 2))
             .unwrap();
-        new_start_checkpoint.pickup_cargo.insert(chosen_cargo);
-        new_start_checkpoint.time = new_start_checkpoint_time;
-
-        let new_end_checkpoint_time = self.find_random_reschedule_time(
-            &out,
-            *truck,
-            end_checkpoint_index,
-            &end_checkpoint.pickup_cargo,
-            &new_end_checkpoint_dropoff,
-        )?;
-        let new_end_checkpoint = out
-            .get_checkpoint_mut(*truck, end_checkpoint_index)
+        generator
+            .set_driver_roster(
+                ["D0", "D1"]
+                    .map(|driver_id| {
+                        PyDriver::new(
+                            driver_id.to_string(),
+                            PyTime(PLANNING_START),
+                            PyTime(PLANNING_START + 24 * HOUR),
+                            vec![],
+                            None,
+                            None,
+                        )
+                    })
+                    .into(),
+            )
+            .unwrap();
+        let schedule = random_walk(&mut generator, 1000)
+            .into_iter()
+            .find(|schedule| !schedule.scheduled_cargo_truck.is_empty())
             .unwrap();
-        new_end_checkpoint.dropoff_cargo.insert(chosen_cargo);
-        new_end_checkpoint.time = new_end_checkpoint_time;
 
-        // Make sure that the times are still in strictly ascending order of time
-        // https://stackoverflow.com/questions/51272571/how-do-i-check-if-a-slice-is-sorted
-        assert!(out
-            .truck_checkpoints
-            .get(truck)
+        let plan: Vec<_> = schedule
+            .to_list_of_tuples(&generator)
+            .into_iter()
+            .map(|(truck_id, time, terminal_id, cargo_id, was_picked_up)| {
+                (
+                    truck_id,
+                    PyTime(time.time),
+                    terminal_id,
+                    cargo_id,
+                    was_picked_up,
+                )
+            })
+            .collect();
+        let breaks: Vec<_> = schedule
+            .breaks_to_list_of_tuples(&generator)
+            .into_iter()
+            .map(|(truck_id, time, duration)| {
+                (truck_id, PyTime(time.time), PyTimeDelta(duration.duration))
+            })
+            .collect();
+        let driver_assignments = schedule.get_driver_assignments(&generator);
+        let loaded = generator
+            .schedule_from_plan(plan.clone(), breaks.clone(), driver_assignments)
+            .unwrap();
+        assert_eq!(loaded.scheduled_cargo_truck, schedule.scheduled_cargo_truck);
+        assert_eq!(loaded.truck_breaks, schedule.truck_breaks);
+        assert_eq!(loaded.driver_trucks, schedule.driver_trucks);
+
+        // Without drivers, nobody can drive the truck
+        assert!(generator
+            .schedule_from_plan(plan, breaks, BTreeMap::new())
+            .is_err());
+    }
+
+    #[test]
+    fn holidays_are_local_dates_of_terminals() {
+        pyo3::prepare_freethreaded_python();
+        // Midnight in Berlin is still the day before in UTC
+        let holiday: PyDate = Python::with_gil(|py| {
+            py.eval(
+                c"__import__('datetime').datetime(2025, 12, 25, tzinfo=__import__('zoneinfo').ZoneInfo('Europe/Berlin'))",
+                None,
+                None,
+            )
+            .unwrap()
+            .extract()
             .unwrap()
-            .windows(2)
-            .all(|checkpoints| checkpoints[0].time < checkpoints[1].time));
+        });
+        assert_eq!(holiday.0, NaiveDate::from_ymd_opt(2025, 12, 25).unwrap());
+
+        let utc = |day: u32, hour: u32| {
+            NaiveDate::from_ymd_opt(2025, 12, day)
+                .unwrap()
+                .and_hms_opt(hour, 0, 0)
+                .unwrap()
+                .and_utc()
+                .timestamp() as Time
+        };
+        let planning_period = (PyTime(utc(24, 0)), PyTime(utc(27, 0)));
+        let generator = ScheduleGenerator::new(
+            PyKeyedRows(BTreeMap::from([("A".to_string(), planning_period.into())])),
+            PyKeyedRows(BTreeMap::new()),
+            PyRows(vec![]),
+            planning_period,
+            false,
+            BTreeMap::from([("DE".to_string(), PyCalendar::new(vec![], vec![holiday]))]),
+            BTreeMap::from([("A".to_string(), "DE".to_string())]),
+            BTreeMap::new(),
+            BTreeMap::from([("A".to_string(), "Europe/Berlin".to_string())]),
+            BTreeMap::new(),
+            None,
+            None,
+        )
+        .unwrap();
+        let open_intervals: Vec<_> = generator.terminal_open_intervals[&Terminal::from_id(0)]
+            .get_intervals()
+            .iter()
+            .map(|interval| (interval.get_start_time(), interval.get_end_time()))
+            .collect();
+        assert_eq!(
+            open_intervals,
+            vec![(utc(24, 0), utc(24, 23)), (utc(25, 23), utc(27, 0))]
+        );
+    }
 
-        // Try to modify the weights and sizes
-        let checkpoints = out.truck_checkpoints.get_mut(truck).unwrap();
-        let booking_info = self.cargo_booking_info.get(&chosen_cargo).unwrap();
+    #[test]
+    fn long_legs_are_split_by_breaks() {
+        let mut generator = generator(vec![]);
+        // Without driving-hour rules, legs are driven in one go
+        assert_eq!(generator.split_long_leg(10 * HOUR), (0, 10 * HOUR));
+        assert_eq!(generator.get_long_leg_break_time(10 * HOUR), 0);
 
-        for checkpoint in &mut checkpoints[start_checkpoint_index..end_checkpoint_index] {
-            // Immediately fail if weight constraint is failed
-            checkpoint.available_weight_kg = checkpoint
-                .available_weight_kg
-                .checked_sub(booking_info.weight_kg)?;
-            checkpoint.available_teu = checkpoint.available_teu.checked_sub(booking_info.teu)?;
+        generator
+            .set_driving_hour_rules(PyTimeDelta(2 * HOUR), PyTimeDelta(HOUR / 2))
+            .unwrap();
+        for (driving_time, num_breaks, first_stretch) in [
+            (2 * HOUR, 0, 2 * HOUR),
+            (2 * HOUR + 1, 1, 1),
+            (6 * HOUR, 2, 2 * HOUR),
+            (6 * HOUR + 1, 3, 1),
+        ] {
+            assert_eq!(
+                generator.split_long_leg(driving_time),
+                (num_breaks, first_stretch)
+            );
+            assert_eq!(
+                generator.get_long_leg_break_time(driving_time),
+                num_breaks * HOUR / 2
+            );
         }
+    }
 
-        out.scheduled_cargo_truck.insert(chosen_cargo, *truck);
+    #[test]
+    fn shortest_paths_go_through_hubs() {
+        const MINUTE: u64 = 60;
+        let mut generator = generator(vec![]);
+        let terminal = |terminal_id: &str| {
+            generator
+                .terminal_mapper
+                .reverse_map(&terminal_id.to_string())
+                .unwrap()
+        };
+        let (a, b) = (terminal("A"), terminal("B"));
+        let minutes = |values: &[u64]| {
+            values
+                .iter()
+                .map(|value| PyTimeDelta(value * MINUTE))
+                .collect()
+        };
+        let order = vec!["C".to_string(), "A".to_string(), "B".to_string()];
+        // Only the driving times to and from the hub C are known
+        let spokes = BTreeMap::from([
+            ("C".to_string(), minutes(&[0, 10, 15])),
+            ("A".to_string(), minutes(&[10])),
+            ("B".to_string(), minutes(&[15])),
+        ]);
+        generator
+            .set_driving_times(
+                order.clone(),
+                PyDrivingTimeTable::Rows(spokes.clone()),
+                None,
+            )
+            .unwrap();
+        let derived = |generator: &ScheduleGenerator| {
+            let mut derived: Vec<_> = generator
+                .get_derived_driving_times()
+                .into_iter()
+                .map(|(from_id, to_id, driving_time)| {
+                    (from_id, to_id, driving_time.duration / MINUTE)
+                })
+                .collect();
+            derived.sort();
+            derived
+        };
+        assert_eq!(derived(&generator), vec![]);
+
+        let cache = &generator.driving_times_cache;
+        assert_eq!(cache.get_driving_time(a, b, None), Ok(25 * MINUTE));
+        // All terminals reachable from A are found at once
+        assert_eq!(
+            derived(&generator),
+            vec![
+                ("A".to_string(), "B".to_string(), 25),
+                ("A".to_string(), "C".to_string(), 10),
+            ]
+        );
 
-        return Some(out);
+        // A direct road from A to B is shorter than going through the hub
+        let mut with_road = spokes;
+        with_road.insert("A".to_string(), minutes(&[10, 0, 20]));
+        generator
+            .set_driving_times(order, PyDrivingTimeTable::Rows(with_road), None)
+            .unwrap();
+        assert_eq!(derived(&generator), vec![]);
+        let cache = &generator.driving_times_cache;
+        assert_eq!(cache.get_driving_time(a, b, None), Ok(20 * MINUTE));
+        assert_eq!(cache.get_driving_time(b, a, None), Ok(25 * MINUTE));
     }
-}
 
-/// Creates an interval [start_time, end_time] and returns an error
-/// if invalid
-fn interval_or_error(start_time: Time, end_time: Time) -> PyResult<Interval> {
-    if let Some(interval) = Interval::new(start_time, end_time, ()) {
-        Ok(interval)
-    } else {
-        Err(PyTypeError::new_err(format!(
-            "Invalid interval starting at {start_time}, ending at {end_time}"
-        )))
-    }
-}
+    #[test]
+    fn driving_time_matrices_need_a_row_and_column_per_terminal() {
+        pyo3::prepare_freethreaded_python();
+        let mut generator = generator_with_providers(vec![], &["static"]);
+        let matrix = |code: &std::ffi::CStr| {
+            Python::with_gil(|py| py.eval(code, None, None).unwrap().extract::<PyMatrix>()).unwrap()
+        };
+        let order = |terminal_ids: &[&str]| {
+            terminal_ids
+                .iter()
+                .map(|terminal_id| terminal_id.to_string())
+                .collect::<Vec<_>>()
+        };
 
-#[pymethods]
-impl ScheduleGenerator {
-    #[new]
-    /// Create a new schedule generator
-    /// terminal_data is a dict sending a terminal id to (opening_time, closing_time)
-    /// truck_data is a dict sending truck id to starting_terminal
-    pub fn new(
-        terminal_data: BTreeMap<PyTerminalID, (Time, Time)>,
-        truck_data: BTreeMap<PyTruckID, PyTruckData>,
-        booking_data: Vec<PyBooking>,
-        planning_period: (Time, Time),
-    ) -> PyResult<Self> {
-        // We want to map between the internally-used
-        // integer ids and the externally-used String ids.
-        // This is done because it is easier to deal with
-        // integers and ownership, while Strings would make
-        // maintenance a bit more tricky
-        let mut terminal_mapper = CounterMapper::new();
-        let mut cargo_mapper = CounterMapper::new();
-        let mut truck_mapper = CounterMapper::new();
+        let non_square = matrix(
+            c"memoryview(__import__('array').array('q', range(6))).cast('B').cast('q', [2, 3])",
+        );
+        let err = generator
+            .set_driving_times(
+                order(&["A", "B"]),
+                PyDrivingTimeTable::Matrix(non_square),
+                None,
+            )
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("shape (2, 3)"), "{err}");
 
-        let planning_period = interval_or_error(planning_period.0, planning_period.1)?;
-        let planning_period_as_interval_chain =
-            IntervalChain::from_interval(planning_period.clone());
+        let square = || {
+            matrix(c"memoryview(__import__('array').array('d', [0, 600, 900, 0])).cast('B').cast('d', [2, 2])")
+        };
+        assert!(generator
+            .set_driving_times(
+                order(&["A", "B", "C"]),
+                PyDrivingTimeTable::Matrix(square()),
+                None
+            )
+            .is_err());
+        generator
+            .set_driving_times(
+                order(&["B", "A"]),
+                PyDrivingTimeTable::Matrix(square()),
+                None,
+            )
+            .unwrap();
+        let terminal = |terminal_id: &str| {
+            generator
+                .terminal_mapper
+                .reverse_map(&terminal_id.to_string())
+                .unwrap()
+        };
+        let (a, b) = (terminal("A"), terminal("B"));
+        let cache = &generator.driving_times_cache;
+        assert_eq!(cache.get_driving_time(b, a, None), Ok(600));
+        assert_eq!(cache.get_driving_time(a, b, None), Ok(900));
+    }
 
-        // Calculate terminal_open_intervals
-        let mut terminal_open_intervals = BTreeMap::new();
-        for (terminal_id, (opening_time, closing_time)) in terminal_data.iter() {
-            let terminal: Terminal = terminal_mapper.add_or_find(terminal_id);
-            // If it is a valid interval, create
-            let interval = interval_or_error(*opening_time, *closing_time)?;
-            // TODO: make opening and closing times repeat day on day
-            // TODO: if you do that, be sure to set the starting point to be sane (and
-            // not e.g. 0 unix time) to avoid considering really old time intervals
-            let intervals = IntervalChain::from_interval(interval);
-            terminal_open_intervals.insert(terminal, intervals);
+    #[test]
+    fn driving_time_sources_are_asked_in_order() {
+        let terminal = |generator: &ScheduleGenerator, terminal_id: &str| -> Terminal {
+            generator
+                .terminal_mapper
+                .reverse_map(&terminal_id.to_string())
+                .unwrap()
+        };
+        let set_driving_time = |generator: &mut ScheduleGenerator, from: &str, to: &str| {
+            generator.set_driving_time(from.to_string(), to.to_string(), PyTimeDelta(HOUR), None)
+        };
+
+        // Driving times cached by a provider don't hide those of providers before it,
+        // and the callback is given the ids of the terminals
+        let mut generator =
+            generator_with_providers(vec![], &["static", "shortest_path", "callback"]);
+        let (a, c) = (terminal(&generator, "A"), terminal(&generator, "C"));
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let callback = py
+                .eval(
+                    c"lambda from_id, to_id: {('A', 'C'): 10 * 3600}[(from_id, to_id)]",
+                    None,
+                    None,
+                )
+                .unwrap();
+            generator
+                .set_driving_time_provider(py, Some(callback.unbind()))
+                .unwrap();
+        });
+        set_driving_time(&mut generator, "A", "B").unwrap();
+        let cache = &generator.driving_times_cache;
+        assert_eq!(cache.get_driving_time(a, c, None), Ok(10 * HOUR));
+        set_driving_time(&mut generator, "B", "C").unwrap();
+        let cache = &generator.driving_times_cache;
+        assert_eq!(cache.get_driving_time(a, c, None), Ok(2 * HOUR));
+
+        // The static map and the profiles are sources like the others
+        for (providers, expected) in [
+            (["time_dependent", "static"], 2 * HOUR),
+            (["static", "time_dependent"], HOUR),
+        ] {
+            let mut generator = generator_with_providers(vec![], &providers);
+            set_driving_time(&mut generator, "A", "C").unwrap();
+            generator
+                .set_driving_time_profile(
+                    "A".to_string(),
+                    "C".to_string(),
+                    vec![(PyTimeDelta(0), PyTimeDelta(2 * HOUR))],
+                )
+                .unwrap();
+            let cache = &generator.driving_times_cache;
+            assert_eq!(
+                cache.get_driving_time_at(a, c, None, PLANNING_START),
+                Ok(expected)
+            );
         }
 
-        let mut trucks = BTreeSet::new();
+        // Setting the driving times of a source that isn't asked is an error,
+        // but the estimated driving speed can always be set
+        let mut generator = generator_with_providers(vec![], &["static"]);
+        assert!(set_driving_time(&mut generator, "A", "B").is_ok());
+        assert!(generator
+            .set_driving_time_profile(
+                "A".to_string(),
+                "B".to_string(),
+                vec![(PyTimeDelta(0), PyTimeDelta(HOUR))]
+            )
+            .is_err());
+        assert!(generator
+            .driving_times_cache
+            .get_driving_time(a, c, None)
+            .is_err());
+        generator.set_estimated_driving_speed(50.0).unwrap();
+        assert!(generator
+            .driving_times_cache
+            .get_driving_time(a, c, None)
+            .is_ok());
+    }
+
+    #[test]
+    fn async_optimizations_can_be_cancelled() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let generator = Bound::new(py, generator(vec![booking("C0")])).unwrap();
+            let schedule = generator.borrow().empty_schedule();
+            let optimize = |num_iterations, progress_interval| {
+                ScheduleGenerator::optimize_async(
+                    &generator,
+                    &schedule,
+                    num_iterations,
+                    10.0,
+                    0.1,
+                    10,
+                    0.0,
+                    progress_interval,
+                )
+            };
+            // Cancellation is only checked when reporting progress
+            assert!(optimize(10, 0).is_err());
+
+            let handle = optimize(1_000_000, 1).unwrap();
+            // The optimization runs on a fork, so the generator can still be changed
+            generator.borrow_mut().add_booking(booking("C1")).unwrap();
+            handle.cancel();
+            handle.result(py).unwrap();
+            assert!(handle.done());
+            assert!(handle.progress().unwrap().0 < 1_000_000);
+        });
+    }
 
-        let mut terminals = BTreeSet::new();
+    #[test]
+    fn time_formats_are_set_per_generator() {
+        let mut generator = generator(vec![booking("C0")]);
+        let schedule = generator.empty_schedule();
+        let schedule = schedule_cargo(&mut generator, &schedule, "C0");
+        let other_generator = generator.fork(0);
+        generator.set_time_formats("datetime", "timedelta").unwrap();
+
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let (_, time, ..) = schedule.to_list_of_tuples(&generator)[0].clone();
+            assert!(!time.into_pyobject(py).unwrap().is_instance_of::<PyInt>());
+            let (_, time, ..) = schedule.to_list_of_tuples(&other_generator)[0].clone();
+            assert!(time.into_pyobject(py).unwrap().is_instance_of::<PyInt>());
+
+            let statistics = schedule.statistics(&generator).unwrap();
+            let driving_time = statistics.total_driving_time.into_pyobject(py).unwrap();
+            assert!(!driving_time.is_instance_of::<PyInt>());
+            let statistics = schedule.statistics(&other_generator).unwrap();
+            let driving_time = statistics.total_driving_time.into_pyobject(py).unwrap();
+            assert!(driving_time.is_instance_of::<PyInt>());
+        });
+    }
 
-        for (truck_id, truck_data) in truck_data.iter() {
-            let starting_terminal_id = &truck_data.starting_terminal;
-            let truck: Truck = truck_mapper.add_or_find(truck_id);
-            let starting_terminal: Terminal = terminal_mapper.add_or_find(&starting_terminal_id);
+    #[test]
+    fn schedules_show_cargo_added_after_them() {
+        let mut generator = generator(vec![booking("C0")]);
+        let schedule = generator.empty_schedule();
+        generator.add_booking(booking("C1")).unwrap();
 
-            trucks.insert(truck);
-            terminals.insert(starting_terminal);
-        }
+        let schedule = schedule_cargo(&mut generator, &schedule, "C1");
+        assert!(schedule.__str__().contains("\"C1\""));
+    }
 
-        // Calculate pickup and dropoff times
-        let mut pickup_times = BTreeMap::new();
-        let mut dropoff_times = BTreeMap::new();
+    #[test]
+    fn schedules_iterate_over_trucks_added_after_them() {
+        let mut generator = generator(vec![booking("C0")]);
+        let schedule = generator.empty_schedule();
+        let schedule = generator
+            .add_truck("K2".to_string(), truck("C"), &schedule)
+            .unwrap();
+        // Only the new truck can deliver the cargo
+        let schedule = generator.remove_truck("K0".to_string(), &schedule).unwrap();
+        let schedule = generator.remove_truck("K1".to_string(), &schedule).unwrap();
+
+        let schedule = schedule_cargo(&mut generator, &schedule, "C0");
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let trucks: Vec<String> = schedule
+                .__iter__(py)
+                .unwrap()
+                .map(|item| item.unwrap().get_item(0).unwrap().extract().unwrap())
+                .collect();
+            assert!(!trucks.is_empty());
+            assert_eq!(trucks.len(), schedule.__len__());
+            assert!(trucks.iter().all(|truck| truck == "K2"));
+        });
+    }
 
-        let mut cargo_booking_info = BTreeMap::new();
-        let mut cargo_by_terminals = BTreeMap::new();
+    #[test]
+    fn unknown_ids_raise_unknown_id_errors() {
+        pyo3::prepare_freethreaded_python();
+        let mut generator = generator(vec![booking("C0")]);
+        let schedule = generator.empty_schedule();
+        let errors = [
+            generator
+                .remove_truck("K9".to_string(), &schedule)
+                .err()
+                .unwrap(),
+            generator
+                .cancel_booking("C9".to_string(), &schedule)
+                .err()
+                .unwrap(),
+        ];
+        Python::with_gil(|py| {
+            for error in errors {
+                assert!(error.is_instance_of::<UnknownIdError>(py));
+                // Still caught by handlers written before there were error classes
+                assert!(error.is_instance_of::<PyTypeError>(py));
+            }
+        });
+    }
 
-        for booking in booking_data.iter() {
-            // Remove irrelevant bookings
-            // Note that this also includes the bookings that are too far in the future -
-            // we are not anticipating anything after the planning period ends.
-            // We want to run this algorithm with a relatively large look-ahead,
-            // so that all relevant bookings are within the planning_period. In
-            // this case, if our plan near the end of the period is suboptimal
-            // because we didn't anticipate bookings after the end of
-            // planning_period, that is not an issue: any plans for that time
-            // become stale as the situation changes
-
-            // TODO: we still might want to consider this in order to e.g.
-            // handle scheduling not-urgent containers more frequently
-
-            // To do that, first shrink the intervals, and then remove the empty ones
-
-            let from_terminal: Terminal = terminal_mapper.add_or_find(&booking.from_terminal);
-            let to_terminal: Terminal = terminal_mapper.add_or_find(&booking.to_terminal);
-
-            let pickup_intervals = [
-                terminal_open_intervals.get(&from_terminal).unwrap().clone(),
-                IntervalChain::from_interval(interval_or_error(
-                    booking.pickup_open_time,
-                    booking.pickup_close_time,
-                )?),
-                planning_period_as_interval_chain.clone(),
-            ]
-            .iter()
-            .intersect_all();
+    #[test]
+    fn routes_are_drawn_along_their_geometries() {
+        let (mut generator, schedule) = planned_delivery();
+        generator
+            .set_route_geometry(
+                "C".to_string(),
+                "A".to_string(),
+                vec![(52.0, 5.3), (52.1, 5.2), (52.0, 5.0)],
+            )
+            .unwrap();
+        let geojson: Value =
+            serde_json::from_str(&schedule.to_geojson(&generator).unwrap()).unwrap();
+        assert_eq!(geojson["type"], "FeatureCollection");
+        let features = geojson["features"].as_array().unwrap();
 
-            let dropoff_intervals = [
-                terminal_open_intervals.get(&to_terminal).unwrap().clone(),
-                IntervalChain::from_interval(interval_or_error(
-                    booking.dropoff_open_time,
-                    booking.dropoff_close_time,
-                )?),
-                planning_period_as_interval_chain.clone(),
-            ]
+        let terminals: Vec<_> = features[..3]
             .iter()
-            .intersect_all();
-
-            // Remove the deliveries we can't do
-            if pickup_intervals.is_empty() || dropoff_intervals.is_empty() {
-                continue;
-            }
+            .map(|feature| (&feature["properties"]["terminal"], &feature["geometry"]))
+            .collect();
+        assert_eq!(
+            terminals,
+            [
+                (
+                    &json!("A"),
+                    &json!({"type": "Point", "coordinates": [5.0, 52.0]})
+                ),
+                (
+                    &json!("B"),
+                    &json!({"type": "Point", "coordinates": [5.0, 52.2]})
+                ),
+                (
+                    &json!("C"),
+                    &json!({"type": "Point", "coordinates": [5.3, 52.0]})
+                ),
+            ]
+        );
 
-            // Only add terminals which are referenced in a relevant booking
-            terminals.insert(from_terminal);
-            terminals.insert(to_terminal);
+        // The leg from C to A follows its geometry, and the one from A to B goes straight
+        assert_eq!(features.len(), 4);
+        let route = &features[3];
+        assert_eq!(
+            route["geometry"],
+            json!({
+                "type": "LineString",
+                "coordinates": [[5.3, 52.0], [5.2, 52.1], [5.0, 52.0], [5.0, 52.2]],
+            })
+        );
+        let properties = &route["properties"];
+        assert_eq!(properties["kind"], "route");
+        assert_eq!(properties["truck"], "K0");
+        assert_eq!(properties["cargo"], json!(["C0"]));
+        assert_eq!(properties["start_time"], PLANNING_START + HOUR);
+        assert_eq!(properties["end_time"], PLANNING_START + 2 * HOUR);
+        assert_eq!(
+            properties["stops"][1],
+            json!({
+                "terminal": "B",
+                "time": PLANNING_START + 2 * HOUR,
+                "pickups": [],
+                "dropoffs": ["C0"],
+            })
+        );
+    }
 
-            let cargo: Cargo = cargo_mapper.add_or_find(&booking.cargo);
-            pickup_times.insert(cargo, pickup_intervals);
-            dropoff_times.insert(cargo, dropoff_intervals);
+    #[test]
+    fn schedules_are_written_as_csv() {
+        let (generator, schedule) = planned_delivery();
+        let csv = schedule.to_csv(&generator, None, None).unwrap().unwrap();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines[0], CSV_COLUMNS.join(","));
+        assert_eq!(
+            lines[1..],
+            [
+                format!("K0,0,{},A,52,5,C0,,29000,1", PLANNING_START + HOUR),
+                format!("K0,1,{},B,52.2,5,,C0,30000,2", PLANNING_START + 2 * HOUR),
+            ]
+        );
 
-            // Update delivery info
-            let booking_info = BookingInformation {
-                from: from_terminal,
-                to: to_terminal,
-                weight_kg: booking.cargo_weight_kg,
-                teu: booking.cargo_teu,
-            };
-            cargo_by_terminals
-                .entry((booking_info.from, booking_info.to))
-                .or_insert(BTreeSet::new())
-                .insert(cargo);
-            cargo_booking_info.insert(cargo, booking_info);
-        }
+        let columns = Some(vec!["terminal".to_string(), "dropoffs".to_string()]);
+        let path = std::env::temp_dir().join(format!("schedule-{}.csv", std::process::id()));
+        let written = schedule
+            .to_csv(&generator, columns, Some(path.clone()))
+            .unwrap();
+        assert_eq!(written, None);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "terminal,dropoffs\nA,\nB,C0\n"
+        );
+        std::fs::remove_file(path).unwrap();
 
-        let truck_data = truck_data
-            .iter()
-            .map(|(truck, data)| {
-                let truck: Truck = truck_mapper.reverse_map(truck).unwrap();
-                let starting_terminal: Terminal = terminal_mapper
-                    .reverse_map(&data.starting_terminal)
-                    .unwrap();
+        assert!(schedule
+            .to_csv(&generator, Some(vec!["weight".to_string()]), None)
+            .is_err());
+    }
 
-                // TODO: in the future, find the time when a driver can start working
-                // in some other way
-                let start_time = terminal_open_intervals
-                    .get(&starting_terminal)
-                    .unwrap()
-                    .get_intervals()
-                    .first()
-                    .unwrap()
-                    .get_start_time();
+    #[test]
+    fn timelines_cover_each_truck_without_gaps() {
+        let (generator, schedule) = planned_delivery();
+        let timeline: Value =
+            serde_json::from_str(&schedule.to_timeline_json(&generator).unwrap()).unwrap();
+        assert_eq!(
+            timeline["groups"],
+            json!([{"id": "K0", "content": "K0"}, {"id": "K1", "content": "K1"}])
+        );
 
-                let data = TruckData {
-                    starting_terminal,
-                    start_time,
-                    max_teu: data.max_teu,
-                    max_weight_kg: data.max_weight_kg,
-                };
-                (truck, data)
+        let items = timeline["items"].as_array().unwrap();
+        let segments: Vec<_> = items
+            .iter()
+            .map(|item| {
+                (
+                    item["segment"].as_str().unwrap(),
+                    item["terminal"].as_str(),
+                    item["cargo"].as_array().unwrap().len(),
+                )
             })
             .collect();
-
-        Ok(Self {
-            driving_times_cache: DrivingTimesCache::new(),
-            cargo_by_terminals,
-            pickup_times,
-            dropoff_times,
-            cargo_booking_info,
-            terminals,
-            trucks,
-            truck_data,
-            planning_period,
-            rng: Xoshiro256PlusPlus::seed_from_u64(0),
-            terminal_mapper,
-            cargo_mapper,
-            truck_mapper,
-        })
-    }
-
-    /// Creates an empty schedule
-    pub fn empty_schedule(&self) -> Schedule {
-        Schedule {
-            // Create empty checkpoints for each truck
-            truck_checkpoints: self.trucks.iter().map(|truck| (*truck, vec![])).collect(),
-            scheduled_cargo_truck: BTreeMap::new(),
-            // Each truck drives 0 distance by default, simply staying where it is
-            truck_driving_times: self.trucks.iter().map(|truck| (*truck, 0)).collect(),
+        assert_eq!(
+            segments,
+            [
+                ("drive", Some("A"), 0),
+                ("wait", None, 0),
+                ("service", Some("A"), 1),
+                ("drive", Some("B"), 1),
+                ("wait", None, 1),
+                ("service", Some("B"), 1),
+            ]
+        );
+        assert_eq!(items[0]["start"], "2025-01-01T00:00:00+00:00");
+        assert_eq!(items[5]["end"], "2025-01-01T02:00:00+00:00");
+        for (item, next_item) in items.iter().zip(&items[1..]) {
+            assert_eq!(item["group"], "K0");
+            assert_eq!(item["end"], next_item["start"]);
         }
     }
 
-    /// Reseeds internal RNG
-    pub fn seed(&mut self, seed: u64) {
-        self.rng = Xoshiro256PlusPlus::seed_from_u64(seed);
+    #[test]
+    fn bookings_outside_the_planning_period_are_skipped_when_raising() {
+        let mut generator = generator(vec![]);
+        generator.data_mut().infeasible_booking_policy = InfeasibleBookingPolicy::Raise;
+
+        let mut late = booking("C0");
+        late.pickup_open_time = Some(PyTime(PLANNING_START + 48 * HOUR));
+        late.pickup_close_time = Some(PyTime(PLANNING_START + 49 * HOUR));
+        late.dropoff_close_time = Some(PyTime(PLANNING_START + 50 * HOUR));
+        assert!(!generator.add_booking(late).unwrap());
+        assert_eq!(generator.rejected_bookings()[0].0, "C0");
+
+        // Bookings within the planning period which can't be done are still raised
+        let mut conflicting = booking("C1");
+        conflicting.pickup_open_time = Some(PyTime(PLANNING_START + 2 * HOUR));
+        conflicting.pickup_close_time = Some(PyTime(PLANNING_START + 3 * HOUR));
+        conflicting.pickup_windows = Some(PyIntervalChain {
+            chain: IntervalChain::from_interval(
+                Interval::new(PLANNING_START + 5 * HOUR, PLANNING_START + 6 * HOUR, ()).unwrap(),
+            ),
+        });
+        assert!(generator.add_booking(conflicting).is_err());
     }
 
-    /// Gets a random neighbour for a schedule.
-    /// Note that the neighbours might not be sampled uniformly.
-    /// Pick an action type and try to execute it randomly up to
-    /// `num_tries_per_action` times. If this fails, pick another action type and repeat.
-    /// This helps to keep frequency of selecting each action type similar to what is expected,
-    /// despite some action types failing more often than others
-    pub fn get_schedule_neighbour(
-        &mut self,
-        schedule: &Schedule,
-        num_tries_per_action: usize,
-    ) -> Schedule {
-        loop {
-            // Randomly decide what we want to do
-            // Prioritise adding and updating checkpoints because we want to explore more of those
-            // options, and also because adding a checkpoint might fail, but removing is a lot less likely to fail
-            let action_index = self.rng.random_range(0..4);
+    #[test]
+    fn interned_columns_index_into_their_ids() {
+        let (generator, schedule) = planned_delivery();
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let named = schedule.to_columns(py, &generator, false).unwrap();
+            let interned = schedule.to_columns(py, &generator, true).unwrap();
+            let column = |columns: &Bound<'_, PyDict>, name: &str| {
+                columns.get_item(name).unwrap().unwrap().unbind()
+            };
 
-            // Try executing this action type a few times
-            for _ in 0..num_tries_per_action {
-                let new_schedule = match action_index {
-                    0..1 => self.remove_random_checkpoint(schedule),
-                    1..2 => self.add_random_checkpoint(schedule),
-                    2..3 => self.remove_random_delivery(schedule),
-                    3..4 => self.add_random_delivery(schedule),
-                    _ => unreachable!(),
-                };
-                if let Some(new_schedule) = new_schedule {
-                    return new_schedule;
-                }
+            for (name, ids) in [
+                ("truck", "truck_ids"),
+                ("terminal", "terminal_ids"),
+                ("cargo", "cargo_ids"),
+            ] {
+                let codes: Vec<usize> = column(&interned, name).extract(py).unwrap();
+                let ids: Vec<String> = column(&interned, ids).extract(py).unwrap();
+                let names: Vec<String> = column(&named, name).extract(py).unwrap();
+                let decoded: Vec<String> = codes.iter().map(|&code| ids[code].clone()).collect();
+                assert_eq!(decoded, names);
             }
-        }
+            let terminals: Vec<String> = column(&named, "terminal").extract(py).unwrap();
+            assert_eq!(terminals, ["A", "B"]);
+            for name in ["time", "action"] {
+                let (named, interned) = (column(&named, name), column(&interned, name));
+                assert!(named.bind(py).eq(interned).unwrap());
+            }
+            assert!(!named.contains("truck_ids").unwrap());
+        });
     }
 
-    /// Returns a score representing how good the Schedule is
-    /// The score is a vector of numbers, where each
-    /// represent a different criterion by which the solution can be judged.
-    /// Higher score is better
-    pub fn scores(&mut self, schedule: &Schedule) -> Vec<f64> {
-        // Maximise the number of deliveries
-        let num_deliveries: usize = schedule.scheduled_cargo_truck.len();
-        // Minimise the number of trucks required
-        let num_free_trucks: usize = schedule
-            .truck_checkpoints
-            .values()
-            .filter(|checkpoints| checkpoints.is_empty())
-            .count();
-
-        // Sum of minimal driving times needed to deliver each piece of cargo that
-        // has been delivered;
-        // this is a very simplistic lower bound
-        let min_driving_time: NonNegativeTimeDelta = schedule
-            .scheduled_cargo_truck
-            .keys()
-            .map(|cargo| {
-                let booking_info = self.cargo_booking_info.get(cargo).unwrap();
-                self.driving_times_cache
-                    .get_driving_time(booking_info.from, booking_info.to)
-            })
-            .sum();
-
-        // Total driving time
-        let total_driving_time: NonNegativeTimeDelta =
-            schedule.truck_driving_times.values().copied().sum();
-
-        // Proportion of deliveries made
-        let deliveries_proportion =
-            (num_deliveries as f64) / (self.cargo_booking_info.len() as f64);
-
-        // Proportion of trucks that are free
-        let free_trucks_proportion = (num_free_trucks as f64) / (self.trucks.len() as f64);
+    #[test]
+    fn forks_share_their_data_across_threads() {
+        let generator = generator_with_all_moves();
+        let schedule = generator.empty_schedule();
+        let run = |mut fork: ScheduleGenerator| {
+            let mut schedule = schedule.clone();
+            for _ in 0..1_000 {
+                fork.find_schedule_neighbour(&mut schedule, 10).unwrap();
+            }
+            schedule
+        };
 
-        // The smaller the total driving time, the larger this is
-        // This can become more than 1 if 2 pieces of cargo are moved at once
-        // Prevent division by 0
-        let driving_time_score = (min_driving_time as f64) / (max(total_driving_time, 1) as f64);
+        let forks: Vec<_> = (0..4).map(|seed| generator.fork(seed)).collect();
+        assert!(forks
+            .iter()
+            .all(|fork| Arc::ptr_eq(&fork.data, &generator.data)));
+        let schedules: Vec<_> = std::thread::scope(|scope| {
+            let threads: Vec<_> = forks
+                .into_iter()
+                .map(|fork| scope.spawn(|| run(fork)))
+                .collect();
+            threads
+                .into_iter()
+                .map(|thread| thread.join().unwrap())
+                .collect()
+        });
+
+        // Each thread gives the same schedule as running its fork on its own
+        for (seed, schedule) in (0..4).zip(schedules) {
+            let expected = run(generator.fork(seed));
+            assert!(schedule.truck_checkpoints == expected.truck_checkpoints);
+            assert!(generator.validate(&schedule).unwrap().is_empty());
+        }
 
-        vec![
-            deliveries_proportion,
-            free_trucks_proportion,
-            driving_time_score,
-        ]
+        // Changing a fork leaves the data of the others as it was
+        let mut fork = generator.fork(0);
+        fork.add_booking(booking("C6")).unwrap();
+        assert!(!Arc::ptr_eq(&fork.data, &generator.data));
+        assert!(!generator.booking_cargo.contains_key("C6"));
     }
 
-    pub fn get_terminal_ids(&self) -> Vec<PyTerminalID> {
-        self.terminals
+    #[test]
+    fn moves_only_check_what_they_changed_without_missing_violations() {
+        // Adding deliveries is only checked for chassis at the terminals it changed,
+        // and for the precedence and stop order of the cargo it rescheduled
+        let mut with_empty_return = booking("C0");
+        with_empty_return.empty_return_terminal = Some("C".to_string());
+        let mut with_stop = booking("C1");
+        with_stop.stops = vec![(
+            "C".to_string(),
+            PyTime(PLANNING_START),
+            PyTime(PLANNING_START + 24 * HOUR),
+            true,
+        )];
+        let mut generator = generator(vec![with_empty_return, with_stop, booking("C2")]);
+        generator
+            .set_chassis_pools(BTreeMap::from([("A".to_string(), 2), ("C".to_string(), 0)]))
+            .unwrap();
+        let schedules = random_walk(&mut generator, 1000);
+        assert!(schedules
             .iter()
-            .map(|terminal| self.terminal_mapper.map(terminal).unwrap())
-            .collect()
+            .any(|schedule| schedule.scheduled_cargo.len() >= 2));
     }
 
-    /// Reset the driving times used by the algorithm
-    /// terminal_id_order gives the order of terminals in `driving_times`
-    /// `driving_times` are the mappings of terminal ids to driving times to all
-    /// the terminals (including itself), in the order given in `terminal_id_order`
-    pub fn set_driving_times(
-        &mut self,
-        terminal_id_order: Vec<PyTerminalID>,
-        driving_times: BTreeMap<PyTerminalID, Vec<u64>>,
-    ) {
-        let mut driving_times_reformatted = BTreeMap::new();
-        for (from_id, times) in driving_times.iter() {
-            for (to_index, time) in times.iter().enumerate() {
-                let from_terminal: Terminal = self.terminal_mapper.reverse_map(from_id).unwrap();
-                let to_id = terminal_id_order.get(to_index).unwrap();
-                let to_terminal: Terminal = self.terminal_mapper.reverse_map(to_id).unwrap();
+    #[test]
+    fn memory_stats_add_up_to_their_totals() {
+        let (generator, schedule) = planned_delivery();
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let sizes =
+                |stats: Bound<'_, PyDict>| -> BTreeMap<String, usize> { stats.extract().unwrap() };
+            let parts_and_total = |mut sizes: BTreeMap<String, usize>| {
+                let total = sizes.remove("total").unwrap();
+                (sizes.values().sum::<usize>(), total)
+            };
 
-                driving_times_reformatted.insert((from_terminal, to_terminal), *time);
-            }
-        }
+            let schedule_sizes = sizes(schedule.memory_stats(py).unwrap());
+            assert_eq!(
+                schedule_sizes.keys().collect::<Vec<_>>(),
+                [
+                    "assignments",
+                    "breaks",
+                    "checkpoint_positions",
+                    "checkpoints",
+                    "total"
+                ]
+            );
+            assert!(schedule_sizes["checkpoints"] > 0);
+            // The total also counts the cached driving times of the trucks
+            let (parts, total) = parts_and_total(schedule_sizes);
+            assert!(parts <= total);
+
+            let generator_sizes = sizes(generator.memory_stats(py).unwrap());
+            assert_eq!(
+                generator_sizes.keys().collect::<Vec<_>>(),
+                [
+                    "bookings",
+                    "drivers",
+                    "driving_times_cache",
+                    "id_mappers",
+                    "inputs",
+                    "interval_chains",
+                    "route_geometries",
+                    "total",
+                    "trucks"
+                ]
+            );
+            assert!(generator_sizes["id_mappers"] > 0);
+            let (parts, total) = parts_and_total(generator_sizes);
+            assert_eq!(parts, total);
+        });
+    }
+
+    #[test]
+    fn infeasibility_is_explained_for_each_truck() {
+        let mut with_empty_return = booking("C2");
+        with_empty_return.empty_return_terminal = Some("C".to_string());
+        with_empty_return.empty_return_close_time = Some(PyTime(PLANNING_START + 3 * HOUR));
+        let mut with_stop = booking("C3");
+        with_stop.pickup_open_time = Some(PyTime(PLANNING_START + 2 * HOUR));
+        with_stop.stops = vec![(
+            "C".to_string(),
+            PyTime(PLANNING_START),
+            PyTime(PLANNING_START + HOUR),
+            false,
+        )];
+        let mut generator = generator(vec![booking("C0"), with_empty_return, with_stop]);
+        let schedule = generator.empty_schedule();
+        let explain = |generator: &mut ScheduleGenerator, cargo_id: &str, schedule: &Schedule| {
+            generator
+                .explain_infeasibility(cargo_id.to_string(), schedule)
+                .unwrap()
+                .remove("K0")
+                .unwrap()
+        };
+        assert_eq!(explain(&mut generator, "C0", &schedule), None);
+
+        // Bookings left out are explained by why they were
+        let mut late = booking("C1");
+        late.pickup_open_time = Some(PyTime(PLANNING_START + 48 * HOUR));
+        late.pickup_close_time = Some(PyTime(PLANNING_START + 49 * HOUR));
+        late.dropoff_close_time = Some(PyTime(PLANNING_START + 50 * HOUR));
+        assert!(!generator.add_booking(late).unwrap());
+        let (constraint, _reason) = explain(&mut generator, "C1", &schedule).unwrap();
+        assert_eq!(constraint, "rejected");
+
+        // The empty container can't be picked up before the full one is dropped off
+        assert_eq!(explain(&mut generator, "C2/empty", &schedule), None);
+        let plan = vec![
+            (
+                "K0".to_string(),
+                PyTime(PLANNING_START + HOUR),
+                "A".to_string(),
+                "C2".to_string(),
+                true,
+            ),
+            (
+                "K0".to_string(),
+                PyTime(PLANNING_START + 10 * HOUR),
+                "B".to_string(),
+                "C2".to_string(),
+                false,
+            ),
+        ];
+        let planned = generator
+            .schedule_from_plan(plan, vec![], BTreeMap::new())
+            .unwrap();
+        let (constraint, _reason) = explain(&mut generator, "C2/empty", &planned).unwrap();
+        assert_eq!(constraint, "precedence");
 
-        self.driving_times_cache = DrivingTimesCache::from_map(driving_times_reformatted)
+        // Stops need to be visited between the pickup and the dropoff
+        let (constraint, reason) = explain(&mut generator, "C3", &schedule).unwrap();
+        assert_eq!(constraint, "driving_time");
+        assert!(reason.contains("other stops"));
     }
 }
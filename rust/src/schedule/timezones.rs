@@ -0,0 +1,104 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeDelta, TimeZone, Utc};
+use chrono_tz::Tz;
+
+use super::common_types::Time;
+use super::intervals::Interval;
+
+/// Converts a local time in `timezone` to the internal timeline.
+/// Local times that happen twice when clocks go back are taken the first time,
+/// and ones skipped when clocks go forward are moved past the gap
+pub fn local_to_time(timezone: Tz, local: NaiveDateTime) -> Time {
+    let mut local = local;
+    // Gaps are at most a few hours long, so this terminates quickly
+    loop {
+        if let Some(datetime) = timezone.from_local_datetime(&local).earliest() {
+            return datetime.timestamp().max(0) as Time;
+        }
+        local += TimeDelta::minutes(15);
+    }
+}
+
+/// Local dates in `timezone` overlapping with `interval`, one per day, so `interval`
+/// should be bounded, e.g. by the planning period. Times too far in the future to have
+/// a date are taken to be on the last date that can be represented in any timezone
+pub fn local_dates(timezone: Tz, interval: &Interval) -> impl Iterator<Item = NaiveDate> {
+    let latest = DateTime::<Utc>::MAX_UTC - TimeDelta::days(2);
+    let to_local_date = |time: Time| {
+        i64::try_from(time)
+            .ok()
+            .and_then(|time| DateTime::from_timestamp(time, 0))
+            .map_or(latest, |datetime| datetime.min(latest))
+            .with_timezone(&timezone)
+            .date_naive()
+    };
+    let first_date = to_local_date(interval.get_start_time());
+    let last_date = to_local_date(interval.get_end_time());
+    first_date
+        .iter_days()
+        .take_while(move |date| *date <= last_date)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveTime};
+
+    use super::*;
+    use crate::schedule::recurring_pattern::RecurringPattern;
+
+    fn local(date: (i32, u32, u32), hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(date.0, date.1, date.2)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    fn utc(date: (i32, u32, u32), hour: u32, minute: u32) -> Time {
+        local(date, hour, minute).and_utc().timestamp() as Time
+    }
+
+    #[test]
+    fn local_times_around_clock_changes() {
+        let berlin = Tz::Europe__Berlin;
+        // Clocks go forward from 02:00 to 03:00, so 02:30 is skipped
+        assert_eq!(
+            local_to_time(berlin, local((2025, 3, 30), 2, 30)),
+            utc((2025, 3, 30), 1, 0)
+        );
+        // Clocks go back from 03:00 to 02:00, so 02:30 happens twice
+        assert_eq!(
+            local_to_time(berlin, local((2025, 10, 26), 2, 30)),
+            utc((2025, 10, 26), 0, 30)
+        );
+    }
+
+    #[test]
+    fn daily_patterns_follow_clock_changes() {
+        let pattern = RecurringPattern::daily(
+            Tz::Europe__Berlin,
+            NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+        );
+        let horizon =
+            Interval::new(utc((2025, 3, 29), 0, 0), utc((2025, 3, 31), 0, 0), ()).unwrap();
+        let occurrences: Vec<_> = pattern
+            .expand(&horizon)
+            .get_intervals()
+            .iter()
+            .map(|interval| (interval.get_start_time(), interval.get_end_time()))
+            .collect();
+        assert_eq!(
+            occurrences,
+            vec![
+                (utc((2025, 3, 29), 7, 0), utc((2025, 3, 29), 16, 0)),
+                (utc((2025, 3, 30), 6, 0), utc((2025, 3, 30), 15, 0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn local_dates_of_far_away_times() {
+        let horizon = Interval::new(u64::MAX - 1, u64::MAX, ()).unwrap();
+        let dates: Vec<_> = local_dates(Tz::Asia__Tokyo, &horizon).collect();
+        assert_eq!(dates.len(), 1);
+    }
+}
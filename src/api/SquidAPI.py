@@ -1,6 +1,6 @@
 import os
 from datetime import date, datetime
-from typing import Dict, List, Optional, Union
+from typing import Dict, List, Optional, Tuple, Union
 
 import pandas as pd
 import requests
@@ -40,6 +40,11 @@ class SquidAPI:
         self.__ROUTESPATH = f"{self.__BASEDIR}/routes.csv"
         self.__TRUCKSTARTSPATH = f"{self.__BASEDIR}/truck_starts.csv"
 
+        # Route geometries fetched so far, by (from, to) coordinates
+        self.__route_geometries: Dict[
+            Tuple[Tuple[float, float], Tuple[float, float]], List[Tuple[float, float]]
+        ] = {}
+
         # Load or fetch all required data
         self.__fetchLocations()
         self.__fetchTrucks()
@@ -878,3 +883,28 @@ class SquidAPI:
             coords.append([loc.longitude, loc.latitude])
 
         return self.getCoordMatrix(coords)
+
+    def getRouteGeometry(
+        self, from_coord: Tuple[float, float], to_coord: Tuple[float, float]
+    ) -> Optional[List[Tuple[float, float]]]:
+        """
+        Get the points along the road between two (lat,long) pairs using OSRM,
+        as (lat,long) pairs. Results are cached for each pair
+        """
+        key = (from_coord, to_coord)
+        if key not in self.__route_geometries:
+            coords = [
+                [float(from_coord[1]), float(from_coord[0])],
+                [float(to_coord[1]), float(to_coord[0])],
+            ]
+            res = self.__osrm_call(
+                "route", f"coordinates={coords}&overview=full&geometries=geojson"
+            )
+            if not res.get("routes"):
+                return None
+            # GeoJSON coordinates are (long,lat)
+            self.__route_geometries[key] = [
+                (lat, long)
+                for long, lat in res["routes"][0]["geometry"]["coordinates"]
+            ]
+        return self.__route_geometries[key]
@@ -9,7 +9,13 @@ from src.metaheuristic.schedule import get_scores_calculator
 
 
 def __deltas_to_probability(deltas: npt.NDArray, temperature: float) -> float:
-    (deliveries_delta, free_trucks_delta, driving_time_delta) = deltas
+    (deliveries_delta, free_trucks_delta, driving_time_delta, penalty_delta) = (
+        deltas
+    )
+
+    # Never move towards violating hard constraints
+    if penalty_delta < 0:
+        return 0.0
 
     # We are mainly optimising for delivered cargo,
     # so encourage the switch
@@ -36,7 +42,12 @@ def __is_better(deltas: npt.NDArray) -> bool:
     Is `schedule1` better than `schedule2`, where
     `deltas` = `schedule1_score - schedule2_score`
     """
-    (deliveries_delta, free_trucks_delta, driving_time_delta) = deltas
+    (deliveries_delta, free_trucks_delta, driving_time_delta, penalty_delta) = (
+        deltas
+    )
+    # Satisfying hard constraints takes priority over everything else
+    if penalty_delta != 0:
+        return penalty_delta > 0
     if deliveries_delta > 0:
         return True
     elif deliveries_delta == 0 and driving_time_delta > 0:
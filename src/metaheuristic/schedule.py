@@ -1,13 +1,13 @@
 import os
 import pickle
 import warnings
-from typing import Callable, Dict, List, Tuple, cast
+from typing import Callable, Dict, List, Optional, Tuple, cast
 
 import numpy as np
 import numpy.typing as npt
 import pandas as pd
 
-from chameleon_rust import PyBooking, PyTruckData, Schedule, ScheduleGenerator
+from chameleon_rust import PyCalendar, PyDriver, Schedule, ScheduleGenerator
 from src.api import SquidAPI
 
 # TODO: collapse 2 consecutive empty transports into 1
@@ -18,6 +18,48 @@ TimeDelta = int
 TerminalID = str
 CargoID = str
 TruckID = str
+DriverID = str
+
+# Columns of the tables of trucks and bookings read by the bindings,
+# named after the fields of `PyTruckData` and `PyBooking`
+TRUCK_COLUMNS = [
+    "starting_terminal",
+    "max_weight_kg",
+    "max_teu",
+    "qualifications",
+    "vehicle_class",
+    "capacities",
+    "tractor_tare_weight_kg",
+    "trailer_tare_weight_kg",
+    "max_gross_weight_kg",
+    "rear_loading",
+    "shift_start_time",
+    "speed_factor",
+]
+BOOKING_COLUMNS = [
+    "cargo",
+    "cargo_weight_kg",
+    "cargo_teu",
+    "from_terminal",
+    "to_terminal",
+    "pickup_open_time",
+    "pickup_close_time",
+    "dropoff_open_time",
+    "dropoff_close_time",
+    "mandatory",
+    "locked_truck",
+    "required_qualifications",
+    "num_containers",
+    "empty_return_terminal",
+    "empty_return_open_time",
+    "empty_return_close_time",
+    "demands",
+    "stops",
+    "priority",
+    "requested_by",
+    "pickup_windows",
+    "dropoff_windows",
+]
 
 
 def make_schedule_generator(
@@ -28,6 +70,9 @@ def make_schedule_generator(
     get_driving_times: Callable[
         [List[TerminalID]], Dict[TerminalID, List[pd.Timedelta]]
     ],
+    relax_time_windows: bool = False,
+    driver_data: Optional[pd.DataFrame] = None,
+    calendars: Optional[Dict[str, PyCalendar]] = None,
 ) -> ScheduleGenerator:
     """
     Creates a blank schedule, given dataframes for data
@@ -38,6 +83,16 @@ def make_schedule_generator(
         Columns:
             Name: opening_time,     dtype: datatime64[ns], in minutes
             Name: closing_time,     dtype: datatime64[ns], in minutes
+            Name: region,           dtype: str (optional) region whose calendar
+            of closed days the terminal follows
+            Name: latitude,         dtype: float (optional) latitude of the terminal
+            Name: longitude,        dtype: float (optional) longitude of the terminal
+            Name: timezone,         dtype: str (optional) timezone of the terminal,
+            e.g. "Europe/Berlin", in which its calendar and daily hours are taken
+            Name: daily_opening_time, dtype: datetime.time (optional) local time
+            at which the terminal opens each day
+            Name: daily_closing_time, dtype: datetime.time (optional) local time
+            at which the terminal closes each day
 
     :param truck_data: dataframe on trucks
         Index:
@@ -45,6 +100,22 @@ def make_schedule_generator(
         Columns:
             Name: starting_terminal,     dtype: str      terminal id where
             truck starts at the beginning of the day
+            Name: qualifications,        dtype: list[str] (optional) qualifications
+            of the truck and its driver, e.g. "ADR"
+            Name: vehicle_class,         dtype: str      (optional) class of the
+            vehicle, selecting which driving times apply to it
+            Name: capacities,            dtype: dict[str, int] (optional) capacities
+            in dimensions other than weight and TEU, e.g. {"pallets": 33}
+            Name: tractor_tare_weight_kg, dtype: int     (optional) weight of the empty tractor
+            Name: trailer_tare_weight_kg, dtype: int     (optional) weight of the empty trailer
+            Name: max_gross_weight_kg,   dtype: int      (optional) legal maximum for
+            the combined weight of tractor, trailer and cargo
+            Name: rear_loading,          dtype: bool     (optional) whether cargo has
+            to be dropped off in reverse order of pickup
+            Name: shift_start_time,      dtype: datetime64[ns] (optional) when the
+            driver's shift starts; defaults to when the starting terminal opens
+            Name: speed_factor,          dtype: float    (optional) how fast the truck
+            drives compared to the driving times, e.g. 0.8 for 25% longer legs
 
     :param requested_transports: dataframe on transports
         Index:
@@ -53,15 +124,60 @@ def make_schedule_generator(
             Name: cargo,                dtype: str            id of cargo to be transported
             Name: from_terminal,        dtype: str            id of terminal to be transported from
             Name: to_terminal,          dtype: str            id of terminal to be transported to
-            Name: pickup_open_time,     dtype: datetime64[ns]   Time from which cargo can be picked up
-            Name: pickup_close_time,    dtype: datetime64[ns]  Time before which cargo must be picked up
-            Name: dropoff_open_time,    dtype: datetime64[ns]  Time from which cargo can be dropped off
-            Name: dropoff_close_time,   dtype: datetime64[ns] Time before which cargo must be dropped off
+            Name: pickup_open_time,     dtype: datetime64[ns]   (optional) Time from which cargo can be picked up,
+            defaults to the start of the planning period
+            Name: pickup_close_time,    dtype: datetime64[ns]  (optional) Time before which cargo must be picked up,
+            defaults to the end of the planning period
+            Name: dropoff_open_time,    dtype: datetime64[ns]  (optional) Time from which cargo can be dropped off,
+            defaults to pickup_open_time
+            Name: dropoff_close_time,   dtype: datetime64[ns] (optional) Time before which cargo must be dropped off,
+            defaults to the end of the planning period
+            Name: mandatory,            dtype: bool           (optional) whether schedules must deliver this cargo
+            Name: locked_truck,         dtype: str            (optional) id of the only truck allowed to deliver this cargo
+            Name: required_qualifications, dtype: list[str]   (optional) qualifications the truck and driver need
+            Name: num_containers,       dtype: int            (optional) number of identical containers,
+            which may be split between trucks
+            Name: empty_return_terminal, dtype: str           (optional) depot the empty container
+            needs to be returned to after dropoff
+            Name: empty_return_open_time, dtype: datetime64[ns] (optional) Time from which the empty can be returned
+            Name: empty_return_close_time, dtype: datetime64[ns] (optional) Time before which the empty must be returned
+            Name: demands,              dtype: dict[str, int] (optional) capacity taken up in dimensions
+            other than weight and TEU, e.g. {"pallets": 10}
+            Name: stops,                dtype: list[tuple]    (optional) additional stops visited in order
+            between pickup and dropoff, as (terminal, open_time, close_time, is_pickup)
+            Name: priority,             dtype: int            (optional) how urgent the delivery is,
+            higher priority cargo counts for more
+            Name: requested_by,         dtype: str            (optional) who requested the delivery
+            Name: pickup_windows,       dtype: list[tuple]    (optional) windows in which cargo
+            can be picked up, as (open_time, close_time)
+            Name: dropoff_windows,      dtype: list[tuple]    (optional) windows in which cargo
+            can be dropped off, as (open_time, close_time)
     :param planning_period an interval during which all the operations need
     to be planned to take place
 
     :param get_driving_times: a callback that takes in a list of terminals
     and returns a matrix of driving times between them
+
+    :param relax_time_windows: if set, transports that can't happen within
+    their requested time windows are kept with relaxed windows and penalised,
+    rather than dropped
+
+    :param driver_data: (optional) dataframe on drivers that can be assigned
+    to trucks
+        Index:
+            pd.Index, dtype=str: id of the driver
+        Columns:
+            Name: shift_start_time,  dtype: datetime64[ns] start of the driver's shift
+            Name: shift_end_time,    dtype: datetime64[ns] end of the driver's shift
+            Name: qualifications,    dtype: list[str]      (optional) qualifications
+            of the driver, needed on top of the truck's
+            Name: region,            dtype: str            (optional) region whose
+            calendar of closed days the driver follows
+            Name: home_terminal,     dtype: str            (optional) the only terminal
+            whose trucks the driver can drive
+
+    :param calendars: (optional) a dict sending a region to the days
+    on which it is closed, e.g. weekends and public holidays
     """
 
     def timestamp_to_seconds(timestamp: pd.Timestamp):
@@ -70,40 +186,36 @@ def make_schedule_generator(
     def timedelta_to_seconds(timestamp: pd.Timedelta):
         return int(timestamp.total_seconds())
 
-    # Repack the data into the format used by the bindings
-    _terminal_data: Dict[TerminalID, Tuple[Time, Time]] = {
-        cast(str, terminal): (
-            timestamp_to_seconds(row["opening_time"]),
-            timestamp_to_seconds(row["closing_time"]),
+    def optional(value, convert=lambda value: value):
+        return convert(value) if pd.notna(value) else None
+
+    def columns(data: pd.DataFrame, names: List[str]) -> pd.DataFrame:
+        # Only the columns the bindings read, so that others don't need to be
+        # convertible to Arrow
+        return data[[name for name in names if name in data.columns]]
+
+    def with_ids(data: pd.DataFrame) -> pd.DataFrame:
+        # The bindings take the ids of terminals and trucks from the "id" column
+        return data.rename_axis("id").reset_index()
+
+    # The tables are passed on as they are and read through Arrow,
+    # rather than making an object for each row
+    _terminal_data = with_ids(columns(terminal_data, ["opening_time", "closing_time"]))
+
+    _truck_data = with_ids(
+        columns(
+            truck_data.rename(
+                # TODO: is loading_capacity how much cargo we can take or truck + cargo?
+                columns={"loading_capacity": "max_weight_kg"}
+            ).assign(
+                # TODO: set the correct value
+                max_teu=40
+            ),
+            TRUCK_COLUMNS,
         )
-        for terminal, row in terminal_data.iterrows()
-    }
-
-    _truck_data: Dict[TruckID, PyTruckData] = {
-        cast(str, truck): PyTruckData(
-            row["starting_terminal"],
-            # TODO: is loading_capacity how much cargo we can take or truck + cargo?
-            row["loading_capacity"],
-            # TODO: set the correct value
-            40,
-        )
-        for truck, row in truck_data.iterrows()
-    }
+    )
 
-    _transpost_data: List[PyBooking] = [
-        PyBooking(
-            cargo=row["cargo"],
-            cargo_weight_kg=int(row["cargo_weight_kg"]),
-            cargo_teu=int(row["cargo_teu"]),
-            from_terminal=row["from_terminal"],
-            to_terminal=row["to_terminal"],
-            pickup_open_time=timestamp_to_seconds(row["pickup_open_time"]),
-            pickup_close_time=timestamp_to_seconds(row["pickup_close_time"]),
-            dropoff_open_time=timestamp_to_seconds(row["dropoff_open_time"]),
-            dropoff_close_time=timestamp_to_seconds(row["dropoff_close_time"]),
-        )
-        for transport_id, row in requested_transports.iterrows()
-    ]
+    _transport_data = columns(requested_transports, BOOKING_COLUMNS)
 
     _planning_period: Tuple[Time, Time] = (
         timestamp_to_seconds(planning_period[0]),
@@ -111,7 +223,33 @@ def make_schedule_generator(
     )
 
     out = ScheduleGenerator(
-        _terminal_data, _truck_data, _transpost_data, _planning_period
+        _terminal_data,
+        _truck_data,
+        _transport_data,
+        _planning_period,
+        relax_time_windows=relax_time_windows,
+        calendars=calendars or {},
+        terminal_regions={
+            cast(str, terminal): row["region"]
+            for terminal, row in terminal_data.iterrows()
+            if pd.notna(row.get("region"))
+        },
+        terminal_locations={
+            cast(str, terminal): (float(row["latitude"]), float(row["longitude"]))
+            for terminal, row in terminal_data.iterrows()
+            if pd.notna(row.get("latitude")) and pd.notna(row.get("longitude"))
+        },
+        terminal_timezones={
+            cast(str, terminal): row["timezone"]
+            for terminal, row in terminal_data.iterrows()
+            if pd.notna(row.get("timezone"))
+        },
+        terminal_opening_hours={
+            cast(str, terminal): (row["daily_opening_time"], row["daily_closing_time"])
+            for terminal, row in terminal_data.iterrows()
+            if pd.notna(row.get("daily_opening_time"))
+            and pd.notna(row.get("daily_closing_time"))
+        },
     )
 
     # Now set up the driving times
@@ -127,6 +265,21 @@ def make_schedule_generator(
 
     out.set_driving_times(relevant_terminal_ids, driving_times)
 
+    if driver_data is not None:
+        out.set_driver_roster(
+            [
+                PyDriver(
+                    cast(str, driver),
+                    timestamp_to_seconds(row["shift_start_time"]),
+                    timestamp_to_seconds(row["shift_end_time"]),
+                    qualifications=list(row.get("qualifications", [])),
+                    home_terminal=optional(row.get("home_terminal")),
+                    region=optional(row.get("region")),
+                )
+                for driver, row in driver_data.iterrows()
+            ]
+        )
+
     return out
 
 
@@ -355,6 +508,68 @@ def cached_make_schedule_data_from_api(
     return (*data, cached_get_driving_times)
 
 
+def fetch_route_geometries(
+    schedule: Schedule,
+    schedule_generator: ScheduleGenerator,
+    get_route_geometry: Callable[
+        [Tuple[float, float], Tuple[float, float]],
+        Optional[List[Tuple[float, float]]],
+    ],
+) -> None:
+    """
+    Sets the geometries of the legs driven in `schedule` which don't have one yet,
+    so that they can be drawn on a map using `Schedule.get_route_legs`
+
+    :param get_route_geometry: a callback that takes in the (latitude, longitude)
+    of two terminals and returns the (latitude, longitude) of points along the road
+    between them, or None if it can't, e.g. `SquidAPI.getRouteGeometry`
+    """
+    locations = schedule_generator.get_terminal_locations()
+    fetched = set()
+    for _truck_id, from_id, to_id, geometry in schedule.get_route_legs(
+        schedule_generator
+    ):
+        if (
+            geometry
+            or (from_id, to_id) in fetched
+            or from_id not in locations
+            or to_id not in locations
+        ):
+            continue
+        fetched.add((from_id, to_id))
+        points = get_route_geometry(locations[from_id], locations[to_id])
+        if points is not None:
+            schedule_generator.set_route_geometry(from_id, to_id, points)
+
+
+def make_osrm_driving_time_provider(
+    schedule_generator: ScheduleGenerator,
+    get_coord_dist: Callable[[List[List[float]]], Optional[Dict]],
+) -> Callable[[str, str], int]:
+    """
+    Makes a driving time provider querying OSRM, to be set with
+    `ScheduleGenerator.set_driving_time_provider` for the "callback" provider
+
+    :param get_coord_dist: a callback that takes in the [longitude, latitude]
+    of terminals and returns the OSRM route between them, e.g. `SquidAPI.getCoordDist`
+    """
+    locations = schedule_generator.get_terminal_locations()
+
+    def provider(from_id: str, to_id: str) -> int:
+        if from_id not in locations or to_id not in locations:
+            raise ValueError(f"No location for terminals {from_id}, {to_id}")
+        coords = [
+            [locations[terminal_id][1], locations[terminal_id][0]]
+            for terminal_id in (from_id, to_id)
+        ]
+        route = get_coord_dist(coords)
+        if route is None:
+            raise ValueError(f"No route from {from_id} to {to_id}")
+        return round(route["duration"])
+
+    return provider
+
+
 def get_scores_calculator(
     schedule_generator: ScheduleGenerator,
 ) -> Callable[[Schedule], npt.NDArray]:
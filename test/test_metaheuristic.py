@@ -82,6 +82,7 @@ def test_get_neighbours():
     schedule_generator = make_schedule_generator(
         terminals, trucks, transports, planning_period, get_driving_time
     )
+    schedule_generator.set_debug_checks(True)
     schedule = schedule_generator.empty_schedule()
 
     # I don't know how to test this without inspecting it by hand,
@@ -91,10 +92,59 @@ def test_get_neighbours():
         schedule = schedule_generator.get_schedule_neighbour(schedule, 100)
 
 
+def test_mandatory_deliveries_are_kept():
+    (terminals, trucks, transports, planning_period, get_driving_time) = (
+        create_schedule_data()
+    )
+    transports["mandatory"] = [True, True, False, False, False, False]
+
+    schedule_generator = make_schedule_generator(
+        terminals, trucks, transports, planning_period, get_driving_time
+    )
+    schedule_generator.set_debug_checks(True)
+    schedule = schedule_generator.empty_schedule()
+    penalty = schedule_generator.scores(schedule)[3]
+    assert penalty == -2
+    assert not schedule_generator.is_feasible(schedule)
+
+    # Once a mandatory delivery is made, it is never removed
+    for _ in range(1000):
+        schedule = schedule_generator.get_schedule_neighbour(schedule, 100)
+        new_penalty = schedule_generator.scores(schedule)[3]
+        assert new_penalty >= penalty
+        penalty = new_penalty
+
+
+def test_schedule_to_numpy():
+    """Tests that `Schedule.to_numpy` has the rows of `Schedule.to_columns`"""
+    schedule_generator = make_schedule_generator(*create_schedule_data())
+    schedule_generator.set_debug_checks(True)
+    schedule = schedule_generator.empty_schedule()
+
+    # An empty schedule still gives all the fields
+    records = schedule.to_numpy(schedule_generator)
+    assert len(records) == 0
+    assert records.dtype.names == ("truck", "time", "terminal", "cargo", "action")
+
+    for _ in range(100):
+        schedule = schedule_generator.get_schedule_neighbour(schedule, 100)
+    records = schedule.to_numpy(schedule_generator)
+    columns = schedule.to_columns(schedule_generator, interned_ids=True)
+    assert len(records) > 0
+    for field in ["truck", "terminal", "cargo"]:
+        assert records[field].tolist() == columns[field]
+    assert records["time"].astype("int64").tolist() == columns["time"]
+    actions = ["pickup", "dropoff"]
+    assert records["action"].tolist() == [
+        actions.index(action) for action in columns["action"]
+    ]
+
+
 def run_sa_with_seed(
     data, seed, num_iterations, print_score=True, print_schedule=False
 ):
     schedule_generator = make_schedule_generator(*data)
+    schedule_generator.set_debug_checks(True)
     schedule_generator.seed(seed)
 
     schedule = schedule_generator.empty_schedule()
@@ -122,6 +172,7 @@ def test_loading_api_data():
 
     data = cached_make_schedule_data_from_api(API(), planning_period)
     schedule_generator = make_schedule_generator(*data)
+    schedule_generator.set_debug_checks(True)
     schedule = schedule_generator.empty_schedule()
 
     # Test that this data doesn't produce errors when looking for neighbours